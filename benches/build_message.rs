@@ -0,0 +1,49 @@
+#![feature(test)]
+
+extern crate test;
+
+use rustwire::MessageBuilder;
+use test::Bencher;
+
+// A repeated 32-byte value with 128 occurrences, encoded into a message of a little over 4k bytes
+const VALUE: &[u8] = &[0xAB; 32];
+const OCCURRENCES: usize = 128;
+const MESSAGE_LEN: usize = OCCURRENCES * (2 + VALUE.len());
+
+// Adds the fields of the message
+fn add_fields(builder: &mut MessageBuilder) {
+    for _ in 0..OCCURRENCES {
+        builder.add_bytes(1, VALUE);
+    }
+}
+
+// Benchmark for building a message in a buffer that grows as fields are added
+#[bench]
+fn bench_builder_new(b: &mut Bencher) {
+    b.iter(|| {
+        let mut builder = MessageBuilder::new();
+        add_fields(&mut builder);
+        test::black_box(builder.finish());
+    });
+}
+
+// Benchmark for building a message in a buffer allocated up front
+#[bench]
+fn bench_builder_with_capacity(b: &mut Bencher) {
+    b.iter(|| {
+        let mut builder = MessageBuilder::with_capacity(MESSAGE_LEN);
+        add_fields(&mut builder);
+        test::black_box(builder.finish());
+    });
+}
+
+// Benchmark for building a message after reserving room for it
+#[bench]
+fn bench_builder_reserve(b: &mut Bencher) {
+    b.iter(|| {
+        let mut builder = MessageBuilder::new();
+        builder.reserve(MESSAGE_LEN);
+        add_fields(&mut builder);
+        test::black_box(builder.finish());
+    });
+}
@@ -0,0 +1,48 @@
+#![feature(test)]
+
+extern crate test;
+
+use rustwire::{encode_varint, MessageBuilder, MessageTemplate};
+use test::Bencher;
+
+// Adds the constant fields of a telemetry sample
+fn add_header(builder: &mut MessageBuilder) {
+    builder
+        .add_string(1, "thermometer-12")
+        .add_string(2, "building-3/floor-2/room-214")
+        .add_string(3, "celsius")
+        .add_varint(4, 2)
+        .add_string(5, "firmware 4.2.1");
+}
+
+// Benchmark for building a telemetry sample field by field
+#[bench]
+fn bench_builder(b: &mut Bencher) {
+    let mut timestamp = 1_700_000_000u64;
+
+    b.iter(|| {
+        timestamp += 1;
+        let mut builder = MessageBuilder::new();
+        add_header(&mut builder);
+        builder.add_varint(6, timestamp).add_double(7, 21.5);
+        test::black_box(builder.finish());
+    });
+}
+
+// Benchmark for building a telemetry sample by patching a template
+#[bench]
+fn bench_template(b: &mut Bencher) {
+    let mut builder = MessageBuilder::new();
+    add_header(&mut builder);
+    builder.add_varint(6, 0).add_double(7, 0.0);
+    let template = MessageTemplate::new(builder);
+    let mut timestamp = 1_700_000_000u64;
+
+    b.iter(|| {
+        timestamp += 1;
+        let message = template
+            .instantiate(&[(6, &encode_varint(timestamp)), (7, &21.5f64.to_le_bytes())])
+            .unwrap();
+        test::black_box(message);
+    });
+}
@@ -0,0 +1,46 @@
+#![feature(test)]
+
+extern crate test;
+
+use rustwire::{encode_varint, encode_varint_array};
+use test::Bencher;
+
+// Values of every varint length from 1 to 10 bytes
+fn values() -> Vec<u64> {
+    (0..64).step_by(7).map(|shift| 1u64 << shift).collect()
+}
+
+// Benchmark for encoding varints into a buffer through a Vec per varint
+#[bench]
+fn bench_encode_varint_vec(b: &mut Bencher) {
+    let values = values();
+    let mut buf = Vec::with_capacity(1024);
+
+    b.iter(|| {
+        buf.clear();
+        for _ in 0..10 {
+            for value in &values {
+                buf.extend_from_slice(&encode_varint(test::black_box(*value)));
+            }
+        }
+        test::black_box(&buf);
+    });
+}
+
+// Benchmark for encoding varints into a buffer through a stack array per varint
+#[bench]
+fn bench_encode_varint_array(b: &mut Bencher) {
+    let values = values();
+    let mut buf = Vec::with_capacity(1024);
+
+    b.iter(|| {
+        buf.clear();
+        for _ in 0..10 {
+            for value in &values {
+                let (len, varint) = encode_varint_array(test::black_box(*value));
+                buf.extend_from_slice(&varint[..len]);
+            }
+        }
+        test::black_box(&buf);
+    });
+}
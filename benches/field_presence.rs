@@ -0,0 +1,62 @@
+#![feature(test)]
+
+extern crate test;
+
+use rustwire::{
+    count_field_occurrences, extract_all_occurrences_by_tag, extract_field_by_tag,
+    message_has_field, MessageBuilder,
+};
+use test::Bencher;
+
+// A message of about 4 kB, with the field being looked for at the end
+fn message() -> Vec<u8> {
+    let mut builder = MessageBuilder::new();
+    for i in 0..70 {
+        builder
+            .add_varint(1, i * 1000)
+            .add_string(2, "the quick brown fox jumps over the lazy dog")
+            .add_double(3, i as f64);
+    }
+    builder.add_varint(4, 1);
+    builder.finish()
+}
+
+// Benchmark for checking field presence with message_has_field
+#[bench]
+fn bench_message_has_field(b: &mut Bencher) {
+    let encoded_message = message();
+
+    b.iter(|| {
+        test::black_box(message_has_field(&encoded_message, 4));
+    });
+}
+
+// Benchmark for checking field presence by extracting the field
+#[bench]
+fn bench_extract_field_by_tag_is_some(b: &mut Bencher) {
+    let encoded_message = message();
+
+    b.iter(|| {
+        test::black_box(extract_field_by_tag(&encoded_message, 4).is_some());
+    });
+}
+
+// Benchmark for counting a repeated field with count_field_occurrences
+#[bench]
+fn bench_count_field_occurrences(b: &mut Bencher) {
+    let encoded_message = message();
+
+    b.iter(|| {
+        test::black_box(count_field_occurrences(&encoded_message, 2));
+    });
+}
+
+// Benchmark for counting a repeated field by extracting every occurrence
+#[bench]
+fn bench_extract_all_occurrences_len(b: &mut Bencher) {
+    let encoded_message = message();
+
+    b.iter(|| {
+        test::black_box(extract_all_occurrences_by_tag(&encoded_message, 2).len());
+    });
+}
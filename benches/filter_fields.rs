@@ -0,0 +1,70 @@
+#![feature(test)]
+
+extern crate test;
+
+use prost::Message;
+use rustwire::{collect_to_message, keep_only_fields, FieldIterator};
+use test::Bencher;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Event {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(bytes = "vec", tag = "3")]
+    pub payload: Vec<u8>,
+    #[prost(double, tag = "4")]
+    pub score: f64,
+    #[prost(string, repeated, tag = "5")]
+    pub labels: Vec<String>,
+    #[prost(fixed64, tag = "6")]
+    pub timestamp: u64,
+}
+
+fn encoded_event() -> Vec<u8> {
+    Event {
+        id: 12345,
+        name: "an event with a reasonably long name".to_string(),
+        payload: (0..2048).map(|i| i as u8).collect(),
+        score: 0.75,
+        labels: (0..20).map(|i| format!("label-{i}")).collect(),
+        timestamp: 1_700_000_000,
+    }
+    .encode_to_vec()
+}
+
+const TAGS: &[u64] = &[1, 2, 4, 6];
+
+// Benchmark for keeping a few fields with `keep_only_fields`
+#[bench]
+fn bench_keep_only_fields(b: &mut Bencher) {
+    let encoded_message = encoded_event();
+
+    b.iter(|| test::black_box(keep_only_fields(test::black_box(&encoded_message), TAGS)));
+}
+
+// Benchmark for keeping the same fields by filtering a `FieldIterator`
+#[bench]
+fn bench_filter_tags_collect(b: &mut Bencher) {
+    let encoded_message = encoded_event();
+
+    b.iter(|| {
+        test::black_box(collect_to_message(
+            FieldIterator::new(test::black_box(&encoded_message)).filter_tags(TAGS),
+        ))
+    });
+}
+
+// Benchmark for visiting the same fields without building a message at all
+#[bench]
+fn bench_filter_tags_visit(b: &mut Bencher) {
+    let encoded_message = encoded_event();
+
+    b.iter(|| {
+        FieldIterator::new(test::black_box(&encoded_message))
+            .filter_tags(TAGS)
+            .map(|field| field.as_bytes().len())
+            .sum::<usize>()
+    });
+}
@@ -0,0 +1,45 @@
+#![feature(test)]
+
+extern crate test;
+
+use rustwire::{extract_field_by_tag, MessageBuilder, MessageIndex};
+use test::Bencher;
+
+const FIELD_COUNT: u64 = 25;
+
+fn message() -> Vec<u8> {
+    let mut builder = MessageBuilder::new();
+    for tag in 1..=FIELD_COUNT {
+        match tag % 3 {
+            0 => builder.add_varint(tag, tag * 1000),
+            1 => builder.add_string(tag, "the quick brown fox jumps over the lazy dog"),
+            _ => builder.add_double(tag, tag as f64),
+        };
+    }
+    builder.finish()
+}
+
+// Benchmark for extracting every field with a separate scan each
+#[bench]
+fn bench_extract_25_fields_without_index(b: &mut Bencher) {
+    let encoded_message = message();
+
+    b.iter(|| {
+        for tag in 1..=FIELD_COUNT {
+            test::black_box(extract_field_by_tag(&encoded_message, tag).unwrap());
+        }
+    });
+}
+
+// Benchmark for indexing the message once and extracting every field from the index
+#[bench]
+fn bench_extract_25_fields_with_index(b: &mut Bencher) {
+    let encoded_message = message();
+
+    b.iter(|| {
+        let index = MessageIndex::new(&encoded_message);
+        for tag in 1..=FIELD_COUNT {
+            test::black_box(index.get(tag).unwrap());
+        }
+    });
+}
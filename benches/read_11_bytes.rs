@@ -3,7 +3,7 @@
 extern crate test;
 
 use prost::Message;
-use rustwire::{extract_field_by_tag, Variant};
+use rustwire::extract_field_by_tag;
 use test::Bencher;
 
 #[derive(Message)]
@@ -3,7 +3,10 @@
 extern crate test;
 
 use prost::Message;
-use rustwire::{extract_field_by_tag, Variant};
+use rustwire::{
+    extract_field_by_tag, extract_multiple_fields_by_tag, extract_multiple_fields_early_exit,
+    FieldIterator,
+};
 use test::Bencher;
 
 #[derive(Clone, PartialEq, Message)]
@@ -30,6 +33,7 @@ pub struct ThisMessage {
     pub field10: i32,
 }
 
+#[allow(clippy::approx_constant)]
 pub fn default_message() -> ThisMessage {
     let default_string = "Lorem ipsum dolor sit amet, consectetur adipiscing elit.".repeat(10);
     let mut default_bytes = Vec::new();
@@ -65,7 +69,7 @@ fn bench_rustwire_extraction(b: &mut Bencher) {
 
     b.iter(|| {
         let result = extract_field_by_tag(&encoded_message, tag_number).unwrap();
-        let result = test::black_box(result);
+        test::black_box(result);
     });
 }
 
@@ -79,3 +83,38 @@ fn bench_prost_extraction(b: &mut Bencher) {
         test::black_box(result);
     });
 }
+
+// Benchmark for extracting fields near the start of the message, scanning the whole message
+#[bench]
+fn bench_rustwire_multiple_extraction(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+
+    b.iter(|| {
+        let result = extract_multiple_fields_by_tag(&encoded_message, &[1, 3]);
+        test::black_box(result);
+    });
+}
+
+// Benchmark for extracting fields near the start of the message, stopping once all are found
+#[bench]
+fn bench_rustwire_multiple_extraction_early_exit(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+
+    b.iter(|| {
+        let result = extract_multiple_fields_early_exit(&encoded_message, &[1, 3]);
+        test::black_box(result);
+    });
+}
+
+// Benchmark for decoding every varint in the message, which is dominated by varint decoding
+#[bench]
+fn bench_rustwire_sum_varints(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+
+    b.iter(|| {
+        let sum = FieldIterator::new(&encoded_message)
+            .filter_map(|field| field.as_u64())
+            .fold(0u64, u64::wrapping_add);
+        test::black_box(sum);
+    });
+}
@@ -3,7 +3,9 @@
 extern crate test;
 
 use prost::Message;
-use rustwire::{extract_field_by_tag, Variant};
+use rustwire::{
+    extract_field_by_tag, extract_field_by_tag_hinted, find_field_tag_offset, FieldIterator,
+};
 use test::Bencher;
 
 #[derive(Message)]
@@ -105,7 +107,7 @@ fn bench_rustwire_extraction(b: &mut Bencher) {
 
     b.iter(|| {
         let result = extract_field_by_tag(&encoded_message, tag_number).unwrap();
-        let result = test::black_box(result);
+        test::black_box(result);
     });
 }
 
@@ -119,3 +121,29 @@ fn bench_prost_extraction(b: &mut Bencher) {
         test::black_box(result);
     });
 }
+
+// Benchmark for extracting a field using rustwire, starting at its known offset
+#[bench]
+fn bench_rustwire_hinted_extraction(b: &mut Bencher) {
+    let encoded_message = default_larger_message().encode_to_vec();
+    let tag_number = 22;
+    let hint = find_field_tag_offset(&encoded_message, tag_number).unwrap();
+
+    b.iter(|| {
+        let result = extract_field_by_tag_hinted(&encoded_message, tag_number, hint).unwrap();
+        test::black_box(result);
+    });
+}
+
+// Benchmark for decoding every varint in the message, which is dominated by varint decoding
+#[bench]
+fn bench_rustwire_sum_varints(b: &mut Bencher) {
+    let encoded_message = default_larger_message().encode_to_vec();
+
+    b.iter(|| {
+        let sum = FieldIterator::new(&encoded_message)
+            .filter_map(|field| field.as_u64())
+            .fold(0u64, u64::wrapping_add);
+        test::black_box(sum);
+    });
+}
@@ -0,0 +1,75 @@
+#![feature(test)]
+
+extern crate test;
+
+use prost::Message;
+use rustwire::{create_header, replace_field_with};
+use test::Bencher;
+
+#[derive(Message)]
+pub struct SimpleMessage {
+    #[prost(int32, tag = "1")]
+    pub field1: i32,
+    #[prost(string, tag = "2")]
+    pub field2: ::prost::alloc::string::String,
+}
+
+const ENCODED_MESSAGE: &[u8] = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+
+// Replacement values with the same, a shorter and a longer length than "testing"
+const SAME_SIZE: &str = "TESTING";
+const SHORTER: &str = "test";
+const LONGER: &str = "testing a longer value";
+
+fn rustwire_replace(encoded_message: &[u8], value: &str) -> Vec<u8> {
+    let replace_with = [
+        create_header(2, 2, value.as_bytes()),
+        value.as_bytes().to_vec(),
+    ]
+    .concat();
+    let mut result = encoded_message.to_vec();
+    replace_field_with(&mut result, 2, &replace_with).unwrap();
+    result
+}
+
+fn prost_replace(encoded_message: &[u8], value: &str) -> Vec<u8> {
+    let mut message = SimpleMessage::decode(encoded_message).unwrap();
+    message.field2 = value.to_string();
+    message.encode_to_vec()
+}
+
+// Benchmark for replacing a field with a value of the same size using rustwire
+#[bench]
+fn bench_rustwire_replace_same_size(b: &mut Bencher) {
+    b.iter(|| test::black_box(rustwire_replace(ENCODED_MESSAGE, SAME_SIZE)));
+}
+
+// Benchmark for replacing a field with a shorter value using rustwire
+#[bench]
+fn bench_rustwire_replace_shorter(b: &mut Bencher) {
+    b.iter(|| test::black_box(rustwire_replace(ENCODED_MESSAGE, SHORTER)));
+}
+
+// Benchmark for replacing a field with a longer value using rustwire
+#[bench]
+fn bench_rustwire_replace_longer(b: &mut Bencher) {
+    b.iter(|| test::black_box(rustwire_replace(ENCODED_MESSAGE, LONGER)));
+}
+
+// Benchmark for decoding, changing and re-encoding the message with a value of the same size
+#[bench]
+fn bench_prost_replace_same_size(b: &mut Bencher) {
+    b.iter(|| test::black_box(prost_replace(ENCODED_MESSAGE, SAME_SIZE)));
+}
+
+// Benchmark for decoding, changing and re-encoding the message with a shorter value
+#[bench]
+fn bench_prost_replace_shorter(b: &mut Bencher) {
+    b.iter(|| test::black_box(prost_replace(ENCODED_MESSAGE, SHORTER)));
+}
+
+// Benchmark for decoding, changing and re-encoding the message with a longer value
+#[bench]
+fn bench_prost_replace_longer(b: &mut Bencher) {
+    b.iter(|| test::black_box(prost_replace(ENCODED_MESSAGE, LONGER)));
+}
@@ -0,0 +1,137 @@
+#![feature(test)]
+
+extern crate test;
+
+use prost::Message;
+use rustwire::{create_header, replace_field_with};
+use test::Bencher;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ThisMessage {
+    #[prost(int32, tag = "1")]
+    pub field1: i32,
+    #[prost(string, tag = "2")]
+    pub field2: String,
+    #[prost(uint64, tag = "3")]
+    pub field3: u64,
+    #[prost(bool, tag = "4")]
+    pub field4: bool,
+    #[prost(float, tag = "5")]
+    pub field5: f32,
+    #[prost(double, tag = "6")]
+    pub field6: f64,
+    #[prost(bytes, tag = "7")]
+    pub field7: Vec<u8>,
+    #[prost(sint32, tag = "8")]
+    pub field8: i32,
+    #[prost(fixed32, tag = "9")]
+    pub field9: u32,
+    #[prost(sfixed32, tag = "10")]
+    pub field10: i32,
+}
+
+#[allow(clippy::approx_constant)]
+pub fn default_message() -> ThisMessage {
+    let default_string = "Lorem ipsum dolor sit amet, consectetur adipiscing elit.".repeat(10);
+    let mut default_bytes = Vec::new();
+    for i in 0..3397 {
+        default_bytes.push(i as u8);
+    }
+
+    ThisMessage {
+        field1: 42,
+        field2: default_string,
+        field3: 1234567890,
+        field4: true,
+        field5: 3.14,
+        field6: 2.71828,
+        field7: default_bytes,
+        field8: -123,
+        field9: 987654321,
+        field10: -987654321,
+    }
+}
+
+// Replacement values for the 560-byte field 2 with the same, a shorter and a longer length
+fn same_size() -> String {
+    "LOREM IPSUM DOLOR SIT AMET, CONSECTETUR ADIPISCING ELIT.".repeat(10)
+}
+
+fn shorter() -> String {
+    "Lorem ipsum dolor sit amet.".to_string()
+}
+
+fn longer() -> String {
+    "Lorem ipsum dolor sit amet, consectetur adipiscing elit.".repeat(20)
+}
+
+fn rustwire_replace(encoded_message: &[u8], value: &str) -> Vec<u8> {
+    let replace_with = [
+        create_header(2, 2, value.as_bytes()),
+        value.as_bytes().to_vec(),
+    ]
+    .concat();
+    let mut result = encoded_message.to_vec();
+    replace_field_with(&mut result, 2, &replace_with).unwrap();
+    result
+}
+
+fn prost_replace(encoded_message: &[u8], value: &str) -> Vec<u8> {
+    let mut message = ThisMessage::decode(encoded_message).unwrap();
+    message.field2 = value.to_string();
+    message.encode_to_vec()
+}
+
+// Benchmark for replacing a field with a value of the same size using rustwire
+#[bench]
+fn bench_rustwire_replace_same_size(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+    let value = same_size();
+
+    b.iter(|| test::black_box(rustwire_replace(&encoded_message, &value)));
+}
+
+// Benchmark for replacing a field with a shorter value using rustwire
+#[bench]
+fn bench_rustwire_replace_shorter(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+    let value = shorter();
+
+    b.iter(|| test::black_box(rustwire_replace(&encoded_message, &value)));
+}
+
+// Benchmark for replacing a field with a longer value using rustwire
+#[bench]
+fn bench_rustwire_replace_longer(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+    let value = longer();
+
+    b.iter(|| test::black_box(rustwire_replace(&encoded_message, &value)));
+}
+
+// Benchmark for decoding, changing and re-encoding the message with a value of the same size
+#[bench]
+fn bench_prost_replace_same_size(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+    let value = same_size();
+
+    b.iter(|| test::black_box(prost_replace(&encoded_message, &value)));
+}
+
+// Benchmark for decoding, changing and re-encoding the message with a shorter value
+#[bench]
+fn bench_prost_replace_shorter(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+    let value = shorter();
+
+    b.iter(|| test::black_box(prost_replace(&encoded_message, &value)));
+}
+
+// Benchmark for decoding, changing and re-encoding the message with a longer value
+#[bench]
+fn bench_prost_replace_longer(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+    let value = longer();
+
+    b.iter(|| test::black_box(prost_replace(&encoded_message, &value)));
+}
@@ -0,0 +1,85 @@
+#![feature(test)]
+
+extern crate test;
+
+use rustwire::{create_header, extract_full_field_byte_range, replace_field_with};
+use test::Bencher;
+
+// A message of about `size` bytes whose field 2 is a string filling most of it
+fn message(size: usize) -> Vec<u8> {
+    let value = vec![b'a'; size - 8];
+    [
+        vec![0x08, 0x01],
+        create_header(2, 2, &value),
+        value,
+        vec![0x18, 0x07],
+    ]
+    .concat()
+}
+
+// A replacement for field 2 that is a few bytes longer than the original
+fn replacement(size: usize) -> Vec<u8> {
+    let value = vec![b'b'; size - 4];
+    [create_header(2, 2, &value), value].concat()
+}
+
+// The previous approach: build a new message from the parts, then copy it back
+fn replace_field_with_copy(
+    encoded_message: &mut Vec<u8>,
+    tag_number: u64,
+    replace_with: &[u8],
+) -> Option<Vec<u8>> {
+    let (start, end) = extract_full_field_byte_range(encoded_message, tag_number)?;
+    let old = encoded_message[start..end].to_vec();
+    let mut new_message =
+        Vec::with_capacity(encoded_message.len() - (end - start) + replace_with.len());
+    new_message.extend_from_slice(&encoded_message[..start]);
+    new_message.extend_from_slice(replace_with);
+    new_message.extend_from_slice(&encoded_message[end..]);
+    encoded_message.clear();
+    encoded_message.extend_from_slice(&new_message);
+    Some(old)
+}
+
+type Replace = fn(&mut Vec<u8>, u64, &[u8]) -> Option<Vec<u8>>;
+
+fn bench_replace(b: &mut Bencher, size: usize, replace: Replace) {
+    let encoded_message = message(size);
+    let replace_with = replacement(size);
+
+    b.iter(|| {
+        let mut replaced = encoded_message.clone();
+        test::black_box(replace(&mut replaced, 2, &replace_with));
+        test::black_box(replaced);
+    });
+}
+
+#[bench]
+fn bench_replace_copy_100b(b: &mut Bencher) {
+    bench_replace(b, 100, replace_field_with_copy);
+}
+
+#[bench]
+fn bench_replace_two_pass_100b(b: &mut Bencher) {
+    bench_replace(b, 100, replace_field_with);
+}
+
+#[bench]
+fn bench_replace_copy_1kb(b: &mut Bencher) {
+    bench_replace(b, 1024, replace_field_with_copy);
+}
+
+#[bench]
+fn bench_replace_two_pass_1kb(b: &mut Bencher) {
+    bench_replace(b, 1024, replace_field_with);
+}
+
+#[bench]
+fn bench_replace_copy_100kb(b: &mut Bencher) {
+    bench_replace(b, 100 * 1024, replace_field_with_copy);
+}
+
+#[bench]
+fn bench_replace_two_pass_100kb(b: &mut Bencher) {
+    bench_replace(b, 100 * 1024, replace_field_with);
+}
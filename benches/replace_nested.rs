@@ -0,0 +1,126 @@
+#![feature(test)]
+
+extern crate test;
+
+use prost::Message;
+use rustwire::{create_header, extract_field_by_tag, replace_field_with};
+use test::Bencher;
+
+#[derive(Clone, Message, PartialEq)]
+pub struct User {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(int32, tag = "2")]
+    pub id: i32,
+    #[prost(string, tag = "3")]
+    pub email: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ThisMessage {
+    #[prost(int32, tag = "1")]
+    pub field1: i32,
+    #[prost(string, tag = "2")]
+    pub field2: String,
+    #[prost(uint64, tag = "3")]
+    pub field3: u64,
+    #[prost(bool, tag = "4")]
+    pub field4: bool,
+
+    // user field
+    #[prost(message, tag = "5")]
+    pub user: Option<User>,
+}
+
+pub fn default_message() -> ThisMessage {
+    let default_string = "Lorem ipsum dolor sit amet, consectetur adipiscing elit.".repeat(10);
+
+    let user = User {
+        name: "John Doe".to_string(),
+        id: 12345,
+        email: "john@doe.com".to_string(),
+    };
+
+    ThisMessage {
+        field1: 42,
+        field2: default_string,
+        field3: 1234567890,
+        field4: true,
+        user: Some(user),
+    }
+}
+
+// Replacement emails with the same, a shorter and a longer length than "john@doe.com"
+const SAME_SIZE: &str = "jane@doe.com";
+const SHORTER: &str = "j@doe.com";
+const LONGER: &str = "john.doe@example.com";
+
+fn rustwire_replace(encoded_message: &[u8], email: &str) -> Vec<u8> {
+    // Replace the email in the user message, then the user message in the outer message
+    let mut user = extract_field_by_tag(encoded_message, 5).unwrap().to_vec();
+    let replace_email = [
+        create_header(3, 2, email.as_bytes()),
+        email.as_bytes().to_vec(),
+    ]
+    .concat();
+    replace_field_with(&mut user, 3, &replace_email).unwrap();
+
+    let replace_user = [create_header(5, 2, &user), user].concat();
+    let mut result = encoded_message.to_vec();
+    replace_field_with(&mut result, 5, &replace_user).unwrap();
+    result
+}
+
+fn prost_replace(encoded_message: &[u8], email: &str) -> Vec<u8> {
+    let mut message = ThisMessage::decode(encoded_message).unwrap();
+    message.user.as_mut().unwrap().email = email.to_string();
+    message.encode_to_vec()
+}
+
+// Benchmark for replacing a nested field with a value of the same size using rustwire
+#[bench]
+fn bench_rustwire_replace_same_size(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+
+    b.iter(|| test::black_box(rustwire_replace(&encoded_message, SAME_SIZE)));
+}
+
+// Benchmark for replacing a nested field with a shorter value using rustwire
+#[bench]
+fn bench_rustwire_replace_shorter(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+
+    b.iter(|| test::black_box(rustwire_replace(&encoded_message, SHORTER)));
+}
+
+// Benchmark for replacing a nested field with a longer value using rustwire
+#[bench]
+fn bench_rustwire_replace_longer(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+
+    b.iter(|| test::black_box(rustwire_replace(&encoded_message, LONGER)));
+}
+
+// Benchmark for decoding, changing and re-encoding the message with a value of the same size
+#[bench]
+fn bench_prost_replace_same_size(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+
+    b.iter(|| test::black_box(prost_replace(&encoded_message, SAME_SIZE)));
+}
+
+// Benchmark for decoding, changing and re-encoding the message with a shorter value
+#[bench]
+fn bench_prost_replace_shorter(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+
+    b.iter(|| test::black_box(prost_replace(&encoded_message, SHORTER)));
+}
+
+// Benchmark for decoding, changing and re-encoding the message with a longer value
+#[bench]
+fn bench_prost_replace_longer(b: &mut Bencher) {
+    let encoded_message = default_message().encode_to_vec();
+
+    b.iter(|| test::black_box(prost_replace(&encoded_message, LONGER)));
+}
@@ -84,14 +84,15 @@ fn prost_replace(encoded_message: &[u8]) -> Vec<u8> {
     out_bytes
 }
 
-fn rustwire_replace(encoded_message: &[u8], user_id: i32) -> Vec<u8> {
+fn rustwire_replace(encoded_message: &[u8]) -> Vec<u8> {
     // first read id from user field
     let user_field = extract_field_by_tag(encoded_message, 5).unwrap();
-    let id = extract_field_by_tag(&user_field, 2).unwrap();
+    let id = extract_field_by_tag(user_field, 2).unwrap();
 
-    let header = create_header(5, Variant::Varint.into(), &id);
-    let replace_with = [header, user_id.to_le_bytes().to_vec()].concat();
-    let result = replace_field_with(&mut encoded_message.to_vec(), 5, &replace_with).unwrap();
+    let header = create_header(5, Variant::Varint.into(), id);
+    let replace_with = [header, id.to_vec()].concat();
+    let mut result = encoded_message.to_vec();
+    replace_field_with(&mut result, 5, &replace_with).unwrap();
     result
 }
 
@@ -101,7 +102,7 @@ fn bench_rustwire_replace(b: &mut Bencher) {
     let encoded_message = default_message().encode_to_vec();
 
     b.iter(|| {
-        let result = extract_field_by_tag(&encoded_message, 5).unwrap();
+        let result = rustwire_replace(&encoded_message);
         test::black_box(result);
     });
 }
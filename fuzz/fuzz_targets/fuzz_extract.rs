@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustwire::{extract_field_by_tag, FieldIterator, MessageIndex};
+
+fuzz_target!(|input: (u64, &[u8])| {
+    let (tag_number, encoded_message) = input;
+
+    if let Some(value) = extract_field_by_tag(encoded_message, tag_number) {
+        // The value must borrow from the message.
+        let message = encoded_message.as_ptr_range();
+        let value = value.as_ptr_range();
+        assert!(message.start <= value.start && value.end <= message.end);
+    }
+
+    // The index must agree with a linear scan over the same fields.
+    let index = MessageIndex::new(encoded_message);
+    let linear: Vec<&[u8]> = FieldIterator::new(encoded_message)
+        .filter(|field| field.tag == tag_number)
+        .map(|field| field.as_bytes())
+        .collect();
+    assert_eq!(index.get(tag_number), linear.first().copied());
+    assert_eq!(index.get_last(tag_number), linear.last().copied());
+    assert_eq!(index.get_all(tag_number), linear.as_slice());
+    assert_eq!(index.has(tag_number), !linear.is_empty());
+});
@@ -0,0 +1,26 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustwire::{extract_field_by_tag, replace_field_with, MessageIndex};
+
+fuzz_target!(|input: (u64, &[u8], &[u8])| {
+    let (tag_number, replace_with, encoded_message) = input;
+    let mut message = encoded_message.to_vec();
+
+    match replace_field_with(&mut message, tag_number, replace_with) {
+        Some(old) => {
+            // The old value is the one the extractor sees.
+            assert_eq!(
+                extract_field_by_tag(encoded_message, tag_number),
+                Some(&old[..])
+            );
+            // The index steps over groups like the extractor, so it sees the same field.
+            assert_eq!(
+                MessageIndex::new(encoded_message).get(tag_number),
+                Some(&old[..])
+            );
+        }
+        // The message is left untouched when nothing is replaced.
+        None => assert_eq!(message, encoded_message),
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustwire::{validate_message, validate_message_recursive, FieldIterator, MessageIndex};
+
+fuzz_target!(|encoded_message: &[u8]| {
+    let _ = validate_message(encoded_message);
+    let _ = validate_message_recursive(encoded_message, 8);
+
+    // Every field seen by a linear scan is indexed.
+    let index = MessageIndex::new(encoded_message);
+    for field in FieldIterator::new(encoded_message) {
+        assert!(index.get_all(field.tag).contains(&field.as_bytes()));
+    }
+});
@@ -0,0 +1,614 @@
+#[cfg(feature = "alloc")]
+use crate::utils::{self, FieldRecord};
+use crate::{encoders, BuilderError, Variant};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+/// Builds an encoded protobuf message from scratch, without a `.proto` schema.
+///
+/// Fields are appended in the order the `add_*` methods are called. Every method returns the
+/// builder, so calls can be chained, and [`finish`](MessageBuilder::finish) returns the encoded
+/// message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::MessageBuilder;
+///
+/// let mut builder = MessageBuilder::new();
+/// builder.add_varint(1, 1).add_string(2, "testing");
+///
+/// assert_eq!(builder.finish(), b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67");
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default)]
+pub struct MessageBuilder {
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl MessageBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        MessageBuilder { buf: Vec::new() }
+    }
+
+    /// Creates an empty builder with room for at least `capacity` bytes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        MessageBuilder {
+            buf: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Reserves room for at least `additional` more bytes, to avoid growing the buffer field by
+    /// field when the size of the rest of the message is known.
+    pub fn reserve(&mut self, additional: usize) {
+        self.buf.reserve(additional);
+    }
+
+    /// Returns the number of bytes encoded so far.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns `true` if no field has been added yet.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Creates a [`StreamingMessageBuilder`] that writes each field straight to `writer` instead
+    /// of buffering the message.
+    #[cfg(feature = "std")]
+    pub fn streaming<W: Write>(writer: W) -> StreamingMessageBuilder<W> {
+        StreamingMessageBuilder::new(writer)
+    }
+
+    /// Adds a varint field (`int32`, `int64`, `uint32`, `uint64`, `bool`, `enum`).
+    pub fn add_varint(&mut self, tag: u64, value: u64) -> &mut Self {
+        self.push_field(tag, Variant::Varint, &encoders::encode_varint(value))
+    }
+
+    /// Adds a zigzag-encoded `sint32` field.
+    pub fn add_sint32(&mut self, tag: u64, value: i32) -> &mut Self {
+        self.add_varint(tag, encoders::encode_zigzag_i32(value))
+    }
+
+    /// Adds a zigzag-encoded `sint64` field.
+    pub fn add_sint64(&mut self, tag: u64, value: i64) -> &mut Self {
+        self.add_varint(tag, encoders::encode_zigzag_i64(value))
+    }
+
+    /// Adds a length-delimited `string` field.
+    pub fn add_string(&mut self, tag: u64, value: &str) -> &mut Self {
+        self.push_field(tag, Variant::LengthDelimited, value.as_bytes())
+    }
+
+    /// Adds a length-delimited `bytes` field.
+    pub fn add_bytes(&mut self, tag: u64, value: &[u8]) -> &mut Self {
+        self.push_field(tag, Variant::LengthDelimited, value)
+    }
+
+    /// Adds a `float` field.
+    pub fn add_float(&mut self, tag: u64, value: f32) -> &mut Self {
+        self.push_field(tag, Variant::ThirtyTwoBit, &encoders::encode_float(value))
+    }
+
+    /// Adds a `double` field.
+    pub fn add_double(&mut self, tag: u64, value: f64) -> &mut Self {
+        self.push_field(tag, Variant::SixtyFourBit, &encoders::encode_double(value))
+    }
+
+    /// Adds a `fixed32` field.
+    pub fn add_fixed32(&mut self, tag: u64, value: u32) -> &mut Self {
+        self.push_field(tag, Variant::ThirtyTwoBit, &encoders::encode_fixed32(value))
+    }
+
+    /// Adds a `fixed64` field.
+    pub fn add_fixed64(&mut self, tag: u64, value: u64) -> &mut Self {
+        self.push_field(tag, Variant::SixtyFourBit, &encoders::encode_fixed64(value))
+    }
+
+    /// Adds an already encoded nested message as a length-delimited field.
+    pub fn add_message(&mut self, tag: u64, nested: &[u8]) -> &mut Self {
+        self.push_field(tag, Variant::LengthDelimited, nested)
+    }
+
+    /// Adds a packed repeated varint field.
+    ///
+    /// An empty `values` slice adds nothing, matching how protobuf encoders omit empty packed
+    /// fields. The same holds for the other `add_packed_*` methods.
+    pub fn add_packed_varint(&mut self, tag: u64, values: &[u64]) -> &mut Self {
+        self.push_packed(tag, &encoders::encode_packed_varint(values))
+    }
+
+    /// Adds a packed repeated zigzag-encoded `sint32` field.
+    pub fn add_packed_sint32(&mut self, tag: u64, values: &[i32]) -> &mut Self {
+        self.push_packed(tag, &encoders::encode_packed_sint32(values))
+    }
+
+    /// Adds a packed repeated zigzag-encoded `sint64` field.
+    pub fn add_packed_sint64(&mut self, tag: u64, values: &[i64]) -> &mut Self {
+        self.push_packed(tag, &encoders::encode_packed_sint64(values))
+    }
+
+    /// Adds a packed repeated `fixed32` field.
+    pub fn add_packed_fixed32(&mut self, tag: u64, values: &[u32]) -> &mut Self {
+        let payload: Vec<u8> = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        self.push_packed(tag, &payload)
+    }
+
+    /// Adds a packed repeated `fixed64` field.
+    pub fn add_packed_fixed64(&mut self, tag: u64, values: &[u64]) -> &mut Self {
+        let payload: Vec<u8> = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        self.push_packed(tag, &payload)
+    }
+
+    /// Adds a packed repeated `float` field.
+    pub fn add_packed_float(&mut self, tag: u64, values: &[f32]) -> &mut Self {
+        self.push_packed(tag, &encoders::encode_packed_float(values))
+    }
+
+    /// Adds a packed repeated `double` field.
+    pub fn add_packed_double(&mut self, tag: u64, values: &[f64]) -> &mut Self {
+        self.push_packed(tag, &encoders::encode_packed_double(values))
+    }
+
+    /// Adds a debug comment as a length-delimited field with [`DEBUG_ANNOTATION_TAG`].
+    ///
+    /// [`DEBUG_ANNOTATION_TAG`]: crate::DEBUG_ANNOTATION_TAG
+    #[cfg(feature = "debug-comments")]
+    pub fn add_unknown_comment(&mut self, comment: &str) -> &mut Self {
+        self.add_string(crate::DEBUG_ANNOTATION_TAG, comment)
+    }
+
+    /// Returns the encoded message.
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn push_field(&mut self, tag: u64, variant: Variant, payload: &[u8]) -> &mut Self {
+        self.buf
+            .extend_from_slice(&crate::create_header(tag, variant.into(), payload));
+        self.buf.extend_from_slice(payload);
+        self
+    }
+
+    /// Adds a packed repeated field, or nothing if `payload` is empty.
+    fn push_packed(&mut self, tag: u64, payload: &[u8]) -> &mut Self {
+        if payload.is_empty() {
+            return self;
+        }
+        self.push_field(tag, Variant::LengthDelimited, payload)
+    }
+}
+
+/// A pre-encoded message whose variable fields are patched for every message built from it.
+///
+/// Systems that emit many similar messages, such as telemetry, spend most of the time of building
+/// each message on encoding the same headers and constant fields. A template encodes the whole
+/// message once, with placeholder values for the variable fields, and
+/// [`instantiate`](MessageTemplate::instantiate) only writes the patched values into a copy of it.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{MessageBuilder, MessageTemplate};
+///
+/// let mut builder = MessageBuilder::new();
+/// builder.add_string(1, "sensor-7").add_varint(2, 0).add_string(3, "");
+/// let template = MessageTemplate::new(builder);
+///
+/// let message = template.instantiate(&[(2, &[0x2a]), (3, b"ok")]).unwrap();
+/// assert_eq!(message, b"\x0a\x08sensor-7\x10\x2a\x1a\x02ok");
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct MessageTemplate {
+    skeleton: Vec<u8>,
+    /// The first occurrence of every field of the skeleton, in order.
+    fields: Vec<FieldRecord>,
+}
+
+#[cfg(feature = "alloc")]
+impl MessageTemplate {
+    /// Creates a template from the message built by `builder`.
+    ///
+    /// The values the builder was given for the variable fields are only placeholders. They end
+    /// up in the messages built from the template for the fields that are not patched.
+    pub fn new(builder: MessageBuilder) -> Self {
+        let skeleton = builder.finish();
+        let mut fields: Vec<FieldRecord> = Vec::new();
+        let mut offset = 0;
+        while let Some(field) = utils::read_field(&skeleton, offset) {
+            if !fields.iter().any(|f| f.field_number == field.field_number) {
+                fields.push(field);
+            }
+            offset = field.end;
+        }
+        MessageTemplate { skeleton, fields }
+    }
+
+    /// Builds a message from the template, replacing the values of the patched fields.
+    ///
+    /// Each patch is a tag number and the new value of the field, in the same form as the values
+    /// returned by [`extract_field_by_tag`](crate::extract_field_by_tag): a new varint value must
+    /// already be varint encoded, and the length prefix of a length-delimited field is written
+    /// automatically.
+    ///
+    /// Returns `None` if a patched value is not a single value of the field's wire type, e.g. an
+    /// unterminated varint or 3 bytes for a `fixed32` field, since the message would no longer
+    /// decode.
+    ///
+    /// # Notes
+    ///
+    /// - Only the first occurrence of a repeated field can be patched.
+    /// - Patches for tags that are not in the template are ignored. If a tag is patched more than
+    ///   once, the first patch is used.
+    pub fn instantiate(&self, patches: &[(u64, &[u8])]) -> Option<Vec<u8>> {
+        let patched_len: usize = patches.iter().map(|(_, value)| value.len()).sum();
+        let mut message = Vec::with_capacity(self.skeleton.len() + patched_len);
+        let mut copied = 0;
+
+        for field in &self.fields {
+            let new_value = match patches.iter().find(|(tag, _)| *tag == field.field_number) {
+                Some((_, new_value)) => new_value,
+                None => continue,
+            };
+            if !utils::value_fits(new_value, field.variant) {
+                return None;
+            }
+            if field.variant == Variant::LengthDelimited {
+                message.extend_from_slice(&self.skeleton[copied..field.start]);
+                let mut header = [0; 20];
+                let header_len = encode_field_header(
+                    field.field_number,
+                    field.variant,
+                    new_value.len(),
+                    &mut header,
+                );
+                message.extend_from_slice(&header[..header_len]);
+            } else {
+                message.extend_from_slice(&self.skeleton[copied..field.value_start]);
+            }
+            message.extend_from_slice(new_value);
+            copied = field.end;
+        }
+
+        message.extend_from_slice(&self.skeleton[copied..]);
+        Some(message)
+    }
+}
+
+/// Builds an encoded protobuf message in a fixed-capacity buffer on the stack.
+///
+/// `MessageBuilderFixed` never allocates: field bytes are written into a `[u8; CAP]` array, and
+/// every `add_*` method returns [`BuilderError::CapacityExceeded`] instead of growing the buffer.
+/// This makes it usable on firmware and WASM targets where heap allocation is unavailable or
+/// undesirable. `CAP` should be chosen based on the largest message you expect to build.
+///
+/// A field that does not fit is not written at all, so the builder stays usable after an error.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::MessageBuilderFixed;
+///
+/// let mut builder = MessageBuilderFixed::<16>::new();
+/// builder.add_varint(1, 1).unwrap().add_string(2, "testing").unwrap();
+///
+/// assert_eq!(builder.build(), b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67");
+/// assert!(builder.add_string(3, "does not fit").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageBuilderFixed<const CAP: usize> {
+    buf: [u8; CAP],
+    len: usize,
+}
+
+impl<const CAP: usize> MessageBuilderFixed<CAP> {
+    /// Creates an empty builder.
+    pub const fn new() -> Self {
+        MessageBuilderFixed {
+            buf: [0; CAP],
+            len: 0,
+        }
+    }
+
+    /// Adds a varint field (`int32`, `int64`, `uint32`, `uint64`, `bool`, `enum`).
+    pub fn add_varint(&mut self, tag: u64, value: u64) -> Result<&mut Self, BuilderError> {
+        let mut payload = [0; 10];
+        let len = encoders::write_varint(value, &mut payload);
+        self.push_field(tag, Variant::Varint, &payload[..len])
+    }
+
+    /// Adds a length-delimited `bytes` field.
+    pub fn add_bytes(&mut self, tag: u64, value: &[u8]) -> Result<&mut Self, BuilderError> {
+        self.push_field(tag, Variant::LengthDelimited, value)
+    }
+
+    /// Adds a length-delimited `string` field.
+    pub fn add_string(&mut self, tag: u64, value: &str) -> Result<&mut Self, BuilderError> {
+        self.push_field(tag, Variant::LengthDelimited, value.as_bytes())
+    }
+
+    /// Adds an already encoded nested message as a length-delimited field.
+    pub fn add_message(&mut self, tag: u64, nested: &[u8]) -> Result<&mut Self, BuilderError> {
+        self.push_field(tag, Variant::LengthDelimited, nested)
+    }
+
+    /// Adds a `float` field.
+    pub fn add_float(&mut self, tag: u64, value: f32) -> Result<&mut Self, BuilderError> {
+        self.push_field(tag, Variant::ThirtyTwoBit, &value.to_le_bytes())
+    }
+
+    /// Adds a `double` field.
+    pub fn add_double(&mut self, tag: u64, value: f64) -> Result<&mut Self, BuilderError> {
+        self.push_field(tag, Variant::SixtyFourBit, &value.to_le_bytes())
+    }
+
+    /// Adds a `fixed32` field.
+    pub fn add_fixed32(&mut self, tag: u64, value: u32) -> Result<&mut Self, BuilderError> {
+        self.push_field(tag, Variant::ThirtyTwoBit, &value.to_le_bytes())
+    }
+
+    /// Adds a `fixed64` field.
+    pub fn add_fixed64(&mut self, tag: u64, value: u64) -> Result<&mut Self, BuilderError> {
+        self.push_field(tag, Variant::SixtyFourBit, &value.to_le_bytes())
+    }
+
+    /// Adds a debug comment as a length-delimited field with [`DEBUG_ANNOTATION_TAG`].
+    ///
+    /// [`DEBUG_ANNOTATION_TAG`]: crate::DEBUG_ANNOTATION_TAG
+    #[cfg(feature = "debug-comments")]
+    pub fn add_unknown_comment(&mut self, comment: &str) -> Result<&mut Self, BuilderError> {
+        self.add_string(crate::DEBUG_ANNOTATION_TAG, comment)
+    }
+
+    /// Returns the encoded message built so far.
+    pub fn build(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    fn push_field(
+        &mut self,
+        tag: u64,
+        variant: Variant,
+        payload: &[u8],
+    ) -> Result<&mut Self, BuilderError> {
+        let mut header = [0; 20];
+        let header_len = encode_field_header(tag, variant, payload.len(), &mut header);
+
+        let required = self.len + header_len + payload.len();
+        if required > CAP {
+            return Err(BuilderError::CapacityExceeded {
+                capacity: CAP,
+                required,
+            });
+        }
+
+        for part in [&header[..header_len], payload] {
+            self.buf[self.len..self.len + part.len()].copy_from_slice(part);
+            self.len += part.len();
+        }
+
+        Ok(self)
+    }
+}
+
+impl<const CAP: usize> Default for MessageBuilderFixed<CAP> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds an encoded protobuf message by writing each field straight to a [`Write`] output.
+///
+/// Unlike [`MessageBuilderFixed`], nothing is buffered: every `add_*` call encodes the field and
+/// writes it to the underlying writer immediately. This keeps memory use flat when building large
+/// messages (e.g. multi-megabyte `bytes` fields) for streaming output. The tradeoff is that fields
+/// cannot be re-ordered or modified once they are written.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::StreamingMessageBuilder;
+///
+/// let mut builder = StreamingMessageBuilder::new(Vec::new());
+/// builder.add_varint(1, 1).unwrap().add_string(2, "testing").unwrap();
+///
+/// let encoded_message = builder.finish().unwrap();
+/// assert_eq!(encoded_message, b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67");
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct StreamingMessageBuilder<W: Write> {
+    writer: W,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> StreamingMessageBuilder<W> {
+    /// Creates a builder writing to `writer`.
+    pub fn new(writer: W) -> Self {
+        StreamingMessageBuilder { writer }
+    }
+
+    /// Writes a varint field (`int32`, `int64`, `uint32`, `uint64`, `bool`, `enum`).
+    pub fn add_varint(&mut self, tag: u64, value: u64) -> io::Result<&mut Self> {
+        let mut payload = [0; 10];
+        let len = encoders::write_varint(value, &mut payload);
+        self.write_field(tag, Variant::Varint, &payload[..len])
+    }
+
+    /// Writes a length-delimited `bytes` field.
+    pub fn add_bytes(&mut self, tag: u64, value: &[u8]) -> io::Result<&mut Self> {
+        self.write_field(tag, Variant::LengthDelimited, value)
+    }
+
+    /// Writes a length-delimited `string` field.
+    pub fn add_string(&mut self, tag: u64, value: &str) -> io::Result<&mut Self> {
+        self.write_field(tag, Variant::LengthDelimited, value.as_bytes())
+    }
+
+    /// Writes an already encoded nested message as a length-delimited field.
+    pub fn add_message(&mut self, tag: u64, nested: &[u8]) -> io::Result<&mut Self> {
+        self.write_field(tag, Variant::LengthDelimited, nested)
+    }
+
+    /// Writes a `float` field.
+    pub fn add_float(&mut self, tag: u64, value: f32) -> io::Result<&mut Self> {
+        self.write_field(tag, Variant::ThirtyTwoBit, &value.to_le_bytes())
+    }
+
+    /// Writes a `double` field.
+    pub fn add_double(&mut self, tag: u64, value: f64) -> io::Result<&mut Self> {
+        self.write_field(tag, Variant::SixtyFourBit, &value.to_le_bytes())
+    }
+
+    /// Writes a `fixed32` field.
+    pub fn add_fixed32(&mut self, tag: u64, value: u32) -> io::Result<&mut Self> {
+        self.write_field(tag, Variant::ThirtyTwoBit, &value.to_le_bytes())
+    }
+
+    /// Writes a `fixed64` field.
+    pub fn add_fixed64(&mut self, tag: u64, value: u64) -> io::Result<&mut Self> {
+        self.write_field(tag, Variant::SixtyFourBit, &value.to_le_bytes())
+    }
+
+    /// Writes a debug comment as a length-delimited field with [`DEBUG_ANNOTATION_TAG`].
+    ///
+    /// [`DEBUG_ANNOTATION_TAG`]: crate::DEBUG_ANNOTATION_TAG
+    #[cfg(feature = "debug-comments")]
+    pub fn add_unknown_comment(&mut self, comment: &str) -> io::Result<&mut Self> {
+        self.add_string(crate::DEBUG_ANNOTATION_TAG, comment)
+    }
+
+    /// Flushes the writer and returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_field(&mut self, tag: u64, variant: Variant, payload: &[u8]) -> io::Result<&mut Self> {
+        let mut header = [0; 20];
+        let header_len = encode_field_header(tag, variant, payload.len(), &mut header);
+
+        self.writer.write_all(&header[..header_len])?;
+        self.writer.write_all(payload)?;
+        Ok(self)
+    }
+}
+
+/// Encodes the tag and, for length-delimited fields, the length prefix of a field into `buf`.
+fn encode_field_header(
+    tag: u64,
+    variant: Variant,
+    payload_len: usize,
+    buf: &mut [u8; 20],
+) -> usize {
+    encoders::encode_header_to_slice(tag, variant, payload_len, buf)
+        .expect("a field header is at most 20 bytes")
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_builder_matches_fixed() {
+        let mut builder = MessageBuilder::new();
+        builder
+            .add_varint(1, 150)
+            .add_string(2, "testing")
+            .add_double(3, 2.5)
+            .add_fixed32(4, 7)
+            .add_packed_varint(5, &[])
+            .add_message(6, &[0x08, 0x01]);
+
+        let mut fixed = MessageBuilderFixed::<64>::new();
+        fixed
+            .add_varint(1, 150)
+            .unwrap()
+            .add_string(2, "testing")
+            .unwrap()
+            .add_double(3, 2.5)
+            .unwrap()
+            .add_fixed32(4, 7)
+            .unwrap()
+            .add_message(6, &[0x08, 0x01])
+            .unwrap();
+
+        assert_eq!(builder.finish(), fixed.build());
+    }
+
+    #[test]
+    fn test_message_builder_len_and_capacity() {
+        let mut builder = MessageBuilder::with_capacity(64);
+        assert!(builder.is_empty());
+        assert_eq!(builder.len(), 0);
+        assert!(builder.buf.capacity() >= 64);
+
+        builder.add_varint(1, 150).add_string(2, "testing");
+        assert!(!builder.is_empty());
+        assert_eq!(builder.len(), 12);
+
+        builder.reserve(1000);
+        assert!(builder.buf.capacity() >= 1012);
+        let capacity = builder.buf.capacity();
+        builder.add_bytes(3, &[0xAB; 900]);
+        assert_eq!(builder.buf.capacity(), capacity);
+        assert_eq!(builder.len(), 915);
+    }
+
+    #[test]
+    fn test_capacity_exceeded() {
+        let mut builder = MessageBuilderFixed::<4>::new();
+        builder.add_varint(1, 150).unwrap();
+
+        let err = builder.add_fixed32(2, 7).unwrap_err();
+        assert_eq!(
+            err,
+            BuilderError::CapacityExceeded {
+                capacity: 4,
+                required: 8
+            }
+        );
+        assert_eq!(builder.build(), [0x08, 0x96, 0x01]);
+    }
+
+    #[test]
+    fn test_streaming_matches_fixed() {
+        let payload = vec![0xAB; 300];
+
+        let mut fixed = MessageBuilderFixed::<512>::new();
+        fixed
+            .add_varint(1, 150)
+            .unwrap()
+            .add_bytes(2, &payload)
+            .unwrap()
+            .add_float(3, 1.5)
+            .unwrap()
+            .add_fixed64(4, u64::MAX)
+            .unwrap();
+
+        let mut streaming = StreamingMessageBuilder::new(Vec::new());
+        streaming
+            .add_varint(1, 150)
+            .unwrap()
+            .add_bytes(2, &payload)
+            .unwrap()
+            .add_float(3, 1.5)
+            .unwrap()
+            .add_fixed64(4, u64::MAX)
+            .unwrap();
+
+        assert_eq!(streaming.finish().unwrap(), fixed.build());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{utils, Variant};
+
+/// A cursor that walks the fields of an encoded protobuf message one step at a time.
+///
+/// Like [`FieldIterator`](crate::FieldIterator), the cursor decodes fields lazily, so creating it
+/// costs nothing. It also supports skipping and seeking, which makes it possible to parse a
+/// message in several steps, e.g. handing some fields to one handler and the rest of the message
+/// to another, without collecting the fields upfront.
+///
+/// Values have the same form as the values returned by
+/// [`extract_field_by_tag`](crate::extract_field_by_tag). Once a field cannot be decoded, the
+/// cursor moves to the end of the message and returns nothing more.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{MessageCursor, Variant};
+///
+/// let encoded_message = b"\x08\x96\x01\x12\x02\x68\x69\x18\x01";
+/// let mut cursor = MessageCursor::new(encoded_message);
+///
+/// assert_eq!(cursor.next_field(), Some((1, Variant::Varint, &[0x96, 0x01][..])));
+/// assert_eq!(cursor.seek_to_tag(3), Some(&[0x01][..]));
+/// assert!(cursor.remaining().is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageCursor<'a> {
+    encoded_message: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> MessageCursor<'a> {
+    /// Creates a cursor positioned at the first field of `encoded_message`.
+    pub fn new(encoded_message: &'a [u8]) -> Self {
+        MessageCursor {
+            encoded_message,
+            offset: 0,
+        }
+    }
+
+    /// Returns the field at the cursor as a `(field_number, variant, value)` tuple and moves past
+    /// it.
+    ///
+    /// Returns `None` at the end of the message or if the field cannot be decoded.
+    pub fn next_field(&mut self) -> Option<(u64, Variant, &'a [u8])> {
+        if self.offset >= self.encoded_message.len() {
+            return None;
+        }
+
+        match utils::read_field(self.encoded_message, self.offset) {
+            Some(field) => {
+                self.offset = field.end;
+                Some((
+                    field.field_number,
+                    field.variant,
+                    &self.encoded_message[field.value_start..field.end],
+                ))
+            }
+            None => {
+                self.offset = self.encoded_message.len();
+                None
+            }
+        }
+    }
+
+    /// Moves past the field at the cursor without returning it.
+    ///
+    /// Returns `true` if a field was skipped.
+    pub fn skip_next(&mut self) -> bool {
+        self.next_field().is_some()
+    }
+
+    /// Moves past fields until one with the given tag number is found, and returns its value.
+    ///
+    /// Only fields after the cursor are searched. The cursor ends up right after the found field,
+    /// so calling `seek_to_tag` again finds the next occurrence. If no field matches, the cursor
+    /// ends up at the end of the message.
+    pub fn seek_to_tag(&mut self, tag: u64) -> Option<&'a [u8]> {
+        while let Some((field_number, _, value)) = self.next_field() {
+            if field_number == tag {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Returns the part of the message after the cursor.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.encoded_message[self.offset..]
+    }
+}
@@ -1,4 +1,21 @@
+use crate::WireError;
+
 pub(crate) fn decode_varint(bytes: &[u8], offset: usize) -> Option<(u64, usize)> {
+    #[cfg(all(
+        feature = "simd",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ))]
+    if let Some(chunk) = bytes.get(offset..).and_then(|rest| rest.first_chunk()) {
+        return simd::decode_varint(chunk).map(|(value, len)| (value, offset + len));
+    }
+
+    decode_varint_scalar(bytes, offset)
+}
+
+/// Decodes a varint one byte at a time.
+#[inline]
+fn decode_varint_scalar(bytes: &[u8], offset: usize) -> Option<(u64, usize)> {
     let mut result = 0u64;
     let mut shift = 0;
     for (i, byte) in bytes.iter().enumerate().skip(offset) {
@@ -14,19 +31,306 @@ pub(crate) fn decode_varint(bytes: &[u8], offset: usize) -> Option<(u64, usize)>
     None
 }
 
-pub(crate) fn decode_float(encoded_message: &[u8], offset: usize) -> Option<usize> {
-    if offset + 4 <= encoded_message.len() {
-        Some(offset + 4)
-    } else {
-        None
+/// Decodes a varint, telling a truncated message apart from an overflowing varint.
+pub(crate) fn decode_varint_checked(
+    bytes: &[u8],
+    offset: usize,
+) -> Result<(u64, usize), WireError> {
+    decode_varint(bytes, offset).ok_or_else(|| {
+        // `decode_varint` only gives up early if ten bytes all have their continuation bit set.
+        if bytes.len().saturating_sub(offset) >= 10 {
+            WireError::VarintOverflow { offset }
+        } else {
+            WireError::TruncatedMessage { offset }
+        }
+    })
+}
+
+/// Returns the raw bytes of the varint starting at `offset`, without decoding its value.
+///
+/// The returned slice ends with the first byte that has its continuation bit cleared, so it can be
+/// copied verbatim into another message. This is cheaper than decoding the varint when only its
+/// bytes are needed, e.g. when splicing fields between messages.
+///
+/// # Arguments
+///
+/// * `bytes` - The buffer containing the varint.
+/// * `offset` - The offset of the first byte of the varint.
+///
+/// # Returns
+///
+/// The varint bytes, or `None` if the varint is truncated or longer than 10 bytes.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::varint_byte_slice;
+///
+/// let bytes = [0x08, 0x96, 0x01, 0x10];
+/// assert_eq!(varint_byte_slice(&bytes, 1), Some(&[0x96, 0x01][..]));
+/// ```
+pub fn varint_byte_slice(bytes: &[u8], offset: usize) -> Option<&[u8]> {
+    let rest = bytes.get(offset..)?;
+    rest.iter()
+        .take(10)
+        .position(|byte| byte & 0x80 == 0)
+        .map(|last| &rest[..=last])
+}
+
+/// Decodes the varint at the start of `buf` and returns it with the bytes following it.
+///
+/// This is the slice-based counterpart of the offset-based decoding used elsewhere in the crate,
+/// for parsing a buffer by progressively consuming it.
+///
+/// # Returns
+///
+/// The decoded value and the rest of `buf`, or `None` if the varint is truncated or longer than 10
+/// bytes.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_varint_from_slice;
+///
+/// let (value, rest) = decode_varint_from_slice(&[0x96, 0x01, 0x08]).unwrap();
+/// assert_eq!((value, rest), (150, &[0x08][..]));
+/// assert_eq!(decode_varint_from_slice(&[0x96]), None);
+/// ```
+pub fn decode_varint_from_slice(buf: &[u8]) -> Option<(u64, &[u8])> {
+    decode_varint(buf, 0).map(|(value, len)| (value, &buf[len..]))
+}
+
+/// Decodes a zigzag-encoded `sint32` value.
+///
+/// This is the inverse of [`encode_zigzag_i32`](crate::encode_zigzag_i32). Only the lower 32 bits
+/// of `value` are used.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_zigzag_i32;
+///
+/// assert_eq!(decode_zigzag_i32(1), -1);
+/// assert_eq!(decode_zigzag_i32(2), 1);
+/// ```
+pub fn decode_zigzag_i32(value: u64) -> i32 {
+    let value = value as u32;
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+/// Decodes a zigzag-encoded `sint64` value.
+///
+/// This is the inverse of [`encode_zigzag_i64`](crate::encode_zigzag_i64).
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_zigzag_i64;
+///
+/// assert_eq!(decode_zigzag_i64(u64::MAX), i64::MIN);
+/// ```
+pub fn decode_zigzag_i64(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Returns the field number encoded in a field tag.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::field_number_from_tag;
+///
+/// assert_eq!(field_number_from_tag(0x12), 2);
+/// ```
+pub const fn field_number_from_tag(tag: u64) -> u64 {
+    tag >> 3
+}
+
+/// Returns the wire type encoded in a field tag.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::wire_type_from_tag;
+///
+/// assert_eq!(wire_type_from_tag(0x12), 2);
+/// ```
+pub const fn wire_type_from_tag(tag: u64) -> u64 {
+    tag & 0x07
+}
+
+/// Returns a human-readable name for a wire type.
+///
+/// Unlike [`Variant`](crate::Variant), this also names the deprecated group wire types. Values
+/// that are not a wire type are named `"unknown"`.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::wire_type_name;
+///
+/// assert_eq!(wire_type_name(2), "length-delimited");
+/// assert_eq!(wire_type_name(3), "start-group");
+/// assert_eq!(wire_type_name(7), "unknown");
+/// ```
+pub const fn wire_type_name(wire_type: u64) -> &'static str {
+    match wire_type {
+        0 => "varint",
+        1 => "64-bit",
+        2 => "length-delimited",
+        3 => "start-group",
+        4 => "end-group",
+        5 => "32-bit",
+        _ => "unknown",
     }
 }
 
+pub(crate) fn decode_float(encoded_message: &[u8], offset: usize) -> Option<usize> {
+    offset
+        .checked_add(4)
+        .filter(|end| *end <= encoded_message.len())
+}
+
 pub(crate) fn decode_double(encoded_message: &[u8], offset: usize) -> Option<usize> {
-    if offset + 8 <= encoded_message.len() {
-        Some(offset + 8)
-    } else {
-        None
+    offset
+        .checked_add(8)
+        .filter(|end| *end <= encoded_message.len())
+}
+
+/// Decodes a length-delimited value: a varint length followed by that many bytes.
+///
+/// # Arguments
+///
+/// * `buf` - The buffer containing the value.
+/// * `offset` - The offset of the length prefix, i.e. right after the field's tag.
+///
+/// # Returns
+///
+/// The payload without its length prefix and the offset right after it, or `None` if the length
+/// prefix cannot be decoded or `buf` holds fewer bytes than it announces.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_length_delimited;
+///
+/// let buf = b"\x12\x02\x68\x69\x08\x01";
+/// assert_eq!(decode_length_delimited(buf, 1), Some((&b"hi"[..], 4)));
+/// assert_eq!(decode_length_delimited(b"\x12\x05\x68", 1), None);
+/// ```
+pub fn decode_length_delimited(buf: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let (length, value_start) = decode_varint(buf, offset)?;
+    let end = value_start.checked_add(usize::try_from(length).ok()?)?;
+    buf.get(value_start..end).map(|value| (value, end))
+}
+
+/// Decodes the value of a field with the given wire type.
+///
+/// This is the dispatcher behind [`extract_field_by_tag`](crate::extract_field_by_tag), for
+/// building custom traversal loops. The returned value has the same form as the values returned by
+/// `extract_field_by_tag`: the raw varint bytes, the payload without its length prefix, or the raw
+/// 4 or 8 bytes of a fixed-width value.
+///
+/// # Arguments
+///
+/// * `buf` - The buffer containing the field.
+/// * `wire_type` - The wire type of the field, as returned by [`wire_type_from_tag`].
+/// * `offset` - The offset of the value, i.e. right after the field's tag.
+///
+/// # Returns
+///
+/// The value and the offset right after it, or `None` if the value is truncated or the wire type
+/// is not 0, 1, 2 or 5. Groups (wire types 3 and 4) cannot be decoded without their field
+/// number and are not supported.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{decode_field_value, decode_varint_from_slice, wire_type_from_tag};
+///
+/// let buf = b"\x08\x96\x01\x12\x02\x68\x69";
+/// let (tag, _) = decode_varint_from_slice(buf).unwrap();
+/// assert_eq!(decode_field_value(buf, wire_type_from_tag(tag), 1), Some((&[0x96, 0x01][..], 3)));
+/// assert_eq!(decode_field_value(buf, 2, 4), Some((&b"hi"[..], 7)));
+/// assert_eq!(decode_field_value(buf, 3, 1), None);
+/// ```
+pub fn decode_field_value(buf: &[u8], wire_type: u64, offset: usize) -> Option<(&[u8], usize)> {
+    let end = match wire_type {
+        0 => decode_varint(buf, offset)?.1,
+        1 => decode_double(buf, offset)?,
+        2 => return decode_length_delimited(buf, offset),
+        5 => decode_float(buf, offset)?,
+        _ => return None,
+    };
+    Some((&buf[offset..end], end))
+}
+
+/// Vectorized varint decoding.
+///
+/// Instead of checking the continuation bit of one byte at a time, 16 bytes are loaded into an SSE2
+/// register and `pmovmskb` collects all their continuation bits at once, which gives the length of
+/// the varint without branching on every byte. The 7-bit groups of the varint are then packed
+/// together with a few shifts and masks. A varint is at most 10 bytes long, so one 16-byte load is
+/// always enough and wider AVX2 registers would not help.
+///
+/// This is only used when at least 16 bytes are left in the buffer. Shorter tails go through the
+/// byte-at-a-time loop in [`decode_varint`].
+#[cfg(all(
+    feature = "simd",
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+mod simd {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::{__m128i, _mm_loadu_si128, _mm_movemask_epi8};
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_movemask_epi8};
+
+    /// Decodes the varint at the start of `chunk`, returning its value and length.
+    ///
+    /// Behaves exactly like the scalar loop: `None` is returned if the first 10 bytes all have
+    /// their continuation bit set, and bits past the 64th are dropped.
+    #[inline]
+    pub(super) fn decode_varint(chunk: &[u8; 16]) -> Option<(u64, usize)> {
+        if chunk[0] & 0x80 == 0 {
+            return Some((chunk[0] as u64, 1));
+        }
+
+        // SAFETY: SSE2 is enabled at compile time, `chunk` is 16 bytes long, and `_mm_loadu_si128`
+        // has no alignment requirement.
+        let continuation_bits =
+            unsafe { _mm_movemask_epi8(_mm_loadu_si128(chunk.as_ptr().cast::<__m128i>())) } as u32;
+        // Bits 16 and up of the inverted mask are always set, so `len` is at most 17.
+        let len = (!continuation_bits).trailing_zeros() as usize + 1;
+
+        let low_word = u64::from_le_bytes([
+            chunk[0], chunk[1], chunk[2], chunk[3], chunk[4], chunk[5], chunk[6], chunk[7],
+        ]);
+        match len {
+            2..=7 => Some((pack_groups(low_word & ((1 << (8 * len)) - 1)), len)),
+            8 => Some((pack_groups(low_word), len)),
+            9 => Some((
+                pack_groups(low_word) | ((chunk[8] & 0x7F) as u64) << 56,
+                len,
+            )),
+            10 => Some((
+                pack_groups(low_word)
+                    | ((chunk[8] & 0x7F) as u64) << 56
+                    | ((chunk[9] & 0x7F) as u64) << 63,
+                len,
+            )),
+            _ => None,
+        }
+    }
+
+    /// Packs the low 7 bits of each of the 8 bytes of `word` into a 56-bit value, with the first
+    /// byte in the least significant bits.
+    #[inline]
+    fn pack_groups(word: u64) -> u64 {
+        let word = word & 0x7F7F_7F7F_7F7F_7F7F;
+        let word = (word & 0x007F_007F_007F_007F) | ((word & 0x7F00_7F00_7F00_7F00) >> 1);
+        let word = (word & 0x0000_3FFF_0000_3FFF) | ((word & 0x3FFF_0000_3FFF_0000) >> 2);
+        (word & 0x0000_0000_0FFF_FFFF) | ((word & 0x0FFF_FFFF_0000_0000) >> 4)
     }
 }
 
@@ -42,6 +346,78 @@ mod tests {
         assert_eq!(new_offset, 2);
     }
 
+    #[cfg(all(
+        feature = "simd",
+        any(target_arch = "x86", target_arch = "x86_64"),
+        target_feature = "sse2"
+    ))]
+    #[test]
+    fn test_simd_matches_scalar() {
+        let check = |chunk: [u8; 16]| {
+            assert_eq!(
+                simd::decode_varint(&chunk),
+                decode_varint_scalar(&chunk, 0),
+                "{:02x?}",
+                chunk
+            );
+        };
+
+        for len in 1..=11 {
+            for last in [0x00, 0x01, 0x7F] {
+                for filler in [0x80, 0xFF] {
+                    let mut chunk = [0xAB; 16];
+                    chunk[..len].fill(filler);
+                    chunk[len - 1] = last;
+                    check(chunk);
+                }
+            }
+        }
+        // Every continuation bit set.
+        check([0xFF; 16]);
+    }
+
+    #[test]
+    fn test_decode_varint_checked() {
+        assert_eq!(decode_varint_checked(&[0x96, 0x01], 0), Ok((150, 2)));
+        assert_eq!(
+            decode_varint_checked(&[0x08, 0x96], 1),
+            Err(WireError::TruncatedMessage { offset: 1 })
+        );
+        assert_eq!(
+            decode_varint_checked(&[0xFF; 10], 0),
+            Err(WireError::VarintOverflow { offset: 0 })
+        );
+    }
+
+    #[test]
+    fn test_varint_byte_slice() {
+        let bytes = [0x96, 0x01, 0x2A];
+        assert_eq!(varint_byte_slice(&bytes, 0), Some(&bytes[..2]));
+        assert_eq!(varint_byte_slice(&bytes, 2), Some(&bytes[2..]));
+        assert_eq!(varint_byte_slice(&bytes, 3), None);
+        assert_eq!(varint_byte_slice(&[0x80, 0x80], 0), None);
+        assert_eq!(varint_byte_slice(&[0xFF; 11], 0), None);
+    }
+
+    #[test]
+    fn test_zigzag_round_trip() {
+        use crate::{encode_zigzag_i32, encode_zigzag_i64};
+
+        for value in [i32::MIN, -1, 0, 1, i32::MAX] {
+            assert_eq!(decode_zigzag_i32(encode_zigzag_i32(value)), value);
+            assert_eq!(
+                decode_zigzag_i64(encode_zigzag_i64(value as i64)),
+                value as i64
+            );
+        }
+        for value in [i64::MIN, i64::MAX] {
+            assert_eq!(decode_zigzag_i64(encode_zigzag_i64(value)), value);
+        }
+
+        assert_eq!(encode_zigzag_i32(i32::MAX), 0xFFFF_FFFE);
+        assert_eq!(encode_zigzag_i32(i32::MIN), 0xFFFF_FFFF);
+    }
+
     #[test]
     fn test_decode_float() {
         let bytes = [0x00, 0x00, 0x48, 0x40];
@@ -55,4 +431,40 @@ mod tests {
         let new_offset = decode_double(&bytes, 0).unwrap();
         assert_eq!(new_offset, 8);
     }
+
+    #[test]
+    fn test_decode_length_delimited() {
+        let bytes = [0x0A, 0x03, 0x61, 0x62, 0x63, 0x0A, 0x00];
+        assert_eq!(decode_length_delimited(&bytes, 1), Some((&bytes[2..5], 5)));
+        assert_eq!(decode_length_delimited(&bytes, 6), Some((&bytes[7..], 7)));
+        assert_eq!(decode_length_delimited(&bytes[..4], 1), None);
+        assert_eq!(decode_length_delimited(&[0xFF; 11], 0), None);
+        // A length that does not fit in the address space is rejected rather than overflowing.
+        let huge = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+        assert_eq!(decode_length_delimited(&huge, 0), None);
+    }
+
+    #[test]
+    fn test_decode_field_value() {
+        let bytes = [0x96, 0x01, 0x00, 0x00, 0x48, 0x40, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(decode_field_value(&bytes, 0, 0), Some((&bytes[..2], 2)));
+        assert_eq!(decode_field_value(&bytes, 5, 2), Some((&bytes[2..6], 6)));
+        assert_eq!(decode_field_value(&bytes, 1, 2), Some((&bytes[2..], 10)));
+        assert_eq!(decode_field_value(&bytes, 1, 3), None);
+        assert_eq!(
+            decode_field_value(&[0x01, 0x2A], 2, 0),
+            Some((&[0x2A][..], 2))
+        );
+        for wire_type in [3, 4, 6, 7] {
+            assert_eq!(decode_field_value(&bytes, wire_type, 0), None);
+        }
+    }
+    #[test]
+    fn test_decode_field_value_huge_offset() {
+        let bytes = [0x00; 4];
+        for wire_type in [0, 1, 2, 5] {
+            assert_eq!(decode_field_value(&bytes, wire_type, usize::MAX - 1), None);
+            assert_eq!(decode_field_value(&bytes, wire_type, usize::MAX), None);
+        }
+    }
 }
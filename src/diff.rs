@@ -0,0 +1,319 @@
+use crate::{decoders, encoders, utils, MessageIndex, Variant, WireError};
+use std::collections::{HashMap, HashSet};
+
+/// A change to one occurrence of a field between two encoded messages, as found by
+/// [`diff_messages`].
+///
+/// Occurrences of a repeated field are compared by position: `position` is the index of the
+/// occurrence among the occurrences of its tag number, counting from 0. Values have the same form
+/// as the values returned by [`extract_field_by_tag`](crate::extract_field_by_tag).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldChange<'a> {
+    /// The occurrence is only present in the second message.
+    Added {
+        tag: u64,
+        position: usize,
+        wire_type: Variant,
+        value: &'a [u8],
+    },
+    /// The occurrence is only present in the first message.
+    Removed {
+        tag: u64,
+        position: usize,
+        value: &'a [u8],
+    },
+    /// The occurrence is present in both messages, with a different value or wire type.
+    Modified {
+        tag: u64,
+        position: usize,
+        wire_type: Variant,
+        old: &'a [u8],
+        new: &'a [u8],
+    },
+}
+
+/// Compares two encoded protobuf messages field by field.
+///
+/// Both messages are indexed with [`MessageIndex`], and the occurrences of every tag number are
+/// compared by position. Since positions are compared, a changed occurrence in the middle of a
+/// repeated field shows up as [`FieldChange::Modified`], and a repeated field that got longer or
+/// shorter shows up as [`FieldChange::Added`] or [`FieldChange::Removed`] occurrences at its end.
+/// The order of fields with different tag numbers is not compared.
+///
+/// # Returns
+///
+/// * `Ok(changes)` - The changes, sorted by tag number and then by position. The list is empty if
+///   both messages hold the same fields.
+/// * `Err(WireError)` - Either message could not be decoded completely.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{diff_messages, FieldChange, Variant};
+///
+/// let before = b"\x08\x01\x12\x02\x68\x69";
+/// let after = b"\x08\x02\x1a\x01\x21";
+///
+/// assert_eq!(
+///     diff_messages(before, after).unwrap(),
+///     [
+///         FieldChange::Modified {
+///             tag: 1,
+///             position: 0,
+///             wire_type: Variant::Varint,
+///             old: &[0x01],
+///             new: &[0x02],
+///         },
+///         FieldChange::Removed { tag: 2, position: 0, value: b"hi" },
+///         FieldChange::Added {
+///             tag: 3,
+///             position: 0,
+///             wire_type: Variant::LengthDelimited,
+///             value: b"!",
+///         },
+///     ]
+/// );
+/// assert!(diff_messages(before, b"\x08").is_err());
+/// ```
+///
+/// # Notes
+///
+/// - Groups (wire type 3) are not compared and never show up as changes. An empty diff therefore
+///   does not imply [`messages_equal`], which does compare groups, and [`apply_diff`] cannot add,
+///   remove or change a group.
+pub fn diff_messages<'a>(
+    before: &'a [u8],
+    after: &'a [u8],
+) -> Result<Vec<FieldChange<'a>>, WireError> {
+    let before = MessageIndex::new_checked(before)?;
+    let after = MessageIndex::new_checked(after)?;
+
+    let mut tags: Vec<u64> = before.tag_numbers().chain(after.tag_numbers()).collect();
+    tags.sort_unstable();
+    tags.dedup();
+
+    let mut changes = Vec::new();
+    for tag in tags {
+        let (old_values, old_variants) = (before.get_all(tag), before.get_all_variants(tag));
+        let (new_values, new_variants) = (after.get_all(tag), after.get_all_variants(tag));
+
+        for position in 0..old_values.len().max(new_values.len()) {
+            let change = match (old_values.get(position), new_values.get(position)) {
+                (Some(&old), Some(&new)) => {
+                    if old == new && old_variants[position] == new_variants[position] {
+                        continue;
+                    }
+                    FieldChange::Modified {
+                        tag,
+                        position,
+                        wire_type: new_variants[position],
+                        old,
+                        new,
+                    }
+                }
+                (Some(&value), None) => FieldChange::Removed {
+                    tag,
+                    position,
+                    value,
+                },
+                (None, Some(&value)) => FieldChange::Added {
+                    tag,
+                    position,
+                    wire_type: new_variants[position],
+                    value,
+                },
+                (None, None) => unreachable!("position is below the length of one of the lists"),
+            };
+            changes.push(change);
+        }
+    }
+    Ok(changes)
+}
+
+/// Checks whether two encoded protobuf messages hold the same fields, without decoding them.
+///
+/// Both messages are indexed with [`MessageIndex`], and every tag number must have the same
+/// occurrences, with the same wire types and values, in both. Fields with different tag numbers
+/// may appear in any order, since protobuf does not guarantee an order, but the occurrences of a
+/// repeated field must be in the same order.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::messages_equal;
+///
+/// // The same two fields, in a different order.
+/// assert!(messages_equal(b"\x08\x01\x12\x02\x68\x69", b"\x12\x02\x68\x69\x08\x01"));
+/// assert!(!messages_equal(b"\x08\x01\x08\x02", b"\x08\x02\x08\x01"));
+/// ```
+///
+/// # Notes
+///
+/// - This is structural equality on the encoded values, not protobuf's semantic equality, which
+///   needs the schema and a full decode. Floats are compared bit for bit, so a `NaN` equals itself
+///   and `0.0` differs from `-0.0`. A field explicitly set to its default value differs from an
+///   absent field, and values encoded differently, such as a non-minimal varint, differ even if
+///   they decode to the same value.
+/// - Nested messages are compared byte for byte, so their fields must be in the same order.
+/// - Groups (wire type 3) are compared byte for byte, with the same ordering rules as fields.
+///   [`diff_messages`] does not compare groups, so two messages with an empty diff are not equal
+///   if their groups differ.
+/// - If either message cannot be decoded completely, `false` is returned, even for two identical
+///   malformed messages.
+pub fn messages_equal(a: &[u8], b: &[u8]) -> bool {
+    let (index_a, index_b) = match (MessageIndex::new_checked(a), MessageIndex::new_checked(b)) {
+        (Ok(index_a), Ok(index_b)) => (index_a, index_b),
+        _ => return false,
+    };
+
+    index_a.tag_numbers().count() == index_b.tag_numbers().count()
+        && index_a.tag_numbers().all(|tag| {
+            index_a.get_all(tag) == index_b.get_all(tag)
+                && index_a.get_all_variants(tag) == index_b.get_all_variants(tag)
+        })
+        && sorted_groups(a) == sorted_groups(b)
+}
+
+/// Collects the groups of a message as `(field_number, group)` pairs, sorted by field number and
+/// in message order within each field number. `MessageIndex` does not index groups.
+fn sorted_groups(encoded_message: &[u8]) -> Vec<(u64, &[u8])> {
+    let mut groups = Vec::new();
+    let mut offset = 0;
+    while offset < encoded_message.len() {
+        let (tag, value_start) = match decoders::decode_varint(encoded_message, offset) {
+            Some(decoded) => decoded,
+            None => break,
+        };
+        let field_number = decoders::field_number_from_tag(tag);
+        let wire_type = decoders::wire_type_from_tag(tag);
+        let end = match utils::skip_field(encoded_message, field_number, wire_type, value_start) {
+            Some(end) => end,
+            None => break,
+        };
+        if wire_type == 3 {
+            groups.push((field_number, &encoded_message[offset..end]));
+        }
+        offset = end;
+    }
+    // A stable sort keeps repeated groups in message order.
+    groups.sort_by_key(|(field_number, _)| *field_number);
+    groups
+}
+
+/// Checks whether a field has the same occurrences in two encoded protobuf messages.
+///
+/// The occurrences of the tag number are compared in order, by wire type and value, like in
+/// [`messages_equal`]. A field that is absent from both messages is equal. If either message
+/// cannot be decoded completely, `false` is returned.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::field_equal;
+///
+/// let a = b"\x08\x01\x12\x02\x68\x69";
+/// let b = b"\x12\x02\x68\x69\x08\x02";
+///
+/// assert!(field_equal(a, b, 2));
+/// assert!(!field_equal(a, b, 1));
+/// assert!(field_equal(a, b, 3));
+/// ```
+pub fn field_equal(a: &[u8], b: &[u8], tag: u64) -> bool {
+    match (MessageIndex::new_checked(a), MessageIndex::new_checked(b)) {
+        (Ok(a), Ok(b)) => {
+            a.get_all(tag) == b.get_all(tag) && a.get_all_variants(tag) == b.get_all_variants(tag)
+        }
+        _ => false,
+    }
+}
+
+/// Applies changes found by [`diff_messages`] to an encoded protobuf message.
+///
+/// Every field of `base` is copied as is, except that [`FieldChange::Modified`] occurrences are
+/// re-encoded with their new value and [`FieldChange::Removed`] occurrences are dropped. The
+/// [`FieldChange::Added`] occurrences are appended at the end, in the order of `diff`.
+///
+/// Applying `diff_messages(before, after)` to `before` gives a message with the same fields as
+/// `after`, and the same order within every repeated field. Fields with different tag numbers may
+/// be ordered differently than in `after`, which protobuf parsers ignore.
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` - The patched message.
+/// * `Err(WireError)` - `base` could not be decoded completely.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{apply_diff, diff_messages};
+///
+/// let before = b"\x08\x01\x12\x02\x68\x69";
+/// let after = b"\x08\x02\x1a\x01\x21";
+///
+/// let diff = diff_messages(before, after).unwrap();
+/// assert_eq!(apply_diff(before, &diff).unwrap(), after);
+/// ```
+///
+/// # Notes
+///
+/// - Groups (wire type 3) in `base` are copied as they are, since [`diff_messages`] does not
+///   compare them.
+pub fn apply_diff(base: &[u8], diff: &[FieldChange]) -> Result<Vec<u8>, WireError> {
+    let mut removed = HashSet::new();
+    let mut modified = HashMap::new();
+    for change in diff {
+        match *change {
+            FieldChange::Removed { tag, position, .. } => {
+                removed.insert((tag, position));
+            }
+            FieldChange::Modified {
+                tag,
+                position,
+                wire_type,
+                new,
+                ..
+            } => {
+                modified.insert((tag, position), (wire_type, new));
+            }
+            FieldChange::Added { .. } => {}
+        }
+    }
+
+    let mut result = Vec::with_capacity(base.len());
+    let mut positions = HashMap::new();
+    let mut offset = 0;
+    while let Some(field) = utils::read_field_checked(base, offset)? {
+        // Groups are not compared, so the ones stepped over are kept as they are.
+        result.extend_from_slice(&base[offset..field.start]);
+        offset = field.end;
+
+        let position = positions.entry(field.field_number).or_insert(0);
+        let key = (field.field_number, *position);
+        *position += 1;
+
+        if removed.contains(&key) {
+            continue;
+        }
+        match modified.get(&key) {
+            Some(&(wire_type, value)) => {
+                result.extend(encoders::encode_field(field.field_number, wire_type, value));
+            }
+            None => result.extend_from_slice(&base[field.start..field.end]),
+        }
+    }
+    // Groups after the last field.
+    result.extend_from_slice(&base[offset..]);
+
+    for change in diff {
+        if let FieldChange::Added {
+            tag,
+            wire_type,
+            value,
+            ..
+        } = *change
+        {
+            result.extend(encoders::encode_field(tag, wire_type, value));
+        }
+    }
+    Ok(result)
+}
@@ -1,3 +1,7 @@
+use crate::Variant;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
 /// Encodes a 64-bit unsigned integer (`u64`) into its varint representation.
 ///
 /// Varints are a compact encoding scheme for integers that uses a variable number of bytes
@@ -27,6 +31,7 @@
 /// let encoded = encode_varint(value);
 /// assert_eq!(encoded, vec![0x2A]);
 /// ```
+#[cfg(feature = "alloc")]
 pub fn encode_varint(value: u64) -> Vec<u8> {
     let mut buffer = Vec::new();
     let mut value = value;
@@ -49,6 +54,23 @@ pub fn encode_varint(value: u64) -> Vec<u8> {
     buffer
 }
 
+/// Encodes a boolean into its varint representation.
+///
+/// Protobuf encodes `bool` fields as varints where `0` means `false` and `1` means `true`.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_bool;
+///
+/// assert_eq!(encode_bool(true), vec![0x01]);
+/// assert_eq!(encode_bool(false), vec![0x00]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_bool(value: bool) -> Vec<u8> {
+    encode_varint(value as u64)
+}
+
 /// Encodes a single-precision floating-point number (`f32`) into its binary representation.
 ///
 /// The encoding process converts the `f32` value into its little-endian byte representation
@@ -71,6 +93,7 @@ pub fn encode_varint(value: u64) -> Vec<u8> {
 /// let encoded = encode_float(value);
 /// assert_eq!(encoded, vec![0xC3, 0xF5, 0x48, 0x40]);
 /// ```
+#[cfg(feature = "alloc")]
 pub fn encode_float(value: f32) -> Vec<u8> {
     let mut buffer = Vec::new();
     buffer.extend_from_slice(&value.to_le_bytes());
@@ -99,15 +122,630 @@ pub fn encode_float(value: f32) -> Vec<u8> {
 /// let encoded = encode_double(value);
 /// assert_eq!(encoded, vec![0x90, 0xF7, 0xAA, 0x95, 0x9, 0xBF, 0x5, 0x40]);
 /// ```
+#[cfg(feature = "alloc")]
 pub fn encode_double(value: f64) -> Vec<u8> {
     let mut buffer = Vec::new();
     buffer.extend_from_slice(&value.to_le_bytes());
     buffer
 }
 
-#[cfg(test)]
+/// Encodes a `fixed32` value into its 4-byte little-endian representation.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_fixed32;
+///
+/// assert_eq!(encode_fixed32(1), vec![0x01, 0x00, 0x00, 0x00]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_fixed32(value: u32) -> Vec<u8> {
+    value.to_le_bytes().to_vec()
+}
+
+/// Encodes a `fixed64` value into its 8-byte little-endian representation.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_fixed64;
+///
+/// assert_eq!(encode_fixed64(1), vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_fixed64(value: u64) -> Vec<u8> {
+    value.to_le_bytes().to_vec()
+}
+
+/// Encodes an `sfixed32` value into its 4-byte little-endian two's complement representation.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_sfixed32;
+///
+/// assert_eq!(encode_sfixed32(-1), vec![0xFF, 0xFF, 0xFF, 0xFF]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_sfixed32(value: i32) -> Vec<u8> {
+    value.to_le_bytes().to_vec()
+}
+
+/// Encodes an `sfixed64` value into its 8-byte little-endian two's complement representation.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_sfixed64;
+///
+/// assert_eq!(encode_sfixed64(-2), vec![0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_sfixed64(value: i64) -> Vec<u8> {
+    value.to_le_bytes().to_vec()
+}
+
+/// Zigzag-encodes a 32-bit signed integer, as used by `sint32` fields.
+///
+/// Zigzag encoding maps signed integers to unsigned ones so that values with a small absolute value
+/// (including negative ones) get a small varint encoding: `0 -> 0`, `-1 -> 1`, `1 -> 2`, `-2 -> 3`,
+/// and so on. The result is meant to be passed to [`encode_varint`].
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_varint, encode_zigzag_i32};
+///
+/// assert_eq!(encode_zigzag_i32(-1), 1);
+/// assert_eq!(encode_varint(encode_zigzag_i32(-64)), vec![0x7F]);
+/// ```
+pub fn encode_zigzag_i32(value: i32) -> u64 {
+    ((value << 1) ^ (value >> 31)) as u32 as u64
+}
+
+/// Zigzag-encodes a 64-bit signed integer, as used by `sint64` fields.
+///
+/// See [`encode_zigzag_i32`] for how zigzag encoding works.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_zigzag_i64;
+///
+/// assert_eq!(encode_zigzag_i64(i64::MIN), u64::MAX);
+/// ```
+pub fn encode_zigzag_i64(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+/// Encodes an `int32` value as a varint.
+///
+/// Negative values are sign-extended to 64 bits before they are encoded, so they always take 10
+/// bytes. This matches the official protobuf libraries and lets `int32` and `int64` fields be
+/// read interchangeably.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_int32;
+///
+/// assert_eq!(encode_int32(150), vec![0x96, 0x01]);
+/// assert_eq!(encode_int32(-1).len(), 10);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_int32(value: i32) -> Vec<u8> {
+    encode_int64(i64::from(value))
+}
+
+/// Encodes an `int64` value as a varint of its two's complement representation.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_int64;
+///
+/// assert_eq!(encode_int64(1), vec![0x01]);
+/// assert_eq!(encode_int64(-1), vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_int64(value: i64) -> Vec<u8> {
+    encode_varint(value as u64)
+}
+
+/// Encodes an `sint32` value as a zigzag-encoded varint.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_sint32;
+///
+/// assert_eq!(encode_sint32(-1), vec![0x01]);
+/// assert_eq!(encode_sint32(1), vec![0x02]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_sint32(value: i32) -> Vec<u8> {
+    encode_varint(encode_zigzag_i32(value))
+}
+
+/// Encodes an `sint64` value as a zigzag-encoded varint.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_sint64;
+///
+/// assert_eq!(encode_sint64(-64), vec![0x7F]);
+/// assert_eq!(encode_sint64(64), vec![0x80, 0x01]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_sint64(value: i64) -> Vec<u8> {
+    encode_varint(encode_zigzag_i64(value))
+}
+
+/// Encodes the elements of a packed repeated varint field (`repeated int64`, `uint64`, `bool`, ...).
+///
+/// The result is the payload of the length-delimited field, without a tag or length prefix: the
+/// varint encoding of every value, back to back.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_packed_varint;
+///
+/// assert_eq!(encode_packed_varint(&[3, 270]), vec![0x03, 0x8E, 0x02]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_packed_varint(values: &[u64]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(values.len());
+    let mut varint = [0; 10];
+    for value in values {
+        let len = write_varint(*value, &mut varint);
+        buffer.extend_from_slice(&varint[..len]);
+    }
+    buffer
+}
+
+/// Encodes the elements of a packed `repeated float` field.
+///
+/// The result is the payload of the length-delimited field: the 4-byte little-endian encoding of
+/// every value, back to back.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_packed_float;
+///
+/// assert_eq!(encode_packed_float(&[1.0, -2.0]), vec![0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x00, 0xC0]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_packed_float(values: &[f32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+/// Encodes the elements of a packed `repeated double` field.
+///
+/// The result is the payload of the length-delimited field: the 8-byte little-endian encoding of
+/// every value, back to back.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_packed_double;
+///
+/// assert_eq!(encode_packed_double(&[1.0]), vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_packed_double(values: &[f64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+/// Encodes the elements of a packed `repeated sint32` field.
+///
+/// The result is the payload of the length-delimited field: the zigzag-encoded varint of every
+/// value, back to back.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_packed_sint32;
+///
+/// assert_eq!(encode_packed_sint32(&[0, -1, 1, -300]), vec![0x00, 0x01, 0x02, 0xD7, 0x04]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_packed_sint32(values: &[i32]) -> Vec<u8> {
+    let values: Vec<u64> = values
+        .iter()
+        .map(|value| encode_zigzag_i32(*value))
+        .collect();
+    encode_packed_varint(&values)
+}
+
+/// Encodes the elements of a packed `repeated sint64` field.
+///
+/// The result is the payload of the length-delimited field: the zigzag-encoded varint of every
+/// value, back to back.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_packed_sint64;
+///
+/// assert_eq!(encode_packed_sint64(&[-2, 300]), vec![0x03, 0xD8, 0x04]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_packed_sint64(values: &[i64]) -> Vec<u8> {
+    let values: Vec<u64> = values
+        .iter()
+        .map(|value| encode_zigzag_i64(*value))
+        .collect();
+    encode_packed_varint(&values)
+}
+
+/// Encodes a `u64` as a varint into the start of `buf`, without allocating.
+///
+/// This is the zero-allocation counterpart of [`encode_varint`], for writing into a pre-allocated
+/// buffer. A `u64` takes at most 10 bytes.
+///
+/// # Returns
+///
+/// `Some(bytes_written)`, or `None` if `buf` is too small, in which case `buf` is left untouched.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_varint_to_slice;
+///
+/// let mut buf = [0; 10];
+/// assert_eq!(encode_varint_to_slice(300, &mut buf), Some(2));
+/// assert_eq!(buf[..2], [0xAC, 0x02]);
+/// assert_eq!(encode_varint_to_slice(300, &mut buf[..1]), None);
+/// ```
+pub fn encode_varint_to_slice(value: u64, buf: &mut [u8]) -> Option<usize> {
+    let mut varint = [0; 10];
+    let len = write_varint(value, &mut varint);
+    buf.get_mut(..len)?.copy_from_slice(&varint[..len]);
+    Some(len)
+}
+
+/// Encodes the tag of a field, made of its field number and wire type, as a varint.
+///
+/// This is the header of every field except length-delimited ones, which also need a length
+/// prefix, see [`create_header`](crate::create_header).
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_tag, Variant};
+///
+/// assert_eq!(encode_tag(1, Variant::Varint), [0x08]);
+/// assert_eq!(encode_tag(16, Variant::LengthDelimited), [0x82, 0x01]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_tag(field_number: u64, variant: Variant) -> Vec<u8> {
+    encode_varint((field_number << 3) | u64::from(variant))
+}
+
+/// Encodes a complete field: its header followed by its payload.
+///
+/// This is the same as concatenating [`create_header`](crate::create_header) and `payload`. The
+/// length prefix of a `Variant::LengthDelimited` field is added automatically, while the payload of
+/// every other wire type must already be encoded, e.g. with [`encode_varint`] for
+/// `Variant::Varint`.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_field, encode_varint, Variant};
+///
+/// assert_eq!(encode_field(1, Variant::Varint, &encode_varint(150)), [0x08, 0x96, 0x01]);
+/// assert_eq!(encode_field(2, Variant::LengthDelimited, b"hi"), b"\x12\x02hi");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_field(tag_number: u64, variant: Variant, payload: &[u8]) -> Vec<u8> {
+    let mut header = [0; 20];
+    let header_len = encode_header_to_slice(tag_number, variant, payload.len(), &mut header)
+        .expect("a field header is at most 20 bytes");
+
+    let mut field = Vec::with_capacity(header_len + payload.len());
+    field.extend_from_slice(&header[..header_len]);
+    field.extend_from_slice(payload);
+    field
+}
+
+/// Encodes a complete varint field (`int32`, `int64`, `uint32`, `uint64`, `bool`, `enum`).
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_varint_field;
+///
+/// assert_eq!(encode_varint_field(1, 150), [0x08, 0x96, 0x01]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_varint_field(tag_number: u64, value: u64) -> Vec<u8> {
+    let mut varint = [0; 10];
+    let len = write_varint(value, &mut varint);
+    encode_field(tag_number, Variant::Varint, &varint[..len])
+}
+
+/// Encodes a complete length-delimited `string` field.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_string_field;
+///
+/// assert_eq!(encode_string_field(2, "hi"), b"\x12\x02hi");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_string_field(tag_number: u64, value: &str) -> Vec<u8> {
+    encode_field(tag_number, Variant::LengthDelimited, value.as_bytes())
+}
+
+/// Encodes a field header into the start of `buf`, without allocating.
+///
+/// The header is the tag varint, followed by the varint encoded `payload_len` for
+/// `Variant::LengthDelimited` fields. This produces the same bytes as
+/// [`create_header`](crate::create_header), but only needs the payload length instead of the
+/// payload. A header takes at most 20 bytes.
+///
+/// # Returns
+///
+/// `Some(bytes_written)`, or `None` if `buf` is too small, in which case `buf` is left untouched.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_header_to_slice, Variant};
+///
+/// let mut buf = [0; 20];
+/// let len = encode_header_to_slice(2, Variant::LengthDelimited, 7, &mut buf).unwrap();
+/// assert_eq!(buf[..len], [0x12, 0x07]);
+/// ```
+pub fn encode_header_to_slice(
+    tag_number: u64,
+    variant: Variant,
+    payload_len: usize,
+    buf: &mut [u8],
+) -> Option<usize> {
+    let mut header = [0; 20];
+    let mut varint = [0; 10];
+
+    let tag_len = write_varint((tag_number << 3) | u64::from(variant), &mut varint);
+    header[..tag_len].copy_from_slice(&varint[..tag_len]);
+    let mut len = tag_len;
+
+    if variant == Variant::LengthDelimited {
+        let length_len = write_varint(payload_len as u64, &mut varint);
+        header[len..len + length_len].copy_from_slice(&varint[..length_len]);
+        len += length_len;
+    }
+
+    buf.get_mut(..len)?.copy_from_slice(&header[..len]);
+    Some(len)
+}
+
+/// Encodes a varint into a fixed-size array, without allocating.
+///
+/// Only the first [`encoded_varint_len(value)`](encoded_varint_len) bytes of the array are part of
+/// the varint. The remaining bytes are zero.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_varint_exact, encoded_varint_len};
+///
+/// let varint = encode_varint_exact(300);
+/// assert_eq!(varint[..encoded_varint_len(300)], [0xAC, 0x02]);
+/// ```
+pub fn encode_varint_exact(value: u64) -> [u8; 10] {
+    let mut varint = [0; 10];
+    write_varint(value, &mut varint);
+    varint
+}
+
+/// Returns the number of meaningful bytes in the array returned by [`encode_varint_exact`].
+///
+/// This is the same as [`measure_encoded_varint_size`].
+pub const fn encoded_varint_len(value: u64) -> usize {
+    measure_encoded_varint_size(value)
+}
+
+/// Encodes a varint into a stack-allocated array.
+///
+/// This is the allocation-free counterpart of [`encode_varint`], for hot loops that copy the
+/// varint into a larger buffer right away.
+///
+/// # Returns
+///
+/// The number of bytes of the varint and the array holding them, e.g. `(len, array)` where
+/// `&array[..len]` is the varint.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_varint_array;
+///
+/// let (len, varint) = encode_varint_array(300);
+/// assert_eq!(varint[..len], [0xAC, 0x02]);
+/// ```
+pub fn encode_varint_array(value: u64) -> (usize, [u8; 10]) {
+    encode_varint_const(value)
+}
+
+/// Encodes a varint at compile time.
+///
+/// This works like [`encode_varint_array`], but can be called in `const` contexts, so the varint
+/// encoding of well-known values costs nothing at runtime.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_varint_const;
+///
+/// const ENCODED: (usize, [u8; 10]) = encode_varint_const(300);
+/// assert_eq!(ENCODED.1[..ENCODED.0], [0xAC, 0x02]);
+/// ```
+pub const fn encode_varint_const(value: u64) -> (usize, [u8; 10]) {
+    let mut varint = [0; 10];
+    let mut value = value;
+    let mut len = 0;
+    loop {
+        varint[len] = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            return (len + 1, varint);
+        }
+        varint[len] |= 0x80;
+        len += 1;
+    }
+}
+
+/// Encodes the tag of a field at compile time, as an array of exactly `N` bytes.
+///
+/// This lets the tags of frequently written fields be defined as constants. `N` must be the
+/// length of the tag, i.e. [`measure_encoded_varint_size`] of `(field_number << 3) | wire_type`:
+/// 1 byte for field numbers 1 to 15, 2 bytes up to 2047, and so on.
+///
+/// # Panics
+///
+/// Panics if `N` is not the length of the tag. In a `const` item this is a compile-time error.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_field_tag_const, Variant};
+///
+/// const NAME_TAG: [u8; 1] = encode_field_tag_const(5, Variant::LengthDelimited);
+/// const ID_TAG: [u8; 2] = encode_field_tag_const(16, Variant::Varint);
+///
+/// assert_eq!(NAME_TAG, [0x2A]);
+/// assert_eq!(ID_TAG, [0x80, 0x01]);
+/// ```
+pub const fn encode_field_tag_const<const N: usize>(
+    field_number: u64,
+    variant: Variant,
+) -> [u8; N] {
+    let wire_type = match variant {
+        Variant::Varint => 0,
+        Variant::SixtyFourBit => 1,
+        Variant::LengthDelimited => 2,
+        Variant::ThirtyTwoBit => 5,
+    };
+    let (len, varint) = encode_varint_const((field_number << 3) | wire_type);
+    assert!(len == N, "N must be the length of the encoded tag");
+
+    let mut tag = [0; N];
+    let mut i = 0;
+    while i < N {
+        tag[i] = varint[i];
+        i += 1;
+    }
+    tag
+}
+
+/// Encodes a varint straight into a [`bytes::BufMut`], without allocating.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_varint_buf;
+///
+/// let mut buf = Vec::new();
+/// encode_varint_buf(300, &mut buf);
+/// assert_eq!(buf, [0xAC, 0x02]);
+/// ```
+#[cfg(feature = "bytes")]
+pub fn encode_varint_buf(value: u64, buf: &mut impl bytes::BufMut) {
+    let (len, varint) = encode_varint_array(value);
+    buf.put_slice(&varint[..len]);
+}
+
+/// Returns the number of bytes the varint encoding of `value` takes, from 1 to 10.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::measure_encoded_varint_size;
+///
+/// assert_eq!(measure_encoded_varint_size(127), 1);
+/// assert_eq!(measure_encoded_varint_size(128), 2);
+/// assert_eq!(measure_encoded_varint_size(u64::MAX), 10);
+/// ```
+pub const fn measure_encoded_varint_size(value: u64) -> usize {
+    // Every byte holds 7 bits, and zero still takes one byte.
+    let bits = 64 - (value | 1).leading_zeros() as usize;
+    bits.div_ceil(7)
+}
+
+/// Returns the number of bytes the header of a field takes: the tag varint, plus the length
+/// prefix for `Variant::LengthDelimited` fields.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{measure_encoded_header_size, Variant};
+///
+/// assert_eq!(measure_encoded_header_size(1, Variant::Varint, 1), 1);
+/// assert_eq!(measure_encoded_header_size(16, Variant::LengthDelimited, 128), 4);
+/// ```
+pub fn measure_encoded_header_size(tag_number: u64, variant: Variant, payload_len: usize) -> usize {
+    let tag_size = measure_encoded_varint_size((tag_number << 3) | u64::from(variant));
+    if variant == Variant::LengthDelimited {
+        tag_size + measure_encoded_varint_size(payload_len as u64)
+    } else {
+        tag_size
+    }
+}
+
+/// Returns the number of bytes a whole field takes: its header plus `payload_len` bytes of
+/// payload.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{measure_encoded_field_size, Variant};
+///
+/// assert_eq!(measure_encoded_field_size(2, Variant::LengthDelimited, 7), 9);
+/// assert_eq!(measure_encoded_field_size(3, Variant::SixtyFourBit, 8), 9);
+/// ```
+pub fn measure_encoded_field_size(tag_number: u64, variant: Variant, payload_len: usize) -> usize {
+    measure_encoded_header_size(tag_number, variant, payload_len) + payload_len
+}
+
+/// Writes the varint representation of `value` into `buf` and returns the number of bytes used.
+#[inline]
+pub(crate) fn write_varint(value: u64, buf: &mut [u8; 10]) -> usize {
+    let mut value = value;
+    let mut len = 0;
+
+    loop {
+        let mut byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value != 0 {
+            byte |= 0x80;
+        }
+
+        buf[len] = byte;
+        len += 1;
+
+        if value == 0 {
+            return len;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
 mod tests {
     use super::*;
+    use alloc::vec;
 
     #[test]
     fn test_encode_varint() {
@@ -124,6 +762,103 @@ mod tests {
     }
 
     #[test]
+    fn test_encode_varint_to_slice() {
+        let mut buf = [0; 10];
+        for shift in 0..64 {
+            for value in [1u64 << shift, (1u64 << shift) - 1, u64::MAX >> shift] {
+                let len = encode_varint_to_slice(value, &mut buf).unwrap();
+                assert_eq!(&buf[..len], encode_varint(value).as_slice());
+                assert_eq!(encode_varint_to_slice(value, &mut buf[..len - 1]), None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_header_to_slice() {
+        let mut buf = [0; 20];
+        for variant in [
+            Variant::Varint,
+            Variant::SixtyFourBit,
+            Variant::LengthDelimited,
+            Variant::ThirtyTwoBit,
+        ] {
+            for (tag_number, payload_len) in
+                [(1, 0), (15, 127), (16, 128), ((1 << 29) - 1, 1 << 20)]
+            {
+                let payload = vec![0; payload_len];
+                let len =
+                    encode_header_to_slice(tag_number, variant, payload_len, &mut buf).unwrap();
+                assert_eq!(
+                    &buf[..len],
+                    crate::create_header(tag_number, variant.into(), &payload).as_slice()
+                );
+            }
+        }
+        assert_eq!(
+            encode_header_to_slice(16, Variant::LengthDelimited, 128, &mut buf[..3]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_measure_encoded_size() {
+        for shift in 0..64 {
+            for value in [1u64 << shift, (1u64 << shift) - 1, u64::MAX >> shift] {
+                assert_eq!(
+                    measure_encoded_varint_size(value),
+                    encode_varint(value).len()
+                );
+            }
+        }
+
+        let mut buf = [0; 20];
+        for (tag_number, payload_len) in [(1, 0), (16, 128), ((1 << 29) - 1, 1 << 20)] {
+            let len =
+                encode_header_to_slice(tag_number, Variant::LengthDelimited, payload_len, &mut buf)
+                    .unwrap();
+            assert_eq!(
+                measure_encoded_header_size(tag_number, Variant::LengthDelimited, payload_len),
+                len
+            );
+            assert_eq!(
+                measure_encoded_field_size(tag_number, Variant::LengthDelimited, payload_len),
+                len + payload_len
+            );
+        }
+    }
+
+    const _: () = assert!(encode_varint_const(0).0 == 1);
+    const _: () = assert!(encode_varint_const(u64::MAX).0 == 10);
+    const NAME_TAG: [u8; 1] = encode_field_tag_const(5, Variant::LengthDelimited);
+    const ID_TAG: [u8; 2] = encode_field_tag_const(16, Variant::Varint);
+    const _: () = assert!(NAME_TAG[0] == 0x2A);
+    const _: () = assert!(ID_TAG[0] == 0x80 && ID_TAG[1] == 0x01);
+
+    #[test]
+    fn test_encode_varint_const() {
+        for shift in 0..64 {
+            for value in [1u64 << shift, (1u64 << shift) - 1, u64::MAX >> shift] {
+                let (len, varint) = encode_varint_const(value);
+                assert_eq!(&varint[..len], encode_varint(value).as_slice());
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_encode_field_tag_const_wrong_length() {
+        let _: [u8; 1] = encode_field_tag_const(16, Variant::Varint);
+    }
+
+    #[test]
+    fn test_write_varint() {
+        let mut buf = [0; 10];
+        let len = write_varint(u64::MAX, &mut buf);
+        assert_eq!(&buf[..len], encode_varint(u64::MAX).as_slice());
+    }
+
+    #[test]
+    #[allow(clippy::approx_constant)]
     fn test_encode_float() {
         let value = 3.14;
         let bytes = encode_float(value);
@@ -131,6 +866,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_encode_double() {
         let value = 3.14;
         let bytes = encode_double(value);
@@ -0,0 +1,203 @@
+use crate::Variant;
+use core::fmt;
+
+/// Errors returned by [`MessageBuilderFixed`](crate::MessageBuilderFixed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuilderError {
+    /// Adding the field would need `required` bytes, but the builder only holds `capacity` bytes.
+    CapacityExceeded { capacity: usize, required: usize },
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuilderError::CapacityExceeded { capacity, required } => write!(
+                f,
+                "message needs {} bytes but the builder capacity is {} bytes",
+                required, capacity
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuilderError {}
+
+/// Errors returned by [`rewrite_varint_in_place`](crate::rewrite_varint_in_place).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewriteError {
+    /// The field was not found, or the message could not be decoded up to it.
+    FieldNotFound,
+    /// The field does not have the varint wire type.
+    NotVarint { wire_type: u64 },
+    /// The new value needs `new` bytes, but the existing encoding uses `old` bytes.
+    EncodedLengthChanged { old: usize, new: usize },
+}
+
+impl fmt::Display for RewriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewriteError::FieldNotFound => write!(f, "field not found"),
+            RewriteError::NotVarint { wire_type } => {
+                write!(f, "field has wire type {} instead of varint", wire_type)
+            }
+            RewriteError::EncodedLengthChanged { old, new } => write!(
+                f,
+                "new value needs {} bytes but the field uses {} bytes",
+                new, old
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RewriteError {}
+
+/// Errors returned by [`MessagePatcher::commit`](crate::MessagePatcher::commit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchError {
+    /// The message could not be decoded before every staged field was found.
+    Malformed(WireError),
+    /// No field with the staged tag number is present in the message.
+    FieldNotFound { tag: u64 },
+    /// The payload staged for `tag` is not a single value of the field's wire type.
+    InvalidPayload { tag: u64, variant: Variant },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Malformed(err) => write!(f, "malformed message: {}", err),
+            PatchError::FieldNotFound { tag } => write!(f, "field {} not found", tag),
+            PatchError::InvalidPayload { tag, variant } => {
+                write!(
+                    f,
+                    "payload of field {} is not a valid {} value",
+                    tag, variant
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchError {}
+
+/// Errors returned by [`validate_strict`](crate::validate_strict).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrictValidationError {
+    /// A field starting at `offset` runs past the end of the message.
+    TruncatedMessage { offset: usize },
+    /// The varint starting at `offset` is longer than 10 bytes.
+    VarintOverflow { offset: usize },
+    /// The field starting at `offset` has a field number outside of `1..=536870911`.
+    InvalidFieldNumber { offset: usize, field_number: u64 },
+    /// The field starting at `offset` uses an undefined or disallowed wire type.
+    UnsupportedWireType { offset: usize, wire_type: u64 },
+    /// The end-group tag at `offset` does not close the group that is currently open.
+    UnmatchedEndGroup { offset: usize, field_number: u64 },
+    /// The group with the given field number is never closed.
+    UnterminatedGroup { field_number: u64 },
+}
+
+impl fmt::Display for StrictValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrictValidationError::TruncatedMessage { offset } => {
+                write!(
+                    f,
+                    "field at offset {} runs past the end of the message",
+                    offset
+                )
+            }
+            StrictValidationError::VarintOverflow { offset } => {
+                write!(f, "varint at offset {} is longer than 10 bytes", offset)
+            }
+            StrictValidationError::InvalidFieldNumber {
+                offset,
+                field_number,
+            } => write!(
+                f,
+                "invalid field number {} at offset {}",
+                field_number, offset
+            ),
+            StrictValidationError::UnsupportedWireType { offset, wire_type } => {
+                write!(
+                    f,
+                    "unsupported wire type {} at offset {}",
+                    wire_type, offset
+                )
+            }
+            StrictValidationError::UnmatchedEndGroup {
+                offset,
+                field_number,
+            } => write!(
+                f,
+                "unmatched end of group {} at offset {}",
+                field_number, offset
+            ),
+            StrictValidationError::UnterminatedGroup { field_number } => {
+                write!(f, "group {} is never closed", field_number)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StrictValidationError {}
+
+/// Errors returned when an encoded message cannot be decoded, e.g. by
+/// [`extract_field_by_tag_checked`](crate::extract_field_by_tag_checked).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireError {
+    /// A tag uses an undefined or unsupported wire type.
+    UnknownWireType(u64),
+    /// A field starting at `offset` runs past the end of the message.
+    TruncatedMessage { offset: usize },
+    /// The varint starting at `offset` is longer than 10 bytes.
+    VarintOverflow { offset: usize },
+    /// The length prefix starting at `offset` points past the end of the message.
+    InvalidLength { offset: usize },
+    /// The message is `size` bytes long, more than the allowed `max_size` bytes.
+    MessageTooLarge { size: usize, max_size: usize },
+    /// The group starting at `offset` is nested deeper than allowed.
+    RecursionLimitExceeded { offset: usize },
+    /// The field starting at `offset` has a field number that is not allowed.
+    UnknownField { offset: usize, field_number: u64 },
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::UnknownWireType(wire_type) => write!(f, "unknown wire type {}", wire_type),
+            WireError::TruncatedMessage { offset } => write!(
+                f,
+                "field at offset {} runs past the end of the message",
+                offset
+            ),
+            WireError::VarintOverflow { offset } => {
+                write!(f, "varint at offset {} is longer than 10 bytes", offset)
+            }
+            WireError::InvalidLength { offset } => write!(
+                f,
+                "length at offset {} runs past the end of the message",
+                offset
+            ),
+            WireError::MessageTooLarge { size, max_size } => write!(
+                f,
+                "message is {} bytes but at most {} bytes are allowed",
+                size, max_size
+            ),
+            WireError::RecursionLimitExceeded { offset } => {
+                write!(f, "group at offset {} is nested too deeply", offset)
+            }
+            WireError::UnknownField {
+                offset,
+                field_number,
+            } => write!(f, "unknown field {} at offset {}", field_number, offset),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WireError {}
@@ -0,0 +1,399 @@
+use crate::decoders;
+#[cfg(feature = "alloc")]
+use crate::encoders;
+#[cfg(feature = "tokio")]
+use crate::WireError;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "tokio")]
+use bytes::{BufMut, BytesMut};
+#[cfg(feature = "tokio")]
+use std::io;
+#[cfg(feature = "tokio")]
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Prepends the varint length of an encoded protobuf message to it.
+///
+/// This is the framing used by `writeDelimitedTo` in the official protobuf libraries and by many
+/// stream protocols to send several messages back to back.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_with_length_prefix;
+///
+/// assert_eq!(encode_with_length_prefix(b"\x08\x01"), b"\x02\x08\x01");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_with_length_prefix(encoded_message: &[u8]) -> Vec<u8> {
+    let mut framed = Vec::with_capacity(
+        encoders::measure_encoded_varint_size(encoded_message.len() as u64) + encoded_message.len(),
+    );
+    let mut prefix = [0; 10];
+    let prefix_len = encoders::write_varint(encoded_message.len() as u64, &mut prefix);
+    framed.extend_from_slice(&prefix[..prefix_len]);
+    framed.extend_from_slice(encoded_message);
+    framed
+}
+
+/// Splits the first length-prefixed message off a buffer.
+///
+/// This is the inverse of [`encode_with_length_prefix`]: the varint length at the start of `buf`
+/// is decoded and that many bytes are returned as the message.
+///
+/// # Returns
+///
+/// The `(message, rest)` slices, where `rest` holds the bytes following the message, or `None` if
+/// the length prefix cannot be decoded or `buf` holds fewer bytes than it announces.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::strip_length_prefix;
+///
+/// let buf = b"\x02\x08\x01\x02\x08\x02";
+/// assert_eq!(strip_length_prefix(buf), Some((&b"\x08\x01"[..], &b"\x02\x08\x02"[..])));
+/// assert_eq!(strip_length_prefix(b"\x05\x08\x01"), None);
+/// ```
+pub fn strip_length_prefix(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (message_len, offset) = decoders::decode_varint(buf, 0)?;
+    let message_len = usize::try_from(message_len).ok()?;
+    let rest = &buf[offset..];
+    if rest.len() < message_len {
+        return None;
+    }
+    Some(rest.split_at(message_len))
+}
+
+/// Prepends the varint length of an encoded protobuf message to it.
+///
+/// This is the same as [`encode_with_length_prefix`], under a name that pairs with
+/// [`strip_varint_length`] and [`split_varint_length_stream`].
+///
+/// # Example
+///
+/// ```
+/// use rustwire::prepend_varint_length;
+///
+/// assert_eq!(prepend_varint_length(b"\x08\x01"), b"\x02\x08\x01");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn prepend_varint_length(msg: &[u8]) -> Vec<u8> {
+    encode_with_length_prefix(msg)
+}
+
+/// Splits the first varint length-prefixed message off a buffer.
+///
+/// This is the same as [`strip_length_prefix`], under a name that pairs with
+/// [`prepend_varint_length`].
+///
+/// # Returns
+///
+/// The `(payload, remainder)` slices, or `None` if the length prefix cannot be decoded or
+/// `framed` holds fewer bytes than it announces.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::strip_varint_length;
+///
+/// let framed = b"\x02\x08\x01\x02\x08\x02";
+/// assert_eq!(strip_varint_length(framed), Some((&b"\x08\x01"[..], &b"\x02\x08\x02"[..])));
+/// ```
+pub fn strip_varint_length(framed: &[u8]) -> Option<(&[u8], &[u8])> {
+    strip_length_prefix(framed)
+}
+
+/// Splits a stream of varint length-prefixed messages into the messages, without copying them.
+///
+/// # Returns
+///
+/// The messages of the stream, in order. Splitting stops at the first length prefix that cannot
+/// be decoded or announces more bytes than are left, so the messages before it are still
+/// returned. Use [`iter_varint_length_messages`] to split the stream lazily.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::split_varint_length_stream;
+///
+/// let stream = b"\x02\x08\x01\x00\x02\x08\x02";
+/// assert_eq!(
+///     split_varint_length_stream(stream),
+///     [&b"\x08\x01"[..], &b""[..], &b"\x08\x02"[..]]
+/// );
+/// ```
+#[cfg(feature = "alloc")]
+pub fn split_varint_length_stream(data: &[u8]) -> Vec<&[u8]> {
+    iter_varint_length_messages(data).collect()
+}
+
+/// Returns an iterator over the messages of a stream of varint length-prefixed messages.
+///
+/// This is the lazy version of [`split_varint_length_stream`] and stops at the same point. The
+/// bytes that were not consumed, such as a partially received message, are available through
+/// [`VarintLengthMessages::remainder`].
+///
+/// # Example
+///
+/// ```
+/// use rustwire::iter_varint_length_messages;
+///
+/// let mut messages = iter_varint_length_messages(b"\x02\x08\x01\x02\x08");
+/// assert_eq!(messages.next(), Some(&b"\x08\x01"[..]));
+/// assert_eq!(messages.next(), None);
+/// assert_eq!(messages.remainder(), b"\x02\x08");
+/// ```
+pub fn iter_varint_length_messages(data: &[u8]) -> VarintLengthMessages<'_> {
+    VarintLengthMessages { rest: data }
+}
+
+/// An iterator over the messages of a stream of varint length-prefixed messages.
+///
+/// Created by [`iter_varint_length_messages`].
+#[derive(Debug, Clone)]
+pub struct VarintLengthMessages<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> VarintLengthMessages<'a> {
+    /// Returns the bytes that have not been consumed yet.
+    pub fn remainder(&self) -> &'a [u8] {
+        self.rest
+    }
+}
+
+impl<'a> Iterator for VarintLengthMessages<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (message, rest) = strip_length_prefix(self.rest)?;
+        self.rest = rest;
+        Some(message)
+    }
+}
+
+/// Prepends the 5-byte gRPC message header to an encoded protobuf message.
+///
+/// gRPC frames every message on an HTTP/2 stream with a one-byte compression flag followed by the
+/// message length as a 4-byte big-endian integer. The message is sent uncompressed, so the flag is
+/// always 0.
+///
+/// # Panics
+///
+/// Panics if the message is longer than `u32::MAX` bytes, which gRPC cannot frame.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_grpc_frame;
+///
+/// assert_eq!(encode_grpc_frame(b"\x08\x01"), b"\x00\x00\x00\x00\x02\x08\x01");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_grpc_frame(message: &[u8]) -> Vec<u8> {
+    let message_len = u32::try_from(message.len()).expect("gRPC messages are at most 4 GiB");
+    let mut frame = Vec::with_capacity(GRPC_HEADER_LEN + message.len());
+    frame.push(0);
+    frame.extend_from_slice(&message_len.to_be_bytes());
+    frame.extend_from_slice(message);
+    frame
+}
+
+/// Decodes the gRPC frame at the start of a buffer.
+///
+/// # Returns
+///
+/// The compression flag and the message of the frame, or `None` if the buffer is shorter than
+/// the frame or the compression flag is neither 0 nor 1. Bytes following the frame are ignored,
+/// use [`split_grpc_stream`] to decode several consecutive frames.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_grpc_frame;
+///
+/// let frame = b"\x00\x00\x00\x00\x02\x08\x01";
+/// assert_eq!(decode_grpc_frame(frame), Some((false, &b"\x08\x01"[..])));
+/// assert_eq!(decode_grpc_frame(&frame[..6]), None);
+/// ```
+pub fn decode_grpc_frame(buf: &[u8]) -> Option<(bool, &[u8])> {
+    split_grpc_frame(buf).map(|(compressed, message, _)| (compressed, message))
+}
+
+/// Decodes a buffer holding several consecutive gRPC frames.
+///
+/// # Returns
+///
+/// The messages of the frames, in order. Decoding stops at the first frame that cannot be
+/// decoded (see [`decode_grpc_frame`]), so the messages of the frames before it are still
+/// returned. The compression flags are not returned; callers that accept compressed messages
+/// should use [`decode_grpc_frame`] instead.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::split_grpc_stream;
+///
+/// let stream = b"\x00\x00\x00\x00\x02\x08\x01\x00\x00\x00\x00\x00";
+/// assert_eq!(split_grpc_stream(stream), [&b"\x08\x01"[..], &b""[..]]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn split_grpc_stream(buf: &[u8]) -> Vec<&[u8]> {
+    let mut messages = Vec::new();
+    let mut rest = buf;
+    while let Some((_, message, next)) = split_grpc_frame(rest) {
+        messages.push(message);
+        rest = next;
+    }
+    messages
+}
+
+/// The length of the gRPC message header: a compression flag and a 4-byte length.
+const GRPC_HEADER_LEN: usize = 5;
+
+/// Splits the gRPC frame at the start of `buf` into its compression flag, its message and the
+/// bytes following it.
+fn split_grpc_frame(buf: &[u8]) -> Option<(bool, &[u8], &[u8])> {
+    let header: [u8; GRPC_HEADER_LEN] = buf.get(..GRPC_HEADER_LEN)?.try_into().ok()?;
+    let compressed = match header[0] {
+        0 => false,
+        1 => true,
+        _ => return None,
+    };
+    let message_len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+    let rest = &buf[GRPC_HEADER_LEN..];
+    if (rest.len() as u64) < u64::from(message_len) {
+        return None;
+    }
+    let (message, rest) = rest.split_at(message_len as usize);
+    Some((compressed, message, rest))
+}
+
+/// A [`tokio_util::codec`] codec that frames encoded protobuf messages with a varint length
+/// prefix.
+///
+/// Encoding writes the message prefixed with its length, like [`encode_with_length_prefix`].
+/// Decoding buffers incoming bytes until a whole message is available and then yields the message
+/// without its prefix. A length prefix that is not a valid varint is reported as an
+/// [`io::ErrorKind::InvalidData`] error.
+///
+/// The length prefix comes from the peer, so it is checked against a maximum frame length before
+/// any memory is reserved for the message. Longer messages are rejected with an
+/// [`io::ErrorKind::InvalidData`] error when decoding, and with an
+/// [`io::ErrorKind::InvalidInput`] error when encoding. The maximum defaults to
+/// [`DEFAULT_MAX_FRAME_LENGTH`](ProtoLengthDelimitedCodec::DEFAULT_MAX_FRAME_LENGTH), like in
+/// [`tokio_util::codec::LengthDelimitedCodec`].
+///
+/// # Example
+///
+/// ```
+/// use bytes::BytesMut;
+/// use rustwire::ProtoLengthDelimitedCodec;
+/// use tokio_util::codec::{Decoder, Encoder};
+///
+/// let mut codec = ProtoLengthDelimitedCodec::new();
+/// let mut buf = BytesMut::new();
+/// codec.encode(&b"\x08\x01"[..], &mut buf).unwrap();
+/// assert_eq!(buf, &b"\x02\x08\x01"[..]);
+///
+/// assert_eq!(codec.decode(&mut buf).unwrap().unwrap(), &b"\x08\x01"[..]);
+/// assert!(buf.is_empty());
+/// ```
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy)]
+pub struct ProtoLengthDelimitedCodec {
+    max_frame_length: usize,
+}
+
+#[cfg(feature = "tokio")]
+impl ProtoLengthDelimitedCodec {
+    /// The default maximum length of a message, 8 MiB.
+    pub const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+    /// Creates a codec accepting messages of up to
+    /// [`DEFAULT_MAX_FRAME_LENGTH`](Self::DEFAULT_MAX_FRAME_LENGTH) bytes.
+    pub fn new() -> Self {
+        Self::with_max_frame_length(Self::DEFAULT_MAX_FRAME_LENGTH)
+    }
+
+    /// Creates a codec accepting messages of up to `max_frame_length` bytes, not counting the
+    /// length prefix.
+    pub fn with_max_frame_length(max_frame_length: usize) -> Self {
+        ProtoLengthDelimitedCodec { max_frame_length }
+    }
+
+    /// Returns the maximum length of a message, not counting the length prefix.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    /// Sets the maximum length of a message, not counting the length prefix.
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.max_frame_length = max_frame_length;
+    }
+
+    /// Returns the error for a message of `size` bytes that is longer than allowed.
+    fn too_large(&self, kind: io::ErrorKind, size: usize) -> io::Error {
+        io::Error::new(
+            kind,
+            WireError::MessageTooLarge {
+                size,
+                max_size: self.max_frame_length,
+            },
+        )
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Default for ProtoLengthDelimitedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Decoder for ProtoLengthDelimitedCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<BytesMut>, io::Error> {
+        let (message_len, offset) = match decoders::decode_varint_checked(src, 0) {
+            Ok(decoded) => decoded,
+            Err(WireError::TruncatedMessage { .. }) => return Ok(None),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+        let message_len = usize::try_from(message_len).unwrap_or(usize::MAX);
+        if message_len > self.max_frame_length {
+            return Err(self.too_large(io::ErrorKind::InvalidData, message_len));
+        }
+        // The prefix is at most 10 bytes and the message is at most `max_frame_length` bytes, so
+        // this is also the most that is ever reserved.
+        let frame_len = offset.saturating_add(message_len);
+
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+
+        let mut frame = src.split_to(frame_len);
+        Ok(Some(frame.split_off(offset)))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Encoder<&[u8]> for ProtoLengthDelimitedCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: &[u8], dst: &mut BytesMut) -> Result<(), io::Error> {
+        if item.len() > self.max_frame_length {
+            return Err(self.too_large(io::ErrorKind::InvalidInput, item.len()));
+        }
+        let mut prefix = [0; 10];
+        let prefix_len = encoders::write_varint(item.len() as u64, &mut prefix);
+        dst.reserve(prefix_len + item.len());
+        dst.put_slice(&prefix[..prefix_len]);
+        dst.put_slice(item);
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{decoders, encoders, Variant, WireError};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// The decoded tag of a field: its field number and wire type.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{parse_field_header, FieldHeader, Variant};
+///
+/// let header = FieldHeader {
+///     field_number: 2,
+///     wire_type: Variant::LengthDelimited,
+/// };
+/// assert_eq!(header.encode(), [0x12]);
+/// assert_eq!(parse_field_header(b"\x08\x01\x12\x02\x68\x69", 2), Some((header, 3)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldHeader {
+    /// The field number.
+    pub field_number: u64,
+    /// The wire type of the field.
+    pub wire_type: Variant,
+}
+
+impl FieldHeader {
+    /// Encodes the tag as a varint.
+    ///
+    /// Only the tag is encoded. The length prefix of a length-delimited field is part of its
+    /// value, see [`create_header`](crate::create_header) to encode both.
+    #[cfg(feature = "alloc")]
+    pub fn encode(&self) -> Vec<u8> {
+        encoders::encode_tag(self.field_number, self.wire_type)
+    }
+
+    /// Encodes the tag as a varint into the start of `buf`, without allocating.
+    ///
+    /// Returns `Some(bytes_written)`, or `None` if `buf` is too small, in which case `buf` is left
+    /// untouched.
+    pub fn encode_to_slice(&self, buf: &mut [u8]) -> Option<usize> {
+        encoders::encode_varint_to_slice(self.tag(), buf)
+    }
+
+    fn tag(&self) -> u64 {
+        (self.field_number << 3) | u64::from(self.wire_type)
+    }
+}
+
+/// Decodes the tag of the field starting at `offset`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `offset` - The offset of the first byte of the tag.
+///
+/// # Returns
+///
+/// The decoded header and the offset of the first byte after the tag, or `None` if the tag is
+/// truncated, overflows, or has a wire type that [`Variant`] does not cover, such as the
+/// deprecated group wire types.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{parse_field_header, Variant};
+///
+/// let (header, offset) = parse_field_header(b"\x08\x96\x01", 0).unwrap();
+/// assert_eq!((header.field_number, header.wire_type), (1, Variant::Varint));
+/// assert_eq!(offset, 1);
+///
+/// // Wire type 3 starts a group.
+/// assert_eq!(parse_field_header(b"\x0b", 0), None);
+/// ```
+pub fn parse_field_header(encoded_message: &[u8], offset: usize) -> Option<(FieldHeader, usize)> {
+    parse_field_header_checked(encoded_message, offset).ok()
+}
+
+/// Decodes the tag of the field starting at `offset`, returning why it could not be decoded.
+pub(crate) fn parse_field_header_checked(
+    encoded_message: &[u8],
+    offset: usize,
+) -> Result<(FieldHeader, usize), WireError> {
+    let (tag, header_end) = decoders::decode_varint_checked(encoded_message, offset)?;
+    let header = FieldHeader {
+        field_number: decoders::field_number_from_tag(tag),
+        wire_type: Variant::try_from(decoders::wire_type_from_tag(tag))?,
+    };
+    Ok((header, header_end))
+}
@@ -0,0 +1,123 @@
+use crate::{utils, FieldIterator, Variant, WireError};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// An index over the fields of an encoded message for fast repeated lookups.
+///
+/// Every call to [`extract_field_by_tag`](crate::extract_field_by_tag) scans the message from the
+/// start, so extracting many fields from a large message adds up. `MessageIndex` scans the message
+/// once when it is created and maps every tag number to the values of all its occurrences, after
+/// which each lookup is a single hash map access. The values borrow from the encoded message and
+/// have the same form as the ones returned by `extract_field_by_tag`.
+///
+/// If the message is malformed, only the fields before the malformed bytes are indexed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::MessageIndex;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x08\x02";
+/// let index = MessageIndex::new(encoded_message);
+///
+/// assert_eq!(index.get(1), Some(&[0x01][..]));
+/// assert_eq!(index.get_last(1), Some(&[0x02][..]));
+/// assert_eq!(index.get_all(2), [&b"hi"[..]]);
+/// assert!(!index.has(3));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageIndex<'a> {
+    /// The values of all fields, grouped by tag number and in message order within each group.
+    values: Vec<&'a [u8]>,
+    /// The wire type of each value in `values`.
+    variants: Vec<Variant>,
+    /// Maps each tag number to the range of its values in `values`.
+    ranges: HashMap<u64, Range<usize>>,
+}
+
+impl<'a> MessageIndex<'a> {
+    /// Scans `encoded_message` and indexes all of its fields.
+    pub fn new(encoded_message: &'a [u8]) -> Self {
+        let fields = FieldIterator::new(encoded_message)
+            .map(|field| (field.tag, field.wire_type, field.as_bytes()))
+            .collect();
+        Self::from_fields(fields)
+    }
+
+    /// Scans `encoded_message` and indexes all of its fields, failing if any part of the message
+    /// cannot be decoded instead of indexing the fields before it.
+    pub(crate) fn new_checked(encoded_message: &'a [u8]) -> Result<Self, WireError> {
+        let mut fields = Vec::new();
+        let mut offset = 0;
+        while let Some(field) = utils::read_field_checked(encoded_message, offset)? {
+            fields.push((
+                field.field_number,
+                field.variant,
+                &encoded_message[field.value_start..field.end],
+            ));
+            offset = field.end;
+        }
+        Ok(Self::from_fields(fields))
+    }
+
+    fn from_fields(mut fields: Vec<(u64, Variant, &'a [u8])>) -> Self {
+        // A stable sort keeps repeated occurrences in message order.
+        fields.sort_by_key(|(field_number, _, _)| *field_number);
+
+        let mut ranges = HashMap::with_capacity(fields.len());
+        let mut start = 0;
+        for (end, window) in fields.windows(2).enumerate() {
+            if window[0].0 != window[1].0 {
+                ranges.insert(window[0].0, start..end + 1);
+                start = end + 1;
+            }
+        }
+        if let Some((field_number, _, _)) = fields.last() {
+            ranges.insert(*field_number, start..fields.len());
+        }
+
+        let variants = fields.iter().map(|(_, variant, _)| *variant).collect();
+        let values = fields.into_iter().map(|(_, _, value)| value).collect();
+        MessageIndex {
+            values,
+            variants,
+            ranges,
+        }
+    }
+
+    /// Returns the value of the first occurrence of the field, like
+    /// [`extract_field_by_tag`](crate::extract_field_by_tag).
+    pub fn get(&self, tag_number: u64) -> Option<&'a [u8]> {
+        self.get_all(tag_number).first().copied()
+    }
+
+    /// Returns the value of the last occurrence of the field. For non-repeated fields this is the
+    /// value a protobuf parser would keep.
+    pub fn get_last(&self, tag_number: u64) -> Option<&'a [u8]> {
+        self.get_all(tag_number).last().copied()
+    }
+
+    /// Returns the values of all occurrences of the field, in message order.
+    pub fn get_all(&self, tag_number: u64) -> &[&'a [u8]] {
+        self.ranges
+            .get(&tag_number)
+            .map_or(&[], |range| &self.values[range.clone()])
+    }
+
+    /// Returns `true` if the field occurs at least once.
+    pub fn has(&self, tag_number: u64) -> bool {
+        self.ranges.contains_key(&tag_number)
+    }
+
+    /// Returns the wire types of all occurrences of the field, matching [`get_all`](Self::get_all).
+    pub(crate) fn get_all_variants(&self, tag_number: u64) -> &[Variant] {
+        self.ranges
+            .get(&tag_number)
+            .map_or(&[], |range| &self.variants[range.clone()])
+    }
+
+    /// Returns the tag numbers of all indexed fields, in no particular order.
+    pub(crate) fn tag_numbers(&self) -> impl Iterator<Item = u64> + '_ {
+        self.ranges.keys().copied()
+    }
+}
@@ -0,0 +1,281 @@
+#[cfg(feature = "alloc")]
+use crate::encoders;
+use crate::{utils, FieldView, Variant};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An iterator over every field of an encoded protobuf message, in wire order.
+///
+/// Each item is a [`FieldView`]. Its raw value has the same form as the value returned by
+/// [`extract_field_by_tag`](crate::extract_field_by_tag): the raw varint bytes for varint fields,
+/// the payload without its length prefix for length-delimited fields, and the raw 4 or 8 bytes
+/// for fixed-width fields.
+///
+/// Groups (wire type 3) have no [`Variant`], so they are skipped as a whole, together with the
+/// groups nested inside them. Iteration stops at the first field that cannot be decoded. A
+/// malformed message never causes a panic.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{FieldIterator, Variant};
+///
+/// let encoded_message = b"\x08\x96\x01\x12\x02\x68\x69";
+/// let mut fields = FieldIterator::new(encoded_message);
+///
+/// let field = fields.next().unwrap();
+/// assert_eq!((field.tag, field.wire_type), (1, Variant::Varint));
+/// assert_eq!(field.as_u64(), Some(150));
+///
+/// let field = fields.next().unwrap();
+/// assert_eq!((field.tag, field.wire_type), (2, Variant::LengthDelimited));
+/// assert_eq!(field.as_str(), Some("hi"));
+///
+/// assert_eq!(fields.next(), None);
+/// ```
+#[derive(Clone)]
+pub struct FieldIterator<'a> {
+    encoded_message: &'a [u8],
+    offset: usize,
+}
+
+/// Shows the position of the iterator, e.g. `FieldIterator { offset: 3, remaining: 8 bytes }`.
+impl fmt::Debug for FieldIterator<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldIterator")
+            .field("offset", &self.offset)
+            .field(
+                "remaining",
+                &format_args!(
+                    "{} bytes",
+                    self.encoded_message.len().saturating_sub(self.offset)
+                ),
+            )
+            .finish()
+    }
+}
+
+impl<'a> FieldIterator<'a> {
+    /// Creates an iterator over the fields of `encoded_message`.
+    pub fn new(encoded_message: &'a [u8]) -> Self {
+        FieldIterator {
+            encoded_message,
+            offset: 0,
+        }
+    }
+}
+
+impl<'a> FieldIterator<'a> {
+    /// Only yields the fields whose tag number appears in `tags`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustwire::FieldIterator;
+    ///
+    /// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x02";
+    /// let tags: Vec<u64> = FieldIterator::new(encoded_message)
+    ///     .filter_tags(&[1, 3])
+    ///     .map(|field| field.tag)
+    ///     .collect();
+    ///
+    /// assert_eq!(tags, [1, 3]);
+    /// ```
+    pub fn filter_tags(self, tags: &'a [u64]) -> FilterFieldsByTag<'a> {
+        FilterFieldsByTag {
+            inner: self,
+            allowed_tags: tags,
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for FieldIterator<'a> {
+    fn from(encoded_message: &'a [u8]) -> Self {
+        FieldIterator::new(encoded_message)
+    }
+}
+
+impl<'a> Iterator for FieldIterator<'a> {
+    type Item = FieldView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.encoded_message.len() {
+            return None;
+        }
+
+        match utils::read_field(self.encoded_message, self.offset) {
+            Some(field) => {
+                self.offset = field.end;
+                Some(FieldView::new(
+                    field.field_number,
+                    field.variant,
+                    &self.encoded_message[field.value_start..field.end],
+                ))
+            }
+            None => {
+                // Don't try to decode the malformed field again on the next call.
+                self.offset = self.encoded_message.len();
+                None
+            }
+        }
+    }
+}
+
+impl core::iter::FusedIterator for FieldIterator<'_> {}
+
+/// An iterator over the fields of an encoded protobuf message whose tag number is in a given list.
+///
+/// This is created by [`FieldIterator::filter_tags`]. Fields are filtered as they are decoded, so
+/// nothing is allocated. Combined with [`collect_to_message`], this is close to
+/// [`keep_only_fields`](crate::keep_only_fields), but lets the fields be inspected or transformed
+/// on the way. Unlike `keep_only_fields`, groups are always dropped, since [`FieldIterator`] skips
+/// them.
+#[derive(Debug, Clone)]
+pub struct FilterFieldsByTag<'a> {
+    inner: FieldIterator<'a>,
+    allowed_tags: &'a [u64],
+}
+
+impl<'a> Iterator for FilterFieldsByTag<'a> {
+    type Item = FieldView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let allowed_tags = self.allowed_tags;
+        self.inner.find(|field| allowed_tags.contains(&field.tag))
+    }
+}
+
+impl core::iter::FusedIterator for FilterFieldsByTag<'_> {}
+
+/// Encodes the fields yielded by an iterator into a new protobuf message.
+///
+/// Every field is written with its tag and wire type, followed by its value. Length-delimited
+/// values get a new length prefix, so the fields may come from different messages.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{collect_to_message, FieldIterator};
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x02";
+/// let filtered = collect_to_message(FieldIterator::new(encoded_message).filter_tags(&[2, 3]));
+///
+/// assert_eq!(filtered, b"\x12\x02\x68\x69\x18\x02");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn collect_to_message<'a, I: Iterator<Item = FieldView<'a>>>(iter: I) -> Vec<u8> {
+    let mut encoded_message = Vec::new();
+    let mut header = [0; 20];
+    for field in iter {
+        let value = field.as_bytes();
+        let header_len =
+            encoders::encode_header_to_slice(field.tag, field.wire_type, value.len(), &mut header)
+                .expect("a field header is at most 20 bytes");
+        encoded_message.extend_from_slice(&header[..header_len]);
+        encoded_message.extend_from_slice(value);
+    }
+    encoded_message
+}
+
+/// Folds every field of an encoded message into an accumulator, in one pass.
+///
+/// `f` is called with the accumulator, the tag number, the wire type and the raw value of every
+/// field, in wire order, and returns the new accumulator. Values have the same form as the values
+/// returned by [`extract_field_by_tag`](crate::extract_field_by_tag). This is
+/// `FieldIterator::new(encoded_message).fold(..)` with the field unpacked.
+///
+/// Folding stops at the first field that cannot be decoded, like [`FieldIterator`].
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{fold_fields, Variant};
+///
+/// // Fields 1 and 3 are varints, field 2 is a string.
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x02\x08\x04";
+/// let sum = fold_fields(encoded_message, 0, |sum, tag, wire_type, value| {
+///     match (tag, wire_type) {
+///         (1, Variant::Varint) => sum + u64::from(value[0]),
+///         _ => sum,
+///     }
+/// });
+///
+/// assert_eq!(sum, 5);
+/// ```
+pub fn fold_fields<T, F>(encoded_message: &[u8], init: T, mut f: F) -> T
+where
+    F: FnMut(T, u64, Variant, &[u8]) -> T,
+{
+    FieldIterator::new(encoded_message).fold(init, |acc, field| {
+        f(acc, field.tag, field.wire_type, field.as_bytes())
+    })
+}
+
+/// Re-encodes a message, passing every field through a mapping function.
+///
+/// `f` is called with every field in wire order. It returns the field to write in its place, or
+/// `None` to remove the field. The returned field may be the same view, or a new one created with
+/// [`FieldView::new`] to change the tag number, wire type or value, as long as its value outlives
+/// the encoded message.
+///
+/// Groups (wire type 3) have no [`Variant`], so they are not passed to `f` but copied as they are.
+/// Mapping stops at the first field that cannot be decoded, like [`FieldIterator`].
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{map_fields, FieldView, Variant};
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x02";
+/// let mapped = map_fields(encoded_message, |field| match field.tag {
+///     // Remove field 1 and move field 3 to field 4.
+///     1 => None,
+///     3 => Some(FieldView::new(4, field.wire_type, field.as_bytes())),
+///     _ => Some(field),
+/// });
+///
+/// assert_eq!(mapped, b"\x12\x02\x68\x69\x20\x02");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn map_fields<'a, F>(encoded_message: &'a [u8], mut f: F) -> Vec<u8>
+where
+    F: FnMut(FieldView<'a>) -> Option<FieldView<'a>>,
+{
+    let mut mapped = Vec::with_capacity(encoded_message.len());
+    let mut header = [0; 20];
+    let mut offset = 0;
+    loop {
+        let field = match utils::read_field_checked(encoded_message, offset) {
+            Ok(Some(field)) => field,
+            // Only groups can be left after the last field.
+            Ok(None) => {
+                mapped.extend_from_slice(&encoded_message[offset..]);
+                break;
+            }
+            Err(_) => break,
+        };
+        // The bytes skipped before the field are groups.
+        mapped.extend_from_slice(&encoded_message[offset..field.start]);
+        offset = field.end;
+
+        let view = FieldView::new(
+            field.field_number,
+            field.variant,
+            &encoded_message[field.value_start..field.end],
+        );
+        if let Some(view) = f(view) {
+            let value = view.as_bytes();
+            let header_len = encoders::encode_header_to_slice(
+                view.tag,
+                view.wire_type,
+                value.len(),
+                &mut header,
+            )
+            .expect("a field header is at most 20 bytes");
+            mapped.extend_from_slice(&header[..header_len]);
+            mapped.extend_from_slice(value);
+        }
+    }
+    mapped
+}
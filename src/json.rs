@@ -0,0 +1,306 @@
+use crate::{
+    encoders, field_looks_like_message, utils, validate_message, FieldIterator, FieldView,
+    MessageView, Variant,
+};
+use serde_json::map::Entry;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// How [`MessageView::as_json_value`] converts the value of a field to JSON.
+///
+/// A hint that does not match the wire type of the field, or a value that cannot be converted, is
+/// ignored, and the field is converted as if it had no hint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WireTypeHint {
+    /// A varint field (`int32`, `int64`) as a signed number.
+    Int64,
+    /// A varint field (`uint32`, `uint64`, `enum`) as an unsigned number.
+    UInt64,
+    /// A zigzag-encoded varint field (`sint32`, `sint64`) as a signed number.
+    SInt64,
+    /// A varint field as a boolean.
+    Bool,
+    /// A 32-bit field as an unsigned number.
+    Fixed32,
+    /// A 32-bit field as a signed number.
+    SFixed32,
+    /// A 32-bit field as a floating-point number.
+    Float,
+    /// A 64-bit field as an unsigned number.
+    Fixed64,
+    /// A 64-bit field as a signed number.
+    SFixed64,
+    /// A 64-bit field as a floating-point number.
+    Double,
+    /// A length-delimited field as a string, if it is valid UTF-8.
+    String,
+    /// A length-delimited field as a base64-encoded string, even if it looks like a message.
+    Bytes,
+    /// A length-delimited field as a nested message, if it decodes completely as one.
+    Message,
+}
+
+impl MessageView<'_> {
+    /// Converts the message to a JSON object with typed values, for logging pipelines.
+    ///
+    /// The object is keyed by field number. The value of a field is converted according to its
+    /// hint in `hints`, see [`WireTypeHint`]. Without a hint:
+    /// - varint, 32-bit and 64-bit fields are unsigned numbers,
+    /// - length-delimited fields that [`field_looks_like_message`] takes for a message are nested
+    ///   objects, converted the same way,
+    /// - other length-delimited fields are base64-encoded strings.
+    ///
+    /// A field that occurs once holds its value, and a field that occurs more than once holds an
+    /// array of its values, in message order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rustwire::{MessageView, WireTypeHint};
+    /// use serde_json::json;
+    /// use std::collections::HashMap;
+    ///
+    /// // Field 1 is the sint64 -2, field 2 the string "hi", field 3 a nested message and field 4
+    /// // is repeated.
+    /// let encoded_message = b"\x08\x03\x12\x02\x68\x69\x1a\x02\x08\x01\x20\x01\x20\x02";
+    /// let message = MessageView::new(encoded_message);
+    ///
+    /// assert_eq!(
+    ///     message.as_json_value(&HashMap::new()),
+    ///     json!({ "1": 3, "2": "aGk=", "3": { "1": 1 }, "4": [1, 2] })
+    /// );
+    ///
+    /// let hints = HashMap::from([(1, WireTypeHint::SInt64), (2, WireTypeHint::String)]);
+    /// assert_eq!(
+    ///     message.as_json_value(&hints),
+    ///     json!({ "1": -2, "2": "hi", "3": { "1": 1 }, "4": [1, 2] })
+    /// );
+    /// ```
+    ///
+    /// # Notes
+    ///
+    /// - `hints` only applies to the fields of this message. Nested messages are converted without
+    ///   hints.
+    /// - Messages nested more than 100 levels deep are shown as base64-encoded strings.
+    /// - Floating-point values that are not finite cannot be represented in JSON and are `null`.
+    /// - Groups (wire type 3) are skipped, and conversion stops at the first field that cannot be
+    ///   decoded.
+    pub fn as_json_value(&self, hints: &HashMap<u64, WireTypeHint>) -> Value {
+        message_to_json(self.as_bytes(), hints, utils::MAX_GROUP_DEPTH)
+    }
+}
+
+fn message_to_json(
+    encoded_message: &[u8],
+    hints: &HashMap<u64, WireTypeHint>,
+    depth_left: u32,
+) -> Value {
+    let mut map = Map::new();
+    for field in FieldIterator::new(encoded_message) {
+        let value = field_to_json(&field, hints.get(&field.tag).copied(), depth_left);
+        match map.entry(field.tag.to_string()) {
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+            Entry::Occupied(mut entry) => {
+                // Field values are never arrays, so an array holds the values of a repeated field.
+                let values = entry.get_mut();
+                if !values.is_array() {
+                    *values = Value::Array(vec![values.take()]);
+                }
+                values
+                    .as_array_mut()
+                    .expect("a repeated field holds an array")
+                    .push(value);
+            }
+        }
+    }
+    Value::Object(map)
+}
+
+fn field_to_json(field: &FieldView, hint: Option<WireTypeHint>, depth_left: u32) -> Value {
+    let raw = field.as_bytes();
+    let fixed32 = match field.wire_type {
+        Variant::ThirtyTwoBit => raw.try_into().ok().map(u32::from_le_bytes),
+        _ => None,
+    };
+    let fixed64 = match field.wire_type {
+        Variant::SixtyFourBit => raw.try_into().ok().map(u64::from_le_bytes),
+        _ => None,
+    };
+    let length_delimited = field.wire_type == Variant::LengthDelimited;
+
+    let hinted = match hint {
+        Some(WireTypeHint::Int64) => field.as_i64().map(Value::from),
+        Some(WireTypeHint::UInt64) => field.as_u64().map(Value::from),
+        Some(WireTypeHint::SInt64) => field.as_sint64().map(Value::from),
+        Some(WireTypeHint::Bool) => field.as_bool().map(Value::from),
+        Some(WireTypeHint::Fixed32) => fixed32.map(Value::from),
+        Some(WireTypeHint::SFixed32) => fixed32.map(|value| Value::from(value as i32)),
+        Some(WireTypeHint::Float) => field.as_f32().map(|value| Value::from(f64::from(value))),
+        Some(WireTypeHint::Fixed64) => fixed64.map(Value::from),
+        Some(WireTypeHint::SFixed64) => fixed64.map(|value| Value::from(value as i64)),
+        Some(WireTypeHint::Double) => field.as_f64().map(Value::from),
+        Some(WireTypeHint::String) => field.as_str().map(Value::from),
+        Some(WireTypeHint::Bytes) if length_delimited => Some(Value::String(to_base64(raw))),
+        Some(WireTypeHint::Message)
+            if length_delimited && depth_left > 0 && validate_message(raw).is_ok() =>
+        {
+            Some(message_to_json(raw, &HashMap::new(), depth_left - 1))
+        }
+        _ => None,
+    };
+    if let Some(value) = hinted {
+        return value;
+    }
+
+    match field.wire_type {
+        Variant::Varint => field.as_u64().map_or(Value::Null, Value::from),
+        Variant::ThirtyTwoBit => fixed32.map_or(Value::Null, Value::from),
+        Variant::SixtyFourBit => fixed64.map_or(Value::Null, Value::from),
+        Variant::LengthDelimited if depth_left > 0 && field_looks_like_message(raw) => {
+            message_to_json(raw, &HashMap::new(), depth_left - 1)
+        }
+        Variant::LengthDelimited => Value::String(to_base64(raw)),
+    }
+}
+
+/// Converts an encoded protobuf message into a JSON object, for logging and debugging.
+///
+/// The object is keyed by field number. Each key holds an array with one entry per occurrence of
+/// the field, in message order, and each entry holds the wire type of the occurrence and its value
+/// as lowercase hex. Values have the same form as the values returned by
+/// [`extract_field_by_tag`](crate::extract_field_by_tag).
+///
+/// Groups (wire types 3 and 4) have no entry in the object and are dropped, so
+/// [`field_map_to_encoded_message`] does not give them back. Decoding stops at the first field
+/// that cannot be decoded, so the object holds the fields found before it.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_message_as_field_map;
+/// use serde_json::json;
+///
+/// let encoded_message = b"\x08\x96\x01\x12\x02\x68\x69";
+/// assert_eq!(
+///     encode_message_as_field_map(encoded_message),
+///     json!({
+///         "1": [{ "wire_type": "Varint", "value": "9601" }],
+///         "2": [{ "wire_type": "LengthDelimited", "value": "6869" }],
+///     })
+/// );
+/// ```
+pub fn encode_message_as_field_map(encoded_message: &[u8]) -> Value {
+    let mut map = Map::new();
+    for field in FieldIterator::new(encoded_message) {
+        let mut entry = Map::new();
+        entry.insert(
+            "wire_type".to_string(),
+            serde_json::to_value(field.wire_type).expect("a wire type serializes as a string"),
+        );
+        entry.insert("value".to_string(), Value::String(to_hex(field.as_bytes())));
+
+        map.entry(field.tag.to_string())
+            .or_insert_with(|| Value::Array(Vec::new()))
+            .as_array_mut()
+            .expect("every field number holds an array")
+            .push(Value::Object(entry));
+    }
+    Value::Object(map)
+}
+
+/// Converts a JSON object produced by [`encode_message_as_field_map`] back into an encoded
+/// protobuf message.
+///
+/// Fields are encoded in ascending field number order, and the occurrences of a repeated field in
+/// the order of their array. For messages whose fields are already in that order, which includes
+/// every message encoded by prost and most other encoders, this gives back the original message,
+/// less any groups it held.
+///
+/// # Returns
+///
+/// The encoded message, or `None` if `map` is not shaped like the output of
+/// [`encode_message_as_field_map`] or holds invalid field numbers, wire types or hex values.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_message_as_field_map, field_map_to_encoded_message};
+///
+/// let encoded_message = b"\x08\x96\x01\x12\x02\x68\x69";
+/// let map = encode_message_as_field_map(encoded_message);
+/// assert_eq!(field_map_to_encoded_message(&map).unwrap(), encoded_message);
+/// ```
+pub fn field_map_to_encoded_message(map: &Value) -> Option<Vec<u8>> {
+    let mut fields = Vec::new();
+    for (field_number, occurrences) in map.as_object()? {
+        let field_number = field_number
+            .parse()
+            .ok()
+            .filter(|field_number| (1..=utils::MAX_FIELD_NUMBER).contains(field_number))?;
+        for occurrence in occurrences.as_array()? {
+            let wire_type: Variant =
+                serde_json::from_value(occurrence.get("wire_type")?.clone()).ok()?;
+            let value = from_hex(occurrence.get("value")?.as_str()?)?;
+            fields.push((field_number, wire_type, value));
+        }
+    }
+    // A stable sort keeps repeated occurrences in array order.
+    fields.sort_by_key(|(field_number, _, _)| *field_number);
+
+    let mut encoded_message = Vec::new();
+    for (field_number, wire_type, value) in fields {
+        let field = encoders::encode_field(field_number, wire_type, &value);
+        // Reject varint values that are not a single varint and fixed-width values of the wrong
+        // width.
+        if utils::read_field(&field, 0)?.end != field.len() {
+            return None;
+        }
+        encoded_message.extend_from_slice(&field);
+    }
+    Some(encoded_message)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        // Writing to a `String` never fails.
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+/// Encodes `bytes` as standard base64, with padding.
+fn to_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut base64 = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                base64.push(char::from(
+                    ALPHABET[(group >> (18 - 6 * i)) as usize & 0x3f],
+                ));
+            } else {
+                base64.push('=');
+            }
+        }
+    }
+    base64
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let pairs = hex.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+    let digit = |c: u8| char::from(c).to_digit(16).map(|digit| digit as u8);
+    pairs
+        .map(|pair| Some((digit(pair[0])? << 4) | digit(pair[1])?))
+        .collect()
+}
@@ -63,285 +63,3460 @@
 //! ## License
 //!
 //! This crate is licensed under the [MIT License](https://opensource.org/licenses/MIT).
+//!
+//! ## Feature flags
+//!
+//! - `std` (enabled by default): enables the `alloc` feature, plus everything that needs the
+//!   standard library: `StreamingMessageBuilder`, `MessageIndex`, `FieldNameResolver` and the
+//!   `std::error::Error` implementations of the error types.
+//! - `alloc`: enables every function that returns or modifies a `Vec`, for `no_std` targets with an
+//!   allocator. Without it, the crate still provides the functions that only read from borrowed
+//!   buffers, such as `extract_field_by_tag`, `message_has_field` and the decoders, as well as
+//!   `MessageBuilderFixed`.
+//! - `debug-comments`: enables debug annotations, see `DEBUG_ANNOTATION_TAG`.
+//! - `bytes`: enables `extract_field_as_bytes_slice`, which extracts fields from a `bytes::Bytes`
+//!   buffer without copying them, and `encode_varint_buf`, which encodes varints into a
+//!   `bytes::BufMut`.
+//! - `simd`: decodes varints with SSE2 instructions on x86 and x86-64 targets that enable SSE2 at
+//!   compile time. Other targets keep the portable decoder.
+//! - `serde`: implements `serde::Serialize` and `serde::Deserialize` for `Variant` and
+//!   `FieldHeader`.
+//! - `serde_json`: enables the `serde` and `std` features, plus `encode_message_as_field_map` and
+//!   `field_map_to_encoded_message`, which convert messages to and from JSON for logging and
+//!   debugging, and `MessageView::as_json_value`, which converts a message to typed JSON values.
+//! - `tokio`: enables the `bytes` and `std` features, plus `ProtoLengthDelimitedCodec`, a
+//!   `tokio_util` codec for streams of length-prefixed messages.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
+mod builder;
+mod cursor;
 mod decoders;
+#[cfg(feature = "std")]
+mod diff;
 mod encoders;
+mod error;
+mod framing;
+mod header;
+#[cfg(feature = "std")]
+mod index;
+mod iter;
+#[cfg(feature = "serde_json")]
+mod json;
+#[cfg(feature = "alloc")]
+mod names;
+mod options;
+#[cfg(feature = "alloc")]
+mod patcher;
+#[cfg(feature = "alloc")]
+mod pretty;
 mod tests;
+mod traits;
 mod utils;
+mod validate;
+mod view;
+mod well_known;
+
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, collections::VecDeque, string::String, vec, vec::Vec};
 
 /// Extracts a field with the given tag number from an encoded protobuf message.
 ///
-/// This function iterates over the encoded message and searches for a field with the specified tag number.
-/// If the field is found, its value is extracted and returned as a byte vector (`Vec<u8>`).
+/// This function iterates over the encoded message and searches for a field with the specified tag number.
+/// If the field is found, its value is extracted and returned as a byte vector (`Vec<u8>`).
+///
+/// The function supports the following wire types:
+/// - Varint (wire type 0)
+/// - Length-delimited (wire type 2)
+///
+/// If the field is not found or if an error occurs during decoding, `None` is returned.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<&[u8]>` - If the field is found, its value is returned as `Some(&[u8])`.
+///   If the field is not found or an error occurs, `None` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use rustwire::extract_field_by_tag;
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+/// let tag_number = 2;
+///
+/// match extract_field_by_tag(encoded_message, tag_number) {
+///     Some(field_value) => println!("Field value: {:?}", field_value),
+///     None => println!("Field not found or invalid encoded message"),
+/// }
+/// ```
+///
+/// # Note
+///
+/// This function assumes a basic understanding of the protobuf encoding format and wire types.
+/// It may need to be adapted to handle more complex field types or nested messages.
+pub fn extract_field_by_tag(encoded_message: &[u8], tag_number: u64) -> Option<&[u8]> {
+    extract_field_by_tag_from(encoded_message, tag_number, 0).map(|(value, _)| value)
+}
+
+/// Extracts the first field with the given tag number at or after a starting offset.
+///
+/// This works like [`extract_field_by_tag`], but scanning starts at `start_offset`, which must be
+/// the start of a field, and the offset right after the found field is returned as well. Passing
+/// that offset back in continues the scan, which visits every occurrence of a repeated field one
+/// at a time.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+/// * `start_offset` - The offset of the field to start scanning at.
+///
+/// # Returns
+///
+/// The value of the field, in the same form as [`extract_field_by_tag`], and the offset right after
+/// the field, or `None` if no field with the tag number follows `start_offset` or the message
+/// cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_by_tag_from;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x08\x02";
+///
+/// let mut values = Vec::new();
+/// let mut offset = 0;
+/// while let Some((value, next)) = extract_field_by_tag_from(encoded_message, 1, offset) {
+///     values.push(value);
+///     offset = next;
+/// }
+/// assert_eq!(values, [&[0x01][..], &[0x02][..]]);
+/// ```
+pub fn extract_field_by_tag_from(
+    encoded_message: &[u8],
+    tag_number: u64,
+    start_offset: usize,
+) -> Option<(&[u8], usize)> {
+    let mut offset = start_offset;
+    while offset < encoded_message.len() {
+        let (tag, new_offset) = decoders::decode_varint(encoded_message, offset)?;
+        offset = new_offset;
+
+        let field_number = decoders::field_number_from_tag(tag);
+        let wire_type = decoders::wire_type_from_tag(tag);
+
+        // Groups have no value to return, so they are skipped like any other field.
+        if field_number == tag_number && wire_type != 3 {
+            return decoders::decode_field_value(encoded_message, wire_type, offset);
+        } else {
+            offset = utils::skip_field(encoded_message, field_number, wire_type, offset)?;
+        }
+    }
+    None
+}
+
+/// Extracts a field with the given tag number, starting the search at an expected offset.
+///
+/// When messages share a deterministic field order, a field is found at the same offset in every
+/// message. Scanning starts at `expected_offset`, which should be the offset returned for a
+/// previous message. Only if the field is not found after it, or the bytes after it cannot be
+/// decoded, is the whole message scanned from the start. With a good hint, the fields before the
+/// target are never looked at.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+/// * `expected_offset` - The offset at which the field's tag is expected to start.
+///
+/// # Returns
+///
+/// The value of the field, in the same form as [`extract_field_by_tag`], and the offset of the
+/// field's tag, to be used as the hint for the next message. `None` is returned if the field is
+/// not found or the message cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_by_tag_hinted;
+///
+/// let first = b"\x08\x01\x12\x02\x68\x69\x18\x05";
+/// let (value, hint) = extract_field_by_tag_hinted(first, 3, 0).unwrap();
+/// assert_eq!((value, hint), (&[0x05][..], 6));
+///
+/// let second = b"\x08\x02\x12\x02\x68\x6f\x18\x07";
+/// assert_eq!(extract_field_by_tag_hinted(second, 3, hint), Some((&[0x07][..], 6)));
+/// ```
+///
+/// # Notes
+///
+/// - `expected_offset` should be the start of a field, or past the end of the message. Scanning
+///   from the middle of a field decodes its bytes as fields, which usually fails and falls back to
+///   the full scan, but can match the wrong bytes. It never panics.
+/// - If the field occurs more than once, the first occurrence at or after `expected_offset` is
+///   returned, which is not necessarily the first one in the message.
+pub fn extract_field_by_tag_hinted(
+    encoded_message: &[u8],
+    tag_number: u64,
+    expected_offset: usize,
+) -> Option<(&[u8], usize)> {
+    let hint = expected_offset.min(encoded_message.len());
+    if let Some(found) = scan_for_field(encoded_message, tag_number, hint, encoded_message.len()) {
+        return Some(found);
+    }
+    scan_for_field(encoded_message, tag_number, 0, encoded_message.len())
+}
+
+/// Scans the fields starting between `start` and `end` for the first field with the tag number,
+/// returning its value and the offset of its tag.
+fn scan_for_field(
+    encoded_message: &[u8],
+    tag_number: u64,
+    start: usize,
+    end: usize,
+) -> Option<(&[u8], usize)> {
+    let mut offset = start;
+    while offset < end {
+        let (tag, value_offset) = decoders::decode_varint(encoded_message, offset)?;
+
+        let field_number = decoders::field_number_from_tag(tag);
+        let wire_type = decoders::wire_type_from_tag(tag);
+
+        if field_number == tag_number && wire_type != 3 {
+            return decoders::decode_field_value(encoded_message, wire_type, value_offset)
+                .map(|(value, _)| (value, offset));
+        }
+        offset = utils::skip_field(encoded_message, field_number, wire_type, value_offset)?;
+    }
+    None
+}
+
+/// Extracts the first field with the given tag number, reporting why a malformed message could not
+/// be decoded.
+///
+/// This is the checked counterpart of [`extract_field_by_tag`], which returns `None` both when the
+/// field is missing and when the message is malformed. Here a missing field is `Ok(None)`, and
+/// malformed input is reported as a [`WireError`].
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Ok(Some(value))` - The value of the field, in the same form as [`extract_field_by_tag`].
+/// * `Ok(None)` - The whole message was decoded and the field is not present.
+/// * `Err(WireError)` - A field before or at the requested one could not be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_tag_checked, WireError};
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+/// assert_eq!(extract_field_by_tag_checked(encoded_message, 2), Ok(Some(&b"testing"[..])));
+/// assert_eq!(extract_field_by_tag_checked(encoded_message, 3), Ok(None));
+///
+/// let truncated = b"\x08\x01\x12\x07\x74\x65";
+/// assert_eq!(
+///     extract_field_by_tag_checked(truncated, 2),
+///     Err(WireError::InvalidLength { offset: 3 })
+/// );
+/// ```
+///
+/// # Notes
+///
+/// - Every field up to the requested one is fully decoded, so a message that is malformed before
+///   the requested field is an error even if [`extract_field_by_tag`] would skip over it.
+/// - Groups (wire type 3) are skipped as a whole, even when they have the requested tag number. An
+///   end-group tag without a matching start-group tag is reported as
+///   [`WireError::UnknownWireType`].
+pub fn extract_field_by_tag_checked(
+    encoded_message: &[u8],
+    tag_number: u64,
+) -> Result<Option<&[u8]>, WireError> {
+    Ok(utils::find_field_checked(encoded_message, tag_number)?
+        .map(|field| &encoded_message[field.value_start..field.end]))
+}
+
+/// Extracts the first field with the given tag number, parsing the message as configured by
+/// `opts`.
+///
+/// With [`MessageParseOptions::default()`] this finds the same fields as [`extract_field_by_tag`],
+/// and every way in which a malformed message is tolerated can be turned into an error instead.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+/// * `opts` - The [`MessageParseOptions`] to parse with.
+///
+/// # Returns
+///
+/// * `Ok(Some(value))` - The value of the field, in the same form as [`extract_field_by_tag`].
+/// * `Ok(None)` - The field was not found before the end of the message, or before malformed
+///   bytes that `opts` allows to be skipped.
+/// * `Err(WireError)` - The message is malformed in a way that `opts` does not allow, nests
+///   groups deeper than `opts.max_depth` when `opts` does not allow that, or is larger than
+///   `opts.max_message_size`.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{
+///     extract_field_by_tag_with_opts, MessageParseOptions, UnknownWireTypeAction, WireError,
+/// };
+///
+/// // Field 1 is followed by a field with the undefined wire type 7.
+/// let encoded_message = b"\x08\x01\x17\x12\x02\x68\x69";
+/// let strict = MessageParseOptions {
+///     on_unknown_wire_type: UnknownWireTypeAction::Fail,
+///     ..MessageParseOptions::default()
+/// };
+///
+/// assert_eq!(
+///     extract_field_by_tag_with_opts(encoded_message, 1, &strict),
+///     Ok(Some(&[0x01][..]))
+/// );
+/// assert_eq!(
+///     extract_field_by_tag_with_opts(encoded_message, 2, &MessageParseOptions::default()),
+///     Ok(None)
+/// );
+/// assert_eq!(
+///     extract_field_by_tag_with_opts(encoded_message, 2, &strict),
+///     Err(WireError::UnknownWireType(7))
+/// );
+/// ```
+///
+/// # Notes
+///
+/// - Groups (wire type 3) are always skipped, even when they have the requested tag number, as in
+///   [`extract_field_by_tag`].
+pub fn extract_field_by_tag_with_opts<'a>(
+    encoded_message: &'a [u8],
+    tag_number: u64,
+    opts: &MessageParseOptions,
+) -> Result<Option<&'a [u8]>, WireError> {
+    if encoded_message.len() > opts.max_message_size {
+        return Err(WireError::MessageTooLarge {
+            size: encoded_message.len(),
+            max_size: opts.max_message_size,
+        });
+    }
+
+    let mut offset = 0;
+    loop {
+        let field =
+            match utils::read_field_checked_with_depth(encoded_message, offset, opts.max_depth) {
+                Ok(Some(field)) => field,
+                Ok(None) => return Ok(None),
+                Err(err) => return opts.handle(err).map(|()| None),
+            };
+
+        if field.field_number == tag_number {
+            return Ok(Some(&encoded_message[field.value_start..field.end]));
+        }
+        offset = field.end;
+    }
+}
+
+/// Finds the byte range of the value of a field in an encoded protobuf message.
+///
+/// The range covers the value only, exactly like the slice returned by [`extract_field_by_tag`]:
+/// the tag and, for length-delimited fields, the length prefix are excluded. Use
+/// [`extract_full_field_byte_range`] to include them.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to look for.
+///
+/// # Returns
+///
+/// The half-open range `(start, end)` of the first field with the tag number, so that
+/// `&encoded_message[start..end]` is its value, or `None` if the field is not found or the
+/// message cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_tag, extract_field_byte_range};
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+/// let (start, end) = extract_field_byte_range(encoded_message, 2).unwrap();
+///
+/// assert_eq!((start, end), (4, 11));
+/// assert_eq!(Some(&encoded_message[start..end]), extract_field_by_tag(encoded_message, 2));
+/// ```
+pub fn extract_field_byte_range(encoded_message: &[u8], tag_number: u64) -> Option<(usize, usize)> {
+    utils::find_field(encoded_message, tag_number).map(|field| (field.value_start, field.end))
+}
+
+/// Finds the byte range of a whole field, including its tag and length prefix, in an encoded
+/// protobuf message.
+///
+/// Removing `encoded_message[start..end]` from the message removes the field entirely, and the
+/// range can be copied into another message as is.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to look for.
+///
+/// # Returns
+///
+/// The half-open range `(start, end)` of the first field with the tag number, or `None` if the
+/// field is not found or the message cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_full_field_byte_range;
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+/// assert_eq!(extract_full_field_byte_range(encoded_message, 2), Some((2, 11)));
+/// assert_eq!(extract_full_field_byte_range(encoded_message, 3), None);
+/// ```
+pub fn extract_full_field_byte_range(
+    encoded_message: &[u8],
+    tag_number: u64,
+) -> Option<(usize, usize)> {
+    utils::find_field(encoded_message, tag_number).map(|field| (field.start, field.end))
+}
+
+/// Finds the offset of the first byte of a field's tag in an encoded protobuf message.
+///
+/// This is the start of the whole field record, unlike [`extract_field_byte_range`], which returns
+/// the range of the value. Slicing the message from this offset gives a message that starts with
+/// the field, which is useful for forwarding part of a buffer without copying it.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to look for.
+///
+/// # Returns
+///
+/// The offset of the tag of the first field with the tag number, or `None` if the field is not
+/// found or the message cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_tag, find_field_tag_offset};
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+/// let offset = find_field_tag_offset(encoded_message, 2).unwrap();
+///
+/// assert_eq!(offset, 2);
+/// assert_eq!(extract_field_by_tag(&encoded_message[offset..], 2), Some(&b"testing"[..]));
+/// assert_eq!(find_field_tag_offset(encoded_message, 3), None);
+/// ```
+pub fn find_field_tag_offset(encoded_message: &[u8], tag_number: u64) -> Option<usize> {
+    utils::find_field(encoded_message, tag_number).map(|field| field.start)
+}
+
+/// Extracts the first field with the given tag number as a [`FieldView`].
+///
+/// This finds the same field as [`extract_field_by_tag`], but also returns its wire type, so the
+/// value can be decoded with the typed accessors of `FieldView`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// The field, or `None` if the field is not found or the message cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_view;
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+///
+/// assert_eq!(extract_field_view(encoded_message, 1).unwrap().as_bool(), Some(true));
+/// assert_eq!(extract_field_view(encoded_message, 2).unwrap().as_str(), Some("testing"));
+/// assert!(extract_field_view(encoded_message, 3).is_none());
+/// ```
+pub fn extract_field_view(encoded_message: &[u8], tag: u64) -> Option<FieldView<'_>> {
+    utils::find_field(encoded_message, tag).map(|field| {
+        FieldView::new(
+            field.field_number,
+            field.variant,
+            &encoded_message[field.value_start..field.end],
+        )
+    })
+}
+
+/// Extracts a whole field, including its tag and length prefix, from an encoded protobuf message.
+///
+/// Unlike [`extract_field_by_tag`], which returns the value only, the returned slice is the
+/// complete field record as it appears on the wire. Appending it to another encoded message adds
+/// the field to that message as is, which is useful when forwarding fields between messages.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// The field record of the first field with the tag number, or `None` if the field is not found
+/// or the message cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_tag, extract_field_with_full_record};
+///
+/// let source = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+/// let record = extract_field_with_full_record(source, 2).unwrap();
+/// assert_eq!(record, b"\x12\x07testing");
+///
+/// let mut target = b"\x18\x03".to_vec();
+/// target.extend_from_slice(record);
+/// assert_eq!(extract_field_by_tag(&target, 2), Some(&b"testing"[..]));
+/// ```
+pub fn extract_field_with_full_record(encoded_message: &[u8], tag_number: u64) -> Option<&[u8]> {
+    let (start, end) = extract_full_field_byte_range(encoded_message, tag_number)?;
+    Some(&encoded_message[start..end])
+}
+
+/// Extracts the value of a length-delimited field together with its length prefix.
+///
+/// The returned slice starts at the length varint, right after the tag, and ends with the last
+/// byte of the payload. It sits between [`extract_field_by_tag`], which strips the length prefix,
+/// and [`extract_field_with_full_record`], which also includes the tag. This is the form a value
+/// takes inside a packed encoding of length-delimited values, so such values can be concatenated
+/// without re-encoding their lengths.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// The length prefix and payload of the first field with the tag number, or `None` if the field
+/// is not found, is not length-delimited, or the message cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_with_length_prefix;
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+///
+/// assert_eq!(extract_field_with_length_prefix(encoded_message, 2), Some(&b"\x07testing"[..]));
+/// assert_eq!(extract_field_with_length_prefix(encoded_message, 1), None);
+/// ```
+pub fn extract_field_with_length_prefix(encoded_message: &[u8], tag_number: u64) -> Option<&[u8]> {
+    let field = utils::find_field(encoded_message, tag_number)?;
+    if field.variant != Variant::LengthDelimited {
+        return None;
+    }
+    let (_, length_start) = decoders::decode_varint(encoded_message, field.start)?;
+    Some(&encoded_message[length_start..field.end])
+}
+
+/// Splits an encoded protobuf message around the value of a field.
+///
+/// This is the slice counterpart of [`extract_field_byte_range`]. The message is cut into three
+/// non-overlapping slices that together cover every byte of it, in order:
+/// - everything before the value, ending with the field's tag and, for length-delimited fields,
+///   its length prefix,
+/// - the value, as returned by [`extract_field_by_tag`],
+/// - everything after the field.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to split at.
+///
+/// # Returns
+///
+/// The `(before, value, after)` slices around the first field with the tag number, or `None` if
+/// the field is not found or the message cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::split_message_at_tag;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x03";
+/// let (before, value, after) = split_message_at_tag(encoded_message, 2).unwrap();
+///
+/// assert_eq!(before, b"\x08\x01\x12\x02");
+/// assert_eq!(value, b"hi");
+/// assert_eq!(after, b"\x18\x03");
+/// ```
+pub fn split_message_at_tag(
+    encoded_message: &[u8],
+    tag_number: u64,
+) -> Option<(&[u8], &[u8], &[u8])> {
+    let (start, end) = extract_field_byte_range(encoded_message, tag_number)?;
+    let (before, rest) = encoded_message.split_at(start);
+    let (value, after) = rest.split_at(end - start);
+    Some((before, value, after))
+}
+
+/// Extracts a field from an encoded protobuf message held in a [`bytes::Bytes`] buffer, without
+/// copying it.
+///
+/// The returned `Bytes` shares the allocation of `buf`, like [`Bytes::slice`](bytes::Bytes::slice):
+/// it keeps the buffer alive through its reference count, so it can be stored beyond the lifetime
+/// of `buf` itself.
+///
+/// # Arguments
+///
+/// * `buf` - A `Bytes` buffer containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// The value of the first field with the tag number, in the same form as [`extract_field_by_tag`],
+/// or `None` if the field is not found or the message cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use bytes::Bytes;
+/// use rustwire::extract_field_as_bytes_slice;
+///
+/// let buf = Bytes::from_static(b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67");
+/// let field = extract_field_as_bytes_slice(&buf, 2).unwrap();
+/// drop(buf);
+///
+/// assert_eq!(field, "testing");
+/// ```
+#[cfg(feature = "bytes")]
+pub fn extract_field_as_bytes_slice(buf: &bytes::Bytes, tag_number: u64) -> Option<bytes::Bytes> {
+    let (start, end) = extract_field_byte_range(buf, tag_number)?;
+    Some(buf.slice(start..end))
+}
+
+/// Extracts a varint field and decodes it as a `u64`.
+///
+/// Varint fields are returned by [`extract_field_by_tag`] as their raw varint bytes, which are not
+/// the same as a little-endian integer. This function decodes them for you.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<u64>` - The decoded value, or `None` if the field is not found, is not a varint, or
+///   the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_u64;
+///
+/// let encoded_message = b"\x08\x96\x01\x12\x02\x68\x69";
+/// assert_eq!(extract_field_as_u64(encoded_message, 1), Some(150));
+/// assert_eq!(extract_field_as_u64(encoded_message, 2), None);
+/// ```
+pub fn extract_field_as_u64(encoded_message: &[u8], tag_number: u64) -> Option<u64> {
+    let field = utils::find_field(encoded_message, tag_number)?;
+    if field.variant != Variant::Varint {
+        return None;
+    }
+    decoders::decode_varint(encoded_message, field.value_start).map(|(value, _)| value)
+}
+
+/// Extracts a varint field and decodes it as an `i64`.
+///
+/// The decoded bits are reinterpreted as a two's complement integer, which is how `int32` and
+/// `int64` fields are encoded. For `sint32` and `sint64` fields, which use zigzag encoding, this
+/// does not give the right value.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<i64>` - The decoded value, or `None` if the field is not found, is not a varint, or
+///   the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_i64;
+///
+/// let encoded_message = b"\x08\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x01";
+/// assert_eq!(extract_field_as_i64(encoded_message, 1), Some(-1));
+/// ```
+pub fn extract_field_as_i64(encoded_message: &[u8], tag_number: u64) -> Option<i64> {
+    extract_field_as_u64(encoded_message, tag_number).map(|value| value as i64)
+}
+
+/// Extracts an `enum` field and decodes it as an `i32`.
+///
+/// Enum values are encoded like `int32` values. The decoded varint is truncated to its lower 32
+/// bits and reinterpreted as a two's complement integer, so negative enum values, which are
+/// encoded sign-extended to 64 bits, decode correctly, and values above `i32::MAX` wrap to negative
+/// numbers like in the official protobuf libraries. Values that are not defined in the enum are
+/// returned as is, matching proto3's handling of unknown enum values.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<i32>` - The decoded value, or `None` if the field is not found, is not a varint, or
+///   the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_enum;
+///
+/// let encoded_message = b"\x08\x02\x10\xFE\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x01";
+/// assert_eq!(extract_field_as_enum(encoded_message, 1), Some(2));
+/// assert_eq!(extract_field_as_enum(encoded_message, 2), Some(-2));
+/// ```
+pub fn extract_field_as_enum(encoded_message: &[u8], tag_number: u64) -> Option<i32> {
+    extract_field_as_u64(encoded_message, tag_number).map(|value| value as i32)
+}
+
+/// Extracts an `enum` field and decodes it as an `i64`, without truncating it to 32 bits.
+///
+/// This is the same as [`extract_field_as_i64`]. Unlike [`extract_field_as_enum`], values that do
+/// not fit in an `i32`, which a conforming encoder never produces, are returned unchanged.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_as_enum, extract_field_as_enum_raw};
+///
+/// let encoded_message = b"\x08\x80\x80\x80\x80\x10";
+/// assert_eq!(extract_field_as_enum_raw(encoded_message, 1), Some(1 << 32));
+/// assert_eq!(extract_field_as_enum(encoded_message, 1), Some(0));
+/// ```
+pub fn extract_field_as_enum_raw(encoded_message: &[u8], tag_number: u64) -> Option<i64> {
+    extract_field_as_i64(encoded_message, tag_number)
+}
+
+/// Extracts a `bool` field.
+///
+/// A varint value of `0` decodes to `false`, any other value to `true`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<bool>` - The decoded value, or `None` if the field is not found, is not a varint, or
+///   the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_bool;
+///
+/// let encoded_message = b"\x08\x01\x10\x00";
+/// assert_eq!(extract_field_as_bool(encoded_message, 1), Some(true));
+/// assert_eq!(extract_field_as_bool(encoded_message, 2), Some(false));
+/// assert_eq!(extract_field_as_bool(encoded_message, 3), None);
+/// ```
+pub fn extract_field_as_bool(encoded_message: &[u8], tag_number: u64) -> Option<bool> {
+    extract_field_as_u64(encoded_message, tag_number).map(|value| value != 0)
+}
+
+/// Extracts a zigzag-encoded `sint32` field.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<i32>` - The decoded value, or `None` if the field is not found, is not a varint, or
+///   the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_sint32;
+///
+/// let encoded_message = b"\x08\x03";
+/// assert_eq!(extract_field_as_sint32(encoded_message, 1), Some(-2));
+/// ```
+pub fn extract_field_as_sint32(encoded_message: &[u8], tag_number: u64) -> Option<i32> {
+    extract_field_as_u64(encoded_message, tag_number).map(decoders::decode_zigzag_i32)
+}
+
+/// Extracts a zigzag-encoded `sint64` field.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<i64>` - The decoded value, or `None` if the field is not found, is not a varint, or
+///   the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_sint64;
+///
+/// let encoded_message = b"\x08\x04";
+/// assert_eq!(extract_field_as_sint64(encoded_message, 1), Some(2));
+/// ```
+pub fn extract_field_as_sint64(encoded_message: &[u8], tag_number: u64) -> Option<i64> {
+    extract_field_as_u64(encoded_message, tag_number).map(decoders::decode_zigzag_i64)
+}
+
+/// Extracts a `fixed32` field and decodes its little-endian bytes as a `u32`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<u32>` - The decoded value, or `None` if the field is not found, is not a 32-bit field,
+///   or the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_fixed32;
+///
+/// let encoded_message = b"\x0d\x01\x00\x00\x00";
+/// assert_eq!(extract_field_as_fixed32(encoded_message, 1), Some(1));
+/// ```
+pub fn extract_field_as_fixed32(encoded_message: &[u8], tag_number: u64) -> Option<u32> {
+    utils::find_fixed(encoded_message, tag_number, Variant::ThirtyTwoBit).map(u32::from_le_bytes)
+}
+
+/// Extracts a `fixed64` field and decodes its little-endian bytes as a `u64`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<u64>` - The decoded value, or `None` if the field is not found, is not a 64-bit field,
+///   or the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_fixed64;
+///
+/// let encoded_message = b"\x09\x01\x00\x00\x00\x00\x00\x00\x00";
+/// assert_eq!(extract_field_as_fixed64(encoded_message, 1), Some(1));
+/// ```
+pub fn extract_field_as_fixed64(encoded_message: &[u8], tag_number: u64) -> Option<u64> {
+    utils::find_fixed(encoded_message, tag_number, Variant::SixtyFourBit).map(u64::from_le_bytes)
+}
+
+/// Extracts a `sfixed32` field and decodes its little-endian bytes as a `i32`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<i32>` - The decoded value, or `None` if the field is not found, is not a 32-bit field,
+///   or the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_sfixed32;
+///
+/// let encoded_message = b"\x0d\xFF\xFF\xFF\xFF";
+/// assert_eq!(extract_field_as_sfixed32(encoded_message, 1), Some(-1));
+/// ```
+pub fn extract_field_as_sfixed32(encoded_message: &[u8], tag_number: u64) -> Option<i32> {
+    utils::find_fixed(encoded_message, tag_number, Variant::ThirtyTwoBit).map(i32::from_le_bytes)
+}
+
+/// Extracts a `sfixed64` field and decodes its little-endian bytes as a `i64`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<i64>` - The decoded value, or `None` if the field is not found, is not a 64-bit field,
+///   or the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_sfixed64;
+///
+/// let encoded_message = b"\x09\xFE\xFF\xFF\xFF\xFF\xFF\xFF\xFF";
+/// assert_eq!(extract_field_as_sfixed64(encoded_message, 1), Some(-2));
+/// ```
+pub fn extract_field_as_sfixed64(encoded_message: &[u8], tag_number: u64) -> Option<i64> {
+    utils::find_fixed(encoded_message, tag_number, Variant::SixtyFourBit).map(i64::from_le_bytes)
+}
+
+/// Extracts a `string` field as a `&str`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<&str>` - The value of the field, or `None` if the field is not found, is not a
+///   length-delimited field, is not valid UTF-8, or the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_str;
+///
+/// let encoded_message = b"\x0a\x02\x68\x69\x12\x01\xff";
+/// assert_eq!(extract_field_as_str(encoded_message, 1), Some("hi"));
+/// assert_eq!(extract_field_as_str(encoded_message, 2), None);
+/// ```
+pub fn extract_field_as_str(encoded_message: &[u8], tag_number: u64) -> Option<&str> {
+    extract_field_view(encoded_message, tag_number)?.as_str()
+}
+
+/// Extracts a `string` field, replacing invalid UTF-8 sequences with `U+FFFD REPLACEMENT
+/// CHARACTER`.
+///
+/// The value is only copied if it contains invalid UTF-8, see [`String::from_utf8_lossy`].
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// * `Option<Cow<str>>` - The value of the field, or `None` if the field is not found, is not a
+///   length-delimited field, or the message is malformed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_as_str_lossy;
+///
+/// let encoded_message = b"\x0a\x02\x68\x69\x12\x01\xff";
+/// assert_eq!(extract_field_as_str_lossy(encoded_message, 1).as_deref(), Some("hi"));
+/// assert_eq!(extract_field_as_str_lossy(encoded_message, 2).as_deref(), Some("\u{FFFD}"));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn extract_field_as_str_lossy(encoded_message: &[u8], tag_number: u64) -> Option<Cow<'_, str>> {
+    let field = extract_field_view(encoded_message, tag_number)?;
+    match field.wire_type {
+        Variant::LengthDelimited => Some(String::from_utf8_lossy(field.as_bytes())),
+        _ => None,
+    }
+}
+
+/// Decodes the payload of a packed repeated varint field into its values.
+///
+/// Proto3 encodes `repeated` scalar fields in packed form: a single length-delimited field whose
+/// payload is every element encoded back to back. [`extract_field_by_tag`] returns that payload
+/// as is; this function splits it into the individual varints.
+///
+/// # Arguments
+///
+/// * `data` - The payload of the packed field, e.g. as returned by [`extract_field_by_tag`].
+///
+/// # Returns
+///
+/// * `Option<Vec<u64>>` - The decoded values, or `None` if the last varint is truncated or a
+///   varint is longer than 10 bytes.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_tag, extract_packed_varint};
+///
+/// let encoded_message = b"\x0a\x03\x03\x8e\x02";
+/// let data = extract_field_by_tag(encoded_message, 1).unwrap();
+/// assert_eq!(extract_packed_varint(data), Some(vec![3, 270]));
+/// ```
+///
+/// # Notes
+///
+/// - The values are returned as raw `u64`s. Use [`decode_zigzag_i64`] for `sint64` elements, or
+///   cast to `i64` for `int64` elements.
+#[cfg(feature = "alloc")]
+pub fn extract_packed_varint(data: &[u8]) -> Option<Vec<u64>> {
+    let mut values = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let (value, new_offset) = decoders::decode_varint(data, offset)?;
+        values.push(value);
+        offset = new_offset;
+    }
+    Some(values)
+}
+
+/// Decodes the payload of a packed `repeated float` field into its values.
+///
+/// See [`extract_packed_varint`] for how packed fields are encoded.
+///
+/// # Returns
+///
+/// * `Option<Vec<f32>>` - The decoded values, or `None` if the length of `data` is not a multiple
+///   of 4.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_packed_float;
+///
+/// let data = [0x00, 0x00, 0x80, 0x3F, 0x00, 0x00, 0x00, 0xC0];
+/// assert_eq!(extract_packed_float(&data), Some(vec![1.0, -2.0]));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn extract_packed_float(data: &[u8]) -> Option<Vec<f32>> {
+    let chunks = data.chunks_exact(4);
+    if !chunks.remainder().is_empty() {
+        return None;
+    }
+    let values = chunks
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect();
+    Some(values)
+}
+
+/// Decodes the payload of a packed `repeated double` field into its values.
+///
+/// See [`extract_packed_varint`] for how packed fields are encoded.
+///
+/// # Returns
+///
+/// * `Option<Vec<f64>>` - The decoded values, or `None` if the length of `data` is not a multiple
+///   of 8.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_packed_double;
+///
+/// let data = [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x3F];
+/// assert_eq!(extract_packed_double(&data), Some(vec![1.0]));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn extract_packed_double(data: &[u8]) -> Option<Vec<f64>> {
+    let chunks = data.chunks_exact(8);
+    if !chunks.remainder().is_empty() {
+        return None;
+    }
+    let values = chunks
+        .map(|chunk| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(chunk);
+            f64::from_le_bytes(bytes)
+        })
+        .collect();
+    Some(values)
+}
+
+/// Decodes the payload of a packed `repeated sint32` field into its values.
+///
+/// This splits the payload like [`extract_packed_varint`] and zigzag-decodes every element.
+///
+/// # Arguments
+///
+/// * `data` - The payload of the packed field, e.g. as returned by [`extract_field_by_tag`].
+///
+/// # Returns
+///
+/// * `Option<Vec<i32>>` - The decoded values, or `None` if the last varint is truncated or a
+///   varint is longer than 10 bytes.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_packed_sint32;
+///
+/// assert_eq!(decode_packed_sint32(&[0x00, 0x01, 0x02, 0xd7, 0x04]), Some(vec![0, -1, 1, -300]));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decode_packed_sint32(data: &[u8]) -> Option<Vec<i32>> {
+    extract_packed_varint(data).map(|values| {
+        values
+            .into_iter()
+            .map(decoders::decode_zigzag_i32)
+            .collect()
+    })
+}
+
+/// Decodes the payload of a packed `repeated sint64` field into its values.
+///
+/// This splits the payload like [`extract_packed_varint`] and zigzag-decodes every element.
+///
+/// # Arguments
+///
+/// * `data` - The payload of the packed field, e.g. as returned by [`extract_field_by_tag`].
+///
+/// # Returns
+///
+/// * `Option<Vec<i64>>` - The decoded values, or `None` if the last varint is truncated or a
+///   varint is longer than 10 bytes.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_packed_sint64;
+///
+/// assert_eq!(decode_packed_sint64(&[0x03, 0xd8, 0x04]), Some(vec![-2, 300]));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decode_packed_sint64(data: &[u8]) -> Option<Vec<i64>> {
+    extract_packed_varint(data).map(|values| {
+        values
+            .into_iter()
+            .map(decoders::decode_zigzag_i64)
+            .collect()
+    })
+}
+
+/// Re-encodes a packed repeated field as one field record per element.
+///
+/// Every length-delimited occurrence of the field is split into its elements, which are written
+/// as individual fields with the same tag number in place of the packed field. Older proto2
+/// parsers that do not understand packed encoding can read the result. The other fields are
+/// copied unchanged.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag` - The tag number of the packed repeated field.
+/// * `element_wire_type` - The wire type of the elements: [`Variant::Varint`] for integer, `bool`
+///   and `enum` elements, [`Variant::ThirtyTwoBit`] for `float`, `fixed32` and `sfixed32`, and
+///   [`Variant::SixtyFourBit`] for `double`, `fixed64` and `sfixed64`. The elements of a packed
+///   field cannot be told apart without it.
+///
+/// # Returns
+///
+/// The re-encoded message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{packed_to_unpacked, Variant};
+///
+/// let packed = b"\x0a\x03\x01\x96\x01";
+/// assert_eq!(packed_to_unpacked(packed, 1, Variant::Varint), b"\x08\x01\x08\x96\x01");
+/// ```
+///
+/// # Notes
+///
+/// - A packed payload that does not split evenly into elements of the given wire type, and a
+///   `Variant::LengthDelimited` element type, leave the field unchanged.
+/// - If the message cannot be decoded, the bytes from the first malformed field on are copied
+///   unchanged.
+#[cfg(feature = "alloc")]
+pub fn packed_to_unpacked(encoded_message: &[u8], tag: u64, element_wire_type: Variant) -> Vec<u8> {
+    let mut out = Vec::with_capacity(encoded_message.len());
+    let mut offset = 0;
+    while offset < encoded_message.len() {
+        let field = match utils::read_field(encoded_message, offset) {
+            Some(field) => field,
+            None => {
+                out.extend_from_slice(&encoded_message[offset..]);
+                break;
+            }
+        };
+
+        // Keep the groups stepped over before the field.
+        out.extend_from_slice(&encoded_message[offset..field.start]);
+        let unpacked = if field.field_number == tag && field.variant == Variant::LengthDelimited {
+            unpack_elements(
+                &encoded_message[field.value_start..field.end],
+                tag,
+                element_wire_type,
+            )
+        } else {
+            None
+        };
+        match unpacked {
+            Some(unpacked) => out.extend_from_slice(&unpacked),
+            None => out.extend_from_slice(&encoded_message[field.start..field.end]),
+        }
+
+        offset = field.end;
+    }
+    out
+}
+
+/// Encodes the elements of a packed payload as individual fields, or returns `None` if the payload
+/// does not split evenly into elements of the given wire type.
+#[cfg(feature = "alloc")]
+fn unpack_elements(payload: &[u8], tag: u64, element_wire_type: Variant) -> Option<Vec<u8>> {
+    let width = match element_wire_type {
+        Variant::Varint => None,
+        Variant::SixtyFourBit => Some(8),
+        Variant::ThirtyTwoBit => Some(4),
+        Variant::LengthDelimited => return None,
+    };
+
+    let mut header = [0; 20];
+    let header_len = encoders::encode_header_to_slice(tag, element_wire_type, 0, &mut header)
+        .expect("a field header is at most 20 bytes");
+    let header = &header[..header_len];
+
+    let mut out = Vec::with_capacity(payload.len() * 2);
+    let mut offset = 0;
+    while offset < payload.len() {
+        let element = match width {
+            Some(width) => payload.get(offset..offset + width)?,
+            None => decoders::varint_byte_slice(payload, offset)?,
+        };
+        out.extend_from_slice(header);
+        out.extend_from_slice(element);
+        offset += element.len();
+    }
+    Some(out)
+}
+
+/// Re-encodes the elements of a repeated field as a single packed field.
+///
+/// This is the reverse of [`packed_to_unpacked`]. The values of all occurrences of the field with
+/// the given element wire type are concatenated into one length-delimited field, which is written
+/// where the first occurrence was. Packed occurrences that are already present are merged in as
+/// well. The other fields are copied unchanged.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag` - The tag number of the repeated field.
+/// * `element_wire_type` - The wire type of the elements, see [`packed_to_unpacked`].
+///
+/// # Returns
+///
+/// The re-encoded message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{unpacked_to_packed, Variant};
+///
+/// let unpacked = b"\x08\x01\x10\x07\x08\x96\x01";
+/// assert_eq!(
+///     unpacked_to_packed(unpacked, 1, Variant::Varint),
+///     b"\x0a\x03\x01\x96\x01\x10\x07"
+/// );
+/// ```
+///
+/// # Notes
+///
+/// - Only scalar numeric fields can be packed. With a `Variant::LengthDelimited` element type, or
+///   if the message cannot be decoded, the message is returned unchanged.
+#[cfg(feature = "alloc")]
+pub fn unpacked_to_packed(encoded_message: &[u8], tag: u64, element_wire_type: Variant) -> Vec<u8> {
+    let unchanged = || encoded_message.to_vec();
+    if element_wire_type == Variant::LengthDelimited {
+        return unchanged();
+    }
+
+    let mut payload = Vec::new();
+    let mut first = None;
+    let mut offset = 0;
+    loop {
+        let field = match utils::read_field_checked(encoded_message, offset) {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(_) => return unchanged(),
+        };
+        if field.field_number == tag
+            && (field.variant == element_wire_type || field.variant == Variant::LengthDelimited)
+        {
+            first.get_or_insert(field.start);
+            payload.extend_from_slice(&encoded_message[field.value_start..field.end]);
+        }
+        offset = field.end;
+    }
+    let first = match first {
+        Some(first) => first,
+        None => return unchanged(),
+    };
+
+    let mut out = Vec::with_capacity(encoded_message.len());
+    out.extend_from_slice(&encoded_message[..first]);
+    if !payload.is_empty() {
+        out.extend_from_slice(&create_header(
+            tag,
+            Variant::LengthDelimited.into(),
+            &payload,
+        ));
+        out.extend_from_slice(&payload);
+    }
+    // Every field was decoded in the first pass, so only groups can be left after the last one.
+    let mut offset = first;
+    while let Some(field) = utils::read_field(encoded_message, offset) {
+        out.extend_from_slice(&encoded_message[offset..field.start]);
+        if field.field_number != tag
+            || (field.variant != element_wire_type && field.variant != Variant::LengthDelimited)
+        {
+            out.extend_from_slice(&encoded_message[field.start..field.end]);
+        }
+        offset = field.end;
+    }
+    out.extend_from_slice(&encoded_message[offset..]);
+    out
+}
+
+/// Checks whether a field with the given tag number is present in an encoded message.
+///
+/// This gives the same answer as `extract_field_by_tag(...).is_some()`, and like it, stops at the
+/// first field with the tag number. The values of the fields before it are skipped rather than
+/// decoded.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to look for.
+///
+/// # Returns
+///
+/// `true` if a field with the tag number is found, `false` otherwise.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::message_has_field;
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
+/// assert!(message_has_field(encoded_message, 2));
+/// assert!(!message_has_field(encoded_message, 3));
+/// ```
+///
+/// # Notes
+///
+/// - A field whose value is truncated is not present.
+/// - Groups are skipped like in [`extract_field_by_tag`], so a group with the tag number is not
+///   present either.
+/// - If the message cannot be decoded before a matching field is found, `false` is returned.
+pub fn message_has_field(encoded_message: &[u8], tag_number: u64) -> bool {
+    utils::find_field(encoded_message, tag_number).is_some()
+}
+
+/// Finds which field of a oneof is set in an encoded message.
+///
+/// Only one field of a oneof is encoded, so the tag number of the field that is present tells
+/// which alternative is set. If fields of several alternatives are present, the last one wins, as
+/// it does when a protobuf implementation decodes the message.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `oneof_tags` - The tag numbers of the fields of the oneof.
+///
+/// # Returns
+///
+/// The tag number of the last field of the oneof in the message, or `None` if no field of the
+/// oneof is present. If the message cannot be decoded, only the fields before the malformed bytes
+/// are considered.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::oneof_detected_tag;
+///
+/// // Field 1 is a regular field, fields 4 and 5 belong to a oneof.
+/// let encoded_message = b"\x08\x01\x2a\x02\x68\x69";
+/// assert_eq!(oneof_detected_tag(encoded_message, &[4, 5]), Some(5));
+/// assert_eq!(oneof_detected_tag(encoded_message, &[6, 7]), None);
+/// ```
+pub fn oneof_detected_tag(encoded_message: &[u8], oneof_tags: &[u64]) -> Option<u64> {
+    FieldIterator::new(encoded_message)
+        .filter(|field| oneof_tags.contains(&field.tag))
+        .last()
+        .map(|field| field.tag)
+}
+
+/// Checks whether fields of more than one alternative of a oneof are present in an encoded
+/// message.
+///
+/// A well-formed encoder only writes the alternative that is set, so this detects messages that
+/// were assembled by hand or concatenated. Several occurrences of the same alternative are not a
+/// conflict.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `oneof_tags` - The tag numbers of the fields of the oneof.
+///
+/// # Returns
+///
+/// `true` if at least two different tag numbers of `oneof_tags` are present, `false` otherwise.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::oneof_conflict_present;
+///
+/// assert!(!oneof_conflict_present(b"\x20\x01\x20\x02", &[4, 5]));
+/// assert!(oneof_conflict_present(b"\x20\x01\x28\x02", &[4, 5]));
+/// ```
+pub fn oneof_conflict_present(encoded_message: &[u8], oneof_tags: &[u64]) -> bool {
+    let mut alternatives = FieldIterator::new(encoded_message)
+        .filter(|field| oneof_tags.contains(&field.tag))
+        .map(|field| field.tag);
+    match alternatives.next() {
+        Some(first) => alternatives.any(|tag| tag != first),
+        None => false,
+    }
+}
+
+/// Counts the occurrences of a field with the given tag number in an encoded message.
+///
+/// Every occurrence of a repeated field is counted separately. A packed repeated field is a single
+/// field, so it counts once regardless of how many elements it holds, and groups are skipped
+/// without being counted. The result is the same as `extract_all_occurrences_by_tag(...).len()`,
+/// but nothing is collected.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to count.
+///
+/// # Returns
+///
+/// The number of occurrences. If the message cannot be decoded, the occurrences found before the
+/// malformed bytes are counted.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::count_field_occurrences;
+///
+/// let encoded_message = b"\x0a\x01\x61\x10\x01\x0a\x02\x62\x63";
+/// assert_eq!(count_field_occurrences(encoded_message, 1), 2);
+/// assert_eq!(count_field_occurrences(encoded_message, 3), 0);
+/// ```
+pub fn count_field_occurrences(encoded_message: &[u8], tag_number: u64) -> usize {
+    let mut count = 0;
+    let mut offset = 0;
+    while let Some(field) = utils::read_field(encoded_message, offset) {
+        if field.field_number == tag_number {
+            count += 1;
+        }
+        offset = field.end;
+    }
+    count
+}
+
+/// Extracts a field nested inside one or more sub-messages by following a path of tag numbers.
+///
+/// Each tag number in `path` except the last one must name a length-delimited field, whose payload
+/// is treated as a nested message to continue the lookup in. The value of the field named by the
+/// last tag number is returned, in the same form as [`extract_field_by_tag`]. No intermediate
+/// buffers are allocated: the result borrows directly from `encoded_message`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `path` - The tag numbers to follow, from the outermost message inwards.
+///
+/// # Returns
+///
+/// * `Option<&[u8]>` - The value of the field, or `None` if a field on the path is not found, an
+///   intermediate field is not length-delimited, or a message on the path is malformed. An empty
+///   path returns the whole message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_by_path;
+///
+/// // Field 5 is a nested message containing the string field 2.
+/// let encoded_message = b"\x08\x01\x2a\x04\x12\x02\x68\x69";
+/// assert_eq!(extract_field_by_path(encoded_message, &[5, 2]), Some(&b"hi"[..]));
+/// assert_eq!(extract_field_by_path(encoded_message, &[1, 2]), None);
+/// ```
+///
+/// # Notes
+///
+/// - Only the first occurrence of each tag number is followed.
+pub fn extract_field_by_path<'a>(encoded_message: &'a [u8], path: &[u64]) -> Option<&'a [u8]> {
+    let mut message = encoded_message;
+    for (depth, tag_number) in path.iter().enumerate() {
+        let field = utils::find_field(message, *tag_number)?;
+        if depth + 1 < path.len() && field.variant != Variant::LengthDelimited {
+            return None;
+        }
+        message = &message[field.value_start..field.end];
+    }
+    Some(message)
+}
+
+/// Extracts multiple fields with the given tag numbers from an encoded protobuf message.
+///
+/// This function iterates over the encoded message and searches for fields with the specified tag numbers.
+/// If a field is found and its tag number is present in the `tag_numbers` slice, its value is extracted
+/// and stored in the returned vector of tuples.
+///
+/// The function supports the following wire types:
+/// - Varint (wire type 0)
+/// - Length-delimited (wire type 2)
+///
+/// If a field is not found or if an error occurs during decoding, it is skipped, and the function continues
+/// processing the remaining fields.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_numbers` - A slice of `u64` values representing the tag numbers of the fields to extract.
+///
+/// # Returns
+///
+/// A vector of tuples `Vec<(u64, &[u8])>`, where each tuple contains the tag number and the corresponding
+/// field value as a byte vector reference. The order of the tuples in the vector corresponds to the order of the
+/// tag numbers in the `tag_numbers` slice.
+///
+/// # Examples
+///
+/// ```
+/// use rustwire::extract_multiple_fields_by_tag;
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67\x1a\x03\x61\x62\x63";
+/// let tag_numbers = &[2, 3];
+///
+/// let fields = extract_multiple_fields_by_tag(encoded_message, tag_numbers);
+/// for (tag_number, field_value) in fields {
+///     println!("Tag: {}, Value: {:?}", tag_number, field_value);
+/// }
+/// ```
+///
+/// # Note
+///
+/// This function assumes a basic understanding of the protobuf encoding format and wire types.
+/// It may need to be adapted to handle more complex field types or nested messages.
+#[cfg(feature = "alloc")]
+pub fn extract_multiple_fields_by_tag<'a>(
+    encoded_message: &'a [u8],
+    tag_numbers: &[u64],
+) -> Vec<(u64, &'a [u8])> {
+    extract_multiple_fields(encoded_message, tag_numbers, false)
+}
+
+/// Extracts multiple fields with the given tag numbers, stopping as soon as every tag number has
+/// been found.
+///
+/// This returns the same fields as [`extract_multiple_fields_by_tag`] as long as every requested
+/// field occurs at most once, but stops scanning the message once the last of them is found. For
+/// fields near the start of a large message, this saves scanning the rest of it.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_numbers` - A slice of `u64` values representing the tag numbers of the fields to extract.
+///
+/// # Returns
+///
+/// A vector of tuples `Vec<(u64, &[u8])>` with the tag number and value of every field found, in
+/// message order.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_multiple_fields_early_exit;
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67\x08\x02";
+///
+/// assert_eq!(
+///     extract_multiple_fields_early_exit(encoded_message, &[1, 2]),
+///     [(1, &[0x01][..]), (2, &b"testing"[..])]
+/// );
+/// ```
+///
+/// # Notes
+///
+/// - Occurrences of a repeated field after the last requested field was first found are not
+///   returned. Use [`extract_multiple_fields_by_tag`] for repeated fields.
+#[cfg(feature = "alloc")]
+pub fn extract_multiple_fields_early_exit<'a>(
+    encoded_message: &'a [u8],
+    tag_numbers: &[u64],
+) -> Vec<(u64, &'a [u8])> {
+    extract_multiple_fields(encoded_message, tag_numbers, true)
+}
+
+/// Extracts the fields with the given tag numbers. With `early_exit`, scanning stops once every
+/// tag number has been found at least once.
+#[cfg(feature = "alloc")]
+fn extract_multiple_fields<'a>(
+    encoded_message: &'a [u8],
+    tag_numbers: &[u64],
+    early_exit: bool,
+) -> Vec<(u64, &'a [u8])> {
+    let mut fields = Vec::new();
+    let mut offset = 0;
+
+    // The distinct tag numbers still to be found, only tracked when exiting early.
+    let mut missing: Vec<u64> = Vec::new();
+    if early_exit {
+        missing.extend_from_slice(tag_numbers);
+        missing.sort_unstable();
+        missing.dedup();
+    }
+
+    while offset < encoded_message.len() {
+        let (tag, new_offset) = match decoders::decode_varint(encoded_message, offset) {
+            Some((tag, new_offset)) => (tag, new_offset),
+            None => break,
+        };
+        offset = new_offset;
+
+        let field_number = decoders::field_number_from_tag(tag);
+        let wire_type = decoders::wire_type_from_tag(tag);
+
+        if tag_numbers.contains(&field_number) && wire_type != 3 {
+            let field_value = decoders::decode_field_value(encoded_message, wire_type, offset).map(
+                |(value, new_offset)| {
+                    offset = new_offset;
+                    value
+                },
+            );
+
+            if let Some(value) = field_value {
+                fields.push((field_number, value));
+                if early_exit {
+                    if let Ok(index) = missing.binary_search(&field_number) {
+                        missing.remove(index);
+                        if missing.is_empty() {
+                            break;
+                        }
+                    }
+                }
+            }
+        } else {
+            offset = match utils::skip_field(encoded_message, field_number, wire_type, offset) {
+                Some(new_offset) => new_offset,
+                None => break,
+            };
+        }
+    }
+
+    fields
+}
+
+/// Extracts every occurrence of a field with the given tag number, in message order.
+///
+/// [`extract_field_by_tag`] only returns the first occurrence of a tag, but a `repeated` field is
+/// encoded as one field per element, all with the same tag number. This function collects all of
+/// them. Unlike [`extract_multiple_fields_by_tag`], which looks up several different tags, it looks
+/// up a single tag and returns all of its values.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the repeated field.
+///
+/// # Returns
+///
+/// A `Vec<&[u8]>` with the value of every occurrence, in the same form as [`extract_field_by_tag`].
+/// The vector is empty if the field is not present.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_all_occurrences_by_tag;
+///
+/// let encoded_message = b"\x0a\x01\x61\x10\x01\x0a\x02\x62\x63";
+/// assert_eq!(
+///     extract_all_occurrences_by_tag(encoded_message, 1),
+///     [&b"a"[..], &b"bc"[..]]
+/// );
+/// ```
+///
+/// # Notes
+///
+/// - Packed repeated fields are a single length-delimited field, so they are returned as one
+///   value containing all the packed elements.
+/// - If the message cannot be decoded, the occurrences found before the malformed bytes are
+///   returned.
+#[cfg(feature = "alloc")]
+pub fn extract_all_occurrences_by_tag(encoded_message: &[u8], tag_number: u64) -> Vec<&[u8]> {
+    FieldIterator::new(encoded_message)
+        .filter(|field| field.tag == tag_number)
+        .map(|field| field.as_bytes())
+        .collect()
+}
+
+/// Extracts a single occurrence of a repeated field.
+///
+/// The first `index` occurrences of the tag are skipped and the next one is returned, so an
+/// `index` of 0 returns the same value as [`extract_field_by_tag`]. Unlike
+/// [`extract_all_occurrences_by_tag`], nothing is collected, and the message is only scanned up to
+/// the requested occurrence.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the repeated field.
+/// * `index` - The zero-based index of the occurrence to extract.
+///
+/// # Returns
+///
+/// The value of the occurrence, in the same form as [`extract_field_by_tag`], or `None` if the
+/// field has `index` or fewer occurrences, or the message cannot be decoded before it.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_at_index;
+///
+/// let encoded_message = b"\x0a\x01\x61\x10\x01\x0a\x02\x62\x63";
+/// assert_eq!(extract_field_at_index(encoded_message, 1, 1), Some(&b"bc"[..]));
+/// assert_eq!(extract_field_at_index(encoded_message, 1, 2), None);
+/// ```
+///
+/// # Notes
+///
+/// - Packed repeated fields are a single length-delimited field, so they count as one occurrence.
+/// - Groups (wire type 3) are skipped as a whole and never count as an occurrence, even when they
+///   have the requested tag number or contain fields with it.
+pub fn extract_field_at_index(
+    encoded_message: &[u8],
+    tag_number: u64,
+    index: usize,
+) -> Option<&[u8]> {
+    FieldIterator::new(encoded_message)
+        .filter(|field| field.tag == tag_number)
+        .nth(index)
+        .map(|field| field.as_bytes())
+}
+
+/// Extracts every occurrence of a repeated `string` field.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_number` - The tag number of the field to extract.
+///
+/// # Returns
+///
+/// A `Vec<&str>` with the value of every occurrence, in the order they appear in the message.
+/// Occurrences that are not length-delimited or not valid UTF-8 are skipped.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_repeated_strings;
+///
+/// let encoded_message = b"\x0a\x01\x61\x0a\x01\xff\x10\x01\x0a\x02\x62\x63";
+/// assert_eq!(extract_repeated_strings(encoded_message, 1), ["a", "bc"]);
+/// ```
+///
+/// # Notes
+///
+/// - If the message cannot be decoded, the strings found before the malformed bytes are
+///   returned.
+#[cfg(feature = "alloc")]
+pub fn extract_repeated_strings(encoded_message: &[u8], tag_number: u64) -> Vec<&str> {
+    FieldIterator::new(encoded_message)
+        .filter(|field| field.tag == tag_number)
+        .filter_map(|field| field.as_str())
+        .collect()
+}
+
+/// Extracts the entries of a proto3 `map` field.
+///
+/// A map field is encoded as a repeated length-delimited field, where every occurrence is a map
+/// entry message holding the key in field 1 and the value in field 2. This function extracts every
+/// occurrence of `map_tag` and returns the key and value of each entry, in the same form as the
+/// values returned by [`extract_field_by_tag`].
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `map_tag` - The tag number of the map field.
+///
+/// # Returns
+///
+/// The `(key, value)` pairs of all entries, in message order. As in proto3, a key or value that is
+/// missing from its entry has the default value, which is returned as an empty slice.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_map_entries, extract_packed_varint};
+///
+/// // Field 3 is a `map<string, uint32>` holding {"a": 1, "b": 2}.
+/// let encoded_message = b"\x1a\x05\x0a\x01\x61\x10\x01\x1a\x05\x0a\x01\x62\x10\x02";
+/// let entries = extract_map_entries(encoded_message, 3);
+///
+/// assert_eq!(entries, [(&b"a"[..], &[0x01][..]), (&b"b"[..], &[0x02][..])]);
+/// ```
+///
+/// # Notes
+///
+/// - If the same key occurs more than once, every entry is returned. Protobuf parsers keep the
+///   last one.
+/// - Entries that cannot be decoded are skipped.
+#[cfg(feature = "alloc")]
+pub fn extract_map_entries(encoded_message: &[u8], map_tag: u64) -> Vec<(&[u8], &[u8])> {
+    FieldIterator::new(encoded_message)
+        .filter(|field| field.tag == map_tag && field.wire_type == Variant::LengthDelimited)
+        .filter(|field| validate_message(field.as_bytes()).is_ok())
+        .map(|field| {
+            let entry = field.as_bytes();
+            let key = extract_field_by_tag(entry, 1).unwrap_or_default();
+            let value = extract_field_by_tag(entry, 2).unwrap_or_default();
+            (key, value)
+        })
+        .collect()
+}
+
+/// Encodes a single map entry message.
+///
+/// The result is the payload of one occurrence of a proto3 `map` field, and still has to be added
+/// to the message as a length-delimited field with the tag number of the map, e.g. with
+/// [`append_field`]. Keys and values have the same form as in [`encode_field`]: the payload of a
+/// `Variant::Varint` key or value must already be varint encoded.
+///
+/// Proto3 map entries always use 1 as `key_tag` and 2 as `value_tag`.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_map_entry, encode_varint, Variant};
+///
+/// let entry = encode_map_entry(1, Variant::LengthDelimited, b"a", 2, Variant::Varint, &encode_varint(1));
+/// assert_eq!(entry, b"\x0a\x01\x61\x10\x01");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_map_entry(
+    key_tag: u64,
+    key_variant: Variant,
+    key: &[u8],
+    value_tag: u64,
+    value_variant: Variant,
+    value: &[u8],
+) -> Vec<u8> {
+    let mut entry = encode_field(key_tag, key_variant, key);
+    entry.extend_from_slice(&encode_field(value_tag, value_variant, value));
+    entry
+}
+
+/// Extracts multiple fields into a caller-provided slice without allocating.
+///
+/// For every tag number in `tag_numbers`, the value of its first occurrence is written to the slot
+/// with the same index in `out`. Slots of fields that are not found are left untouched, so `out`
+/// should be initialized to `None` by the caller. Scanning stops as soon as every slot is filled.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tag_numbers` - A slice of `u64` values representing the tag numbers of the fields to extract.
+/// * `out` - The output slots, one per tag number (`out.len() == tag_numbers.len()`).
+///
+/// # Returns
+///
+/// The number of fields that were found and written to `out`.
+///
+/// # Examples
+///
+/// ```
+/// use rustwire::batch_extract_to_slice;
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67\x1a\x03\x61\x62\x63";
+/// let mut out = [None; 4];
+///
+/// let found = batch_extract_to_slice(encoded_message, &[1, 2, 3, 4], &mut out);
+/// assert_eq!(found, 3);
+/// assert_eq!(out[1], Some(&b"testing"[..]));
+/// assert_eq!(out[3], None);
+/// ```
+///
+/// # Note
+///
+/// If `out` and `tag_numbers` differ in length, only the first `min(out.len(), tag_numbers.len())`
+/// tag numbers are considered. Decoding stops at the first malformed field.
+pub fn batch_extract_to_slice<'a>(
+    encoded_message: &'a [u8],
+    tag_numbers: &[u64],
+    out: &mut [Option<&'a [u8]>],
+) -> usize {
+    let wanted = tag_numbers.len().min(out.len());
+    let mut found = 0;
+    let mut offset = 0;
+
+    while offset < encoded_message.len() && found < wanted {
+        let field = match utils::read_field(encoded_message, offset) {
+            Some(field) => field,
+            None => break,
+        };
+        offset = field.end;
+
+        for (tag_number, slot) in tag_numbers.iter().zip(out.iter_mut()) {
+            if *tag_number == field.field_number && slot.is_none() {
+                *slot = Some(&encoded_message[field.value_start..field.end]);
+                found += 1;
+            }
+        }
+    }
+
+    found
+}
+
+/// Extracts the last `n` fields of an encoded protobuf message.
+///
+/// In append-only logs and streaming contexts the most recently written fields are at the end of
+/// the buffer. This function scans the message once, keeping a rolling window of the last `n`
+/// fields, so memory use is bounded by `n` rather than by the number of fields in the message.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `n` - The maximum number of fields to return.
+///
+/// # Returns
+///
+/// * `Ok(Vec<(u64, &[u8])>)` - The tag number and value of the last `n` fields in wire order. If
+///   the message has fewer than `n` fields, all of them are returned.
+/// * `Err(WireError)` - If the message cannot be decoded, with the reason and offset of the first
+///   malformed field.
+///
+/// # Examples
+///
+/// ```
+/// use rustwire::extract_last_n_fields;
+///
+/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67\x1a\x03\x61\x62\x63";
+///
+/// let fields = extract_last_n_fields(encoded_message, 2).unwrap();
+/// assert_eq!(fields, vec![(2, &b"testing"[..]), (3, &b"abc"[..])]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn extract_last_n_fields(
+    encoded_message: &[u8],
+    n: usize,
+) -> Result<Vec<(u64, &[u8])>, WireError> {
+    // Every field takes at least two bytes, so a large `n` must not size the window.
+    let mut window = VecDeque::with_capacity(n.min(encoded_message.len() / 2));
+    let mut offset = 0;
+
+    while let Some(field) = utils::read_field_checked(encoded_message, offset)? {
+        offset = field.end;
+
+        if n == 0 {
+            continue;
+        }
+        if window.len() == n {
+            window.pop_front();
+        }
+        window.push_back((
+            field.field_number,
+            &encoded_message[field.value_start..field.end],
+        ));
+    }
+
+    Ok(window.into())
+}
+
+/// Copies the fields of an encoded message that match a filter to another encoded message.
+///
+/// Every field of `src` is passed to `filter` together with its wire type and raw value bytes
+/// (without the length prefix for length-delimited fields). Fields for which `filter` returns
+/// `true` are appended verbatim to `dst`, in the order they appear in `src`. This is the most
+/// general form of field projection: it can keep a subset of tags, drop a subset of tags, or select
+/// fields based on their values. Groups (wire type 3) have no value to pass to `filter`, so they
+/// are always copied, like [`strip_fields`] keeps them, and are not counted.
+///
+/// # Arguments
+///
+/// * `src` - A byte slice (`&[u8]`) containing the encoded message to copy from.
+/// * `dst` - A mutable reference to a `Vec<u8>` the matching fields are appended to.
+/// * `filter` - A closure receiving the tag number, wire type and value of each field.
+///
+/// # Returns
+///
+/// * `Ok(usize)` - The number of fields for which `filter` returned `true`.
+/// * `Err(WireError)` - If `src` cannot be decoded, with the reason and offset of the first
+///   malformed field. In that case `dst` is left unchanged.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::copy_fields_filtered;
+///
+/// let src = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67\x1a\x03\x61\x62\x63";
+/// let mut dst = Vec::new();
+///
+/// let copied = copy_fields_filtered(src, &mut dst, |tag, _, _| tag != 2);
+/// assert_eq!(copied, Ok(2));
+/// assert_eq!(dst, b"\x08\x01\x1a\x03\x61\x62\x63");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn copy_fields_filtered<F>(src: &[u8], dst: &mut Vec<u8>, filter: F) -> Result<usize, WireError>
+where
+    F: Fn(u64, Variant, &[u8]) -> bool,
+{
+    let original_len = dst.len();
+    let mut copied = 0;
+    let mut offset = 0;
+
+    loop {
+        let field = match utils::read_field_checked(src, offset) {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => {
+                dst.truncate(original_len);
+                return Err(err);
+            }
+        };
+        // The bytes skipped before the field are groups.
+        dst.extend_from_slice(&src[offset..field.start]);
+        offset = field.end;
+
+        if filter(
+            field.field_number,
+            field.variant,
+            &src[field.value_start..field.end],
+        ) {
+            dst.extend_from_slice(&src[field.start..field.end]);
+            copied += 1;
+        }
+    }
+    // Only groups can be left after the last field.
+    dst.extend_from_slice(&src[offset..]);
+
+    Ok(copied)
+}
+
+/// Projects an encoded message down to the fields with the given tag numbers.
+///
+/// This is the equivalent of a SQL `SELECT`: every field whose tag number appears in `tags` is
+/// copied verbatim to a new buffer, in message order, and all other fields are dropped. All
+/// occurrences of a repeated field are kept. Unlike [`extract_multiple_fields_by_tag`], which
+/// returns references to the values, the result is a standalone encoded message.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tags` - The tag numbers of the fields to keep.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the projected message. If the message cannot be decoded, the matching
+/// fields before the malformed bytes are returned.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::keep_only_fields;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x02";
+/// assert_eq!(keep_only_fields(encoded_message, &[1, 3]), b"\x08\x01\x18\x02");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn keep_only_fields(encoded_message: &[u8], tags: &[u64]) -> Vec<u8> {
+    project_fields(encoded_message, |field_number| tags.contains(&field_number))
+}
+
+/// Removes the fields with the given tag numbers from an encoded message.
+///
+/// This is the complement of [`keep_only_fields`]: every field whose tag number does not appear in
+/// `tags` is copied verbatim to a new buffer, in message order.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `tags` - The tag numbers of the fields to remove.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the remaining fields. If the message cannot be decoded, the remaining
+/// fields before the malformed bytes are returned.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::strip_fields;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x02";
+/// assert_eq!(strip_fields(encoded_message, &[2]), b"\x08\x01\x18\x02");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn strip_fields(encoded_message: &[u8], tags: &[u64]) -> Vec<u8> {
+    project_fields(encoded_message, |field_number| {
+        !tags.contains(&field_number)
+    })
+}
+
+/// Removes every field whose tag number is not in `known_tags` from an encoded message.
+///
+/// This keeps the same fields as [`keep_only_fields`], and is meant for sanitizing messages from
+/// untrusted sources before forwarding them, so fields the receiver does not expect never reach
+/// it. Use [`sanitize_message`] to also reject malformed messages.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `known_tags` - The tag numbers of the fields that are allowed.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the known fields, in message order. If the message cannot be decoded,
+/// the known fields before the malformed bytes are returned.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::strip_unknown_fields;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x02";
+/// assert_eq!(strip_unknown_fields(encoded_message, &[1, 2]), b"\x08\x01\x12\x02\x68\x69");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn strip_unknown_fields(encoded_message: &[u8], known_tags: &[u64]) -> Vec<u8> {
+    keep_only_fields(encoded_message, known_tags)
+}
+
+/// Counts the fields whose tag number is not in `known_tags`, including every occurrence of
+/// repeated fields. A group counts as a single field, judged by its own field number, so this
+/// counts exactly the fields [`strip_unknown_fields`] removes.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `known_tags` - The tag numbers of the fields that are allowed.
+///
+/// # Returns
+///
+/// The number of unknown fields. If the message cannot be decoded, only the unknown fields before
+/// the malformed bytes are counted.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::count_unknown_fields;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x02\x18\x03";
+/// assert_eq!(count_unknown_fields(encoded_message, &[1, 2]), 2);
+/// ```
+pub fn count_unknown_fields(encoded_message: &[u8], known_tags: &[u64]) -> usize {
+    let mut count = 0;
+    let mut offset = 0;
+    while offset < encoded_message.len() {
+        // Groups are counted as a single field, by the field number of their start-group tag.
+        let (field_number, end) = match read_group(encoded_message, offset) {
+            Some(group) => group,
+            None => match utils::read_field(encoded_message, offset) {
+                Some(field) => (field.field_number, field.end),
+                None => break,
+            },
+        };
+        if !known_tags.contains(&field_number) {
+            count += 1;
+        }
+        offset = end;
+    }
+    count
+}
+
+/// Sanitizes an encoded message, handling fields whose tag number is not in `known_tags` as
+/// configured by `policy`.
+///
+/// Unlike [`strip_unknown_fields`], the whole message must be well-formed: malformed input is an
+/// error rather than being cut short, whatever the policy.
+///
+/// # Arguments
+///
+/// * `encoded` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `known_tags` - The tag numbers of the fields that are allowed.
+/// * `policy` - What to do with unknown fields, see [`UnknownFieldPolicy`].
+///
+/// # Returns
+///
+/// * `Ok(Vec<u8>)` - The sanitized message.
+/// * `Err(WireError)` - The message is malformed, or it has an unknown field and `policy` is
+///   [`UnknownFieldPolicy::Fail`], in which case [`WireError::UnknownField`] is returned.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{sanitize_message, UnknownFieldPolicy, WireError};
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x18\x02";
+///
+/// assert_eq!(
+///     sanitize_message(encoded_message, &[1, 2], UnknownFieldPolicy::Strip),
+///     Ok(b"\x08\x01\x12\x02\x68\x69".to_vec())
+/// );
+/// assert_eq!(
+///     sanitize_message(encoded_message, &[1, 2], UnknownFieldPolicy::Fail),
+///     Err(WireError::UnknownField { offset: 6, field_number: 3 })
+/// );
+/// ```
+#[cfg(feature = "alloc")]
+pub fn sanitize_message(
+    encoded: &[u8],
+    known_tags: &[u64],
+    policy: UnknownFieldPolicy,
+) -> Result<Vec<u8>, WireError> {
+    let mut sanitized = Vec::with_capacity(encoded.len());
+    let mut offset = 0;
+    while offset < encoded.len() {
+        // Groups are kept, dropped or rejected as a whole, by the field number of their
+        // start-group tag.
+        let (tag, _) = decoders::decode_varint_checked(encoded, offset)?;
+        let (field_number, end) = if decoders::wire_type_from_tag(tag) == 3 {
+            let end = utils::skip_group(encoded, offset, utils::MAX_GROUP_DEPTH)?;
+            (decoders::field_number_from_tag(tag), end)
+        } else {
+            let field = utils::read_record_checked(encoded, offset)?;
+            (field.field_number, field.end)
+        };
+        if known_tags.contains(&field_number) || policy == UnknownFieldPolicy::PassThrough {
+            sanitized.extend_from_slice(&encoded[offset..end]);
+        } else if policy == UnknownFieldPolicy::Fail {
+            return Err(WireError::UnknownField {
+                offset,
+                field_number,
+            });
+        }
+        offset = end;
+    }
+    Ok(sanitized)
+}
+
+/// Copies the fields for which `keep` returns `true` to a new buffer, stopping at the first field
+/// that cannot be decoded.
+#[cfg(feature = "alloc")]
+fn project_fields(encoded_message: &[u8], keep: impl Fn(u64) -> bool) -> Vec<u8> {
+    let mut projected = Vec::new();
+    let mut offset = 0;
+    while offset < encoded_message.len() {
+        // Groups are kept or dropped as a whole, by the field number of their start-group tag.
+        if let Some((field_number, end)) = read_group(encoded_message, offset) {
+            if keep(field_number) {
+                projected.extend_from_slice(&encoded_message[offset..end]);
+            }
+            offset = end;
+            continue;
+        }
+        let field = match utils::read_field(encoded_message, offset) {
+            Some(field) => field,
+            None => break,
+        };
+        if keep(field.field_number) {
+            projected.extend_from_slice(&encoded_message[field.start..field.end]);
+        }
+        offset = field.end;
+    }
+    projected
+}
+
+/// Returns the field number of the group starting at `offset` and the offset after it, if a
+/// group starts there.
+fn read_group(encoded_message: &[u8], offset: usize) -> Option<(u64, usize)> {
+    let (tag, _) = decoders::decode_varint(encoded_message, offset)?;
+    if decoders::wire_type_from_tag(tag) != 3 {
+        return None;
+    }
+    let end = utils::skip_group(encoded_message, offset, utils::MAX_GROUP_DEPTH).ok()?;
+    Some((decoders::field_number_from_tag(tag), end))
+}
+
+/// Merges two encoded messages following the protobuf merge rules.
+///
+/// Protobuf defines merging two messages as parsing their concatenation: the last value of a
+/// scalar field wins, repeated fields are appended, and embedded messages are merged recursively.
+/// As a result, the merge can be done on the wire without a schema by appending the fields of
+/// `overlay` to the fields of `base`.
+///
+/// # Arguments
+///
+/// * `base` - A byte slice (`&[u8]`) containing the encoded message to merge into.
+/// * `overlay` - A byte slice (`&[u8]`) containing the encoded message whose fields take precedence.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the merged message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::merge_messages;
+///
+/// let base = b"\x08\x01\x12\x02\x68\x69";
+/// let overlay = b"\x08\x02";
+/// let merged = merge_messages(base, overlay);
+///
+/// assert_eq!(merged, b"\x08\x01\x12\x02\x68\x69\x08\x02");
+/// ```
+///
+/// # Notes
+///
+/// - Scalar fields that appear in both messages are encoded twice. Parsers keep the last value,
+///   but use [`merge_messages_last_wins`] to get a smaller message.
+#[cfg(feature = "alloc")]
+pub fn merge_messages(base: &[u8], overlay: &[u8]) -> Vec<u8> {
+    [base, overlay].concat()
+}
+
+/// Merges two encoded messages, dropping the scalar fields of `base` that `overlay` overrides.
+///
+/// Like [`merge_messages`], but every varint, 64-bit or 32-bit field of `base` whose tag number
+/// also appears as such a field in `overlay` is left out instead of being overridden when parsed.
+/// Length-delimited fields are always kept from both messages, since without a schema they cannot
+/// be told apart from repeated fields and embedded messages, which are appended and merged.
+///
+/// # Arguments
+///
+/// * `base` - A byte slice (`&[u8]`) containing the encoded message to merge into.
+/// * `overlay` - A byte slice (`&[u8]`) containing the encoded message whose fields take precedence.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the merged message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::merge_messages_last_wins;
+///
+/// let base = b"\x08\x01\x12\x02\x68\x69";
+/// let overlay = b"\x08\x02";
+/// let merged = merge_messages_last_wins(base, overlay);
+///
+/// assert_eq!(merged, b"\x12\x02\x68\x69\x08\x02");
+/// ```
+///
+/// # Notes
+///
+/// - A non-packed repeated scalar field that appears in both messages is replaced by the values
+///   in `overlay` instead of being appended to.
+/// - If `base` cannot be decoded, it is kept as is, like in [`merge_messages`].
+#[cfg(feature = "alloc")]
+pub fn merge_messages_last_wins(base: &[u8], overlay: &[u8]) -> Vec<u8> {
+    let mut overridden: Vec<u64> = FieldIterator::new(overlay)
+        .filter(|field| field.wire_type != Variant::LengthDelimited)
+        .map(|field| field.tag)
+        .collect();
+    overridden.sort_unstable();
+    overridden.dedup();
+
+    let mut merged = Vec::with_capacity(base.len() + overlay.len());
+    let copied = copy_fields_filtered(base, &mut merged, |field_number, variant, _| {
+        variant == Variant::LengthDelimited || overridden.binary_search(&field_number).is_err()
+    });
+    if copied.is_err() {
+        merged.extend_from_slice(base);
+    }
+    merged.extend_from_slice(overlay);
+    merged
+}
+
+/// Removes duplicate occurrences of scalar fields, keeping the last occurrence of each.
+///
+/// When a scalar field appears more than once, e.g. after [`merge_messages`], a protobuf decoder
+/// uses the last occurrence. This drops the earlier ones, which makes the message smaller without
+/// changing what it decodes to. Fields whose tag number is not in `scalar_tags`, such as repeated
+/// fields and embedded messages, are all kept.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+/// * `scalar_tags` - The tag numbers of the fields that hold a single value.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the deduplicated message, with the remaining fields in message order.
+/// If the message cannot be decoded, the bytes from the first malformed field on are copied as is.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::dedup_last_wins;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x08\x02";
+/// assert_eq!(dedup_last_wins(encoded_message, &[1]), b"\x12\x02\x68\x69\x08\x02");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn dedup_last_wins(encoded_message: &[u8], scalar_tags: &[u64]) -> Vec<u8> {
+    // The start of the last occurrence of every scalar tag that is present.
+    let mut last_starts: Vec<(u64, usize)> = Vec::new();
+    let mut offset = 0;
+    while let Some(field) = utils::read_field(encoded_message, offset) {
+        if scalar_tags.contains(&field.field_number) {
+            match last_starts
+                .iter_mut()
+                .find(|(tag, _)| *tag == field.field_number)
+            {
+                Some((_, start)) => *start = field.start,
+                None => last_starts.push((field.field_number, field.start)),
+            }
+        }
+        offset = field.end;
+    }
+
+    dedup_fields(encoded_message, |field| {
+        last_starts
+            .iter()
+            .all(|(tag, start)| *tag != field.field_number || *start == field.start)
+    })
+}
+
+/// Removes duplicate occurrences of scalar fields, keeping the first occurrence of each.
+///
+/// This works like [`dedup_last_wins`], but the first occurrence of every field in `scalar_tags`
+/// is kept. Note that this changes what the message decodes to, since protobuf decoders use the
+/// last occurrence.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::dedup_first_wins;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69\x08\x02";
+/// assert_eq!(dedup_first_wins(encoded_message, &[1]), b"\x08\x01\x12\x02\x68\x69");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn dedup_first_wins(encoded_message: &[u8], scalar_tags: &[u64]) -> Vec<u8> {
+    let mut seen: Vec<u64> = Vec::new();
+    dedup_fields(encoded_message, |field| {
+        if !scalar_tags.contains(&field.field_number) {
+            return true;
+        }
+        if seen.contains(&field.field_number) {
+            return false;
+        }
+        seen.push(field.field_number);
+        true
+    })
+}
+
+/// Copies the fields for which `keep` returns `true` to a new buffer, and the bytes from the first
+/// field that cannot be decoded on as is.
+#[cfg(feature = "alloc")]
+fn dedup_fields(
+    encoded_message: &[u8],
+    mut keep: impl FnMut(&utils::FieldRecord) -> bool,
+) -> Vec<u8> {
+    let mut deduped = Vec::with_capacity(encoded_message.len());
+    let mut offset = 0;
+    while offset < encoded_message.len() {
+        let field = match utils::read_field(encoded_message, offset) {
+            Some(field) => field,
+            None => break,
+        };
+        deduped.extend_from_slice(&encoded_message[offset..field.start]);
+        if keep(&field) {
+            deduped.extend_from_slice(&encoded_message[field.start..field.end]);
+        }
+        offset = field.end;
+    }
+    deduped.extend_from_slice(&encoded_message[offset..]);
+    deduped
+}
+
+/// Replaces a field with the specified tag number in the encoded message with the given replacement data.
+///
+/// This function modifies the `encoded_message` in-place and returns the old field value as an `Option<Vec<u8>>`.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
+/// * `tag_number` - The tag number of the field to replace.
+/// * `replace_with` - A byte slice (`&[u8]`) containing the replacement data.
+///
+/// # Returns
+///
+/// * `Option<Vec<u8>>` - If the field is found and replaced successfully, returns `Some(old_value)`, where `old_value` is the original field value as a `Vec<u8>`. If the field is not found or an error occurs, returns `None`.
+///
+/// # Example
+///
+/// ```rust
+/// use rustwire::replace_field_with;
+///
+/// let mut encoded_message = vec![0x08, 0x01, 0x12, 0x07, 0x74, 0x65, 0x73, 0x74, 0x69, 0x6e, 0x67];
+/// let tag_number = 2;
+/// let replace_with = b"Hello";
+///
+/// match replace_field_with(&mut encoded_message, tag_number, replace_with) {
+///     Some(old_value) => println!("Replaced field value: {:?}", old_value),
+///     None => println!("Field not found or error occurred"),
+/// }
+/// ```
+///
+/// # Notes
+///
+/// - This function modifies the `encoded_message` in-place.
+/// - If `replace_with` is exactly as long as the old field, which is the common case for fixed-width
+///   fields and for values whose length prefix keeps its size, the field is overwritten directly
+///   without allocating or moving the rest of the message.
+/// - The function supports the following wire types:
+///   - Varint (wire type 0)
+///   - 64-bit (wire type 1)
+///   - Length-delimited (wire type 2)
+///   - 32-bit (wire type 5)
+/// - If the wire type is not supported, the function returns `None`.
+#[cfg(feature = "alloc")]
+pub fn replace_field_with(
+    encoded_message: &mut Vec<u8>,
+    tag_number: u64,
+    replace_with: &[u8],
+) -> Option<Vec<u8>> {
+    replace_field_with_two_pass(encoded_message, tag_number, replace_with)
+}
+
+/// Replaces a field in an encoded protobuf message, moving the rest of the message at most once.
+///
+/// This is the implementation behind [`replace_field_with`] and behaves the same way. The first
+/// pass finds the bounds of the field without touching the message. The second pass reserves
+/// exactly the capacity the new field needs and writes it in place, so the bytes after the field
+/// are moved once, and only if the size of the field changes.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
+/// * `tag_number` - The tag number of the field to replace.
+/// * `replace_with` - A byte slice (`&[u8]`) containing the whole replacement field, including
+///   its tag.
+///
+/// # Returns
+///
+/// The old value of the field, or `None` if the field is not found or the message cannot be
+/// decoded. The message is left untouched when `None` is returned.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::replace_field_with_two_pass;
+///
+/// let mut encoded_message = vec![0x08, 0x01, 0x12, 0x07, 0x74, 0x65, 0x73, 0x74, 0x69, 0x6e, 0x67];
+///
+/// assert_eq!(
+///     replace_field_with_two_pass(&mut encoded_message, 2, b"\x12\x02hi"),
+///     Some(b"testing".to_vec())
+/// );
+/// assert_eq!(encoded_message, b"\x08\x01\x12\x02hi");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn replace_field_with_two_pass(
+    encoded_message: &mut Vec<u8>,
+    tag_number: u64,
+    replace_with: &[u8],
+) -> Option<Vec<u8>> {
+    let field = utils::find_field(encoded_message, tag_number)?;
+    let old = encoded_message[field.value_start..field.end].to_vec();
+
+    let old_len = encoded_message.len();
+    let new_end = field.start + replace_with.len();
+    if new_end > field.end {
+        // Grow the message in place, then move the tail back to make room for the new field.
+        encoded_message.reserve_exact(new_end - field.end);
+        encoded_message.resize(old_len + new_end - field.end, 0);
+        encoded_message.copy_within(field.end..old_len, new_end);
+    } else if new_end < field.end {
+        // Move the tail forward over the end of the old field, then drop the leftover bytes.
+        encoded_message.copy_within(field.end..old_len, new_end);
+        encoded_message.truncate(old_len - (field.end - new_end));
+    }
+    encoded_message[field.start..new_end].copy_from_slice(replace_with);
+
+    Some(old)
+}
+
+/// Replaces the value of a field, keeping its tag and wire type.
+///
+/// Unlike [`replace_field_with`], which expects the whole replacement field including its tag,
+/// `new_payload` is only the value of the field, in the same form as the values returned by
+/// [`extract_field_by_tag`]. The length prefix of a length-delimited field is updated
+/// automatically.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
+/// * `tag_number` - The tag number of the field to replace.
+/// * `new_payload` - The new value of the field, without its tag or length prefix.
+///
+/// # Returns
+///
+/// The old value of the field, or `None` if the field is not found or the message cannot be
+/// decoded. The message is left untouched when `None` is returned.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::replace_field_payload;
+///
+/// let mut encoded_message = vec![0x08, 0x01, 0x12, 0x07, 0x74, 0x65, 0x73, 0x74, 0x69, 0x6e, 0x67];
+///
+/// assert_eq!(
+///     replace_field_payload(&mut encoded_message, 2, b"hi"),
+///     Some(b"testing".to_vec())
+/// );
+/// assert_eq!(encoded_message, b"\x08\x01\x12\x02hi");
+/// ```
+///
+/// # Notes
+///
+/// - A new varint value must already be varint encoded, and a new fixed-width value must have the
+///   same width as the old one, or the message will no longer decode.
+#[cfg(feature = "alloc")]
+pub fn replace_field_payload(
+    encoded_message: &mut Vec<u8>,
+    tag_number: u64,
+    new_payload: &[u8],
+) -> Option<Vec<u8>> {
+    let field = utils::find_field(encoded_message, tag_number)?;
+    let old = encoded_message[field.value_start..field.end].to_vec();
+    utils::replace_value(encoded_message, &field, new_payload);
+    Some(old)
+}
+
+/// Replaces the value of a single occurrence of a repeated field.
+///
+/// The first `index` occurrences of the tag are skipped and the value of the next one is replaced,
+/// so an `index` of 0 replaces the same field as [`replace_field_payload`]. Like there,
+/// `replace_with` is only the value of the field, and the length prefix of a length-delimited
+/// field is updated automatically.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
+/// * `tag_number` - The tag number of the repeated field.
+/// * `index` - The zero-based index of the occurrence to replace.
+/// * `replace_with` - The new value of the occurrence, without its tag or length prefix.
+///
+/// # Returns
+///
+/// The old value of the occurrence, or `None` if the field has `index` or fewer occurrences, the
+/// message cannot be decoded before it, or `replace_with` is not a single value of the field's
+/// wire type, e.g. an unterminated varint or 3 bytes for a `fixed32` field. The message is left
+/// untouched when `None` is returned.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::replace_field_at_index;
+///
+/// let mut encoded_message = b"\x0a\x01\x61\x10\x01\x0a\x02\x62\x63".to_vec();
+///
+/// assert_eq!(
+///     replace_field_at_index(&mut encoded_message, 1, 1, b"xyz"),
+///     Some(b"bc".to_vec())
+/// );
+/// assert_eq!(encoded_message, b"\x0a\x01\x61\x10\x01\x0a\x03xyz");
+/// ```
+///
+/// # Notes
+///
+/// - Occurrences are counted like in [`extract_field_at_index`], so groups are skipped as a whole
+///   and kept as they are.
+#[cfg(feature = "alloc")]
+pub fn replace_field_at_index(
+    encoded_message: &mut Vec<u8>,
+    tag_number: u64,
+    index: usize,
+    replace_with: &[u8],
+) -> Option<Vec<u8>> {
+    let mut remaining = index;
+    let mut offset = 0;
+    let field = loop {
+        let field = utils::read_field(encoded_message, offset)?;
+        if field.field_number == tag_number {
+            if remaining == 0 {
+                break field;
+            }
+            remaining -= 1;
+        }
+        offset = field.end;
+    };
+
+    if !utils::value_fits(replace_with, field.variant) {
+        return None;
+    }
+    let old = encoded_message[field.value_start..field.end].to_vec();
+    utils::replace_value(encoded_message, &field, replace_with);
+    Some(old)
+}
+
+/// Replaces the value of a field with a value computed from the old one.
+///
+/// The first field with the tag number is found, its value is passed to `f`, and the value `f`
+/// returns is written in its place. Values have the same form as the values returned by
+/// [`extract_field_by_tag`]: the length prefix of a length-delimited field is updated
+/// automatically, while a new varint value must already be varint encoded.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
+/// * `tag_number` - The tag number of the field to transform.
+/// * `f` - A closure computing the new value from the old one.
+///
+/// # Returns
+///
+/// `true` if the field was found and transformed, `false` otherwise. `f` is only called if the
+/// field is found. If the value returned by `f` is not a single value of the field's wire type,
+/// e.g. an unterminated varint or 3 bytes for a `fixed32` field, `false` is returned and the
+/// message is left untouched.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_tag, transform_field};
+///
+/// let mut encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67".to_vec();
+///
+/// assert!(transform_field(&mut encoded_message, 2, |old| {
+///     [old, b" more"].concat().to_ascii_uppercase()
+/// }));
+/// assert_eq!(extract_field_by_tag(&encoded_message, 2), Some(&b"TESTING MORE"[..]));
+/// ```
+///
+/// # Notes
+///
+/// - If the new value is exactly as long as the old one, the field is overwritten in place.
+/// - If the message cannot be decoded up to the field, `false` is returned and the message is left
+///   untouched.
+#[cfg(feature = "alloc")]
+pub fn transform_field<F>(encoded_message: &mut Vec<u8>, tag_number: u64, f: F) -> bool
+where
+    F: FnOnce(&[u8]) -> Vec<u8>,
+{
+    let field = match utils::find_field(encoded_message, tag_number) {
+        Some(field) => field,
+        None => return false,
+    };
+    let new_value = f(&encoded_message[field.value_start..field.end]);
+    if !utils::value_fits(&new_value, field.variant) {
+        return false;
+    }
+    utils::replace_value(encoded_message, &field, &new_value);
+    true
+}
+
+/// Replaces the value of a field with a fixed-size value computed from the old one.
+///
+/// This works like [`transform_field`], but `f` returns an array, so no heap allocation is needed
+/// for the new value. This is the natural fit for fixed-width fields, e.g. incrementing a
+/// `fixed32` counter.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_as_fixed32, transform_field_fixed};
+///
+/// let mut encoded_message = b"\x08\x01\x15\x07\x00\x00\x00".to_vec();
+///
+/// assert!(transform_field_fixed(&mut encoded_message, 2, |old| {
+///     let counter = u32::from_le_bytes(old.try_into().unwrap());
+///     (counter + 1).to_le_bytes()
+/// }));
+/// assert_eq!(extract_field_as_fixed32(&encoded_message, 2), Some(8));
+///
+/// // An 8-byte value does not fit the `fixed32` field.
+/// assert!(!transform_field_fixed(&mut encoded_message, 2, |_| [0; 8]));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn transform_field_fixed<const N: usize, F>(
+    encoded_message: &mut Vec<u8>,
+    tag_number: u64,
+    f: F,
+) -> bool
+where
+    F: FnOnce(&[u8]) -> [u8; N],
+{
+    let field = match utils::find_field(encoded_message, tag_number) {
+        Some(field) => field,
+        None => return false,
+    };
+    let new_value = f(&encoded_message[field.value_start..field.end]);
+    if !utils::value_fits(&new_value, field.variant) {
+        return false;
+    }
+    utils::replace_value(encoded_message, &field, &new_value);
+    true
+}
+
+/// Replaces the value of a field with a value computed from the old one.
+///
+/// This is the same as [`transform_field`], under a name that sits next to [`replace_field_with`].
+/// Unlike `replace_field_with`, `f` receives and returns only the value of the field: the length
+/// prefix of a length-delimited field is stripped before `f` is called and written back after.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_tag, replace_field_with_fn};
+///
+/// let mut encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67".to_vec();
+///
+/// assert!(replace_field_with_fn(&mut encoded_message, 2, |old| old[..4].to_vec()));
+/// assert_eq!(extract_field_by_tag(&encoded_message, 2), Some(&b"test"[..]));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn replace_field_with_fn<F>(encoded_message: &mut Vec<u8>, tag_number: u64, f: F) -> bool
+where
+    F: FnOnce(&[u8]) -> Vec<u8>,
+{
+    transform_field(encoded_message, tag_number, f)
+}
+
+/// Replaces the value of a field with a value computed from the old one by a fallible closure.
+///
+/// This works like [`replace_field_with_fn`], but `f` may fail, in which case its error is
+/// returned and the message is left untouched.
+///
+/// # Returns
+///
+/// * `Ok(true)` - The field was found and replaced.
+/// * `Ok(false)` - The field was not found, or the message cannot be decoded up to it. `f` is not
+///   called.
+/// * `Err(E)` - `f` returned an error.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_tag, replace_field_with_fn_result};
+///
+/// let mut encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67".to_vec();
+///
+/// let to_upper = |old: &[u8]| {
+///     std::str::from_utf8(old).map(|old| old.to_uppercase().into_bytes())
+/// };
+/// assert_eq!(replace_field_with_fn_result(&mut encoded_message, 2, to_upper), Ok(true));
+/// assert_eq!(extract_field_by_tag(&encoded_message, 2), Some(&b"TESTING"[..]));
+///
+/// let mut invalid = b"\x12\x01\xff".to_vec();
+/// assert!(replace_field_with_fn_result(&mut invalid, 2, to_upper).is_err());
+/// assert_eq!(invalid, b"\x12\x01\xff");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn replace_field_with_fn_result<F, E>(
+    encoded_message: &mut Vec<u8>,
+    tag_number: u64,
+    f: F,
+) -> Result<bool, E>
+where
+    F: FnOnce(&[u8]) -> Result<Vec<u8>, E>,
+{
+    let field = match utils::find_field(encoded_message, tag_number) {
+        Some(field) => field,
+        None => return Ok(false),
+    };
+    let new_value = f(&encoded_message[field.value_start..field.end])?;
+    utils::replace_value(encoded_message, &field, &new_value);
+    Ok(true)
+}
+
+/// Returns the length `encoded_message` will have after a successful call to
+/// [`replace_field_with`], without modifying it.
+///
+/// This lets callers pre-allocate the exact amount of memory the replacement needs.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded message.
+/// * `tag_number` - The tag number of the field to replace.
+/// * `new_payload` - The replacement bytes, as they would be passed to [`replace_field_with`]. Like
+///   there, they replace the whole field, including its header.
+///
+/// # Returns
+///
+/// * `Option<usize>` - The length of the message after the replacement, or `None` if the field is
+///   not found or the message cannot be decoded up to it.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{estimate_replacement_size, replace_field_with};
+///
+/// let mut encoded_message = vec![0x08, 0x01, 0x12, 0x07, 0x74, 0x65, 0x73, 0x74, 0x69, 0x6e, 0x67];
+/// let replace_with = b"\x12\x02\x68\x69";
+///
+/// let size = estimate_replacement_size(&encoded_message, 2, replace_with);
+/// replace_field_with(&mut encoded_message, 2, replace_with);
+/// assert_eq!(size, Some(encoded_message.len()));
+/// ```
+pub fn estimate_replacement_size(
+    encoded_message: &[u8],
+    tag_number: u64,
+    new_payload: &[u8],
+) -> Option<usize> {
+    let field = utils::find_field(encoded_message, tag_number)?;
+    Some(encoded_message.len() - (field.end - field.start) + new_payload.len())
+}
+
+/// Replaces several fields of an encoded message in a single pass.
+///
+/// Calling [`replace_field_with`] once per field re-scans and copies the whole message every time.
+/// This function scans the message once, records where every replacement goes, and then rebuilds
+/// the message with a single copy.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
+/// * `replacements` - `(tag_number, replace_with)` pairs. As with [`replace_field_with`],
+///   `replace_with` replaces the whole field, including its header.
+///
+/// # Returns
+///
+/// A `Vec<Option<Vec<u8>>>` with one entry per replacement, in the same order as `replacements`.
+/// Each entry is `Some(old_value)` if the field was found and replaced, or `None` otherwise.
+///
+/// # Example
+///
+/// ```rust
+/// use rustwire::{create_header, replace_multiple_fields};
+///
+/// let mut encoded_message = vec![0x08, 0x01, 0x12, 0x02, 0x68, 0x69];
+/// let new_string = [&create_header(2, 2, b"hey")[..], b"hey"].concat();
+/// let new_varint = [0x08, 0x02];
+///
+/// let old_values = replace_multiple_fields(
+///     &mut encoded_message,
+///     &[(2, &new_string), (3, b""), (1, &new_varint)],
+/// );
+///
+/// assert_eq!(old_values, [Some(b"hi".to_vec()), None, Some(vec![0x01])]);
+/// assert_eq!(encoded_message, [0x08, 0x02, 0x12, 0x03, 0x68, 0x65, 0x79]);
+/// ```
+///
+/// # Notes
+///
+/// - Only the first occurrence of each tag number is replaced. If the same tag number appears
+///   more than once in `replacements`, only the first entry is used and the others return `None`.
+/// - If the message cannot be decoded, scanning stops there: fields found before the malformed
+///   bytes are still replaced, and the remaining entries return `None`.
+/// - `encoded_message` is left untouched if no field is found.
+#[cfg(feature = "alloc")]
+pub fn replace_multiple_fields(
+    encoded_message: &mut Vec<u8>,
+    replacements: &[(u64, &[u8])],
+) -> Vec<Option<Vec<u8>>> {
+    let mut old_values = vec![None; replacements.len()];
+    // (start, end, index into `replacements`) of every field to replace, in message order.
+    let mut sites = Vec::new();
+
+    let mut offset = 0;
+    while offset < encoded_message.len() && sites.len() < replacements.len() {
+        let field = match utils::read_field(encoded_message, offset) {
+            Some(field) => field,
+            None => break,
+        };
+
+        let index = replacements
+            .iter()
+            .position(|(tag_number, _)| *tag_number == field.field_number);
+        if let Some(index) = index {
+            if old_values[index].is_none() {
+                old_values[index] = Some(encoded_message[field.value_start..field.end].to_vec());
+                sites.push((field.start, field.end, index));
+            }
+        }
+
+        offset = field.end;
+    }
+
+    if sites.is_empty() {
+        return old_values;
+    }
+
+    let new_len = sites
+        .iter()
+        .fold(encoded_message.len(), |len, (start, end, index)| {
+            len - (end - start) + replacements[*index].1.len()
+        });
+    let mut new_encoded_message = Vec::with_capacity(new_len);
+    let mut copied = 0;
+    for (start, end, index) in sites {
+        new_encoded_message.extend_from_slice(&encoded_message[copied..start]);
+        new_encoded_message.extend_from_slice(replacements[index].1);
+        copied = end;
+    }
+    new_encoded_message.extend_from_slice(&encoded_message[copied..]);
+
+    *encoded_message = new_encoded_message;
+    old_values
+}
+
+/// Replaces a field nested inside one or more sub-messages, following a path of tag numbers.
+///
+/// The field is looked up like in [`extract_field_by_path`] and replaced like in
+/// [`replace_field_with`]: `replace_with` replaces the whole field, including its header. The
+/// length prefixes of all enclosing messages are updated to account for the new size.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
+/// * `path` - The tag numbers to follow, from the outermost message inwards.
+/// * `replace_with` - A byte slice (`&[u8]`) containing the replacement field.
+///
+/// # Returns
+///
+/// * `Option<Vec<u8>>` - The old value of the field, or `None` if the field could not be found,
+///   in which case `encoded_message` is left untouched. An empty path does not name a field and
+///   always returns `None`.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_path, replace_field_by_path};
+///
+/// let mut encoded_message = b"\x08\x01\x2a\x04\x12\x02\x68\x69".to_vec();
+/// let old_value = replace_field_by_path(&mut encoded_message, &[5, 2], b"\x12\x03\x68\x65\x79");
+///
+/// assert_eq!(old_value, Some(b"hi".to_vec()));
+/// assert_eq!(extract_field_by_path(&encoded_message, &[5, 2]), Some(&b"hey"[..]));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn replace_field_by_path(
+    encoded_message: &mut Vec<u8>,
+    path: &[u64],
+    replace_with: &[u8],
+) -> Option<Vec<u8>> {
+    // The field records along the path, with offsets relative to the start of `encoded_message`.
+    let mut fields = Vec::with_capacity(path.len());
+    let mut base = 0;
+    let mut message = &encoded_message[..];
+    for (depth, tag_number) in path.iter().enumerate() {
+        let mut field = utils::find_field(message, *tag_number)?;
+        if depth + 1 < path.len() && field.variant != Variant::LengthDelimited {
+            return None;
+        }
+        message = &message[field.value_start..field.end];
+        field.start += base;
+        field.value_start += base;
+        field.end += base;
+        base = field.value_start;
+        fields.push(field);
+    }
+
+    let target = fields.pop()?;
+    let old_value = encoded_message[target.value_start..target.end].to_vec();
+
+    // Re-encode the enclosing fields from the inside out, since their length prefixes change.
+    let (mut start, mut end) = (target.start, target.end);
+    let mut new_field = replace_with.to_vec();
+    while let Some(parent) = fields.pop() {
+        let payload = [
+            &encoded_message[parent.value_start..start],
+            &new_field,
+            &encoded_message[end..parent.end],
+        ]
+        .concat();
+        new_field = create_header(
+            parent.field_number,
+            Variant::LengthDelimited.into(),
+            &payload,
+        );
+        new_field.extend_from_slice(&payload);
+        (start, end) = (parent.start, parent.end);
+    }
+
+    encoded_message.splice(start..end, new_field);
+    Some(old_value)
+}
+
+/// Replaces a field inside the sub-message stored in a field of the outer message.
+///
+/// This is a shorthand for [`replace_field_by_path`] with the path `[outer_tag, inner_tag]`.
+/// `replace_with` replaces the whole inner field, including its header, and the length prefix of
+/// the outer field is updated when the size of the sub-message changes.
+///
+/// # Arguments
+///
+/// * `outer` - A mutable reference to a `Vec<u8>` containing the encoded outer message.
+/// * `outer_tag` - The tag number of the field holding the sub-message.
+/// * `inner_tag` - The tag number of the field to replace inside the sub-message.
+/// * `replace_with` - A byte slice (`&[u8]`) containing the replacement field.
+///
+/// # Returns
+///
+/// * `Option<Vec<u8>>` - The old value of the inner field, or `None` if either field could not be
+///   found, in which case `outer` is left untouched.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_path, replace_field_in_nested};
+///
+/// let mut encoded_message = b"\x08\x01\x2a\x04\x12\x02\x68\x69".to_vec();
+/// let old_value = replace_field_in_nested(&mut encoded_message, 5, 2, b"\x12\x03\x68\x65\x79");
 ///
-/// The function supports the following wire types:
-/// - Varint (wire type 0)
-/// - Length-delimited (wire type 2)
+/// assert_eq!(old_value, Some(b"hi".to_vec()));
+/// assert_eq!(encoded_message, b"\x08\x01\x2a\x05\x12\x03\x68\x65\x79");
+/// ```
+#[cfg(feature = "alloc")]
+pub fn replace_field_in_nested(
+    outer: &mut Vec<u8>,
+    outer_tag: u64,
+    inner_tag: u64,
+    replace_with: &[u8],
+) -> Option<Vec<u8>> {
+    replace_field_by_path(outer, &[outer_tag, inner_tag], replace_with)
+}
+
+/// Changes the tag number of the first field with the given tag number.
 ///
-/// If the field is not found or if an error occurs during decoding, `None` is returned.
+/// Only the tag varint is re-encoded with `new_tag`; the wire type and the value, including the
+/// length prefix of length-delimited fields, are left untouched. This is useful for migrating
+/// stored messages when a field is renumbered in the schema.
 ///
 /// # Arguments
 ///
-/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
-/// * `tag_number` - The tag number of the field to extract.
+/// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
+/// * `old_tag` - The current tag number of the field.
+/// * `new_tag` - The tag number to give the field.
 ///
 /// # Returns
 ///
-/// * `Option<&[u8]>` - If the field is found, its value is returned as `Some(&[u8])`.
-///                      If the field is not found or an error occurs, `None` is returned.
+/// `true` if the field was found and retagged, `false` otherwise. A `new_tag` outside
+/// `1..=2^29 - 1` is not a valid field number, so `false` is returned and the message is left
+/// untouched.
 ///
-/// # Examples
+/// # Example
 ///
 /// ```
-/// use rustwire::extract_field_by_tag;
-///
-/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67";
-/// let tag_number = 2;
+/// use rustwire::retag_field;
 ///
-/// match extract_field_by_tag(encoded_message, tag_number) {
-///     Some(field_value) => println!("Field value: {:?}", field_value),
-///     None => println!("Field not found or invalid encoded message"),
-/// }
+/// let mut encoded_message = vec![0x08, 0x01, 0x12, 0x02, 0x68, 0x69];
+/// assert!(retag_field(&mut encoded_message, 2, 20));
+/// assert_eq!(encoded_message, [0x08, 0x01, 0xa2, 0x01, 0x02, 0x68, 0x69]);
 /// ```
 ///
-/// # Note
+/// # Notes
 ///
-/// This function assumes a basic understanding of the protobuf encoding format and wire types.
-/// It may need to be adapted to handle more complex field types or nested messages.
-pub fn extract_field_by_tag(encoded_message: &[u8], tag_number: u64) -> Option<&[u8]> {
-    let mut offset = 0;
-    while offset < encoded_message.len() {
-        let (tag, new_offset) = decoders::decode_varint(encoded_message, offset)?;
-        offset = new_offset;
+/// - The new tag may encode to a different number of bytes than the old one, in which case the
+///   rest of the message is shifted.
+#[cfg(feature = "alloc")]
+pub fn retag_field(encoded_message: &mut Vec<u8>, old_tag: u64, new_tag: u64) -> bool {
+    if !(1..=utils::MAX_FIELD_NUMBER).contains(&new_tag) {
+        return false;
+    }
+    let field = match utils::find_field(encoded_message, old_tag) {
+        Some(field) => field,
+        None => return false,
+    };
+    let tag_end = utils::handle_varint(encoded_message, field.start).unwrap_or(field.start);
 
-        let field_number = tag >> 3;
-        let wire_type = tag & 0x07;
+    let new_tag = encoders::encode_varint((new_tag << 3) | u64::from(field.variant));
+    encoded_message.splice(field.start..tag_end, new_tag);
+    true
+}
 
-        if field_number == tag_number {
-            return match wire_type {
-                0 => decoders::decode_varint(encoded_message, offset).map(|(_, new_offset)| {
-                    let varint_slice = &encoded_message[offset..new_offset];
-                    offset = new_offset;
-                    varint_slice
-                }),
-                1 => decoders::decode_double(encoded_message, offset).map(|new_offset| {
-                    let double_slice = &encoded_message[offset..new_offset];
-                    offset = new_offset;
-                    double_slice
-                }),
-                2 => utils::handle_length_delimited(encoded_message, offset),
-                5 => decoders::decode_float(encoded_message, offset).map(|new_offset| {
-                    let fixed32_slice = &encoded_message[offset..new_offset];
-                    offset = new_offset;
-                    fixed32_slice
-                }),
-                _ => None,
-            };
+/// Changes the tag number of every field with the given tag number.
+///
+/// Works like [`retag_field`], but renames all occurrences, e.g. every element of a repeated field,
+/// and rebuilds the message once.
+///
+/// # Returns
+///
+/// The number of fields that were retagged. If part of the message cannot be decoded, that part is
+/// kept as is. If `new_tag` is not a valid field number, 0 is returned and the message is left
+/// untouched.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::retag_all_fields;
+///
+/// let mut encoded_message = vec![0x08, 0x01, 0x10, 0x02, 0x08, 0x03];
+/// assert_eq!(retag_all_fields(&mut encoded_message, 1, 3), 2);
+/// assert_eq!(encoded_message, [0x18, 0x01, 0x10, 0x02, 0x18, 0x03]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn retag_all_fields(encoded_message: &mut Vec<u8>, old_tag: u64, new_tag: u64) -> usize {
+    if !(1..=utils::MAX_FIELD_NUMBER).contains(&new_tag) {
+        return 0;
+    }
+    let mut retagged = Vec::with_capacity(encoded_message.len());
+    let mut count = 0;
+    let mut offset = 0;
+    while offset < encoded_message.len() {
+        let field = match utils::read_field(encoded_message, offset) {
+            Some(field) => field,
+            None => break,
+        };
+        retagged.extend_from_slice(&encoded_message[offset..field.start]);
+        if field.field_number == old_tag {
+            let tag_end = utils::handle_varint(encoded_message, field.start).unwrap_or(field.start);
+            let mut tag = [0; 10];
+            let tag_len =
+                encoders::write_varint((new_tag << 3) | u64::from(field.variant), &mut tag);
+            retagged.extend_from_slice(&tag[..tag_len]);
+            retagged.extend_from_slice(&encoded_message[tag_end..field.end]);
+            count += 1;
         } else {
-            offset = utils::skip_field(encoded_message, wire_type, offset)?;
+            retagged.extend_from_slice(&encoded_message[field.start..field.end]);
         }
+        offset = field.end;
     }
-    None
+
+    if count > 0 {
+        retagged.extend_from_slice(&encoded_message[offset..]);
+        *encoded_message = retagged;
+    }
+    count
 }
 
-/// Extracts multiple fields with the given tag numbers from an encoded protobuf message.
-///
-/// This function iterates over the encoded message and searches for fields with the specified tag numbers.
-/// If a field is found and its tag number is present in the `tag_numbers` slice, its value is extracted
-/// and stored in the returned vector of tuples.
+/// Overwrites the value of a varint field in place, without allocating.
 ///
-/// The function supports the following wire types:
-/// - Varint (wire type 0)
-/// - Length-delimited (wire type 2)
-///
-/// If a field is not found or if an error occurs during decoding, it is skipped, and the function continues
-/// processing the remaining fields.
+/// This is the fast path for updating counters and other small integer fields: as long as the new
+/// value encodes to the same number of bytes as the old one (e.g. changing 100 to 127, which are
+/// both single-byte varints), the bytes are overwritten directly and nothing moves.
 ///
 /// # Arguments
 ///
-/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
-/// * `tag_numbers` - A slice of `u64` values representing the tag numbers of the fields to extract.
+/// * `encoded_message` - A mutable byte slice (`&mut [u8]`) containing the encoded message.
+/// * `tag_number` - The tag number of the varint field to update.
+/// * `new_value` - The new value of the field.
 ///
 /// # Returns
 ///
-/// A vector of tuples `Vec<(u64, &[u8])>`, where each tuple contains the tag number and the corresponding
-/// field value as a byte vector reference. The order of the tuples in the vector corresponds to the order of the
-/// tag numbers in the `tag_numbers` slice.
+/// * `Ok(())` if the field was updated.
+/// * `Err(RewriteError::FieldNotFound)` if the field does not exist or the message is malformed.
+/// * `Err(RewriteError::NotVarint { .. })` if the field is not a varint.
+/// * `Err(RewriteError::EncodedLengthChanged { old, new })` if the new value needs a different
+///   number of bytes. The message is left untouched; use `replace_field_with` instead.
 ///
-/// # Examples
+/// # Example
 ///
-/// ```
-/// use rustwire::extract_multiple_fields_by_tag;
+/// ```rust
+/// use rustwire::{rewrite_varint_in_place, RewriteError};
 ///
-/// let encoded_message = b"\x08\x01\x12\x07\x74\x65\x73\x74\x69\x6e\x67\x1a\x03\x61\x62\x63";
-/// let tag_numbers = &[2, 3];
+/// let mut encoded_message = vec![0x08, 0x64, 0x12, 0x02, 0x68, 0x69];
 ///
-/// let fields = extract_multiple_fields_by_tag(encoded_message, tag_numbers);
-/// for (tag_number, field_value) in fields {
-///     println!("Tag: {}, Value: {:?}", tag_number, field_value);
-/// }
+/// rewrite_varint_in_place(&mut encoded_message, 1, 127).unwrap();
+/// assert_eq!(encoded_message, [0x08, 0x7F, 0x12, 0x02, 0x68, 0x69]);
+///
+/// let err = rewrite_varint_in_place(&mut encoded_message, 1, 128).unwrap_err();
+/// assert_eq!(err, RewriteError::EncodedLengthChanged { old: 1, new: 2 });
 /// ```
 ///
-/// # Note
+/// # Notes
 ///
-/// This function assumes a basic understanding of the protobuf encoding format and wire types.
-/// It may need to be adapted to handle more complex field types or nested messages.
-pub fn extract_multiple_fields_by_tag<'a>(
-    encoded_message: &'a [u8],
-    tag_numbers: &[u64],
-) -> Vec<(u64, &'a [u8])> {
-    let mut fields = Vec::new();
-    let mut offset = 0;
-
-    while offset < encoded_message.len() {
-        let (tag, new_offset) = match decoders::decode_varint(encoded_message, offset) {
-            Some((tag, new_offset)) => (tag, new_offset),
-            None => break,
-        };
-        offset = new_offset;
-
-        let field_number = tag >> 3;
-        let wire_type = tag & 0x07;
-
-        if tag_numbers.contains(&field_number) {
-            let field_value = match wire_type {
-                0 => utils::handle_varint(encoded_message, offset).map(|new_offset| {
-                    let value = &encoded_message[offset..new_offset];
-                    offset = new_offset;
-                    value
-                }),
-                1 => decoders::decode_double(encoded_message, offset).map(|new_offset| {
-                    let double_slice = &encoded_message[offset..new_offset];
-                    offset = new_offset;
-                    double_slice
-                }),
-                2 => utils::handle_length_delimited(encoded_message, offset).map(|value| {
-                    offset += value.len() + 1; // Skip the length prefix
-                    value
-                }),
-                5 => decoders::decode_float(encoded_message, offset).map(|new_offset| {
-                    let float_slice = &encoded_message[offset..new_offset];
-                    offset = new_offset;
-                    float_slice
-                }),
-                _ => None,
-            };
+/// - Only the first occurrence of the field is updated.
+pub fn rewrite_varint_in_place(
+    encoded_message: &mut [u8],
+    tag_number: u64,
+    new_value: u64,
+) -> Result<(), RewriteError> {
+    let field =
+        utils::find_field(encoded_message, tag_number).ok_or(RewriteError::FieldNotFound)?;
+    if field.variant != Variant::Varint {
+        return Err(RewriteError::NotVarint {
+            wire_type: field.variant.into(),
+        });
+    }
 
-            if let Some(value) = field_value {
-                fields.push((field_number, value));
-            }
-        } else {
-            offset = match utils::skip_field(encoded_message, wire_type, offset) {
-                Some(new_offset) => new_offset,
-                None => break,
-            };
-        }
+    let mut new_bytes = [0; 10];
+    let new_len = encoders::write_varint(new_value, &mut new_bytes);
+    let old_len = field.end - field.value_start;
+    if new_len != old_len {
+        return Err(RewriteError::EncodedLengthChanged {
+            old: old_len,
+            new: new_len,
+        });
     }
 
-    fields
+    encoded_message[field.value_start..field.end].copy_from_slice(&new_bytes[..new_len]);
+    Ok(())
 }
 
-/// Replaces a field with the specified tag number in the encoded message with the given replacement data.
+/// Returns the value of the field with the specified tag number, inserting it first if it is missing.
 ///
-/// This function modifies the `encoded_message` in-place and returns the old field value as an `Option<Vec<u8>>`.
+/// If the field is present, its current value is returned and the message is left untouched.
+/// Otherwise a new field is appended to `encoded_message`, using `tag_number`, `variant` and
+/// `default_value`, and the freshly inserted value is returned.
 ///
 /// # Arguments
 ///
 /// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
-/// * `tag_number` - The tag number of the field to replace.
-/// * `replace_with` - A byte slice (`&[u8]`) containing the replacement data.
+/// * `tag_number` - The tag number of the field to look up.
+/// * `variant` - The wire type used when the field has to be inserted.
+/// * `default_value` - The encoded value to insert if the field is missing. For
+///   `Variant::LengthDelimited` this is the raw payload, the length prefix is added automatically.
 ///
 /// # Returns
 ///
-/// * `Option<Vec<u8>>` - If the field is found and replaced successfully, returns `Some(old_value)`, where `old_value` is the original field value as a `Vec<u8>`. If the field is not found or an error occurs, returns `None`.
+/// A byte slice (`&[u8]`) borrowing from `encoded_message` containing the value of the field.
 ///
 /// # Example
 ///
 /// ```rust
-/// use rustwire::replace_field_with;
+/// use rustwire::{get_or_insert_field, Variant};
 ///
-/// let mut encoded_message = vec![0x08, 0x01, 0x12, 0x07, 0x74, 0x65, 0x73, 0x74, 0x69, 0x6e, 0x67];
-/// let tag_number = 2;
-/// let replace_with = b"Hello";
+/// let mut encoded_message = vec![0x08, 0x01];
 ///
-/// match replace_field_with(&mut encoded_message, tag_number, replace_with) {
-///     Some(old_value) => println!("Replaced field value: {:?}", old_value),
-///     None => println!("Field not found or error occurred"),
-/// }
+/// let value = get_or_insert_field(&mut encoded_message, 2, Variant::LengthDelimited, b"Hello");
+/// assert_eq!(value, b"Hello");
+///
+/// let value = get_or_insert_field(&mut encoded_message, 1, Variant::Varint, &[0x02]);
+/// assert_eq!(value, &[0x01]);
 /// ```
 ///
 /// # Notes
 ///
-/// - This function modifies the `encoded_message` in-place.
-/// - The function currently creates a copy of the encoded message during the replacement process. It would be more efficient to overwrite the existing data directly.
-/// - The function supports the following wire types:
-///   - Varint (wire type 0)
-///   - 64-bit (wire type 1)
-///   - Length-delimited (wire type 2)
-///   - 32-bit (wire type 5)
-/// - If the wire type is not supported, the function returns `None`.
-pub fn replace_field_with(
-    encoded_message: &mut Vec<u8>,
+/// - If the message cannot be decoded up to the requested field, the field is treated as missing
+///   and `default_value` is appended.
+#[cfg(feature = "alloc")]
+pub fn get_or_insert_field<'a>(
+    encoded_message: &'a mut Vec<u8>,
     tag_number: u64,
-    replace_with: &[u8],
-) -> Option<Vec<u8>> {
-    let mut offset = 0;
-    while offset < encoded_message.len() {
-        let old_offset = offset;
-        let (tag, new_offset) = decoders::decode_varint(encoded_message, offset)?;
-        offset = new_offset;
-
-        let field_number = tag >> 3;
-        let wire_type = tag & 0x07;
+    variant: Variant,
+    default_value: &[u8],
+) -> &'a [u8] {
+    let (start, end) = match utils::find_field(encoded_message, tag_number) {
+        Some(field) => (field.value_start, field.end),
+        None => {
+            append_field(encoded_message, tag_number, variant, default_value);
+            let end = encoded_message.len();
+            (end - default_value.len(), end)
+        }
+    };
 
-        if field_number == tag_number {
-            let old = match wire_type {
-                0 => decoders::decode_varint(encoded_message, offset).map(|(_, new_offset)| {
-                    let varint_slice = &encoded_message[offset..new_offset];
-                    offset = new_offset;
-                    varint_slice
-                }),
-                1 => decoders::decode_double(encoded_message, offset).map(|new_offset| {
-                    let double_slice = &encoded_message[offset..new_offset];
-                    offset = new_offset;
-                    double_slice
-                }),
-                2 => utils::handle_length_delimited(encoded_message, offset).map(|value| {
-                    offset += value.len() + 1; // Skip the length prefix
-                    value
-                }),
-                5 => decoders::decode_float(encoded_message, offset).map(|new_offset| {
-                    let fixed32_slice = &encoded_message[offset..new_offset];
-                    offset = new_offset;
-                    fixed32_slice
-                }),
-                _ => None,
-            }
-            .map(|old| old.to_vec());
+    &encoded_message[start..end]
+}
 
-            // create two regsions, pre old_offset and post offset
-            let pre_slice = &encoded_message[..old_offset];
-            let post_slice = &encoded_message[offset..];
+/// Appends a new field to the end of an encoded message.
+///
+/// The field header is encoded from `tag_number` and `variant`, followed by `payload`. Existing
+/// fields with the same tag number are left in place; for a non-repeated field, parsers keep the
+/// last occurrence, so the appended field takes precedence.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A mutable reference to a `Vec<u8>` containing the encoded message.
+/// * `tag_number` - The tag number of the new field.
+/// * `variant` - The wire type of the new field.
+/// * `payload` - The encoded value of the new field. For `Variant::LengthDelimited` this is the raw
+///   payload, the length prefix is added automatically.
+///
+/// # Example
+///
+/// ```rust
+/// use rustwire::{append_field, Variant};
+///
+/// let mut encoded_message = vec![0x08, 0x01];
+/// append_field(&mut encoded_message, 2, Variant::LengthDelimited, b"hi");
+///
+/// assert_eq!(encoded_message, [0x08, 0x01, 0x12, 0x02, 0x68, 0x69]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn append_field(
+    encoded_message: &mut Vec<u8>,
+    tag_number: u64,
+    variant: Variant,
+    payload: &[u8],
+) {
+    let header = create_header(tag_number, variant.into(), payload);
+    encoded_message.reserve(header.len() + payload.len());
+    encoded_message.extend_from_slice(&header);
+    encoded_message.extend_from_slice(payload);
+}
 
-            // TODO: This is creating a copy right now, it would be better if it would just overwrite
+/// Inserts a new field at the start of an encoded message.
+///
+/// Works like [`append_field`], except that the field is inserted at position 0 and the existing
+/// fields are shifted back. For a non-repeated field that is already present, parsers keep the
+/// last occurrence, so the existing field takes precedence over the prepended one.
+///
+/// # Example
+///
+/// ```rust
+/// use rustwire::{prepend_field, Variant};
+///
+/// let mut encoded_message = vec![0x12, 0x02, 0x68, 0x69];
+/// prepend_field(&mut encoded_message, 1, Variant::Varint, &[0x01]);
+///
+/// assert_eq!(encoded_message, [0x08, 0x01, 0x12, 0x02, 0x68, 0x69]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn prepend_field(
+    encoded_message: &mut Vec<u8>,
+    tag_number: u64,
+    variant: Variant,
+    payload: &[u8],
+) {
+    let header = create_header(tag_number, variant.into(), payload);
+    encoded_message.splice(0..0, header.into_iter().chain(payload.iter().copied()));
+}
 
-            // create a new vec with the pre_slice, the replace_with and the post_slice
-            let new_len = pre_slice.len() + replace_with.len() + post_slice.len();
-            let mut new_encoded_message = Vec::with_capacity(new_len);
-            new_encoded_message.extend_from_slice(pre_slice);
-            new_encoded_message.extend_from_slice(replace_with);
-            new_encoded_message.extend_from_slice(post_slice);
+/// The tag number reserved for debug annotations added with `add_unknown_comment`.
+///
+/// This is the largest valid field number (`2^29 - 1`), which is very unlikely to be used by a real
+/// schema. Fields with this tag are removed by [`strip_debug_annotations`].
+#[cfg(feature = "debug-comments")]
+pub const DEBUG_ANNOTATION_TAG: u64 = (1 << 29) - 1;
 
-            // TODO: This is creating a copy right now, it would be better if it would just overwrite
-            encoded_message.clear();
-            encoded_message.extend_from_slice(&new_encoded_message);
+/// Removes all debug annotations (fields with [`DEBUG_ANNOTATION_TAG`]) from an encoded message.
+///
+/// The message is compacted in place. If part of the message cannot be decoded, that part is kept
+/// as is.
+///
+/// # Example
+///
+/// ```rust
+/// use rustwire::{strip_debug_annotations, MessageBuilderFixed};
+///
+/// let mut builder = MessageBuilderFixed::<32>::new();
+/// builder.add_varint(1, 1).unwrap().add_unknown_comment("note").unwrap();
+///
+/// let mut encoded_message = builder.build().to_vec();
+/// strip_debug_annotations(&mut encoded_message);
+/// assert_eq!(encoded_message, [0x08, 0x01]);
+/// ```
+#[cfg(all(feature = "alloc", feature = "debug-comments"))]
+pub fn strip_debug_annotations(encoded_message: &mut Vec<u8>) {
+    let mut read = 0;
+    let mut write = 0;
 
-            return old;
-        } else {
-            offset = utils::skip_field(encoded_message, wire_type, offset)?;
+    while read < encoded_message.len() {
+        let field = match utils::read_field(encoded_message, read) {
+            Some(field) => field,
+            None => break,
+        };
+        // Groups stepped over before the field are kept.
+        encoded_message.copy_within(read..field.start, write);
+        write += field.start - read;
+        if field.field_number != DEBUG_ANNOTATION_TAG {
+            encoded_message.copy_within(field.start..field.end, write);
+            write += field.end - field.start;
         }
+        read = field.end;
     }
-    None
+
+    let tail = encoded_message.len() - read;
+    encoded_message.copy_within(read.., write);
+    encoded_message.truncate(write + tail);
 }
 
 /// Creates the header for a field in a protocol buffer message.
@@ -389,6 +3564,7 @@ pub fn replace_field_with(
 /// - The header bytes are encoded using base 128 varint encoding.
 /// - If the `variant` is 2 (length-delimited), the length of the `encoded_message` is encoded as part
 ///   of the header.
+#[cfg(feature = "alloc")]
 pub fn create_header(tag_number: u64, variant: u64, encoded_message: &[u8]) -> Vec<u8> {
     let mut header = Vec::new();
 
@@ -425,6 +3601,164 @@ pub fn create_header(tag_number: u64, variant: u64, encoded_message: &[u8]) -> V
     header
 }
 
+/// Re-assembles an encoded message with its fields sorted by ascending tag number.
+///
+/// Some downstream systems, such as deterministic hashing of messages, need fields to appear in
+/// tag order. The sort is stable, so multiple occurrences of the same tag (repeated fields) keep
+/// their relative order. Every field header is re-encoded with [`create_header`]; the field values
+/// are copied unchanged.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+///
+/// # Returns
+///
+/// * `Option<Vec<u8>>` - The canonicalized message, or `None` if any field fails to parse.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::canonicalize_message;
+///
+/// let encoded_message = b"\x12\x02\x68\x69\x08\x01";
+/// assert_eq!(
+///     canonicalize_message(encoded_message),
+///     Some(vec![0x08, 0x01, 0x12, 0x02, 0x68, 0x69])
+/// );
+/// ```
+///
+/// # Notes
+///
+/// - Unlike [`encode_message_deterministically`], no values are normalized or removed.
+/// - Groups (wire types 3 and 4) cannot be parsed, so messages containing them return `None`.
+#[cfg(feature = "alloc")]
+pub fn canonicalize_message(encoded_message: &[u8]) -> Option<Vec<u8>> {
+    // `FieldIterator` stops silently at malformed bytes, so check the whole message first.
+    validate_message(encoded_message).ok()?;
+
+    let mut fields: Vec<_> = FieldIterator::new(encoded_message).collect();
+    fields.sort_by_key(|field| field.tag);
+
+    let mut canonical = Vec::with_capacity(encoded_message.len());
+    for field in fields {
+        let value = field.as_bytes();
+        canonical.extend_from_slice(&create_header(field.tag, field.wire_type.into(), value));
+        canonical.extend_from_slice(value);
+    }
+    Some(canonical)
+}
+
+/// Encodes a list of fields into a canonical, deterministic protobuf message.
+///
+/// The output only depends on the logical content of `fields`, not on the order they were
+/// collected in, which makes it suitable for content hashing, caching and signing. The following
+/// normalization rules are applied:
+///
+/// 1. Fields are sorted by ascending tag number. The sort is stable, so multiple occurrences of the
+///    same tag (repeated fields) keep their relative order.
+/// 2. Varint values are re-encoded in their shortest form.
+/// 3. For the tag numbers in `singular_tags`, only the last occurrence is kept, since that is the
+///    one a proto3 decoder reads, and it is removed if it holds the proto3 default value: a varint
+///    that decodes to `0`, an empty length-delimited payload, or a 32-bit or 64-bit value whose
+///    bytes are all zero (`-0.0` is kept, as in proto3).
+///
+/// Fields whose tag number is not in `singular_tags`, such as repeated fields, message fields and
+/// proto3 `optional` fields, are never removed, since an explicitly set default value is
+/// meaningful for them. The result is valid proto3 wire format: a proto3 decoder reads the same
+/// values as it would from the unnormalized fields.
+///
+/// # Arguments
+///
+/// * `fields` - The fields to encode as `(tag_number, variant, payload)` tuples. Varint payloads
+///   must be encoded varints, and length-delimited payloads must not include the length prefix.
+/// * `singular_tags` - The tag numbers of singular proto3 fields without presence tracking:
+///   scalar, `string`, `bytes` and enum fields that are neither `repeated` nor `optional`.
+///
+/// # Returns
+///
+/// A `Vec<u8>` containing the encoded message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_message_deterministically, Variant};
+///
+/// let a = encode_message_deterministically(
+///     &[
+///         (2, Variant::LengthDelimited, b"hi"),
+///         (1, Variant::Varint, &[0x01]),
+///         (3, Variant::Varint, &[0x00]),
+///         (4, Variant::Varint, &[0x00]),
+///     ],
+///     &[1, 2, 3],
+/// );
+/// let b = encode_message_deterministically(
+///     &[
+///         (1, Variant::Varint, &[0x01]),
+///         (4, Variant::Varint, &[0x00]),
+///         (2, Variant::LengthDelimited, b"hi"),
+///     ],
+///     &[1, 2, 3],
+/// );
+///
+/// // Field 3 holds a default value and is removed, field 4 is repeated and is kept.
+/// assert_eq!(a, b);
+/// assert_eq!(a, [0x08, 0x01, 0x12, 0x02, 0x68, 0x69, 0x20, 0x00]);
+/// ```
+///
+/// # Notes
+///
+/// - A varint payload that is not exactly one valid varint is copied unchanged, and never counts
+///   as a default value.
+#[cfg(feature = "alloc")]
+pub fn encode_message_deterministically(
+    fields: &[(u64, Variant, &[u8])],
+    singular_tags: &[u64],
+) -> Vec<u8> {
+    let mut sorted: Vec<&(u64, Variant, &[u8])> = fields.iter().collect();
+    sorted.sort_by_key(|(tag_number, _, _)| *tag_number);
+
+    let mut encoded_message = Vec::new();
+    for (i, (tag_number, variant, payload)) in sorted.iter().enumerate() {
+        let singular = singular_tags.contains(tag_number);
+        if singular
+            && sorted
+                .get(i + 1)
+                .is_some_and(|(next_tag_number, _, _)| next_tag_number == tag_number)
+        {
+            // A later occurrence of the singular field wins.
+            continue;
+        }
+
+        let canonical_varint;
+        let payload = match variant {
+            Variant::Varint => match decoders::decode_varint(payload, 0) {
+                Some((value, end)) if end == payload.len() => {
+                    if singular && value == 0 {
+                        continue;
+                    }
+                    canonical_varint = encode_varint(value);
+                    &canonical_varint[..]
+                }
+                _ => payload,
+            },
+            Variant::LengthDelimited if singular && payload.is_empty() => continue,
+            Variant::SixtyFourBit | Variant::ThirtyTwoBit
+                if singular && payload.iter().all(|b| *b == 0) =>
+            {
+                continue
+            }
+            _ => payload,
+        };
+
+        encoded_message.extend_from_slice(&create_header(*tag_number, (*variant).into(), payload));
+        encoded_message.extend_from_slice(payload);
+    }
+
+    encoded_message
+}
+
 /// Represents the wire type variant of a field in a protocol buffer message.
 ///
 /// The `Variant` enum provides a set of predefined wire types that can be used when creating
@@ -440,7 +3774,12 @@ pub fn create_header(tag_number: u64, variant: u64, encoded_message: &[u8]) -> V
 /// # Conversions
 ///
 /// The `Variant` enum implements the `Into<u64>` trait, allowing conversion from a `Variant`
-/// to its corresponding wire type value as a `u64`.
+/// to its corresponding wire type value as a `u64`. The reverse conversion is available through
+/// `TryFrom<u64>` and `TryFrom<u8>`, which return [`WireError::UnknownWireType`] for wire types
+/// without a `Variant` (3, 4, 6 and 7).
+///
+/// `Variant` also implements `Display`, printing a human-readable name such as `"varint"` or
+/// `"length-delimited"`.
 ///
 /// # Examples
 ///
@@ -455,7 +3794,16 @@ pub fn create_header(tag_number: u64, variant: u64, encoded_message: &[u8]) -> V
 /// In this example, the `LengthDelimited` variant is created and then converted into its
 /// corresponding wire type value using the `into()` method. The resulting `wire_type_value`
 /// is of type `u64` and has a value of `2`.
-#[derive(Debug, PartialEq, Eq)]
+///
+/// ```
+/// use rustwire::{Variant, WireError};
+///
+/// assert_eq!(Variant::try_from(5u8), Ok(Variant::ThirtyTwoBit));
+/// assert_eq!(Variant::try_from(3u64), Err(WireError::UnknownWireType(3)));
+/// assert_eq!(Variant::SixtyFourBit.to_string(), "64-bit");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Variant {
     Varint,
     SixtyFourBit,
@@ -463,4 +3811,88 @@ pub enum Variant {
     ThirtyTwoBit,
 }
 
-pub use encoders::{encode_double, encode_float, encode_varint};
+#[cfg(feature = "alloc")]
+pub use builder::MessageBuilder;
+pub use builder::MessageBuilderFixed;
+#[cfg(feature = "alloc")]
+pub use builder::MessageTemplate;
+#[cfg(feature = "std")]
+pub use builder::StreamingMessageBuilder;
+pub use cursor::MessageCursor;
+pub use decoders::{
+    decode_field_value, decode_length_delimited, decode_varint_from_slice, decode_zigzag_i32,
+    decode_zigzag_i64, field_number_from_tag, varint_byte_slice, wire_type_from_tag,
+    wire_type_name,
+};
+#[cfg(feature = "std")]
+pub use diff::{apply_diff, diff_messages, field_equal, messages_equal, FieldChange};
+#[cfg(feature = "bytes")]
+pub use encoders::encode_varint_buf;
+#[cfg(feature = "alloc")]
+pub use encoders::{
+    encode_bool, encode_double, encode_field, encode_fixed32, encode_fixed64, encode_float,
+    encode_int32, encode_int64, encode_packed_double, encode_packed_float, encode_packed_sint32,
+    encode_packed_sint64, encode_packed_varint, encode_sfixed32, encode_sfixed64, encode_sint32,
+    encode_sint64, encode_string_field, encode_tag, encode_varint, encode_varint_field,
+};
+pub use encoders::{
+    encode_field_tag_const, encode_header_to_slice, encode_varint_array, encode_varint_const,
+    encode_varint_exact, encode_varint_to_slice, encode_zigzag_i32, encode_zigzag_i64,
+    encoded_varint_len, measure_encoded_field_size, measure_encoded_header_size,
+    measure_encoded_varint_size,
+};
+pub use error::{BuilderError, PatchError, RewriteError, StrictValidationError, WireError};
+#[cfg(feature = "tokio")]
+pub use framing::ProtoLengthDelimitedCodec;
+pub use framing::{
+    decode_grpc_frame, iter_varint_length_messages, strip_length_prefix, strip_varint_length,
+    VarintLengthMessages,
+};
+#[cfg(feature = "alloc")]
+pub use framing::{
+    encode_grpc_frame, encode_with_length_prefix, prepend_varint_length, split_grpc_stream,
+    split_varint_length_stream,
+};
+pub use header::{parse_field_header, FieldHeader};
+#[cfg(feature = "std")]
+pub use index::MessageIndex;
+#[cfg(feature = "alloc")]
+pub use iter::{collect_to_message, map_fields};
+pub use iter::{fold_fields, FieldIterator, FilterFieldsByTag};
+#[cfg(feature = "serde_json")]
+pub use json::{encode_message_as_field_map, field_map_to_encoded_message, WireTypeHint};
+#[cfg(feature = "alloc")]
+pub use names::from_snake_to_camel_tag;
+#[cfg(feature = "std")]
+pub use names::FieldNameResolver;
+pub use options::{
+    DepthExceededAction, MessageParseOptions, TruncatedMessageAction, UnknownFieldPolicy,
+    UnknownWireTypeAction,
+};
+#[cfg(feature = "alloc")]
+pub use patcher::MessagePatcher;
+#[cfg(feature = "alloc")]
+pub use pretty::{
+    dump_message_as_text, dump_message_as_text_with_options, message_to_hex_dump,
+    pretty_print_wire_format, pretty_print_wire_format_recursive, DumpOptions,
+};
+#[cfg(feature = "alloc")]
+pub use traits::{append_field_generic, ProtoEncode};
+pub use traits::{extract_field_generic, ProtoExtract};
+pub use validate::{
+    estimate_field_count, field_looks_like_message, validate_message, validate_message_recursive,
+    StrictValidationConfig,
+};
+#[cfg(feature = "alloc")]
+pub use validate::{
+    estimate_nesting_depth, estimate_total_field_count_recursive, validate_strict,
+    validate_strict_with_config,
+};
+#[cfg(feature = "alloc")]
+pub use view::SharedMessageView;
+pub use view::{FieldView, MessageView};
+pub use well_known::{decode_any, decode_duration, decode_timestamp, extract_any_type_url};
+#[cfg(feature = "alloc")]
+pub use well_known::{
+    encode_any, encode_duration, encode_duration_field, encode_timestamp, encode_timestamp_field,
+};
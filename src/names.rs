@@ -0,0 +1,110 @@
+use alloc::string::String;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// Resolves proto3 JSON field names to tag numbers.
+///
+/// Proto3's JSON encoding refers to fields by their camelCase JSON name rather than by tag number.
+/// `FieldNameResolver` holds the mapping from JSON names to tag numbers so fields can be referenced
+/// by name without a full proto descriptor.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::FieldNameResolver;
+///
+/// let resolver = FieldNameResolver::new(&[("userId", 1), ("displayName", 2)]);
+/// assert_eq!(resolver.resolve("displayName"), Some(2));
+/// assert_eq!(resolver.resolve("email"), None);
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct FieldNameResolver {
+    names: HashMap<String, u64>,
+}
+
+#[cfg(feature = "std")]
+impl FieldNameResolver {
+    /// Creates a resolver from `(json_name, tag_number)` pairs.
+    ///
+    /// If the same name appears more than once, the last tag number wins.
+    pub fn new(entries: &[(&str, u64)]) -> Self {
+        let names = entries
+            .iter()
+            .map(|(name, tag_number)| (name.to_string(), *tag_number))
+            .collect();
+        FieldNameResolver { names }
+    }
+
+    /// Returns the tag number of the field with the given JSON name, if known.
+    pub fn resolve(&self, json_name: &str) -> Option<u64> {
+        self.names.get(json_name).copied()
+    }
+}
+
+/// Looks up the tag number of a snake_case field name in a list of camelCase JSON names.
+///
+/// The snake_case name is converted to its proto3 JSON name (underscores are removed and the
+/// following letter is upper-cased) before it is compared against the names in `tags`.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::from_snake_to_camel_tag;
+///
+/// let tags = [("userId", 1), ("displayName", 2)];
+/// assert_eq!(from_snake_to_camel_tag("display_name", &tags), Some(2));
+/// assert_eq!(from_snake_to_camel_tag("email", &tags), None);
+/// ```
+pub fn from_snake_to_camel_tag(snake_name: &str, tags: &[(&str, u64)]) -> Option<u64> {
+    let json_name = snake_to_camel(snake_name);
+    tags.iter()
+        .find(|(name, _)| *name == json_name)
+        .map(|(_, tag_number)| *tag_number)
+}
+
+/// Converts a snake_case field name into its proto3 JSON name, following `protoc`'s rules.
+fn snake_to_camel(snake_name: &str) -> String {
+    let mut json_name = String::with_capacity(snake_name.len());
+    let mut capitalize_next = false;
+    for c in snake_name.chars() {
+        if c == '_' {
+            capitalize_next = true;
+        } else if capitalize_next {
+            json_name.extend(c.to_uppercase());
+            capitalize_next = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snake_to_camel() {
+        assert_eq!(snake_to_camel("foo"), "foo");
+        assert_eq!(snake_to_camel("foo_bar"), "fooBar");
+        assert_eq!(snake_to_camel("foo_bar_baz2"), "fooBarBaz2");
+        assert_eq!(snake_to_camel("foo__bar"), "fooBar");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let resolver = FieldNameResolver::new(&[("fooBar", 1), ("baz", 2)]);
+        assert_eq!(resolver.resolve("fooBar"), Some(1));
+        assert_eq!(resolver.resolve("baz"), Some(2));
+        assert_eq!(resolver.resolve("foo_bar"), None);
+    }
+
+    #[test]
+    fn test_from_snake_to_camel_tag() {
+        let tags = [("fooBar", 1), ("baz", 2)];
+        assert_eq!(from_snake_to_camel_tag("foo_bar", &tags), Some(1));
+        assert_eq!(from_snake_to_camel_tag("baz", &tags), Some(2));
+        assert_eq!(from_snake_to_camel_tag("qux", &tags), None);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{utils, WireError};
+
+/// Options controlling how strictly [`extract_field_by_tag_with_opts`] parses a message.
+///
+/// The default options are as permissive as [`extract_field_by_tag`]: malformed input ends the
+/// search without an error, groups are skipped up to 100 levels deep and deeper groups end the
+/// search without an error too, and messages of any size are accepted.
+///
+/// [`extract_field_by_tag`]: crate::extract_field_by_tag
+/// [`extract_field_by_tag_with_opts`]: crate::extract_field_by_tag_with_opts
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{
+///     extract_field_by_tag_with_opts, MessageParseOptions, TruncatedMessageAction, WireError,
+/// };
+///
+/// let truncated = b"\x08\x01\x12\x07\x74\x65";
+/// let strict = MessageParseOptions {
+///     on_truncated: TruncatedMessageAction::Fail,
+///     ..MessageParseOptions::default()
+/// };
+///
+/// assert_eq!(extract_field_by_tag_with_opts(truncated, 2, &MessageParseOptions::default()), Ok(None));
+/// assert_eq!(
+///     extract_field_by_tag_with_opts(truncated, 2, &strict),
+///     Err(WireError::InvalidLength { offset: 3 })
+/// );
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct MessageParseOptions {
+    /// How many groups may be nested inside each other. Deeper groups are handled as configured
+    /// by `on_depth_exceeded`.
+    pub max_depth: u32,
+    /// The size in bytes of the largest message to parse. Larger messages are reported as
+    /// [`WireError::MessageTooLarge`] without being looked at.
+    pub max_message_size: usize,
+    /// What to do with a field with an undefined wire type (6 or 7) or an unmatched end-group tag.
+    pub on_unknown_wire_type: UnknownWireTypeAction,
+    /// What to do with a field that runs past the end of the message or a varint that is too long.
+    pub on_truncated: TruncatedMessageAction,
+    /// What to do with groups nested deeper than `max_depth`.
+    pub on_depth_exceeded: DepthExceededAction,
+}
+
+impl Default for MessageParseOptions {
+    fn default() -> Self {
+        MessageParseOptions {
+            max_depth: utils::MAX_GROUP_DEPTH,
+            max_message_size: usize::MAX,
+            on_unknown_wire_type: UnknownWireTypeAction::Skip,
+            on_truncated: TruncatedMessageAction::Skip,
+            on_depth_exceeded: DepthExceededAction::Skip,
+        }
+    }
+}
+
+/// What to do when a field with an unknown wire type is found.
+///
+/// The length of such a field cannot be known, so parsing can never continue after it: skipping
+/// means ignoring the rest of the message.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum UnknownWireTypeAction {
+    /// Ignore the rest of the message.
+    #[default]
+    Skip,
+    /// Return [`WireError::UnknownWireType`].
+    Fail,
+    /// Ignore the rest of the message, after passing the [`WireError`] to the given function,
+    /// e.g. to log it.
+    Warn(fn(&WireError)),
+}
+
+/// What to do when a truncated field is found.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum TruncatedMessageAction {
+    /// Ignore the rest of the message.
+    #[default]
+    Skip,
+    /// Return the [`WireError`] describing the truncated field.
+    Fail,
+    /// Ignore the rest of the message, after passing the [`WireError`] to the given function,
+    /// e.g. to log it.
+    Warn(fn(&WireError)),
+}
+
+/// What to do when groups are nested deeper than allowed.
+///
+/// The end of a group that is too deep is never looked for, so parsing can never continue after
+/// it: skipping means ignoring the rest of the message.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum DepthExceededAction {
+    /// Ignore the rest of the message.
+    #[default]
+    Skip,
+    /// Return [`WireError::RecursionLimitExceeded`].
+    Fail,
+    /// Ignore the rest of the message, after passing the [`WireError`] to the given function,
+    /// e.g. to log it.
+    Warn(fn(&WireError)),
+}
+
+/// What [`sanitize_message`](crate::sanitize_message) does with fields whose tag number is not
+/// known.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownFieldPolicy {
+    /// Remove unknown fields from the message.
+    #[default]
+    Strip,
+    /// Return [`WireError::UnknownField`] for the first unknown field.
+    Fail,
+    /// Keep unknown fields as they are.
+    PassThrough,
+}
+
+impl MessageParseOptions {
+    /// Decides what to do with an error found while parsing: `Err` to fail, or `Ok` to stop
+    /// parsing without an error.
+    pub(crate) fn handle(&self, err: WireError) -> Result<(), WireError> {
+        let (fail, warn) = match err {
+            WireError::UnknownWireType(_) => match self.on_unknown_wire_type {
+                UnknownWireTypeAction::Skip => (false, None),
+                UnknownWireTypeAction::Fail => (true, None),
+                UnknownWireTypeAction::Warn(warn) => (false, Some(warn)),
+            },
+            WireError::TruncatedMessage { .. }
+            | WireError::VarintOverflow { .. }
+            | WireError::InvalidLength { .. } => match self.on_truncated {
+                TruncatedMessageAction::Skip => (false, None),
+                TruncatedMessageAction::Fail => (true, None),
+                TruncatedMessageAction::Warn(warn) => (false, Some(warn)),
+            },
+            WireError::RecursionLimitExceeded { .. } => match self.on_depth_exceeded {
+                DepthExceededAction::Skip => (false, None),
+                DepthExceededAction::Fail => (true, None),
+                DepthExceededAction::Warn(warn) => (false, Some(warn)),
+            },
+            WireError::MessageTooLarge { .. } | WireError::UnknownField { .. } => (true, None),
+        };
+
+        if fail {
+            return Err(err);
+        }
+        if let Some(warn) = warn {
+            warn(&err);
+        }
+        Ok(())
+    }
+}
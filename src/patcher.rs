@@ -0,0 +1,156 @@
+use crate::{encoders, utils, PatchError};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Stages several field replacements and applies them all at once.
+///
+/// `MessagePatcher` borrows an encoded message and collects replacements with
+/// [`stage`](MessagePatcher::stage) without touching the message. [`commit`](MessagePatcher::commit)
+/// then applies every staged replacement in a single pass, making one copy of the message instead
+/// of one per field as repeated calls to [`replace_field_with`](crate::replace_field_with) would.
+/// [`rollback`](MessagePatcher::rollback), or simply dropping the patcher, discards the staged
+/// replacements and leaves the message unchanged.
+///
+/// As with [`replace_field_payload`](crate::replace_field_payload), each staged payload is only
+/// the new value of the field: the tag and wire type of the field are kept, and the length prefix
+/// of a length-delimited field is updated. Only the first occurrence of each tag number is
+/// replaced.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_by_tag, MessagePatcher};
+///
+/// let mut encoded_message = vec![0x08, 0x01, 0x12, 0x02, 0x68, 0x69];
+///
+/// let mut patcher = MessagePatcher::new(&mut encoded_message);
+/// patcher.stage(1, &[0x02]).stage(2, b"hey");
+/// let old_values = patcher.commit().unwrap();
+///
+/// assert_eq!(old_values, [(1, vec![0x01]), (2, b"hi".to_vec())]);
+/// assert_eq!(encoded_message, b"\x08\x02\x12\x03hey");
+/// assert_eq!(extract_field_by_tag(&encoded_message, 2), Some(&b"hey"[..]));
+/// ```
+#[derive(Debug)]
+pub struct MessagePatcher<'a> {
+    encoded_message: &'a mut Vec<u8>,
+    staged: Vec<(u64, Vec<u8>)>,
+}
+
+impl<'a> MessagePatcher<'a> {
+    /// Creates a patcher for `encoded_message` with nothing staged.
+    pub fn new(encoded_message: &'a mut Vec<u8>) -> Self {
+        MessagePatcher {
+            encoded_message,
+            staged: Vec::new(),
+        }
+    }
+
+    /// Stages a new value for the field with the given tag number.
+    ///
+    /// `payload` is the value without the tag or length prefix, in the same form as the values
+    /// returned by [`extract_field_by_tag`](crate::extract_field_by_tag). A varint value must
+    /// already be varint encoded. Staging the same tag number again replaces the earlier staged
+    /// payload.
+    pub fn stage(&mut self, tag: u64, payload: &[u8]) -> &mut Self {
+        match self
+            .staged
+            .iter_mut()
+            .find(|(staged_tag, _)| *staged_tag == tag)
+        {
+            Some((_, staged_payload)) => *staged_payload = payload.to_vec(),
+            None => self.staged.push((tag, payload.to_vec())),
+        }
+        self
+    }
+
+    /// Applies all staged replacements in a single pass.
+    ///
+    /// Either every staged replacement is applied or none is: the message is left unchanged if it
+    /// cannot be decoded up to the last staged field, if a staged tag number is not present, or
+    /// if a staged payload is not a single value of the field's wire type, e.g. 3 bytes for a
+    /// `fixed32` field or an unterminated varint.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` with the old value of every staged field, in the order the tag numbers were first
+    ///   staged.
+    /// * `Err(PatchError::Malformed(_))` if the message cannot be decoded before every staged
+    ///   field is found.
+    /// * `Err(PatchError::FieldNotFound { tag })` if no field has the staged tag number `tag`.
+    /// * `Err(PatchError::InvalidPayload { tag, variant })` if the payload staged for `tag` does
+    ///   not fit the field's wire type.
+    pub fn commit(self) -> Result<Vec<(u64, Vec<u8>)>, PatchError> {
+        let encoded_message = self.encoded_message;
+        let mut old_values = vec![None; self.staged.len()];
+        // The field record and index into `staged` of every field to replace, in message order.
+        let mut sites = Vec::new();
+
+        let mut offset = 0;
+        while sites.len() < self.staged.len() {
+            let field = match utils::read_field_checked(encoded_message, offset)
+                .map_err(PatchError::Malformed)?
+            {
+                Some(field) => field,
+                None => break,
+            };
+            let index = self
+                .staged
+                .iter()
+                .position(|(tag, _)| *tag == field.field_number);
+            if let Some(index) = index {
+                if old_values[index].is_none() {
+                    let (tag, payload) = &self.staged[index];
+                    if !utils::value_fits(payload, field.variant) {
+                        return Err(PatchError::InvalidPayload {
+                            tag: *tag,
+                            variant: field.variant,
+                        });
+                    }
+                    old_values[index] =
+                        Some(encoded_message[field.value_start..field.end].to_vec());
+                    sites.push((field, index));
+                }
+            }
+            offset = field.end;
+        }
+
+        let old_values = self
+            .staged
+            .iter()
+            .zip(old_values)
+            .map(|((tag, _), old_value)| {
+                old_value
+                    .map(|old_value| (*tag, old_value))
+                    .ok_or(PatchError::FieldNotFound { tag: *tag })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if !sites.is_empty() {
+            let mut patched = Vec::with_capacity(encoded_message.len());
+            let mut copied = 0;
+            let mut header = [0; 20];
+            for (field, index) in sites {
+                let payload = &self.staged[index].1;
+                let header_len = encoders::encode_header_to_slice(
+                    field.field_number,
+                    field.variant,
+                    payload.len(),
+                    &mut header,
+                )
+                .expect("a field header is at most 20 bytes");
+                patched.extend_from_slice(&encoded_message[copied..field.start]);
+                patched.extend_from_slice(&header[..header_len]);
+                patched.extend_from_slice(payload);
+                copied = field.end;
+            }
+            patched.extend_from_slice(&encoded_message[copied..]);
+            *encoded_message = patched;
+        }
+
+        Ok(old_values)
+    }
+
+    /// Discards all staged replacements, leaving the message unchanged.
+    pub fn rollback(self) {}
+}
@@ -0,0 +1,477 @@
+use crate::utils::GroupRecord;
+use crate::{decoders, utils, validate_message, Variant, WireError};
+use alloc::string::String;
+use alloc::vec;
+use core::fmt::Write;
+
+/// Formats an encoded protobuf message as text, one field per line, similar to
+/// `protoc --decode_raw`.
+///
+/// Each line has the form `<field_number> (<wire type>): <value>`, where the value is shown as:
+/// - the decoded integer for varint fields,
+/// - the length followed by the first 16 bytes in hex for length-delimited fields, with `...` if
+///   there are more,
+/// - the bytes in hex for 64-bit and 32-bit fields.
+///
+/// A group is shown like a length-delimited field with the wire type `group`, its contents
+/// standing in for the value. [`pretty_print_wire_format_recursive`] also shows the fields inside
+/// it.
+///
+/// If a field cannot be decoded, a final `error: ...` line describes why and the rest of the
+/// message is not shown.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::pretty_print_wire_format;
+///
+/// let encoded_message = b"\x08\x96\x01\x12\x02\x68\x69\x1d\x07\x00\x00\x00";
+/// assert_eq!(
+///     pretty_print_wire_format(encoded_message),
+///     "1 (varint): 150\n2 (length-delimited): 2 bytes: 68 69\n3 (32-bit): 07 00 00 00\n"
+/// );
+/// ```
+pub fn pretty_print_wire_format(encoded_message: &[u8]) -> String {
+    pretty_print_wire_format_recursive(encoded_message, 0)
+}
+
+/// Formats an encoded protobuf message as text like [`pretty_print_wire_format`], also showing the
+/// fields of nested messages.
+///
+/// A non-empty length-delimited field whose payload passes [`validate_message`] is shown as a
+/// nested message: its fields follow on the next lines, indented by two spaces per level, up to
+/// `max_depth` levels deep. Without a schema this is a guess, so short strings or bytes that
+/// happen to be valid wire format are shown as nested messages too. The fields inside groups are
+/// always shown the same way, since they are known to be fields.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::pretty_print_wire_format_recursive;
+///
+/// let encoded_message = b"\x0a\x02\x08\x01";
+/// assert_eq!(
+///     pretty_print_wire_format_recursive(encoded_message, 1),
+///     "1 (length-delimited): 2 bytes: 08 01\n  1 (varint): 1\n"
+/// );
+/// ```
+pub fn pretty_print_wire_format_recursive(encoded_message: &[u8], max_depth: u32) -> String {
+    let mut out = String::new();
+    write_fields(&mut out, encoded_message, max_depth, 0);
+    out
+}
+
+/// Options for [`dump_message_as_text_with_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DumpOptions {
+    /// How many levels of nested messages are expanded. At 0, nested messages are shown as bytes.
+    pub max_depth: u32,
+    /// The maximum number of characters of a string, or bytes of a bytes value, that are shown.
+    /// Longer values are cut off and followed by `...`.
+    pub max_string_len: usize,
+    /// Whether bytes values are shown in hex, e.g. `0x01ff`, rather than as escaped strings, e.g.
+    /// `"\x01\xff"`.
+    pub hex_bytes: bool,
+}
+
+impl Default for DumpOptions {
+    /// Shows up to 10 levels of nested messages and 256 characters per string, with bytes values
+    /// in hex.
+    fn default() -> Self {
+        DumpOptions {
+            max_depth: 10,
+            max_string_len: 256,
+            hex_bytes: true,
+        }
+    }
+}
+
+/// Formats an encoded protobuf message as text similar to the protobuf text format.
+///
+/// This uses the default [`DumpOptions`]; see [`dump_message_as_text_with_options`] for the
+/// format.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::dump_message_as_text;
+///
+/// let encoded_message = b"\x0a\x05hello\x10\x2a\x1a\x02\x08\x63";
+/// assert_eq!(dump_message_as_text(encoded_message), "1: \"hello\"\n2: 42\n3: { 1: 99 }\n");
+/// ```
+pub fn dump_message_as_text(encoded_message: &[u8]) -> String {
+    dump_message_as_text_with_options(encoded_message, &DumpOptions::default())
+}
+
+/// Formats an encoded protobuf message as text similar to the protobuf text format, with
+/// [`DumpOptions`] controlling the output.
+///
+/// Each field of the message is shown on its own line as `<field_number>: <value>`. Without a
+/// schema the type of a value is guessed from its wire type:
+/// - varint fields are shown as unsigned integers,
+/// - 64-bit and 32-bit fields are shown as hex integers,
+/// - length-delimited fields are shown as a quoted string if they are printable UTF-8, otherwise
+///   as a nested message `{ ... }` if they pass [`validate_message`], and otherwise as bytes,
+/// - groups are shown as a nested message `{ ... }`, or as bytes past `max_depth`.
+///
+/// The fields of nested messages are shown on the same line, separated by spaces. The output is
+/// meant to be read, not parsed back. If a field cannot be decoded, a final `error: ...` line
+/// describes why.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{dump_message_as_text_with_options, DumpOptions};
+///
+/// let encoded_message = b"\x0a\x02\x08\x63\x12\x02\x00\xff";
+/// let options = DumpOptions {
+///     max_depth: 0,
+///     hex_bytes: false,
+///     ..DumpOptions::default()
+/// };
+/// assert_eq!(
+///     dump_message_as_text_with_options(encoded_message, &options),
+///     "1: \"\\x08c\"\n2: \"\\x00\\xff\"\n"
+/// );
+/// ```
+pub fn dump_message_as_text_with_options(encoded_message: &[u8], options: &DumpOptions) -> String {
+    let mut out = String::new();
+    write_text_fields(&mut out, encoded_message, options, 0);
+    out
+}
+
+/// Writes the fields of a message as text, one per line at the top level and separated by spaces
+/// in nested messages.
+fn write_text_fields(out: &mut String, encoded_message: &[u8], options: &DumpOptions, depth: u32) {
+    let mut offset = 0;
+    while offset < encoded_message.len() {
+        let group = match read_group(encoded_message, offset) {
+            Ok(group) => group,
+            Err(err) => {
+                let _ = writeln!(out, "error: {}", err);
+                return;
+            }
+        };
+        if depth > 0 && offset > 0 {
+            out.push(' ');
+        }
+        if let Some(group) = group {
+            let _ = write!(out, "{}: ", group.field_number);
+            let contents = &encoded_message[group.contents_start..group.contents_end];
+            if contents.is_empty() {
+                out.push_str("{}");
+            } else if depth < options.max_depth {
+                out.push_str("{ ");
+                write_text_fields(out, contents, options, depth + 1);
+                out.push_str(" }");
+            } else {
+                write_text_bytes(out, contents, options);
+            }
+            if depth == 0 {
+                out.push('\n');
+            }
+            offset = group.end;
+            continue;
+        }
+
+        let field = match utils::read_record_checked(encoded_message, offset) {
+            Ok(field) => field,
+            Err(err) => {
+                let _ = writeln!(out, "error: {}", err);
+                return;
+            }
+        };
+        let value = &encoded_message[field.value_start..field.end];
+
+        let _ = write!(out, "{}: ", field.field_number);
+        match field.variant {
+            Variant::Varint => {
+                let (decoded, _) = decoders::decode_varint(value, 0).unwrap_or_default();
+                let _ = write!(out, "{}", decoded);
+            }
+            Variant::SixtyFourBit => {
+                let _ = write!(
+                    out,
+                    "{:#018x}",
+                    u64::from_le_bytes(value.try_into().unwrap_or_default())
+                );
+            }
+            Variant::ThirtyTwoBit => {
+                let _ = write!(
+                    out,
+                    "{:#010x}",
+                    u32::from_le_bytes(value.try_into().unwrap_or_default())
+                );
+            }
+            Variant::LengthDelimited => write_text_value(out, value, options, depth),
+        }
+        if depth == 0 {
+            out.push('\n');
+        }
+
+        offset = field.end;
+    }
+}
+
+/// Writes a length-delimited value as a string, a nested message or bytes.
+fn write_text_value(out: &mut String, value: &[u8], options: &DumpOptions, depth: u32) {
+    let text = core::str::from_utf8(value).ok().filter(|text| {
+        text.chars()
+            .all(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+    });
+
+    if let Some(text) = text {
+        let end = text
+            .char_indices()
+            .nth(options.max_string_len)
+            .map_or(text.len(), |(end, _)| end);
+        let _ = write!(out, "{:?}", &text[..end]);
+        if end < text.len() {
+            out.push_str("...");
+        }
+        return;
+    }
+
+    if depth < options.max_depth && !value.is_empty() && validate_message(value).is_ok() {
+        out.push_str("{ ");
+        write_text_fields(out, value, options, depth + 1);
+        out.push_str(" }");
+        return;
+    }
+
+    write_text_bytes(out, value, options);
+}
+
+/// Writes a value as bytes, in hex or as an escaped string.
+fn write_text_bytes(out: &mut String, value: &[u8], options: &DumpOptions) {
+    let shown = &value[..value.len().min(options.max_string_len)];
+    if options.hex_bytes {
+        out.push_str("0x");
+        for byte in shown {
+            let _ = write!(out, "{:02x}", byte);
+        }
+    } else {
+        out.push('"');
+        for byte in shown {
+            let _ = write!(out, "{}", core::ascii::escape_default(*byte));
+        }
+        out.push('"');
+    }
+    if shown.len() < value.len() {
+        out.push_str("...");
+    }
+}
+
+/// Formats an encoded protobuf message as an annotated hex dump.
+///
+/// The bytes of each field are shown on their own lines, 16 bytes per line, with the offset of the
+/// first byte of the line in hex on the left. The first line of each field is annotated with its
+/// tag number and wire type, and for length-delimited fields the length of the value. A
+/// length-delimited value that passes [`validate_message`] is also annotated as a
+/// `(nested message)`; like in [`pretty_print_wire_format_recursive`], this is only a guess. A
+/// group is shown as a whole, annotated with its tag number and the length of its contents.
+///
+/// If a field cannot be decoded, the remaining bytes are shown with an `error: ...` annotation.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::message_to_hex_dump;
+///
+/// let encoded_message = b"\x08\x96\x01\x12\x05hello";
+/// let dump = message_to_hex_dump(encoded_message);
+/// let lines: Vec<&str> = dump.lines().collect();
+///
+/// assert_eq!(lines[0], "00000000  08 96 01                                         tag 1 (varint)");
+/// assert_eq!(lines[1], "00000003  12 05 68 65 6c 6c 6f                             tag 2 (length-delimited, 5 bytes)");
+/// ```
+pub fn message_to_hex_dump(encoded_message: &[u8]) -> String {
+    let mut out = String::new();
+    let mut offset = 0;
+    while offset < encoded_message.len() {
+        let (end, annotation) = match annotate_field(encoded_message, offset) {
+            Ok(annotated) => annotated,
+            Err(err) => {
+                let mut annotation = String::new();
+                let _ = write!(annotation, "error: {}", err);
+                (encoded_message.len(), annotation)
+            }
+        };
+        write_dump_lines(&mut out, encoded_message, offset, end, &annotation);
+        offset = end;
+    }
+    out
+}
+
+/// Returns the offset after the field or group starting at `offset` and its annotation in
+/// [`message_to_hex_dump`].
+fn annotate_field(encoded_message: &[u8], offset: usize) -> Result<(usize, String), WireError> {
+    let mut annotation = String::new();
+    if let Some(group) = read_group(encoded_message, offset)? {
+        let _ = write!(
+            annotation,
+            "tag {} (group, {} bytes)",
+            group.field_number,
+            group.contents_end - group.contents_start
+        );
+        return Ok((group.end, annotation));
+    }
+
+    let field = utils::read_record_checked(encoded_message, offset)?;
+    let value = &encoded_message[field.value_start..field.end];
+    let _ = write!(annotation, "tag {} ({}", field.field_number, field.variant);
+    if field.variant == Variant::LengthDelimited {
+        let _ = write!(annotation, ", {} bytes", value.len());
+    }
+    annotation.push(')');
+    if field.variant == Variant::LengthDelimited
+        && !value.is_empty()
+        && validate_message(value).is_ok()
+    {
+        annotation.push_str(" (nested message)");
+    }
+    Ok((field.end, annotation))
+}
+
+/// The number of bytes per line of [`message_to_hex_dump`].
+const DUMP_LINE_LEN: usize = 16;
+
+/// Writes `encoded_message[start..end]` as hex dump lines, annotating the first line.
+fn write_dump_lines(
+    out: &mut String,
+    encoded_message: &[u8],
+    start: usize,
+    end: usize,
+    annotation: &str,
+) {
+    for (i, line) in encoded_message[start..end]
+        .chunks(DUMP_LINE_LEN)
+        .enumerate()
+    {
+        let _ = write!(out, "{:08x}  ", start + i * DUMP_LINE_LEN);
+        let mut hex = String::new();
+        write_hex(&mut hex, line);
+        if i == 0 {
+            let _ = write!(
+                out,
+                "{:width$}  {}",
+                hex,
+                annotation,
+                width = DUMP_LINE_LEN * 3 - 1
+            );
+        } else {
+            out.push_str(&hex);
+        }
+        out.push('\n');
+    }
+}
+
+/// The number of bytes of a length-delimited field shown in hex.
+const PREVIEW_LEN: usize = 16;
+
+/// Writes the fields of `encoded_message` and, up to `max_depth` levels deep, of the messages
+/// nested inside it.
+///
+/// The messages being written are kept on a heap-allocated stack instead of recursing, each with
+/// the offset of its next field, the depth left below it and its indentation.
+fn write_fields(out: &mut String, encoded_message: &[u8], max_depth: u32, indent: usize) {
+    let mut messages = vec![(encoded_message, 0, max_depth, indent)];
+    while let Some((encoded_message, offset, max_depth, indent)) = messages.pop() {
+        if offset >= encoded_message.len() {
+            continue;
+        }
+        let group = match read_group(encoded_message, offset) {
+            Ok(group) => group,
+            Err(err) => {
+                let _ = writeln!(out, "{:indent$}error: {}", "", err, indent = indent);
+                continue;
+            }
+        };
+        if let Some(group) = group {
+            let contents = &encoded_message[group.contents_start..group.contents_end];
+            let _ = write!(
+                out,
+                "{:indent$}{} (group): ",
+                "",
+                group.field_number,
+                indent = indent
+            );
+            write_preview(out, contents);
+            out.push('\n');
+
+            messages.push((encoded_message, group.end, max_depth, indent));
+            if max_depth > 0 {
+                messages.push((contents, 0, max_depth - 1, indent + 2));
+            }
+            continue;
+        }
+
+        let field = match utils::read_record_checked(encoded_message, offset) {
+            Ok(field) => field,
+            Err(err) => {
+                // Writing to a `String` never fails.
+                let _ = writeln!(out, "{:indent$}error: {}", "", err, indent = indent);
+                continue;
+            }
+        };
+        let value = &encoded_message[field.value_start..field.end];
+
+        let _ = write!(
+            out,
+            "{:indent$}{} ({}): ",
+            "",
+            field.field_number,
+            field.variant,
+            indent = indent
+        );
+        match field.variant {
+            Variant::Varint => {
+                let (decoded, _) = decoders::decode_varint(value, 0).unwrap_or_default();
+                let _ = write!(out, "{}", decoded);
+            }
+            Variant::LengthDelimited => write_preview(out, value),
+            Variant::SixtyFourBit | Variant::ThirtyTwoBit => write_hex(out, value),
+        }
+        out.push('\n');
+
+        messages.push((encoded_message, field.end, max_depth, indent));
+        if max_depth > 0
+            && field.variant == Variant::LengthDelimited
+            && !value.is_empty()
+            && validate_message(value).is_ok()
+        {
+            messages.push((value, 0, max_depth - 1, indent + 2));
+        }
+    }
+}
+
+/// Writes the length of `value` followed by its first bytes in hex.
+fn write_preview(out: &mut String, value: &[u8]) {
+    let _ = write!(out, "{} bytes", value.len());
+    if !value.is_empty() {
+        out.push_str(": ");
+        write_hex(out, &value[..value.len().min(PREVIEW_LEN)]);
+    }
+    if value.len() > PREVIEW_LEN {
+        out.push_str(" ...");
+    }
+}
+
+/// Decodes the group starting at `offset`, or returns `None` if no group starts there.
+fn read_group(encoded_message: &[u8], offset: usize) -> Result<Option<GroupRecord>, WireError> {
+    let (tag, _) = decoders::decode_varint_checked(encoded_message, offset)?;
+    if decoders::wire_type_from_tag(tag) != 3 {
+        return Ok(None);
+    }
+    utils::read_group_checked(encoded_message, offset, utils::MAX_GROUP_DEPTH).map(Some)
+}
+
+/// Writes `bytes` as space-separated hex pairs.
+fn write_hex(out: &mut String, bytes: &[u8]) {
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let _ = write!(out, "{:02x}", byte);
+    }
+}
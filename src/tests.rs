@@ -1,7 +1,51 @@
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
+#[allow(clippy::module_inception)]
 mod tests {
     use crate::{
-        create_header, extract_field_by_tag, extract_multiple_fields_by_tag, replace_field_with,
+        append_field, append_field_generic, apply_diff, batch_extract_to_slice,
+        canonicalize_message, collect_to_message, copy_fields_filtered, count_field_occurrences,
+        count_unknown_fields, create_header, decode_any, decode_duration, decode_grpc_frame,
+        decode_length_delimited, decode_packed_sint32, decode_packed_sint64, decode_timestamp,
+        decode_varint_from_slice, dedup_first_wins, dedup_last_wins, diff_messages,
+        dump_message_as_text, dump_message_as_text_with_options, encode_any, encode_bool,
+        encode_double, encode_duration, encode_duration_field, encode_field, encode_fixed32,
+        encode_fixed64, encode_float, encode_grpc_frame, encode_int32, encode_int64,
+        encode_map_entry, encode_message_deterministically, encode_packed_double,
+        encode_packed_float, encode_packed_sint32, encode_packed_sint64, encode_packed_varint,
+        encode_sfixed32, encode_sfixed64, encode_sint32, encode_sint64, encode_string_field,
+        encode_tag, encode_timestamp, encode_timestamp_field, encode_varint, encode_varint_array,
+        encode_varint_exact, encode_varint_field, encode_with_length_prefix, encoded_varint_len,
+        estimate_field_count, estimate_nesting_depth, estimate_replacement_size,
+        estimate_total_field_count_recursive, extract_all_occurrences_by_tag, extract_any_type_url,
+        extract_field_as_bool, extract_field_as_enum, extract_field_as_enum_raw,
+        extract_field_as_fixed32, extract_field_as_fixed64, extract_field_as_i64,
+        extract_field_as_sfixed32, extract_field_as_sfixed64, extract_field_as_sint32,
+        extract_field_as_sint64, extract_field_as_str, extract_field_as_str_lossy,
+        extract_field_as_u64, extract_field_at_index, extract_field_by_path, extract_field_by_tag,
+        extract_field_by_tag_checked, extract_field_by_tag_from, extract_field_by_tag_hinted,
+        extract_field_by_tag_with_opts, extract_field_byte_range, extract_field_generic,
+        extract_field_view, extract_field_with_full_record, extract_field_with_length_prefix,
+        extract_full_field_byte_range, extract_last_n_fields, extract_map_entries,
+        extract_multiple_fields_by_tag, extract_multiple_fields_early_exit, extract_packed_double,
+        extract_packed_float, extract_packed_varint, extract_repeated_strings, field_equal,
+        field_number_from_tag, find_field_tag_offset, fold_fields, get_or_insert_field,
+        iter_varint_length_messages, keep_only_fields, map_fields, measure_encoded_varint_size,
+        merge_messages, merge_messages_last_wins, message_has_field, message_to_hex_dump,
+        messages_equal, oneof_conflict_present, oneof_detected_tag, packed_to_unpacked,
+        parse_field_header, prepend_field, prepend_varint_length, pretty_print_wire_format,
+        pretty_print_wire_format_recursive, replace_field_at_index, replace_field_by_path,
+        replace_field_in_nested, replace_field_payload, replace_field_with, replace_field_with_fn,
+        replace_field_with_fn_result, replace_field_with_two_pass, replace_multiple_fields,
+        retag_all_fields, retag_field, rewrite_varint_in_place, sanitize_message,
+        split_grpc_stream, split_message_at_tag, split_varint_length_stream, strip_fields,
+        strip_length_prefix, strip_unknown_fields, strip_varint_length, transform_field,
+        transform_field_fixed, unpacked_to_packed, validate_message, validate_message_recursive,
+        validate_strict, validate_strict_with_config, wire_type_from_tag, wire_type_name,
+        DepthExceededAction, DumpOptions, FieldChange, FieldHeader, FieldIterator, FieldView,
+        MessageBuilder, MessageBuilderFixed, MessageCursor, MessageIndex, MessageParseOptions,
+        MessagePatcher, MessageTemplate, MessageView, PatchError, ProtoEncode, ProtoExtract,
+        RewriteError, SharedMessageView, StrictValidationConfig, StrictValidationError,
+        TruncatedMessageAction, UnknownFieldPolicy, UnknownWireTypeAction, Variant, WireError,
     };
     use prost::Message;
 
@@ -384,7 +428,7 @@ mod tests {
         assert_eq!(fields[0].0, 1);
 
         let enc_foo = fields[0].1;
-        let fields = extract_multiple_fields_by_tag(&enc_foo, &[1, 2]);
+        let fields = extract_multiple_fields_by_tag(enc_foo, &[1, 2]);
         assert_eq!(fields.len(), 2);
         assert_eq!(fields[0].0, 1);
         assert_eq!(fields[0].1, b"Me");
@@ -541,4 +585,5252 @@ mod tests {
         let expected = vec![(1 << 3) | 2, 5];
         assert_eq!(header, expected);
     }
+
+    /// Test that an existing field is returned without modifying the message.
+    #[test]
+    fn test_get_or_insert_existing_field() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(string, tag = "1")]
+            bar: ::prost::alloc::string::String,
+            #[prost(uint64, tag = "2")]
+            baz: u64,
+        }
+
+        let foo = Foo {
+            bar: "Me".to_string(),
+            baz: 42,
+        };
+        let mut enc = foo.encode_to_vec();
+        let original = enc.clone();
+
+        let bar = get_or_insert_field(&mut enc, 1, Variant::LengthDelimited, b"You");
+        assert_eq!(bar, b"Me");
+        assert_eq!(enc, original);
+    }
+
+    /// Test that a missing field is inserted and visible to prost.
+    #[test]
+    fn test_get_or_insert_missing_field() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(string, tag = "1")]
+            bar: ::prost::alloc::string::String,
+            #[prost(uint64, tag = "2")]
+            baz: u64,
+        }
+
+        let foo = Foo {
+            bar: "Me".to_string(),
+            baz: 0,
+        };
+        let mut enc = foo.encode_to_vec();
+
+        let baz = get_or_insert_field(&mut enc, 2, Variant::Varint, &[0x2A]);
+        assert_eq!(baz, b"\x2A");
+
+        let decoded = Foo::decode(enc.as_slice()).unwrap();
+        assert_eq!(decoded.bar, "Me");
+        assert_eq!(decoded.baz, 42);
+    }
+
+    /// Test extracting several fields into a pre-allocated slice.
+    #[test]
+    fn test_batch_extract_to_slice() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(string, tag = "1")]
+            bar: ::prost::alloc::string::String,
+            #[prost(uint64, tag = "2")]
+            baz: u64,
+            #[prost(uint64, repeated, tag = "3")]
+            qux: ::prost::alloc::vec::Vec<u64>,
+        }
+
+        let foo = Foo {
+            bar: "Me".to_string(),
+            baz: 42,
+            qux: vec![1, 2],
+        };
+        let enc = foo.encode_to_vec();
+
+        let mut out = [None; 4];
+        let found = batch_extract_to_slice(&enc, &[2, 1, 4, 3], &mut out);
+        assert_eq!(found, 3);
+        assert_eq!(out[0], Some(&b"\x2A"[..]));
+        assert_eq!(out[1], Some(&b"Me"[..]));
+        assert_eq!(out[2], None);
+        assert_eq!(out[3], Some(&b"\x01\x02"[..]));
+    }
+
+    /// Test building a message with a fixed-capacity builder and decoding it with prost.
+    #[test]
+    fn test_message_builder_fixed() {
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: ::prost::alloc::string::String,
+            #[prost(double, tag = "3")]
+            qux: f64,
+            #[prost(fixed32, tag = "4")]
+            quux: u32,
+        }
+
+        let mut builder = MessageBuilderFixed::<64>::new();
+        builder
+            .add_varint(1, 300)
+            .unwrap()
+            .add_string(2, "Me")
+            .unwrap()
+            .add_double(3, 1.5)
+            .unwrap()
+            .add_fixed32(4, 7)
+            .unwrap();
+
+        let expected = Foo {
+            bar: 300,
+            baz: "Me".to_string(),
+            qux: 1.5,
+            quux: 7,
+        };
+        assert_eq!(builder.build(), expected.encode_to_vec());
+    }
+
+    /// Test extracting the last fields of a message with more fields than requested.
+    #[test]
+    fn test_extract_last_n_fields() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(string, tag = "1")]
+            bar: ::prost::alloc::string::String,
+            #[prost(uint64, repeated, packed = "false", tag = "2")]
+            baz: ::prost::alloc::vec::Vec<u64>,
+            #[prost(uint64, tag = "3")]
+            qux: u64,
+        }
+
+        let foo = Foo {
+            bar: "Me".to_string(),
+            baz: vec![42, 43],
+            qux: 44,
+        };
+        let enc = foo.encode_to_vec();
+
+        let fields = extract_last_n_fields(&enc, 2).unwrap();
+        assert_eq!(fields, vec![(2, &b"\x2B"[..]), (3, &b"\x2C"[..])]);
+
+        let fields = extract_last_n_fields(&enc, 10).unwrap();
+        assert_eq!(fields.len(), 4);
+        assert_eq!(fields[0], (1, &b"Me"[..]));
+
+        assert!(extract_last_n_fields(&enc, 0).unwrap().is_empty());
+        assert_eq!(
+            extract_last_n_fields(&enc, usize::MAX),
+            extract_last_n_fields(&enc, 10)
+        );
+        // The value of the last field is missing.
+        assert_eq!(
+            extract_last_n_fields(&enc[..enc.len() - 1], 2),
+            Err(WireError::TruncatedMessage {
+                offset: enc.len() - 1
+            })
+        );
+    }
+
+    /// Test rewriting a varint field in place.
+    #[test]
+    fn test_rewrite_varint_in_place() {
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: ::prost::alloc::string::String,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: "Me".to_string(),
+        };
+        let mut enc = foo.encode_to_vec();
+
+        rewrite_varint_in_place(&mut enc, 1, 16383).unwrap();
+        let decoded = Foo::decode(enc.as_slice()).unwrap();
+        assert_eq!(decoded.bar, 16383);
+        assert_eq!(decoded.baz, "Me");
+
+        assert_eq!(
+            rewrite_varint_in_place(&mut enc, 1, 16384),
+            Err(RewriteError::EncodedLengthChanged { old: 2, new: 3 })
+        );
+        assert_eq!(
+            rewrite_varint_in_place(&mut enc, 2, 1),
+            Err(RewriteError::NotVarint { wire_type: 2 })
+        );
+        assert_eq!(
+            rewrite_varint_in_place(&mut enc, 3, 1),
+            Err(RewriteError::FieldNotFound)
+        );
+    }
+
+    /// Test strict validation of valid and malformed messages.
+    #[test]
+    fn test_validate_strict() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(string, tag = "1")]
+            bar: ::prost::alloc::string::String,
+            #[prost(double, tag = "2")]
+            baz: f64,
+            #[prost(fixed32, tag = "536870911")]
+            qux: u32,
+        }
+
+        let foo = Foo {
+            bar: "Me".to_string(),
+            baz: 1.5,
+            qux: 7,
+        };
+        let enc = foo.encode_to_vec();
+        assert_eq!(validate_strict(&enc), Ok(()));
+
+        assert_eq!(
+            validate_strict(&enc[..enc.len() - 1]),
+            Err(StrictValidationError::TruncatedMessage { offset: 13 })
+        );
+        assert_eq!(
+            validate_strict(b"\x08\x01\x0e\x01"),
+            Err(StrictValidationError::UnsupportedWireType {
+                offset: 2,
+                wire_type: 6
+            })
+        );
+        assert_eq!(
+            validate_strict(b"\x00\x01"),
+            Err(StrictValidationError::InvalidFieldNumber {
+                offset: 0,
+                field_number: 0
+            })
+        );
+        assert_eq!(
+            validate_strict(b"\x08\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x01"),
+            Err(StrictValidationError::VarintOverflow { offset: 1 })
+        );
+    }
+
+    /// Test that groups are only accepted when configured and must be balanced.
+    #[test]
+    fn test_validate_strict_groups() {
+        let allow = StrictValidationConfig { allow_groups: true };
+
+        let group = b"\x0b\x10\x01\x0c\x18\x02";
+        assert_eq!(
+            validate_strict(group),
+            Err(StrictValidationError::UnsupportedWireType {
+                offset: 0,
+                wire_type: 3
+            })
+        );
+        assert_eq!(validate_strict_with_config(group, &allow), Ok(()));
+
+        assert_eq!(
+            validate_strict_with_config(b"\x0b\x10\x01\x14", &allow),
+            Err(StrictValidationError::UnmatchedEndGroup {
+                offset: 3,
+                field_number: 2
+            })
+        );
+        assert_eq!(
+            validate_strict_with_config(b"\x0b\x10\x01", &allow),
+            Err(StrictValidationError::UnterminatedGroup { field_number: 1 })
+        );
+    }
+
+    /// Test that deterministic encoding ignores field order and default values.
+    #[test]
+    fn test_encode_message_deterministically() {
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: ::prost::alloc::string::String,
+            #[prost(double, tag = "3")]
+            qux: f64,
+            #[prost(uint64, repeated, packed = "false", tag = "4")]
+            quux: ::prost::alloc::vec::Vec<u64>,
+        }
+
+        let zero = 0.0f64.to_le_bytes();
+        let one = 1.5f64.to_le_bytes();
+        let shuffled = encode_message_deterministically(
+            &[
+                (4, Variant::Varint, &[0x01]),
+                (3, Variant::SixtyFourBit, &zero),
+                (2, Variant::LengthDelimited, b""),
+                (4, Variant::Varint, &[0x00]),
+                (1, Variant::Varint, &[0x81, 0x00]),
+                (2, Variant::LengthDelimited, b"Me"),
+                (4, Variant::Varint, &[0x02]),
+            ],
+            &[1, 2, 3],
+        );
+        let ordered = encode_message_deterministically(
+            &[
+                (1, Variant::Varint, &[0x01]),
+                (2, Variant::LengthDelimited, b"Me"),
+                (4, Variant::Varint, &[0x01]),
+                (4, Variant::Varint, &[0x00]),
+                (4, Variant::Varint, &[0x02]),
+            ],
+            &[1, 2, 3],
+        );
+        assert_eq!(shuffled, ordered);
+
+        let expected = Foo {
+            bar: 1,
+            baz: "Me".to_string(),
+            qux: 0.0,
+            quux: vec![1, 0, 2],
+        };
+        assert_eq!(shuffled, expected.encode_to_vec());
+
+        let with_double =
+            encode_message_deterministically(&[(3, Variant::SixtyFourBit, &one)], &[1, 2, 3]);
+        assert_eq!(Foo::decode(with_double.as_slice()).unwrap().qux, 1.5);
+
+        // The last occurrence of a singular field wins, even if it holds the default value.
+        let overwritten = encode_message_deterministically(
+            &[
+                (2, Variant::LengthDelimited, b"Me"),
+                (2, Variant::LengthDelimited, b""),
+            ],
+            &[2],
+        );
+        assert!(overwritten.is_empty());
+
+        // Without singular tags, nothing is removed.
+        assert_eq!(
+            encode_message_deterministically(
+                &[(1, Variant::Varint, &[0x01]), (1, Variant::Varint, &[0x00])],
+                &[]
+            ),
+            [0x08, 0x01, 0x08, 0x00]
+        );
+
+        // Varint payloads with trailing bytes are kept as they are.
+        assert_eq!(
+            encode_message_deterministically(&[(1, Variant::Varint, &[0x00, 0x01])], &[1]),
+            [0x08, 0x00, 0x01]
+        );
+    }
+
+    /// Test copying a filtered subset of fields into another message.
+    #[test]
+    fn test_copy_fields_filtered() {
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(string, tag = "1")]
+            bar: ::prost::alloc::string::String,
+            #[prost(uint64, tag = "2")]
+            baz: u64,
+            #[prost(uint64, repeated, tag = "12")]
+            qux: ::prost::alloc::vec::Vec<u64>,
+        }
+
+        let foo = Foo {
+            bar: "Me".to_string(),
+            baz: 42,
+            qux: vec![1, 2],
+        };
+        let enc = foo.encode_to_vec();
+
+        let mut dst = Vec::new();
+        let copied = copy_fields_filtered(&enc, &mut dst, |tag, _, _| tag < 10);
+        assert_eq!(copied, Ok(2));
+        let decoded = Foo::decode(dst.as_slice()).unwrap();
+        assert_eq!(decoded.bar, "Me");
+        assert_eq!(decoded.baz, 42);
+        assert!(decoded.qux.is_empty());
+
+        let mut dst = Vec::new();
+        let copied = copy_fields_filtered(&enc, &mut dst, |_, variant, value| {
+            variant == Variant::LengthDelimited && value.len() == 2
+        });
+        assert_eq!(copied, Ok(2));
+
+        let mut dst = vec![0x08, 0x01];
+        let copied = copy_fields_filtered(&enc[..enc.len() - 1], &mut dst, |_, _, _| true);
+        // The length prefix of the packed field, right before its two elements, is too long.
+        assert_eq!(
+            copied,
+            Err(WireError::InvalidLength {
+                offset: enc.len() - 3
+            })
+        );
+        assert_eq!(dst, [0x08, 0x01]);
+    }
+
+    /// Test that debug comments are ignored by prost and removed by `strip_debug_annotations`.
+    #[cfg(feature = "debug-comments")]
+    #[test]
+    fn test_strip_debug_annotations() {
+        use crate::strip_debug_annotations;
+
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: ::prost::alloc::string::String,
+        }
+
+        let mut builder = MessageBuilderFixed::<64>::new();
+        builder
+            .add_unknown_comment("first")
+            .unwrap()
+            .add_varint(1, 42)
+            .unwrap()
+            .add_unknown_comment("second")
+            .unwrap()
+            .add_string(2, "Me")
+            .unwrap();
+        let mut enc = builder.build().to_vec();
+
+        let expected = Foo {
+            bar: 42,
+            baz: "Me".to_string(),
+        };
+        assert_eq!(Foo::decode(enc.as_slice()).unwrap(), expected);
+
+        strip_debug_annotations(&mut enc);
+        assert_eq!(enc, expected.encode_to_vec());
+    }
+
+    /// Test decoding varint fields to integers.
+    #[test]
+    fn test_extract_field_as_integer() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(int64, tag = "2")]
+            baz: i64,
+            #[prost(int32, tag = "3")]
+            qux: i32,
+            #[prost(string, tag = "4")]
+            quux: ::prost::alloc::string::String,
+        }
+
+        let foo = Foo {
+            bar: u64::MAX,
+            baz: i64::MIN,
+            qux: -42,
+            quux: "Me".to_string(),
+        };
+        let enc = foo.encode_to_vec();
+
+        assert_eq!(extract_field_as_u64(&enc, 1), Some(u64::MAX));
+        assert_eq!(extract_field_as_i64(&enc, 2), Some(i64::MIN));
+        assert_eq!(extract_field_as_i64(&enc, 3), Some(-42));
+        assert_eq!(extract_field_as_u64(&enc, 4), None);
+        assert_eq!(extract_field_as_u64(&enc, 5), None);
+
+        let overlong = b"\x08\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x01";
+        assert_eq!(extract_field_as_u64(overlong, 1), None);
+    }
+
+    /// Test extracting zigzag-encoded sint32 and sint64 fields.
+    #[test]
+    fn test_extract_field_as_sint() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(sint32, tag = "1")]
+            bar: i32,
+            #[prost(sint64, tag = "2")]
+            baz: i64,
+        }
+
+        for value in [i32::MIN, -1, 0, 1, i32::MAX] {
+            let foo = Foo {
+                bar: value,
+                baz: value as i64 * 2,
+            };
+            let enc = foo.encode_to_vec();
+
+            let bar = extract_field_as_sint32(&enc, 1).unwrap_or_default();
+            let baz = extract_field_as_sint64(&enc, 2).unwrap_or_default();
+            assert_eq!(bar, value);
+            assert_eq!(baz, value as i64 * 2);
+        }
+
+        for value in [i64::MIN, i64::MIN + 1, i64::MAX - 1, i64::MAX] {
+            let enc = Foo { bar: 0, baz: value }.encode_to_vec();
+            assert_eq!(extract_field_as_sint64(&enc, 2), Some(value));
+        }
+    }
+
+    /// Test extracting and encoding bool fields.
+    #[test]
+    fn test_extract_field_as_bool() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(bool, tag = "1")]
+            bar: bool,
+            #[prost(bool, optional, tag = "2")]
+            baz: ::core::option::Option<bool>,
+        }
+
+        let foo = Foo {
+            bar: true,
+            baz: Some(false),
+        };
+        let enc = foo.encode_to_vec();
+
+        assert_eq!(extract_field_as_bool(&enc, 1), Some(true));
+        assert_eq!(extract_field_as_bool(&enc, 2), Some(false));
+        assert_eq!(extract_field_as_bool(&enc, 3), None);
+
+        let header = create_header(1, Variant::Varint.into(), &[]);
+        let enc = [header, encode_bool(true)].concat();
+        assert!(Foo::decode(enc.as_slice()).unwrap().bar);
+    }
+
+    /// Test encoding and extracting fixed-width integer fields.
+    #[test]
+    fn test_fixed_width_integers() {
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(fixed32, tag = "1")]
+            bar: u32,
+            #[prost(fixed64, tag = "2")]
+            baz: u64,
+            #[prost(sfixed32, tag = "3")]
+            qux: i32,
+            #[prost(sfixed64, tag = "4")]
+            quux: i64,
+        }
+
+        let foo = Foo {
+            bar: u32::MAX,
+            baz: u64::MAX - 1,
+            qux: i32::MIN,
+            quux: -42,
+        };
+        let enc = foo.encode_to_vec();
+
+        assert_eq!(extract_field_as_fixed32(&enc, 1), Some(u32::MAX));
+        assert_eq!(extract_field_as_fixed64(&enc, 2), Some(u64::MAX - 1));
+        assert_eq!(extract_field_as_sfixed32(&enc, 3), Some(i32::MIN));
+        assert_eq!(extract_field_as_sfixed64(&enc, 4), Some(-42));
+
+        // Mismatched wire types are rejected.
+        assert_eq!(extract_field_as_fixed32(&enc, 2), None);
+        assert_eq!(extract_field_as_fixed64(&enc, 1), None);
+        assert_eq!(extract_field_as_sfixed32(&enc, 4), None);
+        assert_eq!(extract_field_as_sfixed64(&enc, 3), None);
+        assert_eq!(extract_field_as_fixed32(b"\x08\x01", 1), None);
+
+        // Absent and truncated fields are rejected.
+        assert_eq!(extract_field_as_fixed32(&enc, 5), None);
+        assert_eq!(extract_field_as_sfixed64(&enc, 5), None);
+        assert_eq!(extract_field_as_fixed32(b"\x0d\x01\x00\x00", 1), None);
+        assert_eq!(extract_field_as_fixed64(&enc[..enc.len() - 1], 4), None);
+
+        let enc = [
+            create_header(1, Variant::ThirtyTwoBit.into(), &[]),
+            encode_fixed32(u32::MAX),
+            create_header(2, Variant::SixtyFourBit.into(), &[]),
+            encode_fixed64(u64::MAX - 1),
+            create_header(3, Variant::ThirtyTwoBit.into(), &[]),
+            encode_sfixed32(i32::MIN),
+            create_header(4, Variant::SixtyFourBit.into(), &[]),
+            encode_sfixed64(-42),
+        ]
+        .concat();
+        assert_eq!(Foo::decode(enc.as_slice()).unwrap(), foo);
+    }
+
+    /// Test appending and prepending fields to an encoded message.
+    #[test]
+    fn test_append_and_prepend_field() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(fixed32, tag = "3")]
+            qux: u32,
+            #[prost(double, tag = "4")]
+            quux: f64,
+        }
+
+        let foo = Foo {
+            bar: 0,
+            baz: "testing".to_string(),
+            qux: 0,
+            quux: 0.0,
+        };
+        let mut enc = foo.encode_to_vec();
+
+        append_field(&mut enc, 1, Variant::Varint, &encode_varint(300));
+        prepend_field(&mut enc, 3, Variant::ThirtyTwoBit, &7u32.to_le_bytes());
+        append_field(&mut enc, 4, Variant::SixtyFourBit, &1.5f64.to_le_bytes());
+
+        let decoded = Foo::decode(enc.as_slice()).unwrap();
+        assert_eq!(decoded.bar, 300);
+        assert_eq!(decoded.baz, "testing");
+        assert_eq!(decoded.qux, 7);
+        assert_eq!(decoded.quux, 1.5);
+
+        // An appended length-delimited field overrides the existing one.
+        append_field(&mut enc, 2, Variant::LengthDelimited, b"appended");
+        assert_eq!(Foo::decode(enc.as_slice()).unwrap().baz, "appended");
+
+        // A prepended one does not.
+        prepend_field(&mut enc, 2, Variant::LengthDelimited, b"prepended");
+        assert_eq!(Foo::decode(enc.as_slice()).unwrap().baz, "appended");
+    }
+
+    /// Test replacing multiple fields in a single pass.
+    #[test]
+    fn test_replace_multiple_fields() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(fixed32, tag = "3")]
+            qux: u32,
+        }
+
+        let foo = Foo {
+            bar: 1,
+            baz: "testing".to_string(),
+            qux: 42,
+        };
+        let mut enc = foo.encode_to_vec();
+
+        let new_qux = [create_header(3, 5, &[]), 7u32.to_le_bytes().to_vec()].concat();
+        let new_bar = [create_header(1, 0, &[]), encode_varint(300)].concat();
+        let new_baz = [create_header(2, 2, b"replaced"), b"replaced".to_vec()].concat();
+
+        // Replacements are listed in a different order than the fields appear in the message.
+        let old_values = replace_multiple_fields(
+            &mut enc,
+            &[(3, &new_qux), (4, &[]), (1, &new_bar), (2, &new_baz)],
+        );
+
+        assert_eq!(
+            old_values,
+            [
+                Some(42u32.to_le_bytes().to_vec()),
+                None,
+                Some(vec![0x01]),
+                Some(b"testing".to_vec()),
+            ]
+        );
+
+        let decoded = Foo::decode(enc.as_slice()).unwrap();
+        assert_eq!(decoded.bar, 300);
+        assert_eq!(decoded.baz, "replaced");
+        assert_eq!(decoded.qux, 7);
+    }
+
+    /// Test that each `WireError` variant is reported for crafted malformed messages.
+    #[test]
+    fn test_extract_field_by_tag_checked() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+        }
+
+        let foo = Foo {
+            bar: 150,
+            baz: "testing".to_string(),
+        };
+        let enc = foo.encode_to_vec();
+
+        assert_eq!(
+            extract_field_by_tag_checked(&enc, 2),
+            Ok(Some(&b"testing"[..]))
+        );
+        assert_eq!(extract_field_by_tag_checked(&enc, 3), Ok(None));
+
+        // A group for field 1 is skipped, even though it has the requested tag number.
+        assert_eq!(
+            extract_field_by_tag_checked(&[0x0b, 0x10, 0x02, 0x0c, 0x08, 0x01], 1),
+            Ok(Some(&[0x01][..]))
+        );
+        // A group that is never closed.
+        assert_eq!(
+            extract_field_by_tag_checked(&[0x0b, 0x08, 0x01], 1),
+            Err(WireError::TruncatedMessage { offset: 3 })
+        );
+        // An end-group tag without a start-group tag.
+        assert_eq!(
+            extract_field_by_tag_checked(&[0x0c, 0x08, 0x01], 1),
+            Err(WireError::UnknownWireType(4))
+        );
+        // Varint value cut off after its first byte.
+        assert_eq!(
+            extract_field_by_tag_checked(&enc[..2], 1),
+            Err(WireError::TruncatedMessage { offset: 1 })
+        );
+        // Only three of the four bytes of a fixed32 field.
+        assert_eq!(
+            extract_field_by_tag_checked(&[0x0d, 0x01, 0x02, 0x03], 1),
+            Err(WireError::TruncatedMessage { offset: 0 })
+        );
+        // An 11-byte varint.
+        let overflow = [&[0x08][..], &[0xFF; 10], &[0x01]].concat();
+        assert_eq!(
+            extract_field_by_tag_checked(&overflow, 1),
+            Err(WireError::VarintOverflow { offset: 1 })
+        );
+        // The string claims more bytes than are left.
+        assert_eq!(
+            extract_field_by_tag_checked(&enc[..enc.len() - 1], 2),
+            Err(WireError::InvalidLength { offset: 4 })
+        );
+    }
+
+    /// Test converting between `Variant` and raw wire types.
+    #[test]
+    fn test_variant_wire_type_round_trip() {
+        for variant in [
+            Variant::Varint,
+            Variant::SixtyFourBit,
+            Variant::LengthDelimited,
+            Variant::ThirtyTwoBit,
+        ] {
+            let wire_type: u64 = variant.into();
+            assert_eq!(Variant::try_from(wire_type), Ok(variant));
+            assert_eq!(Variant::try_from(wire_type as u8), Ok(variant));
+        }
+
+        for wire_type in [3u8, 4, 6, 7, 0xFF] {
+            assert_eq!(
+                Variant::try_from(wire_type),
+                Err(WireError::UnknownWireType(wire_type as u64))
+            );
+        }
+
+        assert_eq!(Variant::Varint.to_string(), "varint");
+        assert_eq!(Variant::LengthDelimited.to_string(), "length-delimited");
+        assert_eq!(Variant::ThirtyTwoBit.to_string(), "32-bit");
+    }
+
+    /// Test iterating over every field of an encoded message.
+    #[test]
+    fn test_field_iterator() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(double, tag = "3")]
+            qux: f64,
+            #[prost(fixed32, repeated, packed = "false", tag = "4")]
+            quux: Vec<u32>,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: "testing".to_string(),
+            qux: 1.5,
+            quux: vec![7, 8],
+        };
+        let enc = foo.encode_to_vec();
+
+        let fields: Vec<_> = FieldIterator::new(&enc)
+            .map(|field| (field.tag, field.wire_type, field.as_bytes()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                (1, Variant::Varint, &encode_varint(300)[..]),
+                (2, Variant::LengthDelimited, &b"testing"[..]),
+                (3, Variant::SixtyFourBit, &1.5f64.to_le_bytes()[..]),
+                (4, Variant::ThirtyTwoBit, &7u32.to_le_bytes()[..]),
+                (4, Variant::ThirtyTwoBit, &8u32.to_le_bytes()[..]),
+            ]
+        );
+
+        // Iteration stops at the truncated last field.
+        let truncated = &enc[..enc.len() - 1];
+        assert_eq!(FieldIterator::from(truncated).count(), 4);
+
+        // Groups are skipped.
+        let mut iter = FieldIterator::from(&[0x08, 0x01, 0x0b, 0x10, 0x05, 0x0c, 0x08, 0x02][..]);
+        assert_eq!(iter.next().and_then(|field| field.as_u64()), Some(1));
+        assert_eq!(iter.next().and_then(|field| field.as_u64()), Some(2));
+        assert_eq!(iter.next(), None);
+
+        // Iteration stops at a group that is never closed and stays stopped.
+        let mut iter = FieldIterator::from(&[0x08, 0x01, 0x0b, 0x08, 0x02][..]);
+        assert_eq!(iter.next().and_then(|field| field.as_u64()), Some(1));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    /// Test extracting every occurrence of a repeated field.
+    #[test]
+    fn test_extract_all_occurrences_by_tag() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(string, repeated, tag = "1")]
+            bar: Vec<String>,
+            #[prost(uint64, repeated, packed = "false", tag = "2")]
+            baz: Vec<u64>,
+            #[prost(bool, tag = "3")]
+            qux: bool,
+        }
+
+        let foo = Foo {
+            bar: vec!["a".to_string(), "".to_string(), "testing".to_string()],
+            baz: vec![1, 300, u64::MAX],
+            qux: true,
+        };
+        let enc = foo.encode_to_vec();
+
+        let bar = extract_all_occurrences_by_tag(&enc, 1);
+        assert_eq!(bar.len(), foo.bar.len());
+        for (value, expected) in bar.iter().zip(&foo.bar) {
+            assert_eq!(*value, expected.as_bytes());
+        }
+
+        let baz = extract_all_occurrences_by_tag(&enc, 2);
+        assert_eq!(baz.len(), foo.baz.len());
+        for (value, expected) in baz.iter().zip(&foo.baz) {
+            assert_eq!(*value, encode_varint(*expected));
+        }
+
+        assert_eq!(extract_all_occurrences_by_tag(&enc, 3), [&[0x01][..]]);
+        assert!(extract_all_occurrences_by_tag(&enc, 4).is_empty());
+    }
+
+    /// Test extracting and encoding packed repeated fields.
+    #[test]
+    fn test_packed_repeated_fields() {
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, repeated, tag = "1")]
+            bar: Vec<u64>,
+            #[prost(float, repeated, tag = "2")]
+            baz: Vec<f32>,
+            #[prost(double, repeated, tag = "3")]
+            qux: Vec<f64>,
+        }
+
+        let foo = Foo {
+            bar: vec![0, 1, 300, u64::MAX],
+            baz: vec![1.5, -0.25, f32::MAX],
+            qux: vec![2.5, f64::MIN_POSITIVE],
+        };
+        let enc = foo.encode_to_vec();
+
+        let bar = extract_field_by_tag(&enc, 1).unwrap();
+        let baz = extract_field_by_tag(&enc, 2).unwrap();
+        let qux = extract_field_by_tag(&enc, 3).unwrap();
+        assert_eq!(extract_packed_varint(bar), Some(foo.bar.clone()));
+        assert_eq!(extract_packed_float(baz), Some(foo.baz.clone()));
+        assert_eq!(extract_packed_double(qux), Some(foo.qux.clone()));
+
+        assert_eq!(encode_packed_varint(&foo.bar), bar);
+        assert_eq!(encode_packed_float(&foo.baz), baz);
+        assert_eq!(encode_packed_double(&foo.qux), qux);
+
+        // Truncated payloads are rejected.
+        assert_eq!(extract_packed_varint(&bar[..bar.len() - 1]), None);
+        assert_eq!(extract_packed_float(&baz[..baz.len() - 1]), None);
+        assert_eq!(extract_packed_double(&qux[..qux.len() - 1]), None);
+    }
+
+    /// Test that fields after a group are still reachable.
+    #[test]
+    fn test_skip_group_fields() {
+        let encoded_message = [
+            0x08, 0x01, // field 1: varint 1
+            0x13, // field 2: start group
+            0x08, 0x02, // field 1 inside the group: varint 2
+            0x1b, // field 3: start nested group
+            0x22, 0x01, 0x78, // field 4 inside the nested group: "x"
+            0x1c, // field 3: end nested group
+            0x14, // field 2: end group
+            0x22, 0x02, 0x68, 0x69, // field 4: "hi"
+            0x2d, 0x07, 0x00, 0x00, 0x00, // field 5: fixed32 7
+        ];
+
+        assert_eq!(extract_field_by_tag(&encoded_message, 1), Some(&[0x01][..]));
+        assert_eq!(extract_field_by_tag(&encoded_message, 4), Some(&b"hi"[..]));
+        assert_eq!(
+            extract_field_by_tag(&encoded_message, 5),
+            Some(&[0x07, 0x00, 0x00, 0x00][..])
+        );
+        assert_eq!(
+            extract_multiple_fields_by_tag(&encoded_message, &[4, 5]),
+            [(4, &b"hi"[..]), (5, &[0x07, 0x00, 0x00, 0x00][..])]
+        );
+
+        let mut replaced = encoded_message.to_vec();
+        assert_eq!(
+            replace_field_with(&mut replaced, 4, &[0x22, 0x01, 0x79]),
+            Some(b"hi".to_vec())
+        );
+        assert_eq!(replaced[..11], encoded_message[..11]);
+        assert_eq!(replaced[11..14], [0x22, 0x01, 0x79]);
+
+        // A group closed by the wrong end-group tag cannot be skipped.
+        let mismatched = [0x13, 0x08, 0x02, 0x1c, 0x22, 0x02, 0x68, 0x69];
+        assert_eq!(extract_field_by_tag(&mismatched, 4), None);
+
+        // Neither can a group that is never closed.
+        let unterminated = [0x13, 0x08, 0x02];
+        assert_eq!(extract_field_by_tag(&unterminated, 4), None);
+
+        // Groups are followed up to 100 levels deep.
+        let nested = |depth| [vec![0x0b; depth], vec![0x0c; depth], vec![0x10, 0x01]].concat();
+        assert_eq!(extract_field_by_tag(&nested(100), 2), Some(&[0x01][..]));
+        assert_eq!(extract_field_by_tag(&nested(101), 2), None);
+    }
+
+    /// Test that the record-based functions step over a group before the requested field
+    #[test]
+    fn test_group_before_field() {
+        // Field 1 is a group containing the varint field 2, followed by the varint field 3.
+        let encoded_message = b"\x0b\x10\x01\x0c\x18\x05";
+
+        assert_eq!(extract_field_by_tag(encoded_message, 3), Some(&[0x05][..]));
+        assert_eq!(extract_field_byte_range(encoded_message, 3), Some((5, 6)));
+        assert_eq!(
+            extract_full_field_byte_range(encoded_message, 3),
+            Some((4, 6))
+        );
+        assert_eq!(find_field_tag_offset(encoded_message, 3), Some(4));
+        assert_eq!(
+            extract_field_with_full_record(encoded_message, 3),
+            Some(&[0x18, 0x05][..])
+        );
+        assert_eq!(extract_field_as_u64(encoded_message, 3), Some(5));
+        assert_eq!(
+            extract_field_view(encoded_message, 3).unwrap().as_u64(),
+            Some(5)
+        );
+        assert_eq!(
+            extract_field_at_index(encoded_message, 3, 0),
+            Some(&[0x05][..])
+        );
+        assert_eq!(
+            extract_all_occurrences_by_tag(encoded_message, 3),
+            [&[0x05][..]]
+        );
+        assert_eq!(
+            extract_last_n_fields(encoded_message, 2),
+            Ok(vec![(3, &[0x05][..])])
+        );
+
+        // The group itself has no value, so it is skipped even when its tag is requested.
+        assert_eq!(extract_field_by_tag(encoded_message, 1), None);
+        assert_eq!(extract_field_byte_range(encoded_message, 1), None);
+        assert_eq!(extract_field_at_index(encoded_message, 1, 0), None);
+        // The fields inside the group are not fields of the message.
+        assert_eq!(extract_field_by_tag(encoded_message, 2), None);
+        assert_eq!(extract_field_view(encoded_message, 2), None);
+
+        let fields: Vec<_> = FieldIterator::new(encoded_message)
+            .map(|field| (field.tag, field.as_u64()))
+            .collect();
+        assert_eq!(fields, [(3, Some(5))]);
+        assert_eq!(MessageView::new(encoded_message).field_count(), 1);
+        assert_eq!(
+            MessageView::new(encoded_message).field(3).unwrap().as_u64(),
+            Some(5)
+        );
+
+        let mut cursor = MessageCursor::new(encoded_message);
+        assert_eq!(cursor.next_field(), Some((3, Variant::Varint, &[0x05][..])));
+        assert_eq!(cursor.next_field(), None);
+
+        let index = MessageIndex::new(encoded_message);
+        assert_eq!(index.get(3), Some(&[0x05][..]));
+        assert!(!index.has(1));
+
+        // A trailing group does not hide the fields before it.
+        let trailing = b"\x18\x05\x0b\x10\x01\x0c";
+        assert_eq!(extract_field_byte_range(trailing, 3), Some((1, 2)));
+        assert_eq!(
+            extract_last_n_fields(trailing, 1),
+            Ok(vec![(3, &[0x05][..])])
+        );
+        assert_eq!(FieldIterator::new(trailing).count(), 1);
+    }
+
+    /// Test that functions rebuilding a message keep the groups in it
+    #[test]
+    fn test_group_kept_when_rebuilding() {
+        let encoded_message = b"\x0b\x10\x01\x0c\x18\x05\x18\x06\x13\x14";
+
+        let mut replaced = encoded_message.to_vec();
+        assert_eq!(
+            replace_field_payload(&mut replaced, 3, &[0x07]),
+            Some(vec![0x05])
+        );
+        assert_eq!(replaced, b"\x0b\x10\x01\x0c\x18\x07\x18\x06\x13\x14");
+
+        let mut replaced = encoded_message.to_vec();
+        assert_eq!(
+            replace_field_at_index(&mut replaced, 3, 1, &[0x08]),
+            Some(vec![0x06])
+        );
+        assert_eq!(replaced, b"\x0b\x10\x01\x0c\x18\x05\x18\x08\x13\x14");
+
+        // Groups are kept or dropped by their own field number.
+        assert_eq!(
+            keep_only_fields(encoded_message, &[1, 2, 3]),
+            encoded_message
+        );
+        assert_eq!(
+            keep_only_fields(encoded_message, &[1, 3]),
+            b"\x0b\x10\x01\x0c\x18\x05\x18\x06"
+        );
+        assert_eq!(keep_only_fields(encoded_message, &[3]), b"\x18\x05\x18\x06");
+        assert_eq!(
+            strip_fields(encoded_message, &[3]),
+            b"\x0b\x10\x01\x0c\x13\x14"
+        );
+
+        assert_eq!(
+            dedup_last_wins(encoded_message, &[3]),
+            b"\x0b\x10\x01\x0c\x18\x06\x13\x14"
+        );
+
+        let mut retagged = encoded_message.to_vec();
+        assert_eq!(retag_all_fields(&mut retagged, 3, 4), 2);
+        assert_eq!(retagged, b"\x0b\x10\x01\x0c\x20\x05\x20\x06\x13\x14");
+
+        assert_eq!(
+            unpacked_to_packed(encoded_message, 3, Variant::Varint),
+            b"\x0b\x10\x01\x0c\x1a\x02\x05\x06\x13\x14"
+        );
+        assert_eq!(
+            packed_to_unpacked(
+                b"\x0b\x10\x01\x0c\x1a\x02\x05\x06\x13\x14",
+                3,
+                Variant::Varint
+            ),
+            encoded_message
+        );
+
+        // Groups cannot be passed to the filter, so they are always copied but not counted.
+        let mut dst = Vec::new();
+        assert_eq!(
+            copy_fields_filtered(encoded_message, &mut dst, |_, _, _| true),
+            Ok(2)
+        );
+        assert_eq!(dst, encoded_message);
+        let mut dst = Vec::new();
+        assert_eq!(
+            copy_fields_filtered(encoded_message, &mut dst, |tag, _, _| tag != 3),
+            Ok(0)
+        );
+        assert_eq!(dst, strip_fields(encoded_message, &[3]));
+    }
+
+    /// Test that a message built with `MessageBuilder` decodes with prost.
+    #[test]
+    fn test_message_builder() {
+        #[derive(Clone, Message, PartialEq)]
+        struct Bar {
+            #[prost(string, tag = "1")]
+            name: String,
+        }
+
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            a: u64,
+            #[prost(sint32, tag = "2")]
+            b: i32,
+            #[prost(sint64, tag = "3")]
+            c: i64,
+            #[prost(string, tag = "4")]
+            d: String,
+            #[prost(bytes = "vec", tag = "5")]
+            e: Vec<u8>,
+            #[prost(float, tag = "6")]
+            f: f32,
+            #[prost(double, tag = "7")]
+            g: f64,
+            #[prost(fixed32, tag = "8")]
+            h: u32,
+            #[prost(fixed64, tag = "9")]
+            i: u64,
+            #[prost(message, optional, tag = "10")]
+            j: Option<Bar>,
+            #[prost(uint64, repeated, tag = "11")]
+            k: Vec<u64>,
+        }
+
+        let bar = Bar {
+            name: "nested".to_string(),
+        };
+        let foo = Foo {
+            a: 300,
+            b: -5,
+            c: i64::MIN,
+            d: "testing".to_string(),
+            e: vec![0xDE, 0xAD],
+            f: 1.5,
+            g: -2.25,
+            h: 7,
+            i: u64::MAX,
+            j: Some(bar.clone()),
+            k: vec![1, 2, 300],
+        };
+
+        let mut builder = MessageBuilder::new();
+        builder
+            .add_varint(1, foo.a)
+            .add_sint32(2, foo.b)
+            .add_sint64(3, foo.c)
+            .add_string(4, &foo.d)
+            .add_bytes(5, &foo.e)
+            .add_float(6, foo.f)
+            .add_double(7, foo.g)
+            .add_fixed32(8, foo.h)
+            .add_fixed64(9, foo.i)
+            .add_message(10, &bar.encode_to_vec())
+            .add_packed_varint(11, &foo.k);
+        let enc = builder.finish();
+
+        assert_eq!(enc, foo.encode_to_vec());
+        assert_eq!(Foo::decode(enc.as_slice()).unwrap(), foo);
+    }
+
+    /// Test extracting and replacing fields three levels deep.
+    #[test]
+    fn test_field_by_path() {
+        #[derive(Message, PartialEq)]
+        struct Inner {
+            #[prost(string, tag = "1")]
+            name: String,
+            #[prost(uint64, tag = "2")]
+            count: u64,
+        }
+
+        #[derive(Message, PartialEq)]
+        struct Middle {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(message, optional, tag = "2")]
+            inner: Option<Inner>,
+            #[prost(string, tag = "3")]
+            note: String,
+        }
+
+        #[derive(Message, PartialEq)]
+        struct Outer {
+            #[prost(string, tag = "1")]
+            title: String,
+            #[prost(message, optional, tag = "5")]
+            middle: Option<Middle>,
+            #[prost(uint64, tag = "6")]
+            trailer: u64,
+        }
+
+        let outer = Outer {
+            title: "outer".to_string(),
+            middle: Some(Middle {
+                id: 7,
+                inner: Some(Inner {
+                    name: "inner".to_string(),
+                    count: 3,
+                }),
+                note: "after inner".to_string(),
+            }),
+            trailer: 42,
+        };
+        let mut enc = outer.encode_to_vec();
+
+        assert_eq!(extract_field_by_path(&enc, &[]), Some(&enc[..]));
+        assert_eq!(extract_field_by_path(&enc, &[5, 2, 1]), Some(&b"inner"[..]));
+        assert_eq!(extract_field_by_path(&enc, &[5, 2, 2]), Some(&[0x03][..]));
+        assert_eq!(extract_field_by_path(&enc, &[5, 2, 3]), None);
+        // Field 6 is a varint, not a nested message.
+        assert_eq!(extract_field_by_path(&enc, &[6, 1]), None);
+
+        let name = "a much longer inner name".repeat(10);
+        let new_field = [
+            create_header(1, 2, name.as_bytes()),
+            name.clone().into_bytes(),
+        ]
+        .concat();
+        assert_eq!(
+            replace_field_by_path(&mut enc, &[5, 2, 1], &new_field),
+            Some(b"inner".to_vec())
+        );
+
+        let decoded = Outer::decode(enc.as_slice()).unwrap();
+        let middle = decoded.middle.unwrap();
+        assert_eq!(decoded.title, "outer");
+        assert_eq!(decoded.trailer, 42);
+        assert_eq!(middle.id, 7);
+        assert_eq!(middle.note, "after inner");
+        assert_eq!(middle.inner, Some(Inner { name, count: 3 }));
+
+        let before = enc.clone();
+        assert_eq!(
+            replace_field_by_path(&mut enc, &[5, 4, 1], &new_field),
+            None
+        );
+        assert_eq!(replace_field_by_path(&mut enc, &[], &new_field), None);
+        assert_eq!(enc, before);
+    }
+
+    /// Test that `MessageIndex` agrees with the scanning extraction functions.
+    #[test]
+    fn test_message_index() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, repeated, tag = "2")]
+            baz: Vec<String>,
+            #[prost(double, tag = "3")]
+            qux: f64,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            qux: 1.5,
+        };
+        let enc = foo.encode_to_vec();
+        let index = MessageIndex::new(&enc);
+
+        for tag in 1..=4 {
+            assert_eq!(index.get(tag), extract_field_by_tag(&enc, tag));
+            assert_eq!(
+                index.get_all(tag),
+                extract_all_occurrences_by_tag(&enc, tag)
+            );
+            assert_eq!(index.has(tag), tag <= 3);
+        }
+        assert_eq!(index.get_last(2), Some(&b"c"[..]));
+        assert_eq!(index.get_last(4), None);
+    }
+
+    /// Test validating messages and nested messages.
+    #[test]
+    fn test_validate_message() {
+        #[derive(Message)]
+        struct Inner {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(fixed32, tag = "2")]
+            baz: u32,
+        }
+
+        #[derive(Message)]
+        struct Outer {
+            #[prost(double, tag = "1")]
+            qux: f64,
+            #[prost(message, optional, tag = "2")]
+            inner: Option<Inner>,
+        }
+
+        let outer = Outer {
+            qux: 1.5,
+            inner: Some(Inner { bar: 300, baz: 7 }),
+        };
+        let enc = outer.encode_to_vec();
+        assert_eq!(validate_message(&enc), Ok(()));
+        assert_eq!(validate_message_recursive(&enc, 4), Ok(()));
+
+        // The nested message is cut short, so its length prefix runs past the end.
+        assert_eq!(
+            validate_message(&enc[..enc.len() - 1]),
+            Err(WireError::InvalidLength { offset: 10 })
+        );
+        // Truncated fixed-width field.
+        assert_eq!(
+            validate_message(&enc[..5]),
+            Err(WireError::TruncatedMessage { offset: 0 })
+        );
+
+        // Unknown wire type.
+        assert_eq!(
+            validate_message(&[0x08, 0x01, 0x0e]),
+            Err(WireError::UnknownWireType(6))
+        );
+
+        // Overflowing tag varint.
+        assert_eq!(
+            validate_message(&[0xFF; 11]),
+            Err(WireError::VarintOverflow { offset: 0 })
+        );
+
+        // Corrupt the nested message by shortening its fixed32 field, keeping the outer length
+        // prefix consistent.
+        let mut nested = enc.clone();
+        nested[10] -= 1;
+        nested.pop();
+        assert_eq!(validate_message(&nested), Ok(()));
+        assert_eq!(validate_message_recursive(&nested, 0), Ok(()));
+        assert_eq!(
+            validate_message_recursive(&nested, 1),
+            Err(WireError::TruncatedMessage { offset: 14 })
+        );
+
+        // Deeply nested input does not overflow the stack, whatever the requested depth.
+        let mut headers = Vec::new();
+        let mut len = 0;
+        for _ in 0..300_000 {
+            let header = [vec![0x0a], encode_varint(len as u64)].concat();
+            len += header.len();
+            headers.push(header);
+        }
+        let deep: Vec<u8> = headers.into_iter().rev().flatten().collect();
+        assert_eq!(validate_message_recursive(&deep, u32::MAX), Ok(()));
+    }
+
+    /// Test checking field presence and counting occurrences.
+    #[test]
+    fn test_field_presence_and_count() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, repeated, tag = "2")]
+            baz: Vec<String>,
+            #[prost(uint64, repeated, tag = "3")]
+            qux: Vec<u64>,
+            #[prost(fixed64, repeated, packed = "false", tag = "4")]
+            quux: Vec<u64>,
+        }
+
+        let foo = Foo {
+            bar: 0,
+            baz: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            qux: vec![1, 2, 3],
+            quux: vec![4, 5],
+        };
+        let enc = foo.encode_to_vec();
+
+        // Default scalar values are not encoded.
+        assert!(!message_has_field(&enc, 1));
+        assert!(message_has_field(&enc, 2));
+        assert!(message_has_field(&enc, 3));
+        assert!(message_has_field(&enc, 4));
+
+        assert_eq!(count_field_occurrences(&enc, 1), 0);
+        assert_eq!(count_field_occurrences(&enc, 2), foo.baz.len());
+        // Packed fields are a single occurrence.
+        assert_eq!(count_field_occurrences(&enc, 3), 1);
+        assert_eq!(count_field_occurrences(&enc, 4), foo.quux.len());
+
+        // The last fixed64 field is truncated and not counted.
+        assert_eq!(count_field_occurrences(&enc[..enc.len() - 1], 4), 1);
+
+        // A tag without its value is not present.
+        assert!(!message_has_field(b"\x10", 2));
+        assert_eq!(count_field_occurrences(b"\x10", 2), 0);
+        assert_eq!(extract_field_by_tag(b"\x10", 2), None);
+
+        // Groups are skipped, even if they carry the tag number.
+        let group = b"\x13\x14\x10\x01";
+        assert!(message_has_field(group, 2));
+        assert_eq!(count_field_occurrences(group, 2), 1);
+        assert_eq!(extract_all_occurrences_by_tag(group, 2).len(), 1);
+        assert!(!message_has_field(b"\x13\x14", 2));
+        assert_eq!(count_field_occurrences(b"\x13\x14", 2), 0);
+        assert_eq!(extract_field_by_tag(b"\x13\x14", 2), None);
+    }
+
+    /// Test that merging on the wire matches prost's merge semantics.
+    #[test]
+    fn test_merge_messages() {
+        #[derive(Clone, Message, PartialEq)]
+        struct Bar {
+            #[prost(uint64, tag = "1")]
+            a: u64,
+            #[prost(string, tag = "2")]
+            b: String,
+        }
+
+        #[derive(Clone, Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(double, tag = "2")]
+            baz: f64,
+            #[prost(string, repeated, tag = "3")]
+            qux: Vec<String>,
+            #[prost(message, optional, tag = "4")]
+            nested: Option<Bar>,
+            #[prost(uint64, repeated, tag = "5")]
+            packed: Vec<u64>,
+            #[prost(string, tag = "6")]
+            name: String,
+        }
+
+        let base = Foo {
+            bar: 1,
+            baz: 1.5,
+            qux: vec!["a".to_string()],
+            nested: Some(Bar {
+                a: 10,
+                b: "base".to_string(),
+            }),
+            packed: vec![1, 2],
+            name: "base".to_string(),
+        };
+        let overlay = Foo {
+            bar: 2,
+            baz: 0.0,
+            qux: vec!["b".to_string(), "c".to_string()],
+            nested: Some(Bar {
+                a: 20,
+                b: String::new(),
+            }),
+            packed: vec![3],
+            name: "overlay".to_string(),
+        };
+
+        let mut expected = base.clone();
+        expected.merge(overlay.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(expected.nested.as_ref().unwrap().b, "base");
+
+        let base_enc = base.encode_to_vec();
+        let overlay_enc = overlay.encode_to_vec();
+
+        let merged = merge_messages(&base_enc, &overlay_enc);
+        assert_eq!(Foo::decode(merged.as_slice()).unwrap(), expected);
+
+        let merged = merge_messages_last_wins(&base_enc, &overlay_enc);
+        assert_eq!(Foo::decode(merged.as_slice()).unwrap(), expected);
+        assert_eq!(count_field_occurrences(&merged, 1), 1);
+        // `baz` is a default value in the overlay, so it is not encoded and the base value stays.
+        assert_eq!(count_field_occurrences(&merged, 2), 1);
+        assert!(merged.len() < base_enc.len() + overlay_enc.len());
+    }
+
+    /// Test sorting the fields of a message by tag number.
+    #[test]
+    fn test_canonicalize_message() {
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, repeated, tag = "2")]
+            baz: Vec<String>,
+            #[prost(double, tag = "3")]
+            qux: f64,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            qux: 1.5,
+        };
+        let enc = foo.encode_to_vec();
+
+        // Shuffle the fields: qux, baz[0], bar, baz[1], baz[2].
+        let fields: Vec<_> = FieldIterator::new(&enc).collect();
+        let mut shuffled = Vec::new();
+        for index in [4, 1, 0, 2, 3] {
+            let field = fields[index];
+            append_field(&mut shuffled, field.tag, field.wire_type, field.as_bytes());
+        }
+        assert_ne!(shuffled, enc);
+
+        let canonical = canonicalize_message(&shuffled).unwrap();
+        assert_eq!(canonical, enc);
+        assert_eq!(Foo::decode(canonical.as_slice()).unwrap(), foo);
+
+        assert_eq!(canonicalize_message(&shuffled[..shuffled.len() - 1]), None);
+    }
+
+    /// Test keeping and stripping subsets of fields.
+    #[test]
+    fn test_keep_only_and_strip_fields() {
+        #[derive(Clone, Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, repeated, tag = "2")]
+            baz: Vec<String>,
+            #[prost(double, tag = "3")]
+            qux: f64,
+            #[prost(bytes = "vec", tag = "4")]
+            quux: Vec<u8>,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: vec!["a".to_string(), "b".to_string()],
+            qux: 1.5,
+            quux: vec![0xDE, 0xAD],
+        };
+        let enc = foo.encode_to_vec();
+
+        let kept = keep_only_fields(&enc, &[2, 4]);
+        assert_eq!(
+            Foo::decode(kept.as_slice()).unwrap(),
+            Foo {
+                bar: 0,
+                qux: 0.0,
+                ..foo.clone()
+            }
+        );
+
+        let stripped = strip_fields(&enc, &[2, 4]);
+        assert_eq!(
+            Foo::decode(stripped.as_slice()).unwrap(),
+            Foo {
+                baz: Vec::new(),
+                quux: Vec::new(),
+                ..foo.clone()
+            }
+        );
+
+        assert_eq!(kept.len() + stripped.len(), enc.len());
+        assert!(keep_only_fields(&enc, &[]).is_empty());
+        assert_eq!(strip_fields(&enc, &[]), enc);
+    }
+
+    /// Test that retagged fields are read by prost under their new field name.
+    #[test]
+    fn test_retag_field() {
+        #[derive(Message)]
+        struct Old {
+            #[prost(string, tag = "2")]
+            name: String,
+            #[prost(uint64, repeated, packed = "false", tag = "3")]
+            ids: Vec<u64>,
+            #[prost(fixed32, tag = "4")]
+            flags: u32,
+        }
+
+        #[derive(Message, PartialEq)]
+        struct New {
+            #[prost(fixed32, tag = "4")]
+            flags: u32,
+            #[prost(uint64, repeated, packed = "false", tag = "300")]
+            ids: Vec<u64>,
+            #[prost(string, tag = "2000")]
+            name: String,
+        }
+
+        let old = Old {
+            name: "testing".to_string(),
+            ids: vec![1, 2, 3],
+            flags: 7,
+        };
+        let mut enc = old.encode_to_vec();
+
+        // Invalid field numbers are rejected without touching the message.
+        let original = enc.clone();
+        for invalid in [0, (1 << 29), (1 << 61) + 5] {
+            assert!(!retag_field(&mut enc, 2, invalid));
+            assert_eq!(retag_all_fields(&mut enc, 3, invalid), 0);
+            assert_eq!(enc, original);
+        }
+        let max = (1 << 29) - 1;
+        let mut max_retagged = enc.clone();
+        assert!(retag_field(&mut max_retagged, 4, max));
+        assert_eq!(
+            extract_field_by_tag(&max_retagged, max),
+            Some(&[7, 0, 0, 0][..])
+        );
+        assert_eq!(retag_all_fields(&mut max_retagged, 3, max), 3);
+        assert_eq!(count_field_occurrences(&max_retagged, max), 4);
+
+        assert!(retag_field(&mut enc, 2, 2000));
+        assert!(!retag_field(&mut enc, 2, 2000));
+        assert_eq!(retag_all_fields(&mut enc, 3, 300), 3);
+        assert_eq!(retag_all_fields(&mut enc, 3, 300), 0);
+
+        assert_eq!(
+            New::decode(enc.as_slice()).unwrap(),
+            New {
+                flags: 7,
+                ids: vec![1, 2, 3],
+                name: "testing".to_string(),
+            }
+        );
+    }
+
+    /// Test that the estimated replacement size matches the replaced message.
+    #[test]
+    fn test_estimate_replacement_size() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(double, tag = "3")]
+            qux: f64,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: "testing".to_string(),
+            qux: 1.5,
+        };
+        let enc = foo.encode_to_vec();
+
+        let long = "x".repeat(200);
+        let replacements = [
+            (
+                1,
+                [create_header(1, 0, &[]), encode_varint(u64::MAX)].concat(),
+            ),
+            (
+                2,
+                [create_header(2, 2, long.as_bytes()), long.into_bytes()].concat(),
+            ),
+            (3, Vec::new()),
+        ];
+        for (tag_number, replace_with) in replacements {
+            let mut replaced = enc.clone();
+            let size = estimate_replacement_size(&replaced, tag_number, &replace_with);
+            replace_field_with(&mut replaced, tag_number, &replace_with).unwrap();
+            assert_eq!(size, Some(replaced.len()));
+        }
+
+        assert_eq!(estimate_replacement_size(&enc, 4, &[]), None);
+    }
+
+    /// Test that same-size replacements overwrite the field without reallocating.
+    #[test]
+    fn test_replace_field_with_same_size() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(double, tag = "1")]
+            bar: f64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(fixed32, tag = "3")]
+            qux: u32,
+        }
+
+        let foo = Foo {
+            bar: 1.5,
+            baz: "a".repeat(200),
+            qux: 7,
+        };
+        let mut enc = foo.encode_to_vec();
+        enc.shrink_to_fit();
+        let ptr = enc.as_ptr();
+
+        let new_bar = [create_header(1, 1, &[]), encode_double(-2.5)].concat();
+        assert_eq!(
+            replace_field_with(&mut enc, 1, &new_bar),
+            Some(1.5f64.to_le_bytes().to_vec())
+        );
+
+        let new_baz = "b".repeat(200);
+        let new_baz = [
+            create_header(2, 2, new_baz.as_bytes()),
+            new_baz.into_bytes(),
+        ]
+        .concat();
+        assert_eq!(
+            replace_field_with(&mut enc, 2, &new_baz),
+            Some(foo.baz.clone().into_bytes())
+        );
+
+        let new_qux = [create_header(3, 5, &[]), encode_fixed32(8)].concat();
+        assert_eq!(
+            replace_field_with(&mut enc, 3, &new_qux),
+            Some(7u32.to_le_bytes().to_vec())
+        );
+
+        assert_eq!(enc.as_ptr(), ptr);
+        let decoded = Foo::decode(enc.as_slice()).unwrap();
+        assert_eq!(decoded.bar, -2.5);
+        assert_eq!(decoded.baz, "b".repeat(200));
+        assert_eq!(decoded.qux, 8);
+
+        // A length-delimited field with a two-byte length prefix is removed completely when it is
+        // replaced with a smaller one.
+        let short = [create_header(2, 2, b"short"), b"short".to_vec()].concat();
+        assert_eq!(
+            replace_field_with(&mut enc, 2, &short),
+            Some("b".repeat(200).into_bytes())
+        );
+        assert_eq!(Foo::decode(enc.as_slice()).unwrap().baz, "short");
+    }
+
+    /// Test committing and rolling back staged replacements.
+    #[test]
+    fn test_message_patcher() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(fixed32, tag = "3")]
+            qux: u32,
+        }
+
+        let foo = Foo {
+            bar: 1,
+            baz: "testing".to_string(),
+            qux: 7,
+        };
+        let original = foo.encode_to_vec();
+        let mut enc = original.clone();
+
+        let new_bar = encode_varint(300);
+        let new_baz = b"patched".to_vec();
+        let new_qux = encode_fixed32(8);
+
+        let mut patcher = MessagePatcher::new(&mut enc);
+        patcher
+            .stage(3, &new_qux)
+            .stage(1, &new_bar)
+            .stage(4, &new_bar);
+        patcher.rollback();
+        assert_eq!(enc, original);
+
+        // A staged tag that is missing fails the whole commit.
+        let mut patcher = MessagePatcher::new(&mut enc);
+        patcher.stage(1, &new_bar).stage(4, &new_bar);
+        assert_eq!(patcher.commit(), Err(PatchError::FieldNotFound { tag: 4 }));
+        assert_eq!(enc, original);
+
+        // So does a payload that does not fit the wire type of the field.
+        let mut patcher = MessagePatcher::new(&mut enc);
+        patcher.stage(1, &new_bar).stage(3, &[0x01, 0x02, 0x03]);
+        assert_eq!(
+            patcher.commit(),
+            Err(PatchError::InvalidPayload {
+                tag: 3,
+                variant: Variant::ThirtyTwoBit,
+            })
+        );
+        let mut patcher = MessagePatcher::new(&mut enc);
+        patcher.stage(1, &[0x80]);
+        assert_eq!(
+            patcher.commit(),
+            Err(PatchError::InvalidPayload {
+                tag: 1,
+                variant: Variant::Varint,
+            })
+        );
+        assert_eq!(enc, original);
+
+        // And a message that cannot be decoded before the last staged field.
+        let mut truncated = original[..original.len() - 1].to_vec();
+        let mut patcher = MessagePatcher::new(&mut truncated);
+        patcher.stage(1, &new_bar).stage(3, &new_qux);
+        assert!(matches!(patcher.commit(), Err(PatchError::Malformed(_))));
+        assert_eq!(truncated, original[..original.len() - 1]);
+
+        let mut patcher = MessagePatcher::new(&mut enc);
+        patcher
+            .stage(3, &new_bar)
+            .stage(2, &new_baz)
+            .stage(1, &new_bar)
+            .stage(3, &new_qux);
+        let old_values = patcher.commit().unwrap();
+
+        assert_eq!(
+            old_values,
+            [
+                (3, 7u32.to_le_bytes().to_vec()),
+                (2, b"testing".to_vec()),
+                (1, vec![0x01]),
+            ]
+        );
+
+        let decoded = Foo::decode(enc.as_slice()).unwrap();
+        assert_eq!(decoded.bar, 300);
+        assert_eq!(decoded.baz, "patched");
+        assert_eq!(decoded.qux, 8);
+
+        // The staged bytes are the value only, so the header of the field is kept.
+        let mut enc = vec![0x08, 0x01, 0x10, 0x02];
+        let mut patcher = MessagePatcher::new(&mut enc);
+        patcher.stage(1, &[0x05]);
+        assert_eq!(patcher.commit(), Ok(vec![(1, vec![0x01])]));
+        assert_eq!(enc, [0x08, 0x05, 0x10, 0x02]);
+    }
+
+    /// Test interleaving next_field, skip_next and seek_to_tag on a MessageCursor
+    #[test]
+    fn test_message_cursor() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(uint64, tag = "3")]
+            qux: u64,
+            #[prost(fixed32, tag = "4")]
+            quux: u32,
+            #[prost(string, tag = "5")]
+            corge: String,
+        }
+
+        let foo = Foo {
+            bar: 150,
+            baz: "testing".to_string(),
+            qux: 3,
+            quux: 4,
+            corge: "last".to_string(),
+        };
+        let enc = foo.encode_to_vec();
+        let mut cursor = MessageCursor::new(&enc);
+
+        assert_eq!(
+            cursor.next_field(),
+            Some((1, Variant::Varint, &[0x96, 0x01][..]))
+        );
+        assert_eq!(cursor.seek_to_tag(3), Some(&[0x03][..]));
+        assert_eq!(
+            cursor.next_field(),
+            Some((4, Variant::ThirtyTwoBit, &4u32.to_le_bytes()[..]))
+        );
+        assert_eq!(cursor.remaining(), b"\x2a\x04last");
+
+        // Fields before the cursor are not found again.
+        assert_eq!(cursor.clone().seek_to_tag(2), None);
+
+        assert!(cursor.skip_next());
+        assert!(!cursor.skip_next());
+        assert_eq!(cursor.next_field(), None);
+        assert!(cursor.remaining().is_empty());
+
+        let mut cursor = MessageCursor::new(&enc);
+        assert_eq!(cursor.seek_to_tag(6), None);
+        assert!(cursor.remaining().is_empty());
+
+        let mut cursor = MessageCursor::new(b"\x08\x01\x12\x05\x68");
+        assert!(cursor.skip_next());
+        assert_eq!(cursor.next_field(), None);
+        assert!(cursor.remaining().is_empty());
+    }
+
+    /// Test that field byte ranges index the same bytes as extract_field_by_tag
+    #[test]
+    fn test_extract_field_byte_range() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(double, tag = "3")]
+            qux: f64,
+            #[prost(fixed32, tag = "4")]
+            quux: u32,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: "testing".to_string(),
+            qux: 1.5,
+            quux: 7,
+        };
+        let enc = foo.encode_to_vec();
+
+        for tag in 1..=4 {
+            let (start, end) = extract_field_byte_range(&enc, tag).unwrap();
+            assert_eq!(Some(&enc[start..end]), extract_field_by_tag(&enc, tag));
+
+            let (full_start, full_end) = extract_full_field_byte_range(&enc, tag).unwrap();
+            assert!(full_start < start);
+            assert_eq!(full_end, end);
+
+            let mut removed = enc.clone();
+            removed.drain(full_start..full_end);
+            assert!(!message_has_field(&removed, tag));
+        }
+
+        assert_eq!(extract_full_field_byte_range(&enc, 2), Some((3, 12)));
+        assert_eq!(extract_field_byte_range(&enc, 5), None);
+        assert_eq!(extract_full_field_byte_range(&enc, 5), None);
+    }
+
+    /// Test that split_message_at_tag covers the message exactly once
+    #[test]
+    fn test_split_message_at_tag() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(fixed64, tag = "3")]
+            qux: u64,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: "testing".to_string(),
+            qux: 7,
+        };
+        let enc = foo.encode_to_vec();
+
+        for tag in 1..=3 {
+            let (before, value, after) = split_message_at_tag(&enc, tag).unwrap();
+            assert_eq!(Some(value), extract_field_by_tag(&enc, tag));
+            assert_eq!([before, value, after].concat(), enc);
+            assert_eq!(before.len(), extract_field_byte_range(&enc, tag).unwrap().0);
+        }
+
+        let (before, value, after) = split_message_at_tag(&enc, 2).unwrap();
+        assert_eq!(before, b"\x08\xac\x02\x12\x07");
+        assert_eq!(value, b"testing");
+        assert_eq!(after.len(), 9);
+
+        assert_eq!(split_message_at_tag(&enc, 4), None);
+    }
+
+    /// Test that extracted Bytes slices share and keep alive the parent buffer
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_extract_field_as_bytes_slice() {
+        use crate::extract_field_as_bytes_slice;
+
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+        }
+
+        let foo = Foo {
+            bar: 1,
+            baz: "testing".to_string(),
+        };
+        let buf = bytes::Bytes::from(foo.encode_to_vec());
+        let parent = buf.as_ptr_range();
+
+        let bar = extract_field_as_bytes_slice(&buf, 1).unwrap();
+        let baz = extract_field_as_bytes_slice(&buf, 2).unwrap();
+        assert!(extract_field_as_bytes_slice(&buf, 3).is_none());
+
+        // Both slices point into the parent allocation rather than a copy.
+        assert!(parent.contains(&bar.as_ptr()));
+        assert!(parent.contains(&baz.as_ptr()));
+
+        // The slices keep the shared allocation alive after the parent handle is gone.
+        drop(buf);
+        assert_eq!(bar, &[0x01][..]);
+        assert_eq!(baz, "testing");
+        assert!(!baz.is_unique());
+
+        drop(bar);
+        assert!(baz.is_unique());
+    }
+
+    /// Test framing several messages with a length prefix and splitting them again
+    #[test]
+    fn test_length_prefix_framing() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(bytes, tag = "2")]
+            baz: Vec<u8>,
+        }
+
+        let messages = [
+            Foo {
+                bar: 1,
+                baz: vec![],
+            },
+            Foo {
+                bar: 2,
+                baz: vec![0xAB; 200],
+            },
+        ]
+        .map(|foo| foo.encode_to_vec());
+
+        let mut stream = Vec::new();
+        for message in &messages {
+            let framed = encode_with_length_prefix(message);
+            let mut expected = Vec::new();
+            Foo::decode(message.as_slice())
+                .unwrap()
+                .encode_length_delimited(&mut expected)
+                .unwrap();
+            assert_eq!(framed, expected);
+            stream.extend_from_slice(&framed);
+        }
+
+        let (first, rest) = strip_length_prefix(&stream).unwrap();
+        assert_eq!(first, messages[0]);
+        let (second, rest) = strip_length_prefix(rest).unwrap();
+        assert_eq!(second, messages[1]);
+        assert!(rest.is_empty());
+
+        assert_eq!(strip_length_prefix(&stream[3..stream.len() - 1]), None);
+        assert_eq!(strip_length_prefix(&[]), None);
+        assert_eq!(strip_length_prefix(&[0x80]), None);
+    }
+
+    /// Test that ProtoLengthDelimitedCodec waits for whole frames
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_proto_length_delimited_codec() {
+        use crate::ProtoLengthDelimitedCodec;
+        use bytes::BytesMut;
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let mut codec = ProtoLengthDelimitedCodec::new();
+        let large = vec![0xAB; 300];
+
+        let mut encoded = BytesMut::new();
+        codec.encode(&b"\x08\x01"[..], &mut encoded).unwrap();
+        codec.encode(&large[..], &mut encoded).unwrap();
+        codec.encode(&[][..], &mut encoded).unwrap();
+        assert_eq!(encoded.len(), 3 + 302 + 1);
+
+        // Feed the stream one byte at a time.
+        let mut src = BytesMut::new();
+        let mut frames = Vec::new();
+        for byte in encoded.iter() {
+            src.extend_from_slice(&[*byte]);
+            while let Some(frame) = codec.decode(&mut src).unwrap() {
+                frames.push(frame);
+            }
+        }
+        assert!(src.is_empty());
+        assert_eq!(frames, [&b"\x08\x01"[..], &large[..], &[][..]]);
+
+        let mut overflowing = BytesMut::from(&[0xFF; 11][..]);
+        let err = codec.decode(&mut overflowing).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    /// Test that ProtoLengthDelimitedCodec rejects frames longer than its maximum
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_proto_length_delimited_codec_max_frame_length() {
+        use crate::ProtoLengthDelimitedCodec;
+        use bytes::BytesMut;
+        use tokio_util::codec::{Decoder, Encoder};
+
+        let mut codec = ProtoLengthDelimitedCodec::default();
+        assert_eq!(codec.max_frame_length(), 8 * 1024 * 1024);
+
+        // A prefix of 2^63 - 1 bytes, as sent by a malicious peer.
+        let mut huge = BytesMut::from(&b"\xff\xff\xff\xff\xff\xff\xff\xff\x7f"[..]);
+        let err = codec.decode(&mut huge).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(huge.capacity(), 9);
+
+        // One byte over the default maximum is rejected before any memory is reserved.
+        let mut src = BytesMut::from(&encode_varint(8 * 1024 * 1024 + 1)[..]);
+        let capacity = src.capacity();
+        assert!(codec.decode(&mut src).is_err());
+        assert_eq!(src.capacity(), capacity);
+
+        let mut codec = ProtoLengthDelimitedCodec::with_max_frame_length(4);
+        let mut encoded = BytesMut::new();
+        codec
+            .encode(&b"\x08\x01\x10\x02"[..], &mut encoded)
+            .unwrap();
+        let err = codec
+            .encode(&b"\x08\x01\x10\x02\x18"[..], &mut encoded)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(
+            codec.decode(&mut encoded).unwrap().unwrap(),
+            &b"\x08\x01\x10\x02"[..]
+        );
+
+        let mut src = BytesMut::from(&b"\x05\x08\x01"[..]);
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.into_inner().unwrap().to_string(),
+            "message is 5 bytes but at most 4 bytes are allowed"
+        );
+
+        codec.set_max_frame_length(5);
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+    }
+
+    /// Test gRPC framing against the Length-Prefixed-Message layout of the gRPC HTTP/2 protocol
+    #[test]
+    fn test_grpc_framing() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(bytes, tag = "2")]
+            baz: Vec<u8>,
+        }
+
+        let small = Foo {
+            bar: 1,
+            baz: vec![],
+        }
+        .encode_to_vec();
+        let large = Foo {
+            bar: 2,
+            baz: vec![0xAB; 300],
+        }
+        .encode_to_vec();
+
+        // Compressed-Flag 0, Message-Length 2 (big-endian), Message.
+        let small_frame = encode_grpc_frame(&small);
+        assert_eq!(small_frame, [0x00, 0x00, 0x00, 0x00, 0x02, 0x08, 0x01]);
+        // Message-Length 305 = 0x0131.
+        let large_frame = encode_grpc_frame(&large);
+        assert_eq!(large_frame[..5], [0x00, 0x00, 0x00, 0x01, 0x31]);
+        assert_eq!(large_frame.len(), 5 + 305);
+
+        assert_eq!(decode_grpc_frame(&small_frame), Some((false, &small[..])));
+        assert_eq!(decode_grpc_frame(&large_frame), Some((false, &large[..])));
+
+        let mut compressed = small_frame.clone();
+        compressed[0] = 1;
+        assert_eq!(decode_grpc_frame(&compressed), Some((true, &small[..])));
+        compressed[0] = 2;
+        assert_eq!(decode_grpc_frame(&compressed), None);
+        assert_eq!(decode_grpc_frame(&small_frame[..4]), None);
+        assert_eq!(decode_grpc_frame(&large_frame[..300]), None);
+
+        let empty_frame = encode_grpc_frame(&[]);
+        assert_eq!(empty_frame, [0x00; 5]);
+
+        let stream = [&small_frame[..], &large_frame, &empty_frame, &small_frame].concat();
+        assert_eq!(
+            split_grpc_stream(&stream),
+            [&small[..], &large[..], &[][..], &small[..]]
+        );
+        assert_eq!(
+            split_grpc_stream(&stream[..stream.len() - 1]),
+            [&small[..], &large[..], &[][..]]
+        );
+        assert!(split_grpc_stream(&[]).is_empty());
+    }
+
+    /// Test pretty printing a message with a nested message
+    #[test]
+    fn test_pretty_print_wire_format() {
+        #[derive(Message)]
+        struct Inner {
+            #[prost(sint64, tag = "1")]
+            value: i64,
+        }
+
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(bytes, tag = "2")]
+            baz: Vec<u8>,
+            #[prost(double, tag = "3")]
+            qux: f64,
+            #[prost(message, optional, tag = "4")]
+            inner: Option<Inner>,
+            #[prost(fixed32, tag = "5")]
+            quux: u32,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: (0..20).collect(),
+            qux: 1.0,
+            inner: Some(Inner { value: -2 }),
+            quux: 0xdeadbeef,
+        };
+        let enc = foo.encode_to_vec();
+
+        assert_eq!(
+            pretty_print_wire_format(&enc),
+            "1 (varint): 300\n\
+             2 (length-delimited): 20 bytes: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f ...\n\
+             3 (64-bit): 00 00 00 00 00 00 f0 3f\n\
+             4 (length-delimited): 2 bytes: 08 03\n\
+             5 (32-bit): ef be ad de\n"
+        );
+        assert_eq!(
+            pretty_print_wire_format_recursive(&enc, 1),
+            "1 (varint): 300\n\
+             2 (length-delimited): 20 bytes: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f ...\n\
+             3 (64-bit): 00 00 00 00 00 00 f0 3f\n\
+             4 (length-delimited): 2 bytes: 08 03\n  \
+             1 (varint): 3\n\
+             5 (32-bit): ef be ad de\n"
+        );
+
+        assert_eq!(pretty_print_wire_format(&[]), "");
+        assert_eq!(
+            pretty_print_wire_format(b"\x08\x01\x12\x05\x68"),
+            "1 (varint): 1\nerror: length at offset 3 runs past the end of the message\n"
+        );
+
+        // Deeply nested input is written without recursing, even on a small stack.
+        let mut deep = b"\x08\x01".to_vec();
+        for _ in 0..2000 {
+            deep = [create_header(1, 2, &deep), deep].concat();
+        }
+        let pretty = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(move || pretty_print_wire_format_recursive(&deep, u32::MAX))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(pretty.lines().count(), 2001);
+        assert!(pretty.ends_with(&format!("{:4000}1 (varint): 1\n", "")));
+    }
+
+    /// Test that groups are shown by every printer, and do not hide the fields after them
+    #[test]
+    fn test_pretty_print_groups() {
+        // Field 1, group 5 holding field 2 and a nested group 6, field 3 and field 1 again.
+        let encoded_message = b"\x08\x01\x2b\x10\x03\x33\x34\x2c\x1a\x02\x68\x69\x08\x02";
+
+        assert_eq!(
+            pretty_print_wire_format(encoded_message),
+            "1 (varint): 1\n\
+             5 (group): 4 bytes: 10 03 33 34\n\
+             3 (length-delimited): 2 bytes: 68 69\n\
+             1 (varint): 2\n"
+        );
+        assert_eq!(
+            pretty_print_wire_format_recursive(encoded_message, 2),
+            "1 (varint): 1\n\
+             5 (group): 4 bytes: 10 03 33 34\n  \
+             2 (varint): 3\n  \
+             6 (group): 0 bytes\n\
+             3 (length-delimited): 2 bytes: 68 69\n  \
+             13 (varint): 105\n\
+             1 (varint): 2\n"
+        );
+        assert_eq!(
+            dump_message_as_text(encoded_message),
+            "1: 1\n5: { 2: 3 6: {} }\n3: \"hi\"\n1: 2\n"
+        );
+        let options = DumpOptions {
+            max_depth: 0,
+            ..DumpOptions::default()
+        };
+        assert_eq!(
+            dump_message_as_text_with_options(encoded_message, &options),
+            "1: 1\n5: 0x10033334\n3: \"hi\"\n1: 2\n"
+        );
+        let dump = message_to_hex_dump(encoded_message);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("00000002  2b 10 03 33 34 2c  "));
+        assert!(lines[1].ends_with("  tag 5 (group, 4 bytes)"));
+        assert!(lines[2].ends_with("  tag 3 (length-delimited, 2 bytes) (nested message)"));
+    }
+
+    /// Test that the tag helpers agree with prost's encoding
+    #[test]
+    fn test_tag_helpers() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(fixed64, tag = "20")]
+            baz: u64,
+            #[prost(fixed32, tag = "300")]
+            qux: u32,
+        }
+
+        let foo = Foo {
+            bar: 1,
+            baz: 2,
+            qux: 3,
+        };
+        let enc = foo.encode_to_vec();
+
+        let mut offset = 0;
+        for (field_number, variant, value_len) in [
+            (1, Variant::Varint, 1),
+            (20, Variant::SixtyFourBit, 8),
+            (300, Variant::ThirtyTwoBit, 4),
+        ] {
+            let tag = encode_tag(field_number, variant);
+            assert_eq!(tag, create_header(field_number, variant.into(), &[]));
+            assert_eq!(enc[offset..offset + tag.len()], tag);
+
+            let decoded = extract_packed_varint(&tag).unwrap()[0];
+            assert_eq!(field_number_from_tag(decoded), field_number);
+            assert_eq!(wire_type_from_tag(decoded), u64::from(variant));
+            assert_eq!(
+                wire_type_name(wire_type_from_tag(decoded)),
+                variant.to_string()
+            );
+
+            offset += tag.len() + value_len;
+        }
+        assert_eq!(offset, enc.len());
+    }
+
+    /// Test the typed accessors of FieldView
+    #[test]
+    fn test_field_view() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(int64, tag = "1")]
+            bar: i64,
+            #[prost(sint64, tag = "2")]
+            baz: i64,
+            #[prost(float, tag = "3")]
+            qux: f32,
+            #[prost(double, tag = "4")]
+            quux: f64,
+            #[prost(bool, tag = "5")]
+            corge: bool,
+            #[prost(string, tag = "6")]
+            grault: String,
+            #[prost(bytes, tag = "7")]
+            garply: Vec<u8>,
+        }
+
+        let foo = Foo {
+            bar: -5,
+            baz: -6,
+            qux: 1.5,
+            quux: -2.25,
+            corge: true,
+            grault: "testing".to_string(),
+            garply: vec![0xFF, 0xFE],
+        };
+        let enc = foo.encode_to_vec();
+
+        let bar = extract_field_view(&enc, 1).unwrap();
+        assert_eq!(bar.tag, 1);
+        assert_eq!(bar.wire_type, Variant::Varint);
+        assert_eq!(bar.as_i64(), Some(-5));
+        assert_eq!(bar.as_u64(), Some(-5i64 as u64));
+        assert_eq!(bar.as_bytes(), extract_field_by_tag(&enc, 1).unwrap());
+
+        let baz = extract_field_view(&enc, 2).unwrap();
+        assert_eq!(baz.as_sint64(), Some(-6));
+
+        let qux = extract_field_view(&enc, 3).unwrap();
+        assert_eq!(qux.as_f32(), Some(1.5));
+        assert_eq!(qux.as_f64(), None);
+        assert_eq!(qux.as_u64(), None);
+
+        let quux = extract_field_view(&enc, 4).unwrap();
+        assert_eq!(quux.as_f64(), Some(-2.25));
+        assert_eq!(quux.as_f32(), None);
+
+        let corge = extract_field_view(&enc, 5).unwrap();
+        assert_eq!(corge.as_bool(), Some(true));
+        assert_eq!(corge.as_str(), None);
+
+        let grault = extract_field_view(&enc, 6).unwrap();
+        assert_eq!(grault.as_str(), Some("testing"));
+        assert_eq!(grault.as_bool(), None);
+        assert_eq!(grault.as_i64(), None);
+
+        let garply = extract_field_view(&enc, 7).unwrap();
+        assert_eq!(garply.as_str(), None);
+        assert_eq!(garply.as_bytes(), [0xFF, 0xFE]);
+
+        assert!(extract_field_view(&enc, 8).is_none());
+        assert_eq!(
+            FieldIterator::new(&enc).collect::<Vec<_>>(),
+            [bar, baz, qux, quux, corge, grault, garply]
+        );
+    }
+
+    /// Test parsing and re-encoding field headers
+    #[test]
+    fn test_field_header() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "16")]
+            baz: String,
+            #[prost(fixed32, tag = "2047")]
+            qux: u32,
+        }
+
+        let foo = Foo {
+            bar: 1,
+            baz: "testing".to_string(),
+            qux: 3,
+        };
+        let enc = foo.encode_to_vec();
+
+        let mut offset = 0;
+        let mut headers = Vec::new();
+        for field in FieldIterator::new(&enc) {
+            let (header, value_offset) = parse_field_header(&enc, offset).unwrap();
+            assert_eq!(header.field_number, field.tag);
+            assert_eq!(header.wire_type, field.wire_type);
+            assert_eq!(header.encode(), enc[offset..value_offset]);
+
+            let mut buf = [0; 10];
+            let len = header.encode_to_slice(&mut buf).unwrap();
+            assert_eq!(buf[..len], enc[offset..value_offset]);
+            assert_eq!(header.encode_to_slice(&mut buf[..len - 1]), None);
+
+            headers.push(header);
+            offset += extract_full_field_byte_range(&enc[offset..], field.tag)
+                .unwrap()
+                .1;
+        }
+        assert_eq!(
+            headers,
+            [
+                FieldHeader {
+                    field_number: 1,
+                    wire_type: Variant::Varint
+                },
+                FieldHeader {
+                    field_number: 16,
+                    wire_type: Variant::LengthDelimited
+                },
+                FieldHeader {
+                    field_number: 2047,
+                    wire_type: Variant::ThirtyTwoBit
+                },
+            ]
+        );
+
+        assert_eq!(parse_field_header(&enc, enc.len()), None);
+        assert_eq!(parse_field_header(&[0x0c], 0), None);
+        assert_eq!(parse_field_header(&[0x80], 0), None);
+    }
+
+    /// Test encoding complete fields against prost
+    #[test]
+    fn test_encode_field() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(double, tag = "3")]
+            qux: f64,
+            #[prost(bytes, tag = "20")]
+            quux: Vec<u8>,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: "testing".to_string(),
+            qux: 1.5,
+            quux: vec![0xAB; 200],
+        };
+        let enc = foo.encode_to_vec();
+
+        let fields = [
+            encode_varint_field(1, 300),
+            encode_string_field(2, "testing"),
+            encode_field(3, Variant::SixtyFourBit, &encode_double(1.5)),
+            encode_field(20, Variant::LengthDelimited, &[0xAB; 200]),
+        ];
+        assert_eq!(fields.concat(), enc);
+
+        for field in &fields {
+            let (header, value_offset) = parse_field_header(field, 0).unwrap();
+            let payload = extract_field_by_tag(field, header.field_number).unwrap();
+            assert_eq!(
+                field[..value_offset],
+                encode_tag(header.field_number, header.wire_type)
+            );
+            assert_eq!(
+                *field,
+                [
+                    create_header(header.field_number, header.wire_type.into(), payload),
+                    payload.to_vec()
+                ]
+                .concat()
+            );
+        }
+    }
+
+    /// Test forwarding full field records from one message to another
+    #[test]
+    fn test_extract_field_with_full_record() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(bytes, tag = "3")]
+            qux: Vec<u8>,
+        }
+
+        let source = Foo {
+            bar: 300,
+            baz: "testing".to_string(),
+            qux: vec![0xAB; 200],
+        }
+        .encode_to_vec();
+        let mut target = Foo {
+            bar: 1,
+            baz: String::new(),
+            qux: vec![],
+        }
+        .encode_to_vec();
+
+        for tag in [2, 3] {
+            let record = extract_field_with_full_record(&source, tag).unwrap();
+            target.extend_from_slice(record);
+            assert_eq!(
+                extract_field_by_tag(&target, tag),
+                extract_field_by_tag(&source, tag)
+            );
+        }
+
+        let decoded = Foo::decode(target.as_slice()).unwrap();
+        assert_eq!(decoded.bar, 1);
+        assert_eq!(decoded.baz, "testing");
+        assert_eq!(decoded.qux, vec![0xAB; 200]);
+
+        let record = extract_field_with_full_record(&source, 1).unwrap();
+        assert_eq!(record, [0x08, 0xAC, 0x02]);
+        assert_eq!(extract_field_with_full_record(&source, 4), None);
+    }
+
+    /// Test transforming fields based on their old values
+    #[test]
+    fn test_transform_field() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(fixed32, tag = "3")]
+            qux: u32,
+            #[prost(string, tag = "4")]
+            quux: String,
+        }
+
+        let foo = Foo {
+            bar: 127,
+            baz: "testing".to_string(),
+            qux: 7,
+            quux: "last".to_string(),
+        };
+        let mut enc = foo.encode_to_vec();
+
+        // The varint grows from one to two bytes.
+        assert!(transform_field(&mut enc, 1, |old| {
+            encode_varint(extract_packed_varint(old).unwrap()[0] + 1)
+        }));
+        // The length prefix grows from one to two bytes.
+        assert!(transform_field(&mut enc, 2, |old| old.repeat(20)));
+        assert!(transform_field_fixed(&mut enc, 3, |old| {
+            let qux = u32::from_le_bytes(old.try_into().unwrap());
+            (qux * 2).to_le_bytes()
+        }));
+        assert!(transform_field(&mut enc, 4, |old| old.to_ascii_uppercase()));
+
+        let decoded = Foo::decode(enc.as_slice()).unwrap();
+        assert_eq!(decoded.bar, 128);
+        assert_eq!(decoded.baz, "testing".repeat(20));
+        assert_eq!(decoded.qux, 14);
+        assert_eq!(decoded.quux, "LAST");
+
+        let before = enc.clone();
+        assert!(!transform_field(&mut enc, 5, |_| unreachable!()));
+        assert!(!transform_field_fixed(&mut enc, 5, |_| -> [u8; 4] {
+            unreachable!()
+        }));
+        assert_eq!(enc, before);
+
+        // Values that do not fit the wire type of the field are rejected.
+        assert!(!transform_field_fixed(&mut enc, 3, |_| [0; 8]));
+        assert!(!transform_field(&mut enc, 3, |_| vec![1, 2, 3]));
+        assert!(!transform_field(&mut enc, 1, |_| vec![0x80, 0x80]));
+        assert!(!transform_field(&mut enc, 1, |_| vec![0x01, 0x02]));
+        assert_eq!(enc, before);
+
+        // Shrinking a length-delimited field shrinks its length prefix.
+        assert!(transform_field(&mut enc, 2, |_| b"short".to_vec()));
+        assert_eq!(extract_field_by_tag(&enc, 2), Some(&b"short"[..]));
+        assert_eq!(Foo::decode(enc.as_slice()).unwrap().quux, "LAST");
+    }
+
+    /// Test converting a message view to typed JSON values
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_message_view_as_json_value() {
+        use crate::WireTypeHint;
+        use serde_json::json;
+        use std::collections::HashMap;
+
+        #[derive(Message)]
+        struct Inner {
+            #[prost(string, tag = "1")]
+            name: String,
+        }
+
+        #[derive(Message)]
+        struct Foo {
+            #[prost(int64, tag = "1")]
+            a: i64,
+            #[prost(sint32, tag = "2")]
+            b: i32,
+            #[prost(bool, tag = "3")]
+            c: bool,
+            #[prost(sfixed32, tag = "4")]
+            d: i32,
+            #[prost(double, tag = "5")]
+            e: f64,
+            #[prost(bytes, tag = "6")]
+            f: Vec<u8>,
+            #[prost(message, optional, tag = "7")]
+            g: Option<Inner>,
+            #[prost(uint64, repeated, packed = "false", tag = "8")]
+            h: Vec<u64>,
+            #[prost(float, tag = "9")]
+            i: f32,
+        }
+
+        let enc = Foo {
+            a: -1,
+            b: -3,
+            c: true,
+            d: -4,
+            e: 2.5,
+            f: b"hi".to_vec(),
+            g: Some(Inner {
+                name: "x".to_string(),
+            }),
+            h: vec![7, 8, 9],
+            i: f32::NAN,
+        }
+        .encode_to_vec();
+        let message = MessageView::new(&enc);
+
+        assert_eq!(
+            message.as_json_value(&HashMap::new()),
+            json!({
+                "1": u64::MAX,
+                "2": 5,
+                "3": 1,
+                "4": (-4i32) as u32,
+                "5": 2.5f64.to_bits(),
+                "6": "aGk=",
+                "7": { "1": "eA==" },
+                "8": [7, 8, 9],
+                "9": f32::NAN.to_bits(),
+            })
+        );
+
+        let hints = HashMap::from([
+            (1, WireTypeHint::Int64),
+            (2, WireTypeHint::SInt64),
+            (3, WireTypeHint::Bool),
+            (4, WireTypeHint::SFixed32),
+            (5, WireTypeHint::Double),
+            (6, WireTypeHint::String),
+            (7, WireTypeHint::Bytes),
+            (8, WireTypeHint::UInt64),
+            (9, WireTypeHint::Float),
+        ]);
+        assert_eq!(
+            message.as_json_value(&hints),
+            json!({
+                "1": -1,
+                "2": -3,
+                "3": true,
+                "4": -4,
+                "5": 2.5,
+                "6": "hi",
+                "7": "CgF4",
+                "8": [7, 8, 9],
+                "9": null,
+            })
+        );
+
+        // Hints that do not match the wire type are ignored, and printable text is only taken for
+        // a message when hinted.
+        let hints = HashMap::from([(1, WireTypeHint::Double), (6, WireTypeHint::Message)]);
+        let value = message.as_json_value(&hints);
+        assert_eq!(value["1"], json!(u64::MAX));
+        assert_eq!(value["6"], json!({ "13": 105 }));
+
+        // Base64 padding for every remainder length.
+        for (payload, base64) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v")] {
+            let enc = [create_header(1, 2, payload.as_bytes()), payload.into()].concat();
+            assert_eq!(
+                MessageView::new(&enc).as_json_value(&HashMap::new()),
+                json!({ "1": base64 })
+            );
+        }
+
+        assert_eq!(
+            MessageView::new(&[]).as_json_value(&HashMap::new()),
+            json!({})
+        );
+    }
+
+    /// Test converting messages to JSON field maps and back
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_field_map_round_trip() {
+        use crate::{encode_message_as_field_map, field_map_to_encoded_message};
+        use serde_json::json;
+
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, repeated, tag = "2")]
+            baz: Vec<String>,
+            #[prost(double, tag = "10")]
+            qux: f64,
+        }
+
+        let foo = Foo {
+            bar: 300,
+            baz: vec!["a".to_string(), "bc".to_string()],
+            qux: 1.0,
+        };
+        let enc = foo.encode_to_vec();
+
+        let map = encode_message_as_field_map(&enc);
+        assert_eq!(
+            map,
+            json!({
+                "1": [{ "wire_type": "Varint", "value": "ac02" }],
+                "2": [
+                    { "wire_type": "LengthDelimited", "value": "61" },
+                    { "wire_type": "LengthDelimited", "value": "6263" },
+                ],
+                "10": [{ "wire_type": "SixtyFourBit", "value": "000000000000f03f" }],
+            })
+        );
+        assert_eq!(field_map_to_encoded_message(&map).unwrap(), enc);
+
+        // The JSON survives a round trip through text.
+        let text = serde_json::to_string(&map).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(field_map_to_encoded_message(&parsed).unwrap(), enc);
+
+        // The largest field number is accepted.
+        let largest = json!({ "536870911": [{ "wire_type": "Varint", "value": "01" }] });
+        assert_eq!(
+            field_map_to_encoded_message(&largest).unwrap(),
+            [0xf8, 0xff, 0xff, 0xff, 0x0f, 0x01]
+        );
+
+        // Groups have no entry in the map, so the round trip drops them.
+        let grouped = b"\x08\x01\x2b\x10\x03\x2c";
+        let map = encode_message_as_field_map(grouped);
+        assert_eq!(
+            map,
+            json!({ "1": [{ "wire_type": "Varint", "value": "01" }] })
+        );
+        assert_eq!(field_map_to_encoded_message(&map).unwrap(), b"\x08\x01");
+
+        let header = FieldHeader {
+            field_number: 2,
+            wire_type: Variant::LengthDelimited,
+        };
+        let header_json = serde_json::to_value(header).unwrap();
+        assert_eq!(
+            header_json,
+            json!({ "field_number": 2, "wire_type": "LengthDelimited" })
+        );
+        assert_eq!(
+            serde_json::from_value::<FieldHeader>(header_json).unwrap(),
+            header
+        );
+
+        for invalid in [
+            json!([]),
+            json!({ "x": [] }),
+            json!({ "0": [{ "wire_type": "Varint", "value": "01" }] }),
+            json!({ "536870912": [{ "wire_type": "Varint", "value": "01" }] }),
+            json!({ "2305843009213693953": [{ "wire_type": "Varint", "value": "01" }] }),
+            json!({ "1": [{ "wire_type": "Group", "value": "01" }] }),
+            json!({ "1": [{ "wire_type": "Varint", "value": "0" }] }),
+            json!({ "1": [{ "wire_type": "Varint", "value": "+1" }] }),
+            json!({ "1": [{ "wire_type": "Varint", "value": "0101" }] }),
+            json!({ "1": [{ "wire_type": "ThirtyTwoBit", "value": "01" }] }),
+        ] {
+            assert_eq!(field_map_to_encoded_message(&invalid), None, "{}", invalid);
+        }
+    }
+
+    /// Test diffing two versions of a message and re-applying the diff
+    #[test]
+    fn test_diff_messages() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, repeated, tag = "2")]
+            baz: Vec<String>,
+            #[prost(uint64, repeated, packed = "false", tag = "3")]
+            qux: Vec<u64>,
+            #[prost(string, tag = "4")]
+            quux: String,
+        }
+
+        let before = Foo {
+            bar: 1,
+            baz: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            qux: vec![7],
+            quux: "same".to_string(),
+        }
+        .encode_to_vec();
+        let after = Foo {
+            bar: 2,
+            baz: vec!["a".to_string(), "x".to_string()],
+            qux: vec![7, 8, 9],
+            quux: "same".to_string(),
+        }
+        .encode_to_vec();
+
+        let diff = diff_messages(&before, &after).unwrap();
+        assert_eq!(
+            diff,
+            [
+                FieldChange::Modified {
+                    tag: 1,
+                    position: 0,
+                    wire_type: Variant::Varint,
+                    old: &[0x01],
+                    new: &[0x02],
+                },
+                FieldChange::Modified {
+                    tag: 2,
+                    position: 1,
+                    wire_type: Variant::LengthDelimited,
+                    old: b"b",
+                    new: b"x",
+                },
+                FieldChange::Removed {
+                    tag: 2,
+                    position: 2,
+                    value: b"c",
+                },
+                FieldChange::Added {
+                    tag: 3,
+                    position: 1,
+                    wire_type: Variant::Varint,
+                    value: &[0x08],
+                },
+                FieldChange::Added {
+                    tag: 3,
+                    position: 2,
+                    wire_type: Variant::Varint,
+                    value: &[0x09],
+                },
+            ]
+        );
+
+        let applied = apply_diff(&before, &diff).unwrap();
+        let decoded = Foo::decode(applied.as_slice()).unwrap();
+        assert_eq!(decoded.bar, 2);
+        assert_eq!(decoded.baz, ["a", "x"]);
+        assert_eq!(decoded.qux, [7, 8, 9]);
+        assert_eq!(decoded.quux, "same");
+        assert_eq!(canonicalize_message(&applied), canonicalize_message(&after));
+
+        assert!(diff_messages(&before, &before).unwrap().is_empty());
+        assert_eq!(apply_diff(&before, &[]).unwrap(), before);
+
+        // A changed wire type with the same bytes is a modification too.
+        assert_eq!(
+            diff_messages(&[0x08, 0x01], &[0x0a, 0x01, 0x01]).unwrap(),
+            [FieldChange::Modified {
+                tag: 1,
+                position: 0,
+                wire_type: Variant::LengthDelimited,
+                old: &[0x01],
+                new: &[0x01],
+            }]
+        );
+
+        // Malformed input is an error instead of being cut short.
+        assert_eq!(
+            diff_messages(b"\x08\x01\xff", b"\x08\x01"),
+            Err(WireError::TruncatedMessage { offset: 2 })
+        );
+        assert!(diff_messages(b"\x08\x01", b"\x0b\x08\x01").is_err());
+        assert!(apply_diff(b"\x08\x01\x12\x05\x68", &[]).is_err());
+
+        // Groups are not diffed, and the ones in the base are kept, including trailing ones.
+        let base = b"\x0b\x08\x01\x0c\x10\x01\x1b\x1c";
+        let diff = diff_messages(base, b"\x10\x02").unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(
+            apply_diff(base, &diff).unwrap(),
+            b"\x0b\x08\x01\x0c\x10\x02\x1b\x1c"
+        );
+
+        // So an empty diff does not mean the messages are equal.
+        let grouped = b"\x08\x01\x2b\x10\x03\x2c\x1a\x02\x68\x69\x08\x02";
+        let without_group = b"\x08\x01\x1a\x02\x68\x69\x08\x02";
+        assert!(diff_messages(grouped, without_group).unwrap().is_empty());
+        assert!(!messages_equal(grouped, without_group));
+    }
+
+    /// Test map entries against prost's map encoding
+    #[test]
+    fn test_map_entries() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(btree_map = "string, uint64", tag = "2")]
+            baz: std::collections::BTreeMap<String, u64>,
+            #[prost(btree_map = "uint32, string", tag = "3")]
+            qux: std::collections::BTreeMap<u32, String>,
+        }
+
+        let foo = Foo {
+            bar: 1,
+            baz: [("a".to_string(), 1), ("bc".to_string(), 300)].into(),
+            qux: [(7, "seven".to_string()), (0, "zero".to_string())].into(),
+        };
+        let enc = foo.encode_to_vec();
+
+        let baz = extract_map_entries(&enc, 2);
+        assert_eq!(
+            baz,
+            [
+                (&b"a"[..], &encode_varint(1)[..]),
+                (&b"bc"[..], &encode_varint(300)[..]),
+            ]
+        );
+        // prost omits the default key 0, so it is returned as an empty slice.
+        let qux = extract_map_entries(&enc, 3);
+        assert_eq!(qux, [(&[][..], &b"zero"[..]), (&[0x07][..], &b"seven"[..])]);
+        assert!(extract_map_entries(&enc, 4).is_empty());
+
+        let mut rebuilt = encode_varint_field(1, 1);
+        for (key, value) in [("a", 1), ("bc", 300)] {
+            let entry = encode_map_entry(
+                1,
+                Variant::LengthDelimited,
+                key.as_bytes(),
+                2,
+                Variant::Varint,
+                &encode_varint(value),
+            );
+            append_field(&mut rebuilt, 2, Variant::LengthDelimited, &entry);
+        }
+        for (key, value) in [(0, "zero"), (7, "seven")] {
+            let entry = encode_map_entry(
+                1,
+                Variant::Varint,
+                &encode_varint(key),
+                2,
+                Variant::LengthDelimited,
+                value.as_bytes(),
+            );
+            append_field(&mut rebuilt, 3, Variant::LengthDelimited, &entry);
+        }
+
+        let decoded = Foo::decode(rebuilt.as_slice()).unwrap();
+        assert_eq!(decoded.bar, foo.bar);
+        assert_eq!(decoded.baz, foo.baz);
+        assert_eq!(decoded.qux, foo.qux);
+    }
+
+    /// Test consuming a message with the slice-based varint functions
+    #[test]
+    fn test_decode_varint_from_slice() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, repeated, tag = "1")]
+            bar: Vec<u64>,
+        }
+
+        let values = [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX];
+        let foo = Foo {
+            bar: values.to_vec(),
+        };
+        let enc = foo.encode_to_vec();
+
+        let packed = extract_field_by_tag(&enc, 1).unwrap();
+        let mut rest = packed;
+        let mut decoded = Vec::new();
+        while !rest.is_empty() {
+            let (value, next) = decode_varint_from_slice(rest).unwrap();
+            decoded.push(value);
+            rest = next;
+        }
+        assert_eq!(decoded, values);
+
+        let mut reencoded = Vec::new();
+        for value in values {
+            let varint = encode_varint_exact(value);
+            let len = encoded_varint_len(value);
+            assert_eq!(varint[..len], encode_varint(value));
+            assert!(varint[len..].iter().all(|byte| *byte == 0));
+            reencoded.extend_from_slice(&varint[..len]);
+        }
+        assert_eq!(reencoded, packed);
+
+        assert_eq!(decode_varint_from_slice(&[]), None);
+        assert_eq!(decode_varint_from_slice(&[0xFF; 11]), None);
+    }
+
+    /// Test that the allocation-free varint encoders match encode_varint
+    #[test]
+    fn test_encode_varint_array() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+        }
+
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let (len, varint) = encode_varint_array(value);
+            assert_eq!(varint[..len], encode_varint(value));
+            assert_eq!(len, measure_encoded_varint_size(value));
+
+            let enc = Foo { bar: value }.encode_to_vec();
+            if value != 0 {
+                assert_eq!(extract_field_by_tag(&enc, 1), Some(&varint[..len]));
+            }
+
+            #[cfg(feature = "bytes")]
+            {
+                let mut buf = bytes::BytesMut::new();
+                crate::encode_varint_buf(value, &mut buf);
+                assert_eq!(buf, varint[..len]);
+            }
+        }
+    }
+
+    /// Test that the two-pass replacement matches the protobuf encoding of the replaced message.
+    #[test]
+    fn test_replace_field_with_two_pass() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            bar: u64,
+            #[prost(string, tag = "2")]
+            baz: String,
+            #[prost(fixed32, tag = "3")]
+            qux: u32,
+        }
+
+        let foo = Foo {
+            bar: 1,
+            baz: "a".repeat(50),
+            qux: 7,
+        };
+        let enc = foo.encode_to_vec();
+
+        for baz in ["", "short", &"b".repeat(50), &"b".repeat(300)] {
+            let mut replaced = enc.clone();
+            let replace_with =
+                [create_header(2, 2, baz.as_bytes()), baz.as_bytes().to_vec()].concat();
+            assert_eq!(
+                replace_field_with_two_pass(&mut replaced, 2, &replace_with),
+                Some(foo.baz.clone().into_bytes())
+            );
+
+            let decoded = Foo::decode(replaced.as_slice()).unwrap();
+            assert_eq!(
+                (decoded.bar, decoded.baz.as_str(), decoded.qux),
+                (1, baz, 7)
+            );
+        }
+
+        let mut missing = enc.clone();
+        assert_eq!(
+            replace_field_with_two_pass(&mut missing, 4, b"\x20\x01"),
+            None
+        );
+        assert_eq!(missing, enc);
+    }
+
+    /// Test that replacing a field in a nested message updates the outer length prefix.
+    #[test]
+    fn test_replace_field_in_nested() {
+        #[derive(Message, PartialEq)]
+        struct Inner {
+            #[prost(string, tag = "1")]
+            name: String,
+        }
+
+        #[derive(Message, PartialEq)]
+        struct Middle {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(message, optional, tag = "2")]
+            inner: Option<Inner>,
+            #[prost(string, tag = "3")]
+            note: String,
+        }
+
+        #[derive(Message, PartialEq)]
+        struct Outer {
+            #[prost(string, tag = "1")]
+            title: String,
+            #[prost(message, optional, tag = "5")]
+            middle: Option<Middle>,
+            #[prost(uint64, tag = "6")]
+            trailer: u64,
+        }
+
+        let outer = Outer {
+            title: "outer".to_string(),
+            middle: Some(Middle {
+                id: 7,
+                inner: Some(Inner {
+                    name: "inner".to_string(),
+                }),
+                note: "note".to_string(),
+            }),
+            trailer: 42,
+        };
+        let mut enc = outer.encode_to_vec();
+        let old_inner = Inner {
+            name: "inner".to_string(),
+        }
+        .encode_to_vec();
+
+        // Replacing the whole innermost message makes the middle message grow past 127 bytes, so
+        // its length prefix needs a second byte.
+        let inner = Inner {
+            name: "a much longer inner name".repeat(10),
+        };
+        let new_field = [
+            create_header(2, 2, &inner.encode_to_vec()),
+            inner.encode_to_vec(),
+        ]
+        .concat();
+        assert_eq!(
+            replace_field_in_nested(&mut enc, 5, 2, &new_field),
+            Some(old_inner)
+        );
+
+        let decoded = Outer::decode(enc.as_slice()).unwrap();
+        let middle = decoded.middle.unwrap();
+        assert_eq!(decoded.title, "outer");
+        assert_eq!(decoded.trailer, 42);
+        assert_eq!(middle.id, 7);
+        assert_eq!(middle.note, "note");
+        assert_eq!(middle.inner, Some(inner));
+
+        // Shrinking it again gives back the original encoding.
+        let new_field = [
+            create_header(2, 2, b"\x0a\x05inner"),
+            b"\x0a\x05inner".to_vec(),
+        ]
+        .concat();
+        assert!(replace_field_in_nested(&mut enc, 5, 2, &new_field).is_some());
+        assert_eq!(enc, outer.encode_to_vec());
+
+        assert_eq!(replace_field_in_nested(&mut enc, 5, 4, &new_field), None);
+        assert_eq!(replace_field_in_nested(&mut enc, 6, 1, &new_field), None);
+        assert_eq!(enc, outer.encode_to_vec());
+    }
+
+    /// Test that the packed `add_*` methods of `MessageBuilder` match prost's packed encoding.
+    #[test]
+    fn test_message_builder_packed() {
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(fixed32, repeated, tag = "1")]
+            a: Vec<u32>,
+            #[prost(fixed64, repeated, tag = "2")]
+            b: Vec<u64>,
+            #[prost(float, repeated, tag = "3")]
+            c: Vec<f32>,
+            #[prost(double, repeated, tag = "4")]
+            d: Vec<f64>,
+            #[prost(sint32, repeated, tag = "5")]
+            e: Vec<i32>,
+            #[prost(sint64, repeated, tag = "6")]
+            f: Vec<i64>,
+            #[prost(float, repeated, tag = "7")]
+            g: Vec<f32>,
+        }
+
+        let foo = Foo {
+            a: vec![0, 7, u32::MAX],
+            b: vec![u64::MAX, 1],
+            c: vec![1.5, -0.0, f32::MAX, f32::MIN_POSITIVE, f32::INFINITY],
+            d: vec![-2.25, f64::EPSILON],
+            e: vec![0, -1, 1, i32::MIN, i32::MAX],
+            f: vec![i64::MIN, -300, i64::MAX],
+            g: Vec::new(),
+        };
+
+        let mut builder = MessageBuilder::new();
+        builder
+            .add_packed_fixed32(1, &foo.a)
+            .add_packed_fixed64(2, &foo.b)
+            .add_packed_float(3, &foo.c)
+            .add_packed_double(4, &foo.d)
+            .add_packed_sint32(5, &foo.e)
+            .add_packed_sint64(6, &foo.f)
+            .add_packed_float(7, &foo.g);
+        let enc = builder.finish();
+
+        assert_eq!(enc, foo.encode_to_vec());
+        let decoded = Foo::decode(enc.as_slice()).unwrap();
+        assert_eq!(decoded.c.len(), foo.c.len());
+        for (decoded, expected) in decoded.c.iter().zip(&foo.c) {
+            assert_eq!(decoded.to_bits(), expected.to_bits());
+        }
+        assert_eq!(decoded, foo);
+    }
+
+    /// Test extracting string fields as `&str`, with and without UTF-8 validation.
+    #[test]
+    fn test_extract_field_as_str() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(string, tag = "1")]
+            name: String,
+            #[prost(bytes = "vec", tag = "2")]
+            raw: Vec<u8>,
+            #[prost(uint64, tag = "3")]
+            id: u64,
+            #[prost(string, repeated, tag = "4")]
+            aliases: Vec<String>,
+        }
+
+        let foo = Foo {
+            name: "grüße".to_string(),
+            raw: vec![0x66, 0xFF, 0x6F],
+            id: 7,
+            aliases: vec!["a".to_string(), String::new(), "ünï".to_string()],
+        };
+        let mut enc = foo.encode_to_vec();
+
+        assert_eq!(extract_field_as_str(&enc, 1), Some("grüße"));
+        assert_eq!(extract_field_as_str(&enc, 2), None);
+        assert_eq!(extract_field_as_str(&enc, 3), None);
+        assert_eq!(extract_field_as_str(&enc, 5), None);
+
+        assert!(matches!(
+            extract_field_as_str_lossy(&enc, 1),
+            Some(std::borrow::Cow::Borrowed("grüße"))
+        ));
+        assert_eq!(
+            extract_field_as_str_lossy(&enc, 2).as_deref(),
+            Some("f\u{FFFD}o")
+        );
+        assert_eq!(extract_field_as_str_lossy(&enc, 3), None);
+
+        assert_eq!(extract_repeated_strings(&enc, 4), ["a", "", "ünï"]);
+
+        // Occurrences that are not valid UTF-8 are skipped.
+        enc.extend_from_slice(&[0x22, 0x01, 0xFF, 0x22, 0x01, 0x62]);
+        assert_eq!(extract_repeated_strings(&enc, 4), ["a", "", "ünï", "b"]);
+        assert!(extract_repeated_strings(&enc, 1) == ["grüße"]);
+    }
+
+    /// Test that filtering fields while iterating matches `keep_only_fields`.
+    #[test]
+    fn test_filter_fields_by_tag() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            a: u64,
+            #[prost(string, tag = "2")]
+            b: String,
+            #[prost(fixed32, tag = "3")]
+            c: u32,
+            #[prost(double, tag = "4")]
+            d: f64,
+            #[prost(string, repeated, tag = "5")]
+            e: Vec<String>,
+        }
+
+        let foo = Foo {
+            a: 300,
+            b: "x".repeat(200),
+            c: 7,
+            d: -1.5,
+            e: vec!["one".to_string(), "two".to_string()],
+        };
+        let enc = foo.encode_to_vec();
+
+        for tags in [&[][..], &[1], &[2, 4], &[5, 3, 1], &[1, 2, 3, 4, 5], &[6]] {
+            let filtered = collect_to_message(FieldIterator::new(&enc).filter_tags(tags));
+            assert_eq!(filtered, keep_only_fields(&enc, tags));
+        }
+
+        let tags: Vec<u64> = FieldIterator::new(&enc)
+            .filter_tags(&[5])
+            .map(|field| field.tag)
+            .collect();
+        assert_eq!(tags, [5, 5]);
+
+        // Fields from different messages can be combined into one.
+        let other = Foo {
+            a: 1,
+            b: "y".to_string(),
+            c: 0,
+            d: 0.0,
+            e: Vec::new(),
+        }
+        .encode_to_vec();
+        let combined = collect_to_message(
+            FieldIterator::new(&other)
+                .filter_tags(&[2])
+                .chain(FieldIterator::new(&enc).filter_tags(&[1])),
+        );
+        let decoded = Foo::decode(combined.as_slice()).unwrap();
+        assert_eq!((decoded.a, decoded.b.as_str()), (300, "y"));
+    }
+
+    /// Test that `MessageParseOptions` turn tolerated malformed input into errors.
+    #[test]
+    fn test_extract_field_by_tag_with_opts() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            a: u64,
+            #[prost(string, tag = "2")]
+            b: String,
+            #[prost(double, tag = "3")]
+            c: f64,
+        }
+
+        let enc = Foo {
+            a: 300,
+            b: "testing".to_string(),
+            c: 1.5,
+        }
+        .encode_to_vec();
+        let with_group = [
+            &[0x2b, 0x08, 0x02, 0x33, 0x34, 0x2c][..], // field 5: group with a nested group
+            &enc,
+        ]
+        .concat();
+        let unknown_wire_type = [&enc[..3], &[0x27], &enc[3..]].concat();
+        let truncated = &enc[..enc.len() - 2];
+
+        let default = MessageParseOptions::default();
+        for message in [&enc[..], &with_group, &unknown_wire_type, truncated] {
+            for tag_number in 1..=4 {
+                assert_eq!(
+                    extract_field_by_tag_with_opts(message, tag_number, &default),
+                    Ok(extract_field_by_tag(message, tag_number))
+                );
+            }
+        }
+
+        let strict = MessageParseOptions {
+            on_unknown_wire_type: UnknownWireTypeAction::Fail,
+            on_truncated: TruncatedMessageAction::Fail,
+            ..default
+        };
+        assert_eq!(
+            extract_field_by_tag_with_opts(&with_group, 3, &strict),
+            Ok(Some(&1.5f64.to_le_bytes()[..]))
+        );
+        assert_eq!(
+            extract_field_by_tag_with_opts(&unknown_wire_type, 2, &strict),
+            Err(WireError::UnknownWireType(7))
+        );
+        assert_eq!(
+            extract_field_by_tag_with_opts(truncated, 3, &strict),
+            Err(WireError::TruncatedMessage { offset: 12 })
+        );
+        assert_eq!(
+            extract_field_by_tag_with_opts(truncated, 1, &strict),
+            Ok(Some(&[0xAC, 0x02][..]))
+        );
+
+        std::thread_local! {
+            static WARNINGS: std::cell::RefCell<Vec<WireError>> = const {
+                std::cell::RefCell::new(Vec::new())
+            };
+        }
+        fn record_warning(err: &WireError) {
+            WARNINGS.with(|warnings| warnings.borrow_mut().push(*err));
+        }
+        let warn = MessageParseOptions {
+            on_unknown_wire_type: UnknownWireTypeAction::Warn(record_warning),
+            on_truncated: TruncatedMessageAction::Warn(record_warning),
+            ..default
+        };
+        assert_eq!(
+            extract_field_by_tag_with_opts(&unknown_wire_type, 2, &warn),
+            Ok(None)
+        );
+        assert_eq!(
+            extract_field_by_tag_with_opts(truncated, 3, &warn),
+            Ok(None)
+        );
+        assert_eq!(
+            extract_field_by_tag_with_opts(&enc, 3, &warn),
+            Ok(Some(&1.5f64.to_le_bytes()[..]))
+        );
+        assert_eq!(
+            WARNINGS.with(|warnings| warnings.take()),
+            [
+                WireError::UnknownWireType(7),
+                WireError::TruncatedMessage { offset: 12 }
+            ]
+        );
+
+        // Groups nested too deeply end the search like in `extract_field_by_tag`, unless
+        // configured to fail.
+        let nested = |depth| [vec![0x0b; depth], vec![0x0c; depth], vec![0x10, 0x01]].concat();
+        assert_eq!(
+            extract_field_by_tag_with_opts(&nested(100), 2, &default),
+            Ok(Some(&[0x01][..]))
+        );
+        assert_eq!(
+            extract_field_by_tag_with_opts(&nested(101), 2, &default),
+            Ok(extract_field_by_tag(&nested(101), 2))
+        );
+        assert_eq!(
+            extract_field_by_tag_with_opts(&nested(101), 2, &strict),
+            Ok(None)
+        );
+        let shallow = MessageParseOptions {
+            max_depth: 1,
+            on_depth_exceeded: DepthExceededAction::Fail,
+            ..default
+        };
+        assert_eq!(
+            extract_field_by_tag_with_opts(&with_group, 3, &shallow),
+            Err(WireError::RecursionLimitExceeded { offset: 3 })
+        );
+        assert_eq!(
+            extract_field_by_tag_with_opts(
+                &with_group,
+                3,
+                &MessageParseOptions {
+                    on_depth_exceeded: DepthExceededAction::Skip,
+                    ..shallow
+                }
+            ),
+            Ok(None)
+        );
+
+        let small = MessageParseOptions {
+            max_message_size: enc.len() - 1,
+            ..default
+        };
+        assert_eq!(
+            extract_field_by_tag_with_opts(&enc, 1, &small),
+            Err(WireError::MessageTooLarge {
+                size: enc.len(),
+                max_size: enc.len() - 1
+            })
+        );
+    }
+
+    /// Test walking into deeply nested messages with `MessageView`.
+    #[test]
+    fn test_message_view() {
+        #[derive(Clone, Message)]
+        struct Node {
+            #[prost(string, tag = "1")]
+            name: String,
+            #[prost(message, optional, boxed, tag = "2")]
+            child: Option<Box<Node>>,
+            #[prost(uint64, repeated, tag = "3")]
+            ids: Vec<u64>,
+        }
+
+        let mut node = Node {
+            name: "leaf".to_string(),
+            child: None,
+            ids: vec![1, 2],
+        };
+        for depth in 0..4 {
+            node = Node {
+                name: format!("level {}", depth),
+                child: Some(Box::new(node)),
+                ids: Vec::new(),
+            };
+        }
+        let enc = node.encode_to_vec();
+        let view = MessageView::new(&enc);
+
+        let leaf = view
+            .nested_field(2)
+            .and_then(|view| view.nested_field(2))
+            .and_then(|view| view.nested_field(2))
+            .and_then(|view| view.nested_field(2))
+            .unwrap();
+        assert_eq!(leaf.field(1).and_then(|field| field.as_str()), Some("leaf"));
+        assert_eq!(&leaf[1], b"leaf");
+        assert!(!leaf.has_field(2));
+        assert_eq!(leaf.nested_field(2), None);
+        assert_eq!(leaf.field_count(), 2);
+        assert_eq!(extract_packed_varint(&leaf[3]), Some(vec![1, 2]));
+
+        let level_2 = view.nested_field(2).unwrap().nested_field(2).unwrap();
+        assert_eq!(&level_2[1], b"level 1");
+        assert!(level_2.has_field(2));
+        assert_eq!(
+            level_2
+                .all_fields()
+                .map(|field| field.tag)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+
+        // A varint field is not a nested message.
+        let enc = [0x08, 0x01];
+        assert_eq!(MessageView::new(&enc).nested_field(1), None);
+    }
+
+    /// Test that indexing a `MessageView` by a missing tag number panics.
+    #[test]
+    #[should_panic(expected = "no field with tag number 2")]
+    fn test_message_view_index_missing() {
+        let enc = [0x08, 0x01];
+        let _ = &MessageView::new(&enc)[2];
+    }
+
+    /// Test replacing field values through infallible and fallible closures.
+    #[test]
+    fn test_replace_field_with_fn() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            a: u64,
+            #[prost(string, tag = "2")]
+            b: String,
+            #[prost(fixed32, tag = "3")]
+            c: u32,
+        }
+
+        let enc = Foo {
+            a: 1,
+            b: "x".repeat(100),
+            c: 7,
+        }
+        .encode_to_vec();
+
+        let mut replaced = enc.clone();
+        assert!(replace_field_with_fn(&mut replaced, 2, |old| {
+            assert_eq!(old, "x".repeat(100).as_bytes());
+            b"short".to_vec()
+        }));
+        let decoded = Foo::decode(replaced.as_slice()).unwrap();
+        assert_eq!((decoded.a, decoded.b.as_str(), decoded.c), (1, "short", 7));
+        assert!(!replace_field_with_fn(&mut replaced, 4, |_| unreachable!()));
+
+        let parse_counter = |old: &[u8]| -> Result<Vec<u8>, &'static str> {
+            let counter = u32::from_le_bytes(old.try_into().map_err(|_| "not a fixed32")?);
+            counter
+                .checked_add(1)
+                .map(|counter| counter.to_le_bytes().to_vec())
+                .ok_or("overflow")
+        };
+
+        let mut replaced = enc.clone();
+        assert_eq!(
+            replace_field_with_fn_result(&mut replaced, 3, parse_counter),
+            Ok(true)
+        );
+        assert_eq!(extract_field_as_fixed32(&replaced, 3), Some(8));
+        assert_eq!(
+            replace_field_with_fn_result(&mut replaced, 4, parse_counter),
+            Ok(false)
+        );
+
+        let mut replaced = enc.clone();
+        assert_eq!(
+            replace_field_with_fn_result(&mut replaced, 2, parse_counter),
+            Err("not a fixed32")
+        );
+        assert_eq!(replaced, enc);
+    }
+
+    /// Test sanitizing a message from a newer schema down to the fields of an older one.
+    #[test]
+    fn test_sanitize_message() {
+        #[derive(Message)]
+        struct V2 {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, tag = "2")]
+            name: String,
+            #[prost(string, tag = "3")]
+            secret: String,
+            #[prost(uint64, repeated, packed = "false", tag = "4")]
+            flags: Vec<u64>,
+        }
+
+        #[derive(Message, PartialEq)]
+        struct V1 {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, tag = "2")]
+            name: String,
+        }
+
+        let v2 = V2 {
+            id: 7,
+            name: "name".to_string(),
+            secret: "secret".to_string(),
+            flags: vec![1, 2, 3],
+        };
+        let v1 = V1 {
+            id: 7,
+            name: "name".to_string(),
+        };
+        let enc = v2.encode_to_vec();
+        let known = [1, 2];
+
+        assert_eq!(strip_unknown_fields(&enc, &known), v1.encode_to_vec());
+        assert_eq!(count_unknown_fields(&enc, &known), 4);
+        assert_eq!(count_unknown_fields(&v1.encode_to_vec(), &known), 0);
+
+        assert_eq!(
+            sanitize_message(&enc, &known, UnknownFieldPolicy::Strip),
+            Ok(v1.encode_to_vec())
+        );
+        assert_eq!(
+            sanitize_message(&enc, &known, UnknownFieldPolicy::PassThrough),
+            Ok(enc.clone())
+        );
+        assert_eq!(
+            sanitize_message(&enc, &known, UnknownFieldPolicy::Fail),
+            Err(WireError::UnknownField {
+                offset: 8,
+                field_number: 3
+            })
+        );
+        assert_eq!(
+            sanitize_message(&v1.encode_to_vec(), &known, UnknownFieldPolicy::Fail),
+            Ok(v1.encode_to_vec())
+        );
+
+        // Malformed messages are rejected whatever the policy.
+        let truncated = &enc[..enc.len() - 1];
+        for policy in [UnknownFieldPolicy::Strip, UnknownFieldPolicy::PassThrough] {
+            assert_eq!(
+                sanitize_message(truncated, &known, policy),
+                Err(WireError::TruncatedMessage {
+                    offset: enc.len() - 1
+                })
+            );
+        }
+
+        // Groups are judged by their own field number.
+        let grouped = b"\x08\x01\x2b\x10\x03\x2c\x1a\x02\x68\x69\x08\x02";
+        let stripped = b"\x08\x01\x1a\x02\x68\x69\x08\x02";
+        assert_eq!(strip_unknown_fields(grouped, &[1, 3]), stripped);
+        assert_eq!(count_unknown_fields(grouped, &[1, 3]), 1);
+        assert_eq!(count_unknown_fields(grouped, &[1, 3, 5]), 0);
+        assert_eq!(
+            sanitize_message(grouped, &[1, 3], UnknownFieldPolicy::Strip),
+            Ok(stripped.to_vec())
+        );
+        assert_eq!(
+            sanitize_message(grouped, &[1, 3], UnknownFieldPolicy::PassThrough),
+            Ok(grouped.to_vec())
+        );
+        assert_eq!(
+            sanitize_message(grouped, &[1, 3, 5], UnknownFieldPolicy::Fail),
+            Ok(grouped.to_vec())
+        );
+        assert_eq!(
+            sanitize_message(grouped, &[1, 3], UnknownFieldPolicy::Fail),
+            Err(WireError::UnknownField {
+                offset: 2,
+                field_number: 5
+            })
+        );
+        // An unterminated group is malformed.
+        assert_eq!(
+            sanitize_message(&grouped[..4], &[1, 3, 5], UnknownFieldPolicy::PassThrough),
+            Err(WireError::TruncatedMessage { offset: 4 })
+        );
+    }
+
+    /// Test detecting which alternative of a prost oneof is set.
+    #[test]
+    fn test_oneof_detected_tag() {
+        #[derive(Clone, PartialEq, prost::Oneof)]
+        enum Payload {
+            #[prost(string, tag = "4")]
+            Text(String),
+            #[prost(uint64, tag = "5")]
+            Number(u64),
+            #[prost(bytes, tag = "6")]
+            Raw(Vec<u8>),
+        }
+
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(oneof = "Payload", tags = "4, 5, 6")]
+            payload: Option<Payload>,
+            #[prost(string, tag = "7")]
+            trailer: String,
+        }
+
+        let oneof_tags = [4, 5, 6];
+        let cases = [
+            (Some(Payload::Text("hi".to_string())), Some(4)),
+            (Some(Payload::Number(0)), Some(5)),
+            (Some(Payload::Raw(vec![1, 2])), Some(6)),
+            (None, None),
+        ];
+        for (payload, expected) in cases {
+            let enc = Foo {
+                id: 1,
+                payload,
+                trailer: "end".to_string(),
+            }
+            .encode_to_vec();
+            assert_eq!(oneof_detected_tag(&enc, &oneof_tags), expected);
+            assert!(!oneof_conflict_present(&enc, &oneof_tags));
+        }
+
+        // Concatenating two messages sets two alternatives, and the last one wins.
+        let text = Foo {
+            id: 1,
+            payload: Some(Payload::Text("hi".to_string())),
+            trailer: String::new(),
+        }
+        .encode_to_vec();
+        let number = Foo {
+            id: 1,
+            payload: Some(Payload::Number(3)),
+            trailer: String::new(),
+        }
+        .encode_to_vec();
+        let enc = [text.clone(), number].concat();
+        assert_eq!(oneof_detected_tag(&enc, &oneof_tags), Some(5));
+        assert!(oneof_conflict_present(&enc, &oneof_tags));
+        assert_eq!(
+            Foo::decode(enc.as_slice()).unwrap().payload,
+            Some(Payload::Number(3))
+        );
+
+        let enc = [text.clone(), text].concat();
+        assert_eq!(oneof_detected_tag(&enc, &oneof_tags), Some(4));
+        assert!(!oneof_conflict_present(&enc, &oneof_tags));
+    }
+
+    /// Test the well-known `Timestamp` and `Duration` helpers against prost's encoding of the same
+    /// messages.
+    #[test]
+    fn test_timestamp_and_duration() {
+        #[derive(Clone, PartialEq, Message)]
+        struct SecondsAndNanos {
+            #[prost(int64, tag = "1")]
+            seconds: i64,
+            #[prost(int32, tag = "2")]
+            nanos: i32,
+        }
+
+        #[derive(Message)]
+        struct Event {
+            #[prost(string, tag = "1")]
+            name: String,
+            #[prost(message, optional, tag = "2")]
+            created_at: Option<SecondsAndNanos>,
+            #[prost(message, optional, tag = "3")]
+            timeout: Option<SecondsAndNanos>,
+        }
+
+        let values = [
+            (0, 0),
+            (1_700_000_000, 123_456_789),
+            (-62_135_596_800, 0),
+            (0, 999_999_999),
+            (-1, -500_000_000),
+            (i64::MAX, i32::MIN),
+        ];
+        for (seconds, nanos) in values {
+            let expected = SecondsAndNanos { seconds, nanos }.encode_to_vec();
+            assert_eq!(encode_timestamp(seconds, nanos), expected);
+            assert_eq!(encode_duration(seconds, nanos), expected);
+            assert_eq!(decode_timestamp(&expected), Some((seconds, nanos)));
+            assert_eq!(decode_duration(&expected), Some((seconds, nanos)));
+        }
+
+        let event = Event {
+            name: "deploy".to_string(),
+            created_at: Some(SecondsAndNanos {
+                seconds: 1_700_000_000,
+                nanos: 5,
+            }),
+            timeout: Some(SecondsAndNanos {
+                seconds: 30,
+                nanos: 0,
+            }),
+        };
+        let enc = [
+            encode_string_field(1, "deploy"),
+            encode_timestamp_field(2, 1_700_000_000, 5),
+            encode_duration_field(3, 30, 0),
+        ]
+        .concat();
+        assert_eq!(enc, event.encode_to_vec());
+        assert_eq!(
+            decode_timestamp(extract_field_by_tag(&enc, 2).unwrap()),
+            Some((1_700_000_000, 5))
+        );
+        assert_eq!(
+            decode_duration(extract_field_by_tag(&enc, 3).unwrap()),
+            Some((30, 0))
+        );
+
+        // The last occurrence wins, unknown fields are ignored and wrong wire types are rejected.
+        assert_eq!(decode_timestamp(b"\x08\x01\x18\x07\x08\x02"), Some((2, 0)));
+        assert_eq!(decode_timestamp(b"\x0a\x01\x01"), None);
+        assert_eq!(decode_timestamp(b"\x08"), None);
+    }
+
+    /// Test packed `sint32` and `sint64` fields against prost's packed encoding.
+    #[test]
+    fn test_packed_sint() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(sint32, repeated, tag = "1")]
+            a: Vec<i32>,
+            #[prost(sint64, repeated, tag = "2")]
+            b: Vec<i64>,
+        }
+
+        let foo = Foo {
+            a: vec![0, -1, 1, -64, 64, i32::MIN, i32::MAX],
+            b: vec![0, -1, 1, i64::MIN, i64::MAX, -300],
+        };
+        let enc = foo.encode_to_vec();
+
+        let a = extract_field_by_tag(&enc, 1).unwrap();
+        let b = extract_field_by_tag(&enc, 2).unwrap();
+        assert_eq!(decode_packed_sint32(a), Some(foo.a.clone()));
+        assert_eq!(decode_packed_sint64(b), Some(foo.b.clone()));
+        assert_eq!(encode_packed_sint32(&foo.a), a);
+        assert_eq!(encode_packed_sint64(&foo.b), b);
+
+        assert_eq!(decode_packed_sint32(&[]), Some(Vec::new()));
+        assert_eq!(decode_packed_sint64(&b[..b.len() - 1]), None);
+    }
+
+    /// Test folding over and mapping the fields of a message.
+    #[test]
+    fn test_fold_and_map_fields() {
+        #[derive(Message)]
+        struct Foo {
+            #[prost(uint64, repeated, packed = "false", tag = "1")]
+            amounts: Vec<u64>,
+            #[prost(string, tag = "2")]
+            name: String,
+            #[prost(string, tag = "3")]
+            secret: String,
+            #[prost(fixed32, tag = "4")]
+            version: u32,
+        }
+
+        let foo = Foo {
+            amounts: vec![1, 300, 70000],
+            name: "name".to_string(),
+            secret: "secret".to_string(),
+            version: 3,
+        };
+        let enc = foo.encode_to_vec();
+
+        let sum = fold_fields(&enc, 0, |sum, tag, wire_type, value| {
+            match (tag, wire_type) {
+                (1, Variant::Varint) => sum + decode_varint_from_slice(value).unwrap().0,
+                _ => sum,
+            }
+        });
+        assert_eq!(sum, 70301);
+        assert_eq!(fold_fields(&enc, 0, |count, _, _, _| count + 1), 6);
+
+        // Drop the secret, rename the string and bump the version.
+        let version = 4u32.to_le_bytes();
+        let mapped = map_fields(&enc, |field| match field.tag {
+            2 => Some(FieldView::new(2, field.wire_type, b"renamed")),
+            3 => None,
+            4 => Some(FieldView::new(4, field.wire_type, &version)),
+            _ => Some(field),
+        });
+        let decoded = Foo::decode(mapped.as_slice()).unwrap();
+        assert_eq!(decoded.amounts, foo.amounts);
+        assert_eq!(decoded.name, "renamed");
+        assert_eq!(decoded.secret, "");
+        assert_eq!(decoded.version, 4);
+
+        assert_eq!(map_fields(&enc, Some), enc);
+        assert_eq!(map_fields(&enc, |_| None), b"");
+
+        // Groups are copied as they are, wherever they are.
+        let grouped = b"\x08\x01\x2b\x10\x03\x2c\x1a\x02\x68\x69\x08\x02\x33\x34";
+        assert_eq!(map_fields(grouped, Some), grouped);
+        assert_eq!(map_fields(grouped, |_| None), b"\x2b\x10\x03\x2c\x33\x34");
+    }
+
+    /// Test the nesting depth and field count estimates on nested and adversarial messages.
+    #[test]
+    fn test_estimate_message_complexity() {
+        #[derive(Clone, Message)]
+        struct Node {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(message, repeated, tag = "2")]
+            children: Vec<Node>,
+        }
+
+        let leaf = |id| Node {
+            id,
+            children: Vec::new(),
+        };
+        let tree = Node {
+            id: 1,
+            children: vec![
+                Node {
+                    id: 2,
+                    children: vec![leaf(3), leaf(4)],
+                },
+                leaf(5),
+            ],
+        };
+        let enc = tree.encode_to_vec();
+
+        assert_eq!(estimate_nesting_depth(&enc), 2);
+        assert_eq!(estimate_field_count(&enc), 3);
+        assert_eq!(estimate_total_field_count_recursive(&enc, 0), 3);
+        assert_eq!(estimate_total_field_count_recursive(&enc, 1), 7);
+        assert_eq!(estimate_total_field_count_recursive(&enc, 2), 9);
+        assert_eq!(estimate_total_field_count_recursive(&enc, u32::MAX), 9);
+
+        assert_eq!(estimate_nesting_depth(&[]), 0);
+        assert_eq!(estimate_field_count(&[]), 0);
+
+        // Strings are scanned like messages, but bytes that do not decode just stop the scan.
+        let enc = [encode_string_field(1, "\u{7f}"), encode_varint_field(2, 1)].concat();
+        assert_eq!(estimate_nesting_depth(&enc), 1);
+        assert_eq!(estimate_total_field_count_recursive(&enc, 1), 2);
+
+        // Deeply nested input is walked without recursing.
+        let mut deep = Vec::new();
+        for _ in 0..5000 {
+            deep = [create_header(1, 2, &deep), deep].concat();
+        }
+        assert_eq!(estimate_nesting_depth(&deep), 5000);
+        assert_eq!(estimate_total_field_count_recursive(&deep, 10), 11);
+    }
+
+    /// Test removing the duplicate scalar fields left by naively merging messages.
+    #[test]
+    fn test_dedup_fields() {
+        #[derive(Message, PartialEq)]
+        struct Foo {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, tag = "2")]
+            name: String,
+            #[prost(string, repeated, tag = "3")]
+            labels: Vec<String>,
+            #[prost(double, tag = "4")]
+            score: f64,
+        }
+
+        let first = Foo {
+            id: 1,
+            name: "first".to_string(),
+            labels: vec!["a".to_string()],
+            score: 0.5,
+        };
+        let second = Foo {
+            id: 2,
+            name: "second".to_string(),
+            labels: vec!["b".to_string(), "c".to_string()],
+            score: 0.0,
+        };
+        let merged = merge_messages(&first.encode_to_vec(), &second.encode_to_vec());
+        let scalar_tags = [1, 2, 4];
+
+        let last = dedup_last_wins(&merged, &scalar_tags);
+        assert!(last.len() < merged.len());
+        assert_eq!(
+            Foo::decode(last.as_slice()).unwrap(),
+            Foo::decode(merged.as_slice()).unwrap()
+        );
+        assert_eq!(count_field_occurrences(&last, 1), 1);
+        assert_eq!(count_field_occurrences(&last, 3), 3);
+        assert_eq!(
+            Foo::decode(last.as_slice()).unwrap(),
+            Foo {
+                id: 2,
+                name: "second".to_string(),
+                labels: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                score: 0.5,
+            }
+        );
+
+        let first_wins = dedup_first_wins(&merged, &scalar_tags);
+        assert_eq!(
+            Foo::decode(first_wins.as_slice()).unwrap(),
+            Foo {
+                labels: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+                ..first
+            }
+        );
+
+        // Without scalar tags nothing is removed, and a malformed tail is kept as is.
+        assert_eq!(dedup_last_wins(&merged, &[]), merged);
+        let malformed = [&merged[..], &[0x0f, 0x01]].concat();
+        assert_eq!(
+            dedup_first_wins(&malformed, &scalar_tags)[first_wins.len()..],
+            [0x0f, 0x01]
+        );
+    }
+
+    /// Test the `Any` helpers against prost's encoding of the same message.
+    #[test]
+    fn test_any() {
+        #[derive(Message)]
+        struct Any {
+            #[prost(string, tag = "1")]
+            type_url: String,
+            #[prost(bytes = "vec", tag = "2")]
+            value: Vec<u8>,
+        }
+
+        #[derive(Message, PartialEq)]
+        struct Event {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, tag = "2")]
+            name: String,
+        }
+
+        let event = Event {
+            id: 7,
+            name: "created".to_string(),
+        };
+        let type_url = "type.googleapis.com/events.Event";
+        let any = Any {
+            type_url: type_url.to_string(),
+            value: event.encode_to_vec(),
+        };
+        let enc = any.encode_to_vec();
+
+        assert_eq!(encode_any(type_url, &event.encode_to_vec()), enc);
+        assert_eq!(extract_any_type_url(&enc), Some(type_url));
+        let (decoded_url, value) = decode_any(&enc).unwrap();
+        assert_eq!(decoded_url, type_url);
+        assert_eq!(Event::decode(value).unwrap(), event);
+
+        let empty = Any {
+            type_url: String::new(),
+            value: Vec::new(),
+        }
+        .encode_to_vec();
+        assert_eq!(encode_any("", &[]), empty);
+        assert_eq!(decode_any(&empty), Some(("", &[][..])));
+        assert_eq!(extract_any_type_url(&empty), None);
+
+        // Invalid UTF-8 in the type URL and a varint value are rejected.
+        assert_eq!(decode_any(b"\x0a\x01\xff"), None);
+        assert_eq!(decode_any(b"\x10\x01"), None);
+    }
+
+    /// Test extracting multiple fields with an early exit
+    #[test]
+    fn test_extract_multiple_fields_early_exit() {
+        #[derive(Message)]
+        struct Record {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, tag = "2")]
+            name: String,
+            #[prost(bytes, tag = "3")]
+            payload: Vec<u8>,
+            #[prost(fixed64, tag = "4")]
+            checksum: u64,
+            #[prost(uint32, repeated, packed = "false", tag = "5")]
+            flags: Vec<u32>,
+        }
+
+        let record = Record {
+            id: 99,
+            name: "record".to_string(),
+            payload: vec![7; 300],
+            checksum: 0xdead_beef,
+            flags: vec![1, 2],
+        };
+        let enc = record.encode_to_vec();
+
+        // The 300-byte payload has a two-byte length prefix.
+        for tags in [&[1, 2][..], &[2, 4], &[3, 4], &[3], &[4, 4], &[6], &[]] {
+            assert_eq!(
+                extract_multiple_fields_early_exit(&enc, tags),
+                extract_multiple_fields_by_tag(&enc, tags),
+                "tags {:?}",
+                tags
+            );
+        }
+        assert_eq!(
+            extract_multiple_fields_early_exit(&enc, &[3, 4]),
+            [(3, &[7; 300][..]), (4, &0xdead_beef_u64.to_le_bytes()[..])]
+        );
+
+        // Later occurrences of a repeated field are not returned once all tags are found.
+        assert_eq!(
+            extract_multiple_fields_by_tag(&enc, &[5]),
+            [(5, &[1][..]), (5, &[2][..])]
+        );
+        assert_eq!(
+            extract_multiple_fields_early_exit(&enc, &[5]),
+            [(5, &[1][..])]
+        );
+    }
+
+    /// Test finding the offset of a field's tag
+    #[test]
+    fn test_find_field_tag_offset() {
+        #[derive(Message)]
+        struct Request {
+            #[prost(string, tag = "1")]
+            path: String,
+            #[prost(bytes, tag = "2")]
+            body: Vec<u8>,
+            #[prost(uint32, tag = "300")]
+            priority: u32,
+        }
+
+        let request = Request {
+            path: "/users".to_string(),
+            body: vec![1; 200],
+            priority: 5,
+        };
+        let enc = request.encode_to_vec();
+
+        assert_eq!(find_field_tag_offset(&enc, 1), Some(0));
+        assert_eq!(find_field_tag_offset(&enc, 2), Some(8));
+        // The body has a two-byte length prefix.
+        let offset = find_field_tag_offset(&enc, 300).unwrap();
+        assert_eq!(offset, 8 + 3 + 200);
+
+        // The tail starting at the field parses as a message starting with that field.
+        let tail = &enc[offset..];
+        assert_eq!(
+            extract_full_field_byte_range(tail, 300),
+            Some((0, tail.len()))
+        );
+        assert_eq!(Request::decode(tail).unwrap().priority, 5);
+        let body_tail = &enc[find_field_tag_offset(&enc, 2).unwrap()..];
+        assert_eq!(extract_field_by_tag(body_tail, 2), Some(&[1; 200][..]));
+
+        assert_eq!(find_field_tag_offset(&enc, 3), None);
+        assert_eq!(find_field_tag_offset(&[], 1), None);
+    }
+
+    /// Test formatting a message as an annotated hex dump
+    #[test]
+    fn test_message_to_hex_dump() {
+        #[derive(Message)]
+        struct Inner {
+            #[prost(uint32, tag = "1")]
+            value: u32,
+        }
+
+        #[derive(Message)]
+        struct Outer {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(message, optional, tag = "2")]
+            inner: Option<Inner>,
+            #[prost(bytes, tag = "3")]
+            data: Vec<u8>,
+            #[prost(fixed32, tag = "4")]
+            crc: u32,
+        }
+
+        let outer = Outer {
+            id: 150,
+            inner: Some(Inner { value: 1 }),
+            data: vec![0xff; 20],
+            crc: 7,
+        };
+        let enc = outer.encode_to_vec();
+        let dump = message_to_hex_dump(&enc);
+        let lines: Vec<&str> = dump.lines().collect();
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("00000000  08 96 01 "));
+        assert!(lines[0].ends_with("  tag 1 (varint)"));
+        assert!(lines[1].starts_with("00000003  12 02 08 01 "));
+        assert!(lines[1].ends_with("tag 2 (length-delimited, 2 bytes) (nested message)"));
+        assert!(lines[2].starts_with("00000007  1a 14 ff "));
+        assert!(lines[2].ends_with("tag 3 (length-delimited, 20 bytes)"));
+        // The remaining bytes of the field continue on an unannotated line.
+        assert_eq!(lines[3], "00000017  ff ff ff ff ff ff");
+        assert!(lines[4].ends_with("tag 4 (32-bit)"));
+        // The annotations line up in one column.
+        assert!(lines
+            .iter()
+            .filter(|line| line.contains("tag"))
+            .all(|line| line.find("tag") == Some(59)));
+
+        assert_eq!(message_to_hex_dump(&[]), "");
+
+        // Malformed input is shown with an error instead of panicking.
+        for malformed in [
+            &b"\x08"[..],
+            b"\x12\x05\x68",
+            b"\x08\x01\x0f",
+            b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01",
+            &enc[..enc.len() - 1],
+        ] {
+            let dump = message_to_hex_dump(malformed);
+            assert!(dump.lines().last().unwrap().contains("error: "), "{}", dump);
+        }
+    }
+
+    /// Test the Debug and Display output of fields and field iterators
+    #[test]
+    fn test_field_view_formatting() {
+        #[derive(Message)]
+        struct Sample {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, tag = "2")]
+            name: String,
+            #[prost(bytes, tag = "3")]
+            data: Vec<u8>,
+            #[prost(fixed64, tag = "4")]
+            big: u64,
+            #[prost(fixed32, tag = "5")]
+            small: u32,
+        }
+
+        let sample = Sample {
+            id: 150,
+            name: "test".to_string(),
+            data: vec![0xff, 0x00],
+            big: 0x0102,
+            small: 0xabcd,
+        };
+        let enc = sample.encode_to_vec();
+        let fields: Vec<FieldView> = FieldIterator::new(&enc).collect();
+
+        assert_eq!(
+            format!("{:?}", fields[1]),
+            "FieldView { tag: 2, wire_type: LengthDelimited, raw: [0x74, 0x65, 0x73, 0x74] }"
+        );
+        assert_eq!(
+            format!("{:?}", fields[0]),
+            "FieldView { tag: 1, wire_type: Varint, raw: [0x96, 0x01] }"
+        );
+
+        let displayed: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+        assert_eq!(
+            displayed,
+            [
+                "#1: 150",
+                "#2: \"test\"",
+                "#3: [0xff, 0x00]",
+                "#4: 0x0000000000000102",
+                "#5: 0x0000abcd",
+            ]
+        );
+
+        let mut iter = FieldIterator::new(&enc);
+        assert_eq!(
+            format!("{:?}", iter),
+            format!(
+                "FieldIterator {{ offset: 0, remaining: {} bytes }}",
+                enc.len()
+            )
+        );
+        iter.next();
+        assert_eq!(
+            format!("{:?}", iter),
+            format!(
+                "FieldIterator {{ offset: 3, remaining: {} bytes }}",
+                enc.len() - 3
+            )
+        );
+    }
+
+    /// Test replacing the payload of a field
+    #[test]
+    fn test_replace_field_payload() {
+        #[derive(Message)]
+        struct Profile {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, tag = "2")]
+            name: String,
+            #[prost(fixed32, tag = "3")]
+            score: u32,
+            #[prost(bytes, tag = "4")]
+            avatar: Vec<u8>,
+        }
+
+        let profile = Profile {
+            id: 1,
+            name: "alice".to_string(),
+            score: 10,
+            avatar: vec![1; 100],
+        };
+        let enc = profile.encode_to_vec();
+
+        let new_name = "a much longer name".as_bytes();
+        let new_avatar = vec![2; 200];
+        let new_score = 20u32.to_le_bytes();
+        let new_id = encode_varint(300);
+        let replacements: [(u64, u64, &[u8], &[u8]); 4] = [
+            (1, 0, &new_id, &[1]),
+            (2, 2, new_name, b"alice"),
+            (3, 5, &new_score, &10u32.to_le_bytes()),
+            (4, 2, &new_avatar, &[1; 100]),
+        ];
+        for (tag, variant, payload, old) in replacements {
+            let mut with_payload = enc.clone();
+            assert_eq!(
+                replace_field_payload(&mut with_payload, tag, payload).as_deref(),
+                Some(old)
+            );
+
+            // The old API needs the header to be encoded by the caller.
+            let mut with_field = enc.clone();
+            let replace_with = [create_header(tag, variant, payload), payload.to_vec()].concat();
+            assert_eq!(
+                replace_field_with(&mut with_field, tag, &replace_with).as_deref(),
+                Some(old)
+            );
+
+            assert_eq!(with_payload, with_field, "tag {}", tag);
+            assert_eq!(extract_field_by_tag(&with_payload, tag), Some(payload));
+        }
+
+        let mut updated = enc.clone();
+        replace_field_payload(&mut updated, 2, new_name).unwrap();
+        let decoded = Profile::decode(updated.as_slice()).unwrap();
+        assert_eq!(decoded.name, "a much longer name");
+        assert_eq!(decoded.avatar, vec![1; 100]);
+
+        let mut missing = enc.clone();
+        assert_eq!(replace_field_payload(&mut missing, 5, b"x"), None);
+        assert_eq!(missing, enc);
+    }
+
+    /// Test framing and splitting streams of varint length-prefixed messages
+    #[test]
+    fn test_varint_length_stream() {
+        #[derive(Message, PartialEq)]
+        struct Ping {
+            #[prost(uint64, tag = "1")]
+            seq: u64,
+            #[prost(bytes, tag = "2")]
+            payload: Vec<u8>,
+        }
+
+        let pings: Vec<Ping> = [0, 5, 300]
+            .iter()
+            .map(|&len| Ping {
+                seq: len as u64,
+                payload: vec![9; len],
+            })
+            .collect();
+
+        let mut stream = Vec::new();
+        for ping in &pings {
+            let framed = prepend_varint_length(&ping.encode_to_vec());
+            assert_eq!(framed, ping.encode_length_delimited_to_vec());
+            stream.extend_from_slice(&framed);
+        }
+
+        let messages = split_varint_length_stream(&stream);
+        assert_eq!(messages.len(), 3);
+        for (message, ping) in messages.iter().zip(&pings) {
+            assert_eq!(&Ping::decode(*message).unwrap(), ping);
+        }
+        assert!(iter_varint_length_messages(&stream).eq(messages.iter().copied()));
+
+        let (first, rest) = strip_varint_length(&stream).unwrap();
+        assert_eq!(first, messages[0]);
+        assert_eq!(rest.len(), stream.len() - 1 - first.len());
+
+        // A partially received message is left in the remainder.
+        let partial = &stream[..stream.len() - 10];
+        assert_eq!(split_varint_length_stream(partial), &messages[..2]);
+        let mut iter = iter_varint_length_messages(partial);
+        assert_eq!(iter.by_ref().count(), 2);
+        let third = prepend_varint_length(&pings[2].encode_to_vec());
+        assert_eq!(iter.remainder(), &third[..third.len() - 10]);
+
+        assert!(split_varint_length_stream(&[]).is_empty());
+        assert_eq!(strip_varint_length(&[0xff]), None);
+    }
+
+    proptest::proptest! {
+        /// Test that every u64 round-trips through varint encoding and decoding
+        #[test]
+        fn prop_varint_round_trip(value: u64) {
+            let encoded = encode_varint(value);
+            proptest::prop_assert_eq!(encoded.len(), encoded_varint_len(value));
+            proptest::prop_assert_eq!(
+                crate::decoders::decode_varint(&encoded, 0),
+                Some((value, encoded.len()))
+            );
+        }
+
+        /// Test that every f32 and f64 round-trips through fixed-width fields, bit for bit
+        #[test]
+        fn prop_float_double_round_trip(float: f32, double: f64) {
+            let enc = [
+                encode_field(1, Variant::ThirtyTwoBit, &encode_float(float)),
+                encode_field(2, Variant::SixtyFourBit, &encode_double(double)),
+            ]
+            .concat();
+            let decoded_float = extract_field_view(&enc, 1).and_then(|field| field.as_f32());
+            let decoded_double = extract_field_view(&enc, 2).and_then(|field| field.as_f64());
+            proptest::prop_assert_eq!(decoded_float.map(f32::to_bits), Some(float.to_bits()));
+            proptest::prop_assert_eq!(decoded_double.map(f64::to_bits), Some(double.to_bits()));
+        }
+
+        /// Test that extracting from arbitrary bytes never panics and only returns bytes of the
+        /// input
+        #[test]
+        fn prop_extract_arbitrary_bytes(
+            bytes in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..64),
+            tag in 0u64..20,
+        ) {
+            if let Some(value) = extract_field_by_tag(&bytes, tag) {
+                let input = bytes.as_ptr_range();
+                let output = value.as_ptr_range();
+                proptest::prop_assert!(input.start <= output.start && output.end <= input.end);
+            }
+        }
+
+        /// Test that a replaced field is extracted with the replacement payload
+        #[test]
+        fn prop_replace_then_extract(
+            before in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..200),
+            after in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..200),
+            other: u64,
+        ) {
+            let mut enc = [
+                encode_varint_field(1, other),
+                encode_field(2, Variant::LengthDelimited, &before),
+                encode_varint_field(3, other),
+            ]
+            .concat();
+            let replace_with = encode_field(2, Variant::LengthDelimited, &after);
+
+            proptest::prop_assert_eq!(replace_field_with(&mut enc, 2, &replace_with), Some(before));
+            proptest::prop_assert_eq!(extract_field_by_tag(&enc, 2), Some(&after[..]));
+            proptest::prop_assert_eq!(extract_field_as_u64(&enc, 1), Some(other));
+            proptest::prop_assert_eq!(extract_field_as_u64(&enc, 3), Some(other));
+        }
+    }
+
+    /// Test that a length prefix larger than the address space is rejected without overflowing
+    #[test]
+    fn test_extract_field_huge_length_prefix() {
+        let enc = b"\x0a\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01";
+        assert_eq!(extract_field_by_tag(enc, 1), None);
+        assert_eq!(replace_field_with(&mut enc.to_vec(), 1, b""), None);
+    }
+
+    /// Test extracting fields starting at an offset
+    #[test]
+    fn test_extract_field_by_tag_from() {
+        #[derive(Message)]
+        struct Batch {
+            #[prost(string, repeated, tag = "1")]
+            items: Vec<String>,
+            #[prost(uint64, tag = "2")]
+            count: u64,
+            #[prost(string, repeated, tag = "3")]
+            more: Vec<String>,
+        }
+
+        let batch = Batch {
+            items: vec!["a".to_string(), "b".repeat(200), String::new()],
+            count: 3,
+            more: vec!["c".to_string()],
+        };
+        let enc = batch.encode_to_vec();
+
+        let mut values = Vec::new();
+        let mut offset = 0;
+        while let Some((value, next)) = extract_field_by_tag_from(&enc, 1, offset) {
+            assert!(next > offset);
+            values.push(value);
+            offset = next;
+        }
+        assert_eq!(values, extract_all_occurrences_by_tag(&enc, 1));
+
+        // The returned offset is right after the field.
+        let (count, next) = extract_field_by_tag_from(&enc, 2, 0).unwrap();
+        assert_eq!(count, [3]);
+        assert_eq!(extract_field_by_tag(&enc[next..], 3), Some(&b"c"[..]));
+        assert_eq!(next, enc.len() - 3);
+
+        assert_eq!(extract_field_by_tag_from(&enc, 2, next), None);
+        assert_eq!(extract_field_by_tag_from(&enc, 1, enc.len()), None);
+        assert_eq!(extract_field_by_tag_from(&enc, 1, enc.len() + 10), None);
+    }
+
+    /// Test sharing a message between threads
+    #[test]
+    fn test_shared_message_view() {
+        #[derive(Message)]
+        struct Order {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, tag = "2")]
+            customer: String,
+            #[prost(bytes, tag = "3")]
+            items: Vec<u8>,
+        }
+
+        let order = Order {
+            id: 42,
+            customer: "acme".to_string(),
+            items: vec![5; 4096],
+        };
+        let enc = order.encode_to_vec();
+        let message = SharedMessageView::new(enc.clone());
+
+        let (id, customer) = std::thread::scope(|scope| {
+            let id = scope.spawn(|| message.extract_field(1));
+            let customer = {
+                let message = message.clone();
+                scope.spawn(move || message.extract_field(2))
+            };
+            (id.join().unwrap(), customer.join().unwrap())
+        });
+        assert_eq!(id, Some(vec![42]));
+        assert_eq!(customer, Some(b"acme".to_vec()));
+        assert_eq!(message.extract_field(4), None);
+
+        assert_eq!(
+            message.extract_multiple_fields(&[3, 1]),
+            [(1, vec![42]), (3, vec![5; 4096])]
+        );
+
+        // Clones share one buffer.
+        let buffer = message.clone_arc();
+        assert_eq!(*buffer, enc);
+        assert_eq!(std::sync::Arc::strong_count(&buffer), 2);
+        assert_eq!(extract_field_by_tag(&buffer, 2), Some(&b"acme"[..]));
+    }
+
+    /// Test dumping a message as text
+    #[test]
+    fn test_dump_message_as_text() {
+        #[derive(Message)]
+        struct Address {
+            #[prost(string, tag = "1")]
+            city: String,
+            #[prost(uint32, tag = "2")]
+            zip: u32,
+        }
+
+        #[derive(Message)]
+        struct Customer {
+            #[prost(string, tag = "1")]
+            name: String,
+            #[prost(int64, tag = "2")]
+            balance: i64,
+            #[prost(message, optional, tag = "3")]
+            address: Option<Address>,
+            #[prost(bytes, tag = "4")]
+            avatar: Vec<u8>,
+            #[prost(fixed32, tag = "5")]
+            flags: u32,
+            #[prost(double, tag = "6")]
+            score: f64,
+        }
+
+        let customer = Customer {
+            name: "Zoë \"Z\"".to_string(),
+            balance: -1,
+            address: Some(Address {
+                city: "Berlin".to_string(),
+                zip: 10115,
+            }),
+            avatar: vec![0x00, 0xff, 0x80],
+            flags: 0xabcd,
+            score: 1.0,
+        };
+        let enc = customer.encode_to_vec();
+
+        assert_eq!(
+            dump_message_as_text(&enc),
+            "1: \"Zoë \\\"Z\\\"\"\n\
+             2: 18446744073709551615\n\
+             3: { 1: \"Berlin\" 2: 10115 }\n\
+             4: 0x00ff80\n\
+             5: 0x0000abcd\n\
+             6: 0x3ff0000000000000\n"
+        );
+
+        let options = DumpOptions {
+            max_depth: 0,
+            max_string_len: 2,
+            hex_bytes: false,
+        };
+        let dump = dump_message_as_text_with_options(&enc, &options);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines[0], "1: \"Zo\"...");
+        assert_eq!(lines[2], "3: \"\\n\\x06\"...");
+        assert_eq!(lines[3], "4: \"\\x00\\xff\"...");
+
+        assert_eq!(dump_message_as_text(&[]), "");
+        assert_eq!(
+            dump_message_as_text(b"\x08\x01\x12\x05\x68"),
+            "1: 1\nerror: length at offset 3 runs past the end of the message\n"
+        );
+    }
+
+    /// Test converting repeated fields between packed and unpacked encoding
+    #[test]
+    fn test_packed_unpacked_conversion() {
+        #[derive(Message, PartialEq)]
+        struct Packed {
+            #[prost(int32, repeated, tag = "1")]
+            ids: Vec<i32>,
+            #[prost(string, tag = "2")]
+            name: String,
+            #[prost(float, repeated, tag = "3")]
+            weights: Vec<f32>,
+            #[prost(double, repeated, tag = "4")]
+            scores: Vec<f64>,
+            #[prost(sint64, repeated, tag = "5")]
+            deltas: Vec<i64>,
+        }
+
+        #[derive(Message, PartialEq)]
+        struct Unpacked {
+            #[prost(int32, repeated, packed = "false", tag = "1")]
+            ids: Vec<i32>,
+            #[prost(string, tag = "2")]
+            name: String,
+            #[prost(float, repeated, packed = "false", tag = "3")]
+            weights: Vec<f32>,
+            #[prost(double, repeated, packed = "false", tag = "4")]
+            scores: Vec<f64>,
+            #[prost(sint64, repeated, packed = "false", tag = "5")]
+            deltas: Vec<i64>,
+        }
+
+        let packed = Packed {
+            ids: vec![1, -1, 300, 0],
+            name: "series".to_string(),
+            weights: vec![0.5, -2.0],
+            scores: vec![1.25],
+            deltas: vec![-5, 5, i64::MIN],
+        };
+        let unpacked = Unpacked {
+            ids: packed.ids.clone(),
+            name: packed.name.clone(),
+            weights: packed.weights.clone(),
+            scores: packed.scores.clone(),
+            deltas: packed.deltas.clone(),
+        };
+        let packed_enc = packed.encode_to_vec();
+        let unpacked_enc = unpacked.encode_to_vec();
+
+        let fields = [
+            (1, Variant::Varint),
+            (3, Variant::ThirtyTwoBit),
+            (4, Variant::SixtyFourBit),
+            (5, Variant::Varint),
+        ];
+        let mut converted = packed_enc.clone();
+        for (tag, wire_type) in fields {
+            converted = packed_to_unpacked(&converted, tag, wire_type);
+        }
+        assert_eq!(converted, unpacked_enc);
+        assert_eq!(Unpacked::decode(converted.as_slice()).unwrap(), unpacked);
+
+        let mut converted = unpacked_enc.clone();
+        for (tag, wire_type) in fields {
+            converted = unpacked_to_packed(&converted, tag, wire_type);
+        }
+        assert_eq!(converted, packed_enc);
+        assert_eq!(Packed::decode(converted.as_slice()).unwrap(), packed);
+
+        // Occurrences spread over the message are gathered at the first one.
+        let spread = [
+            encode_varint_field(1, 1),
+            encode_string_field(2, "x"),
+            encode_varint_field(1, 2),
+        ]
+        .concat();
+        let gathered = unpacked_to_packed(&spread, 1, Variant::Varint);
+        assert_eq!(gathered, b"\x0a\x02\x01\x02\x12\x01x");
+        assert_eq!(Packed::decode(gathered.as_slice()).unwrap().ids, [1, 2]);
+
+        // Fields that cannot be converted are left unchanged.
+        // The 14-byte payload of field 1 does not split into fixed-width elements.
+        assert_eq!(
+            packed_to_unpacked(&packed_enc, 1, Variant::SixtyFourBit),
+            packed_enc
+        );
+        assert_eq!(
+            packed_to_unpacked(&packed_enc, 1, Variant::ThirtyTwoBit),
+            packed_enc
+        );
+        assert_eq!(
+            packed_to_unpacked(&packed_enc, 1, Variant::LengthDelimited),
+            packed_enc
+        );
+        assert_eq!(
+            unpacked_to_packed(&unpacked_enc, 2, Variant::LengthDelimited),
+            unpacked_enc
+        );
+        assert_eq!(
+            unpacked_to_packed(&unpacked_enc, 6, Variant::Varint),
+            unpacked_enc
+        );
+        assert_eq!(unpacked_to_packed(b"\x08", 1, Variant::Varint), b"\x08");
+        assert_eq!(
+            packed_to_unpacked(b"\x08\x01\x0a\x05", 1, Variant::Varint),
+            b"\x08\x01\x0a\x05"
+        );
+    }
+
+    /// Test encoding signed integers as int32, int64, sint32 and sint64 varints
+    #[test]
+    fn test_encode_signed_integers() {
+        #[derive(Message)]
+        struct Signed {
+            #[prost(int32, tag = "1")]
+            int32: i32,
+            #[prost(int64, tag = "2")]
+            int64: i64,
+            #[prost(sint32, tag = "3")]
+            sint32: i32,
+            #[prost(sint64, tag = "4")]
+            sint64: i64,
+        }
+
+        for (small, large) in [
+            (1, 1),
+            (-1, -1),
+            (150, 1 << 40),
+            (-150, -(1 << 40)),
+            (i32::MIN, i64::MIN),
+            (i32::MAX, i64::MAX),
+        ] {
+            let enc = Signed {
+                int32: small,
+                int64: large,
+                sint32: small,
+                sint64: large,
+            }
+            .encode_to_vec();
+
+            assert_eq!(
+                extract_field_by_tag(&enc, 1),
+                Some(&encode_int32(small)[..])
+            );
+            assert_eq!(
+                extract_field_by_tag(&enc, 2),
+                Some(&encode_int64(large)[..])
+            );
+            assert_eq!(
+                extract_field_by_tag(&enc, 3),
+                Some(&encode_sint32(small)[..])
+            );
+            assert_eq!(
+                extract_field_by_tag(&enc, 4),
+                Some(&encode_sint64(large)[..])
+            );
+        }
+
+        // A negative int32 can be read back as an int64.
+        assert_eq!(encode_int32(-5), encode_int64(-5));
+        assert_eq!(encode_sint32(0), [0x00]);
+    }
+
+    /// Test extracting enum fields
+    #[test]
+    fn test_extract_field_as_enum() {
+        #[derive(Message)]
+        struct Shipment {
+            #[prost(enumeration = "Status", tag = "1")]
+            status: i32,
+            #[prost(enumeration = "Status", repeated, packed = "false", tag = "2")]
+            history: Vec<i32>,
+            #[prost(string, tag = "3")]
+            note: String,
+        }
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+        enum Status {
+            Unknown = 0,
+            Shipped = 1,
+            Returned = -1,
+        }
+
+        let shipment = Shipment {
+            status: Status::Returned as i32,
+            history: vec![Status::Shipped as i32, 99, i32::MIN],
+            note: "late".to_string(),
+        };
+        let enc = shipment.encode_to_vec();
+
+        assert_eq!(
+            extract_field_as_enum(&enc, 1),
+            Some(Status::Returned as i32)
+        );
+        assert_eq!(extract_field_as_enum_raw(&enc, 1), Some(-1));
+        let history: Vec<i32> = extract_all_occurrences_by_tag(&enc, 2)
+            .into_iter()
+            .map(|value| extract_field_as_enum(&[&[0x08][..], value].concat(), 1).unwrap())
+            .collect();
+        assert_eq!(history, shipment.history);
+
+        // Values beyond 32 bits wrap, like in prost.
+        let wide = encode_varint_field(1, u64::from(u32::MAX) + 2);
+        assert_eq!(extract_field_as_enum(&wide, 1), Some(1));
+        assert_eq!(
+            extract_field_as_enum_raw(&wide, 1),
+            Some(i64::from(u32::MAX) + 2)
+        );
+        assert_eq!(Shipment::decode(wide.as_slice()).unwrap().status, 1);
+        let above_max = encode_varint_field(1, u64::from(u32::MAX));
+        assert_eq!(extract_field_as_enum(&above_max, 1), Some(-1));
+
+        assert_eq!(extract_field_as_enum(&enc, 3), None);
+        assert_eq!(extract_field_as_enum(&enc, 4), None);
+        assert_eq!(Status::Unknown as i32, 0);
+    }
+
+    /// Test encoding and extracting fields through the ProtoEncode and ProtoExtract traits
+    #[test]
+    fn test_proto_encode_and_extract_traits() {
+        #[derive(Message, PartialEq)]
+        struct Scalars {
+            #[prost(uint64, tag = "1")]
+            a: u64,
+            #[prost(uint32, tag = "2")]
+            b: u32,
+            #[prost(int64, tag = "3")]
+            c: i64,
+            #[prost(int32, tag = "4")]
+            d: i32,
+            #[prost(float, tag = "5")]
+            e: f32,
+            #[prost(double, tag = "6")]
+            f: f64,
+            #[prost(bool, tag = "7")]
+            g: bool,
+            #[prost(string, tag = "8")]
+            h: String,
+            #[prost(bytes, tag = "9")]
+            i: Vec<u8>,
+            #[prost(string, tag = "10")]
+            j: String,
+            #[prost(bytes, tag = "11")]
+            k: Vec<u8>,
+        }
+
+        let scalars = Scalars {
+            a: u64::MAX,
+            b: 300,
+            c: -2,
+            d: i32::MIN,
+            e: 1.5,
+            f: -0.25,
+            g: true,
+            h: "owned".to_string(),
+            i: vec![1, 2, 3],
+            j: "borrowed".to_string(),
+            k: vec![4, 5],
+        };
+
+        let mut enc = Vec::new();
+        append_field_generic(&mut enc, 1, &scalars.a);
+        append_field_generic(&mut enc, 2, &scalars.b);
+        append_field_generic(&mut enc, 3, &scalars.c);
+        append_field_generic(&mut enc, 4, &scalars.d);
+        append_field_generic(&mut enc, 5, &scalars.e);
+        append_field_generic(&mut enc, 6, &scalars.f);
+        append_field_generic(&mut enc, 7, &scalars.g);
+        append_field_generic(&mut enc, 8, &scalars.h);
+        append_field_generic(&mut enc, 9, &scalars.i);
+        append_field_generic(&mut enc, 10, "borrowed");
+        append_field_generic(&mut enc, 11, &[4u8, 5][..]);
+        assert_eq!(enc, scalars.encode_to_vec());
+
+        assert_eq!(extract_field_generic(&enc, 1), Some(scalars.a));
+        assert_eq!(extract_field_generic(&enc, 2), Some(scalars.b));
+        assert_eq!(extract_field_generic(&enc, 3), Some(scalars.c));
+        assert_eq!(extract_field_generic(&enc, 4), Some(scalars.d));
+        assert_eq!(extract_field_generic(&enc, 5), Some(scalars.e));
+        assert_eq!(extract_field_generic(&enc, 6), Some(scalars.f));
+        assert_eq!(extract_field_generic(&enc, 7), Some(scalars.g));
+        assert_eq!(extract_field_generic(&enc, 8), Some(scalars.h.clone()));
+        assert_eq!(extract_field_generic(&enc, 9), Some(scalars.i.clone()));
+
+        // Wire type mismatches and invalid values are rejected.
+        assert_eq!(extract_field_generic::<u64>(&enc, 5), None);
+        assert_eq!(extract_field_generic::<f64>(&enc, 5), None);
+        assert_eq!(extract_field_generic::<String>(&enc, 1), None);
+        assert_eq!(extract_field_generic::<u64>(&enc, 12), None);
+        assert_eq!(String::extract(&[0xff]), None);
+        assert_eq!(u64::extract(&[0x01, 0x02]), None);
+        assert_eq!(f32::extract(&[0; 8]), None);
+
+        assert_eq!(300u32.encode_field(2), enc[11..14]);
+        assert_eq!(<str as ProtoEncode>::wire_type(), Variant::LengthDelimited);
+        assert_eq!(<bool as ProtoExtract>::wire_type(), Variant::Varint);
+    }
+
+    /// Test extracting fields with an offset hint
+    #[test]
+    fn test_extract_field_by_tag_hinted() {
+        #[derive(Message)]
+        struct Reading {
+            #[prost(uint64, tag = "1")]
+            sensor: u64,
+            #[prost(string, tag = "2")]
+            unit: String,
+            #[prost(double, tag = "3")]
+            value: f64,
+        }
+
+        let reading = |sensor, unit: &str, value| {
+            Reading {
+                sensor,
+                unit: unit.to_string(),
+                value,
+            }
+            .encode_to_vec()
+        };
+        let first = reading(1, "kPa", 1.5);
+        let second = reading(2, "kPa", 2.5);
+        // A longer unit moves field 3 further back.
+        let third = reading(3, "kilopascal", 3.5);
+
+        let (value, hint) = extract_field_by_tag_hinted(&first, 3, 0).unwrap();
+        assert_eq!(value, 1.5f64.to_le_bytes());
+        assert_eq!(Some(hint), find_field_tag_offset(&first, 3));
+
+        assert_eq!(
+            extract_field_by_tag_hinted(&second, 3, hint),
+            Some((&2.5f64.to_le_bytes()[..], hint))
+        );
+        // The hint points into the longer unit, which does not decode, so the whole message is
+        // scanned.
+        assert_eq!(
+            extract_field_by_tag_hinted(&third, 3, hint),
+            Some((&3.5f64.to_le_bytes()[..], hint + 7))
+        );
+        assert_eq!(
+            extract_field_by_tag_hinted(&third, 1, hint),
+            Some((&[3][..], 0))
+        );
+
+        // Hints past the end scan the whole message.
+        for tag in 1..=3 {
+            assert_eq!(
+                extract_field_by_tag_hinted(&third, tag, usize::MAX).map(|(value, _)| value),
+                extract_field_by_tag(&third, tag)
+            );
+        }
+        assert_eq!(extract_field_by_tag_hinted(&third, 4, hint), None);
+        assert_eq!(extract_field_by_tag_hinted(&[], 1, 0), None);
+    }
+
+    /// Test building messages from a template
+    #[test]
+    fn test_message_template() {
+        #[derive(Message)]
+        struct Sample {
+            #[prost(string, tag = "1")]
+            device: String,
+            #[prost(uint64, tag = "2")]
+            timestamp: u64,
+            #[prost(double, tag = "3")]
+            value: f64,
+            #[prost(string, tag = "4")]
+            status: String,
+        }
+
+        let mut builder = MessageBuilder::new();
+        builder
+            .add_string(1, "thermometer-12")
+            .add_varint(2, 1)
+            .add_double(3, 1.0)
+            .add_string(4, "pending");
+        let template = MessageTemplate::new(builder);
+
+        let expected = |timestamp, value, status: &str| {
+            Sample {
+                device: "thermometer-12".to_string(),
+                timestamp,
+                value,
+                status: status.to_string(),
+            }
+            .encode_to_vec()
+        };
+
+        for (timestamp, value, status) in [
+            (1_700_000_000u64, 21.5f64, "ok"),
+            (1, -3.0, "sensor fault, reading is an estimate"),
+            (u64::MAX, 0.25, "x"),
+        ] {
+            let message = template.instantiate(&[
+                (2, &encode_varint(timestamp)),
+                (3, &value.to_le_bytes()),
+                (4, status.as_bytes()),
+            ]);
+            assert_eq!(message, Some(expected(timestamp, value, status)));
+        }
+
+        // Unpatched fields keep their placeholder, and unknown tags are ignored.
+        assert_eq!(
+            template.instantiate(&[(3, &1.5f64.to_le_bytes()), (9, b"unused")]),
+            Some(expected(1, 1.5, "pending"))
+        );
+        assert_eq!(template.instantiate(&[]), Some(expected(1, 1.0, "pending")));
+
+        // Values that do not fit the wire type of the field are rejected.
+        assert_eq!(template.instantiate(&[(3, &[1, 2, 3])]), None);
+        assert_eq!(template.instantiate(&[(2, &[0x80])]), None);
+        assert_eq!(template.instantiate(&[(2, &[0x01, 0x02])]), None);
+    }
+
+    /// Test extracting length-delimited fields with their length prefix
+    #[test]
+    fn test_extract_field_with_length_prefix() {
+        #[derive(Message)]
+        struct Comment {
+            #[prost(uint32, tag = "1")]
+            votes: u32,
+            #[prost(string, tag = "2")]
+            text: String,
+        }
+
+        let text = Comment {
+            votes: 0,
+            text: "a".repeat(200),
+        }
+        .encode_to_vec();
+        // The tag takes one byte and the length prefix two.
+        assert_eq!(extract_field_with_length_prefix(&text, 2), Some(&text[1..]));
+        assert_eq!(
+            decode_length_delimited(&text[1..], 0),
+            Some((&b"a".repeat(200)[..], 202))
+        );
+
+        let encoded_message = Comment {
+            votes: 3,
+            text: "first".to_string(),
+        }
+        .encode_to_vec();
+        let with_prefix = extract_field_with_length_prefix(&encoded_message, 2).unwrap();
+        assert_eq!(with_prefix, b"\x05first");
+        assert_eq!(
+            &extract_field_with_full_record(&encoded_message, 2).unwrap()[1..],
+            with_prefix
+        );
+
+        // Varint fields have no length prefix.
+        assert_eq!(extract_field_with_length_prefix(&encoded_message, 1), None);
+        assert_eq!(extract_field_with_length_prefix(&encoded_message, 3), None);
+        assert_eq!(extract_field_with_length_prefix(b"\x12\x05fir", 2), None);
+    }
+
+    /// Test getting and setting single elements of a repeated field
+    #[test]
+    fn test_field_at_index() {
+        #[derive(Message)]
+        struct Playlist {
+            #[prost(string, repeated, tag = "1")]
+            songs: Vec<String>,
+            #[prost(uint32, tag = "2")]
+            position: u32,
+        }
+
+        let mut playlist = Playlist {
+            songs: ["intro", "verse", "chorus", "bridge", "outro"]
+                .iter()
+                .map(|song| song.to_string())
+                .collect(),
+            position: 3,
+        };
+        let mut encoded_message = playlist.encode_to_vec();
+
+        for (index, song) in playlist.songs.iter().enumerate() {
+            assert_eq!(
+                extract_field_at_index(&encoded_message, 1, index),
+                Some(song.as_bytes())
+            );
+        }
+        assert_eq!(
+            extract_field_at_index(&encoded_message, 1, 0),
+            extract_field_by_tag(&encoded_message, 1)
+        );
+        assert_eq!(extract_field_at_index(&encoded_message, 1, 5), None);
+        assert_eq!(
+            extract_field_at_index(&encoded_message, 2, 0),
+            Some(&[3][..])
+        );
+        assert_eq!(extract_field_at_index(&encoded_message, 2, 1), None);
+
+        for (index, song) in [(3, "a much longer bridge"), (0, "x"), (4, "coda")] {
+            let old = replace_field_at_index(&mut encoded_message, 1, index, song.as_bytes());
+            assert_eq!(old, Some(playlist.songs[index].clone().into_bytes()));
+            playlist.songs[index] = song.to_string();
+            assert_eq!(encoded_message, playlist.encode_to_vec());
+        }
+
+        let unchanged = encoded_message.clone();
+        assert_eq!(
+            replace_field_at_index(&mut encoded_message, 1, 5, b"extra"),
+            None
+        );
+        assert_eq!(
+            replace_field_at_index(&mut encoded_message, 3, 0, b"extra"),
+            None
+        );
+        // Values that do not fit the wire type of the field are rejected.
+        assert_eq!(
+            replace_field_at_index(&mut encoded_message, 2, 0, &[0x80]),
+            None
+        );
+        assert_eq!(
+            replace_field_at_index(&mut encoded_message, 2, 0, &[0x01, 0x02]),
+            None
+        );
+        assert_eq!(encoded_message, unchanged);
+        let mut fixed = b"\x0d\x01\x00\x00\x00\x10\x05".to_vec();
+        assert_eq!(replace_field_at_index(&mut fixed, 1, 0, &[1, 2, 3]), None);
+        assert_eq!(fixed, b"\x0d\x01\x00\x00\x00\x10\x05");
+
+        // Groups never count as occurrences, whether they have the tag number or contain it. The
+        // message is field 1, group 1 and group 2 each holding a field 1, then field 1 again.
+        let mut with_groups =
+            b"\x0a\x01\x61\x0b\x0a\x01\x7a\x0c\x13\x0a\x01\x79\x14\x0a\x01\x62".to_vec();
+        assert_eq!(extract_field_at_index(&with_groups, 1, 1), Some(&b"b"[..]));
+        assert_eq!(extract_field_at_index(&with_groups, 1, 2), None);
+        assert_eq!(extract_field_at_index(&with_groups, 2, 0), None);
+        assert_eq!(
+            replace_field_at_index(&mut with_groups, 1, 1, b"cd"),
+            Some(b"b".to_vec())
+        );
+        assert_eq!(
+            with_groups,
+            b"\x0a\x01\x61\x0b\x0a\x01\x7a\x0c\x13\x0a\x01\x79\x14\x0a\x02\x63\x64"
+        );
+        assert_eq!(replace_field_at_index(&mut with_groups, 2, 0, b"x"), None);
+    }
+
+    /// Test comparing encoded messages field by field
+    #[test]
+    fn test_messages_equal() {
+        #[derive(Message)]
+        struct Order {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+            #[prost(string, repeated, tag = "2")]
+            items: Vec<String>,
+            #[prost(double, tag = "3")]
+            total: f64,
+        }
+
+        let order = |items: &[&str], total| {
+            Order {
+                id: 7,
+                items: items.iter().map(|item| item.to_string()).collect(),
+                total,
+            }
+            .encode_to_vec()
+        };
+        let encoded_message = order(&["tea", "milk"], 4.5);
+
+        // The same fields, encoded in reverse order.
+        let mut builder = MessageBuilder::new();
+        builder
+            .add_double(3, 4.5)
+            .add_string(2, "tea")
+            .add_varint(1, 7)
+            .add_string(2, "milk");
+        let reordered = builder.finish();
+        assert_ne!(encoded_message, reordered);
+        assert!(messages_equal(&encoded_message, &reordered));
+        assert!(messages_equal(&reordered, &encoded_message));
+
+        // The order of repeated occurrences matters.
+        let swapped = order(&["milk", "tea"], 4.5);
+        assert!(!messages_equal(&encoded_message, &swapped));
+        assert!(!field_equal(&encoded_message, &swapped, 2));
+        assert!(field_equal(&encoded_message, &swapped, 1));
+        assert!(field_equal(&encoded_message, &swapped, 3));
+
+        assert!(!messages_equal(&encoded_message, &order(&["tea"], 4.5)));
+        assert!(!messages_equal(
+            &encoded_message,
+            &order(&["tea", "milk"], 5.0)
+        ));
+        assert!(!field_equal(&encoded_message, &order(&[], 4.5), 2));
+        assert!(field_equal(&encoded_message, &order(&[], 4.5), 4));
+
+        // Same value, different wire type.
+        assert!(!messages_equal(
+            b"\x0d\x01\x00\x00\x00",
+            b"\x0a\x04\x01\x00\x00\x00"
+        ));
+
+        // Floats are compared by their bits.
+        let nan = order(&[], f64::NAN);
+        assert!(messages_equal(&nan, &nan));
+        let zero = |value: f64| {
+            let mut builder = MessageBuilder::new();
+            builder.add_double(3, value);
+            builder.finish()
+        };
+        assert!(!messages_equal(&zero(0.0), &zero(-0.0)));
+
+        assert!(messages_equal(&[], &[]));
+        assert!(!messages_equal(&encoded_message, &[]));
+
+        // Messages that cannot be decoded completely are never equal.
+        assert!(!messages_equal(b"\x08\x01", b"\x08\x01\xff"));
+        assert!(!messages_equal(b"\x08\x01\xff", b"\x08\x01\xff"));
+        assert!(!messages_equal(b"\x0f", b"\x07\x07"));
+        assert!(!messages_equal(b"\x12\x05hi", b"\x12\x05hi"));
+        assert!(!field_equal(b"\x08\x01", b"\x08\x01\xff", 1));
+        assert!(!field_equal(b"\x08\x01\x0b", b"\x08\x01", 1));
+
+        // Fields after a group are compared.
+        assert!(!messages_equal(b"\x0b\x0c\x10\x01", b"\x10\x01"));
+        assert!(!messages_equal(b"\x0b\x0c\x10\x01", b"\x0b\x0c\x10\x02"));
+        assert!(!field_equal(b"\x0b\x0c\x10\x01", b"\x0b\x0c\x10\x02", 2));
+        assert!(messages_equal(
+            b"\x0b\x08\x01\x0c\x10\x01",
+            b"\x10\x01\x0b\x08\x01\x0c"
+        ));
+        assert!(!messages_equal(
+            b"\x0b\x08\x01\x0c\x10\x01",
+            b"\x0b\x08\x02\x0c\x10\x01"
+        ));
+    }
 }
@@ -0,0 +1,265 @@
+#[cfg(feature = "alloc")]
+use crate::{append_field, create_header, encoders};
+use crate::{decoders, extract_field_view, Variant};
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+
+/// A value that can be encoded as the value of a protobuf field.
+///
+/// Each implementation encodes its type as one protobuf scalar type:
+///
+/// | Rust type         | Protobuf type | Wire type                    |
+/// | ----------------- | ------------- | ---------------------------- |
+/// | `u64`             | `uint64`      | [`Variant::Varint`]          |
+/// | `u32`             | `uint32`      | [`Variant::Varint`]          |
+/// | `i64`             | `int64`       | [`Variant::Varint`]          |
+/// | `i32`             | `int32`       | [`Variant::Varint`]          |
+/// | `bool`            | `bool`        | [`Variant::Varint`]          |
+/// | `f64`             | `double`      | [`Variant::SixtyFourBit`]    |
+/// | `f32`             | `float`       | [`Variant::ThirtyTwoBit`]    |
+/// | `String`, `str`   | `string`      | [`Variant::LengthDelimited`] |
+/// | `Vec<u8>`, `[u8]` | `bytes`       | [`Variant::LengthDelimited`] |
+///
+/// Implementing the trait for other types lets them be written with generic code such as
+/// [`append_field_generic`].
+///
+/// # Example
+///
+/// ```
+/// use rustwire::ProtoEncode;
+///
+/// assert_eq!(150u64.encode_field(1), [0x08, 0x96, 0x01]);
+/// assert_eq!("hi".encode_field(2), [0x12, 0x02, 0x68, 0x69]);
+/// ```
+#[cfg(feature = "alloc")]
+pub trait ProtoEncode {
+    /// The wire type of fields holding this type.
+    fn wire_type() -> Variant;
+
+    /// Encodes the value without a tag or length prefix, in the same form as the values returned
+    /// by [`extract_field_by_tag`](crate::extract_field_by_tag).
+    fn encode_payload(&self) -> Vec<u8>;
+
+    /// Encodes the value as a whole field with the given tag number.
+    fn encode_field(&self, tag: u64) -> Vec<u8> {
+        let payload = self.encode_payload();
+        let mut field = create_header(tag, Self::wire_type().into(), &payload);
+        field.extend_from_slice(&payload);
+        field
+    }
+}
+
+/// A value that can be decoded from the value of a protobuf field.
+///
+/// This is the counterpart of [`ProtoEncode`], with the same mapping between Rust and protobuf
+/// types. `extract` takes the value of a field as returned by
+/// [`extract_field_by_tag`](crate::extract_field_by_tag), so the wire type is not checked; use
+/// [`extract_field_generic`] to check it as well.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::ProtoExtract;
+///
+/// assert_eq!(u64::extract(&[0x96, 0x01]), Some(150));
+/// assert_eq!(f32::extract(&[0x00, 0x00]), None);
+/// ```
+pub trait ProtoExtract: Sized {
+    /// The wire type of fields holding this type.
+    fn wire_type() -> Variant;
+
+    /// Decodes a field value, or returns `None` if it is not a valid value of this type.
+    fn extract(bytes: &[u8]) -> Option<Self>;
+}
+
+/// Appends a field holding `value` to the end of an encoded message.
+///
+/// This is [`append_field`] with the wire type and payload taken from the [`ProtoEncode`]
+/// implementation of the value.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::append_field_generic;
+///
+/// let mut encoded_message = Vec::new();
+/// append_field_generic(&mut encoded_message, 1, &true);
+/// append_field_generic(&mut encoded_message, 2, "hi");
+///
+/// assert_eq!(encoded_message, [0x08, 0x01, 0x12, 0x02, 0x68, 0x69]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn append_field_generic<T: ProtoEncode + ?Sized>(msg: &mut Vec<u8>, tag: u64, value: &T) {
+    append_field(msg, tag, T::wire_type(), &value.encode_payload());
+}
+
+/// Extracts the first field with the given tag number and decodes it with its [`ProtoExtract`]
+/// implementation.
+///
+/// # Returns
+///
+/// The decoded value, or `None` if the field is not found, has a different wire type than `T`, or
+/// cannot be decoded as `T`.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::extract_field_generic;
+///
+/// let encoded_message = b"\x08\x01\x12\x02\x68\x69";
+///
+/// assert_eq!(extract_field_generic::<bool>(encoded_message, 1), Some(true));
+/// assert_eq!(extract_field_generic::<String>(encoded_message, 2), Some("hi".to_string()));
+/// assert_eq!(extract_field_generic::<u64>(encoded_message, 2), None);
+/// ```
+pub fn extract_field_generic<T: ProtoExtract>(encoded_message: &[u8], tag: u64) -> Option<T> {
+    let field = extract_field_view(encoded_message, tag)?;
+    if field.wire_type != T::wire_type() {
+        return None;
+    }
+    T::extract(field.as_bytes())
+}
+
+/// Decodes a varint field value, which must not be followed by other bytes.
+fn extract_varint(bytes: &[u8]) -> Option<u64> {
+    match decoders::decode_varint(bytes, 0) {
+        Some((value, len)) if len == bytes.len() => Some(value),
+        _ => None,
+    }
+}
+
+macro_rules! impl_varint {
+    ($($ty:ty => $encode:expr, $decode:expr;)*) => {
+        $(
+            #[cfg(feature = "alloc")]
+            impl ProtoEncode for $ty {
+                fn wire_type() -> Variant {
+                    Variant::Varint
+                }
+
+                fn encode_payload(&self) -> Vec<u8> {
+                    let encode: fn($ty) -> u64 = $encode;
+                    encoders::encode_varint(encode(*self))
+                }
+            }
+
+            impl ProtoExtract for $ty {
+                fn wire_type() -> Variant {
+                    Variant::Varint
+                }
+
+                fn extract(bytes: &[u8]) -> Option<Self> {
+                    let decode: fn(u64) -> $ty = $decode;
+                    extract_varint(bytes).map(decode)
+                }
+            }
+        )*
+    };
+}
+
+impl_varint! {
+    u64 => |value| value, |value| value;
+    u32 => u64::from, |value| value as u32;
+    // Negative `int32` values are sign-extended to 64 bits, like in the official libraries.
+    i64 => |value| value as u64, |value| value as i64;
+    i32 => |value| i64::from(value) as u64, |value| value as i32;
+    bool => u64::from, |value| value != 0;
+}
+
+macro_rules! impl_fixed {
+    ($($ty:ty => $variant:expr;)*) => {
+        $(
+            #[cfg(feature = "alloc")]
+            impl ProtoEncode for $ty {
+                fn wire_type() -> Variant {
+                    $variant
+                }
+
+                fn encode_payload(&self) -> Vec<u8> {
+                    self.to_le_bytes().to_vec()
+                }
+            }
+
+            impl ProtoExtract for $ty {
+                fn wire_type() -> Variant {
+                    $variant
+                }
+
+                fn extract(bytes: &[u8]) -> Option<Self> {
+                    bytes.try_into().ok().map(<$ty>::from_le_bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed! {
+    f64 => Variant::SixtyFourBit;
+    f32 => Variant::ThirtyTwoBit;
+}
+
+#[cfg(feature = "alloc")]
+impl ProtoEncode for str {
+    fn wire_type() -> Variant {
+        Variant::LengthDelimited
+    }
+
+    fn encode_payload(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ProtoEncode for String {
+    fn wire_type() -> Variant {
+        Variant::LengthDelimited
+    }
+
+    fn encode_payload(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ProtoExtract for String {
+    fn wire_type() -> Variant {
+        Variant::LengthDelimited
+    }
+
+    fn extract(bytes: &[u8]) -> Option<Self> {
+        core::str::from_utf8(bytes).ok().map(String::from)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ProtoEncode for [u8] {
+    fn wire_type() -> Variant {
+        Variant::LengthDelimited
+    }
+
+    fn encode_payload(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ProtoEncode for Vec<u8> {
+    fn wire_type() -> Variant {
+        Variant::LengthDelimited
+    }
+
+    fn encode_payload(&self) -> Vec<u8> {
+        self.clone()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ProtoExtract for Vec<u8> {
+    fn wire_type() -> Variant {
+        Variant::LengthDelimited
+    }
+
+    fn extract(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
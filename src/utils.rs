@@ -1,4 +1,9 @@
-use crate::{decoders, Variant};
+#[cfg(feature = "alloc")]
+use crate::encoders;
+use crate::{decoders, header, Variant, WireError};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
 
 impl From<Variant> for u64 {
     fn from(variant: Variant) -> u64 {
@@ -11,31 +16,367 @@ impl From<Variant> for u64 {
     }
 }
 
+impl TryFrom<u64> for Variant {
+    type Error = WireError;
+
+    fn try_from(wire_type: u64) -> Result<Self, Self::Error> {
+        match wire_type {
+            0 => Ok(Variant::Varint),
+            1 => Ok(Variant::SixtyFourBit),
+            2 => Ok(Variant::LengthDelimited),
+            5 => Ok(Variant::ThirtyTwoBit),
+            _ => Err(WireError::UnknownWireType(wire_type)),
+        }
+    }
+}
+
+impl TryFrom<u8> for Variant {
+    type Error = WireError;
+
+    fn try_from(wire_type: u8) -> Result<Self, Self::Error> {
+        Variant::try_from(u64::from(wire_type))
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(decoders::wire_type_name(u64::from(*self)))
+    }
+}
+
+#[cfg(feature = "alloc")]
 #[inline]
 pub(crate) fn handle_varint(encoded_message: &[u8], offset: usize) -> Option<usize> {
     decoders::decode_varint(encoded_message, offset).map(|(_, new_offset)| new_offset)
 }
 
-#[inline]
-pub(crate) fn handle_length_delimited(encoded_message: &[u8], offset: usize) -> Option<&[u8]> {
-    let (length, offset) = decoders::decode_varint(encoded_message, offset)?;
-    let end_offset = offset + length as usize;
-    if end_offset > encoded_message.len() {
-        return None;
-    }
-    Some(&encoded_message[offset..end_offset])
-}
+/// The maximum nesting depth of groups followed by [`skip_field`] and [`read_field`], matching the
+/// default recursion limit of the reference protobuf implementations.
+pub(crate) const MAX_GROUP_DEPTH: u32 = 100;
+
+/// The largest field number allowed by the protobuf specification (`2^29 - 1`).
+#[cfg(feature = "alloc")]
+pub(crate) const MAX_FIELD_NUMBER: u64 = (1 << 29) - 1;
 
+/// Skips the value of a field whose tag ends right before `offset`, returning the offset after it.
+///
+/// Groups (wire type 3) are skipped up to and including the end-group tag with the same field
+/// number, along with any groups nested inside them, up to [`MAX_GROUP_DEPTH`] levels deep.
 #[inline]
-pub(crate) fn skip_field(encoded_message: &[u8], wire_type: u64, offset: usize) -> Option<usize> {
+pub(crate) fn skip_field(
+    encoded_message: &[u8],
+    field_number: u64,
+    wire_type: u64,
+    offset: usize,
+) -> Option<usize> {
     match wire_type {
         0 => decoders::decode_varint(encoded_message, offset).map(|(_, new_offset)| new_offset),
-        1 => Some(offset + 8),
+        1 => offset.checked_add(8),
         2 => {
             let (length, offset) = decoders::decode_varint(encoded_message, offset)?;
-            Some(offset + length as usize)
+            offset.checked_add(usize::try_from(length).ok()?)
         }
-        5 => Some(offset + 4),
+        3 => skip_group_contents(
+            encoded_message,
+            field_number,
+            offset,
+            offset,
+            MAX_GROUP_DEPTH,
+        )
+        .map(|(_, end)| end)
+        .ok(),
+        5 => offset.checked_add(4),
         _ => None,
     }
 }
+
+/// Skips the group whose start-group tag is at `offset`, along with the groups nested inside it,
+/// returning the offset after its end-group tag.
+///
+/// At most `max_depth` groups may be nested inside each other, counting this one, so a
+/// `max_depth` of 0 rejects every group with [`WireError::RecursionLimitExceeded`].
+pub(crate) fn skip_group(
+    encoded_message: &[u8],
+    offset: usize,
+    max_depth: u32,
+) -> Result<usize, WireError> {
+    let (tag, contents_start) = decoders::decode_varint_checked(encoded_message, offset)?;
+    skip_group_contents(
+        encoded_message,
+        decoders::field_number_from_tag(tag),
+        offset,
+        contents_start,
+        max_depth,
+    )
+    .map(|(_, end)| end)
+}
+
+/// The location of a group inside an encoded message.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GroupRecord {
+    pub(crate) field_number: u64,
+    /// Offset of the first byte after the start-group tag.
+    pub(crate) contents_start: usize,
+    /// Offset of the first byte of the end-group tag.
+    pub(crate) contents_end: usize,
+    /// Offset of the first byte after the end-group tag.
+    pub(crate) end: usize,
+}
+
+/// Decodes the group whose start-group tag is at `offset`, like [`skip_group`], returning where
+/// its contents start and end.
+#[cfg(feature = "alloc")]
+pub(crate) fn read_group_checked(
+    encoded_message: &[u8],
+    offset: usize,
+    max_depth: u32,
+) -> Result<GroupRecord, WireError> {
+    let (tag, contents_start) = decoders::decode_varint_checked(encoded_message, offset)?;
+    let field_number = decoders::field_number_from_tag(tag);
+    let (contents_end, end) = skip_group_contents(
+        encoded_message,
+        field_number,
+        offset,
+        contents_start,
+        max_depth,
+    )?;
+    Ok(GroupRecord {
+        field_number,
+        contents_start,
+        contents_end,
+        end,
+    })
+}
+
+/// Skips the contents of the group with the given field number, whose start-group tag at
+/// `group_start` ends right before `offset`, returning the offsets of the first byte of its
+/// end-group tag and of the byte after it.
+fn skip_group_contents(
+    encoded_message: &[u8],
+    field_number: u64,
+    group_start: usize,
+    mut offset: usize,
+    depth_left: u32,
+) -> Result<(usize, usize), WireError> {
+    let depth_left = depth_left
+        .checked_sub(1)
+        .ok_or(WireError::RecursionLimitExceeded {
+            offset: group_start,
+        })?;
+
+    loop {
+        let tag_start = offset;
+        let (tag, value_start) = decoders::decode_varint_checked(encoded_message, offset)?;
+        let nested_field_number = decoders::field_number_from_tag(tag);
+
+        offset = match decoders::wire_type_from_tag(tag) {
+            3 => {
+                skip_group_contents(
+                    encoded_message,
+                    nested_field_number,
+                    tag_start,
+                    value_start,
+                    depth_left,
+                )?
+                .1
+            }
+            4 if nested_field_number == field_number => return Ok((tag_start, value_start)),
+            // An end-group tag that does not close this group is reported as unknown.
+            wire_type => {
+                let variant = Variant::try_from(wire_type)?;
+                read_value(encoded_message, variant, tag_start, value_start)?.1
+            }
+        };
+    }
+}
+
+/// The location of a single field record inside an encoded message.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FieldRecord {
+    pub(crate) field_number: u64,
+    pub(crate) variant: Variant,
+    /// Offset of the first byte of the tag.
+    pub(crate) start: usize,
+    /// Offset of the first byte of the value, after the length prefix for length-delimited fields.
+    pub(crate) value_start: usize,
+    /// Offset of the first byte after the field.
+    pub(crate) end: usize,
+}
+
+/// Decodes the next field record at or after `offset`, stepping over groups.
+///
+/// Returns `None` at the end of the message and if the field cannot be decoded. See
+/// [`read_field_checked`] for how groups are handled.
+#[inline]
+pub(crate) fn read_field(encoded_message: &[u8], offset: usize) -> Option<FieldRecord> {
+    read_field_checked(encoded_message, offset).ok().flatten()
+}
+
+/// Decodes the next field record at or after `offset`, stepping over groups, and returns why it
+/// could not be decoded.
+///
+/// Groups (wire types 3 and 4) have no [`Variant`], so they cannot be returned as records. Any
+/// groups starting at `offset` are skipped as a whole, up to [`MAX_GROUP_DEPTH`] levels deep, and
+/// the field after them is returned: the bytes between `offset` and the `start` of the record are
+/// groups. `Ok(None)` is returned if nothing but groups is left before the end of the message.
+#[inline]
+pub(crate) fn read_field_checked(
+    encoded_message: &[u8],
+    offset: usize,
+) -> Result<Option<FieldRecord>, WireError> {
+    read_field_checked_with_depth(encoded_message, offset, MAX_GROUP_DEPTH)
+}
+
+/// Like [`read_field_checked`], but skips groups up to `max_group_depth` levels deep.
+pub(crate) fn read_field_checked_with_depth(
+    encoded_message: &[u8],
+    mut offset: usize,
+    max_group_depth: u32,
+) -> Result<Option<FieldRecord>, WireError> {
+    while offset < encoded_message.len() {
+        match read_record_checked(encoded_message, offset) {
+            Err(WireError::UnknownWireType(3)) => {
+                offset = skip_group(encoded_message, offset, max_group_depth)?;
+            }
+            record => return record.map(Some),
+        }
+    }
+    Ok(None)
+}
+
+/// Decodes the field record starting exactly at `offset`, checking that it fits inside the
+/// message.
+///
+/// Unlike [`read_field_checked`], groups are not skipped but reported as
+/// [`WireError::UnknownWireType`].
+pub(crate) fn read_record_checked(
+    encoded_message: &[u8],
+    offset: usize,
+) -> Result<FieldRecord, WireError> {
+    let (header, header_end) = header::parse_field_header_checked(encoded_message, offset)?;
+    let (value_start, end) = read_value(encoded_message, header.wire_type, offset, header_end)?;
+
+    Ok(FieldRecord {
+        field_number: header.field_number,
+        variant: header.wire_type,
+        start: offset,
+        value_start,
+        end,
+    })
+}
+
+/// Decodes the bounds of the value of the field starting at `start`, whose tag ends right before
+/// `header_end`, returning the offsets of the first byte of the value and of the byte after it.
+fn read_value(
+    encoded_message: &[u8],
+    variant: Variant,
+    start: usize,
+    header_end: usize,
+) -> Result<(usize, usize), WireError> {
+    let truncated = WireError::TruncatedMessage { offset: start };
+
+    match variant {
+        Variant::Varint => Ok((
+            header_end,
+            decoders::decode_varint_checked(encoded_message, header_end)?.1,
+        )),
+        Variant::SixtyFourBit => Ok((
+            header_end,
+            decoders::decode_double(encoded_message, header_end).ok_or(truncated)?,
+        )),
+        Variant::LengthDelimited => {
+            let (length, value_start) =
+                decoders::decode_varint_checked(encoded_message, header_end)?;
+            let end = usize::try_from(length)
+                .ok()
+                .and_then(|length| value_start.checked_add(length))
+                .filter(|end| *end <= encoded_message.len())
+                .ok_or(WireError::InvalidLength { offset: header_end })?;
+            Ok((value_start, end))
+        }
+        Variant::ThirtyTwoBit => Ok((
+            header_end,
+            decoders::decode_float(encoded_message, header_end).ok_or(truncated)?,
+        )),
+    }
+}
+
+/// Replaces the value of `field` with `new_value`, updating the length prefix of length-delimited
+/// fields.
+#[cfg(feature = "alloc")]
+pub(crate) fn replace_value(encoded_message: &mut Vec<u8>, field: &FieldRecord, new_value: &[u8]) {
+    if new_value.len() == field.end - field.value_start {
+        encoded_message[field.value_start..field.end].copy_from_slice(new_value);
+    } else if field.variant != Variant::LengthDelimited {
+        encoded_message.splice(field.value_start..field.end, new_value.iter().copied());
+    } else {
+        let mut header = [0; 20];
+        let header_len = encoders::encode_header_to_slice(
+            field.field_number,
+            field.variant,
+            new_value.len(),
+            &mut header,
+        )
+        .expect("a field header is at most 20 bytes");
+        encoded_message.splice(
+            field.start..field.end,
+            header[..header_len].iter().chain(new_value).copied(),
+        );
+    }
+}
+
+/// Checks that `value` is exactly one value of the given wire type, so that it can be written as
+/// the value of a field with that wire type without corrupting the message.
+#[cfg(feature = "alloc")]
+pub(crate) fn value_fits(value: &[u8], variant: Variant) -> bool {
+    match variant {
+        Variant::Varint => {
+            decoders::decode_varint(value, 0).is_some_and(|(_, len)| len == value.len())
+        }
+        Variant::SixtyFourBit => value.len() == 8,
+        Variant::LengthDelimited => true,
+        Variant::ThirtyTwoBit => value.len() == 4,
+    }
+}
+
+/// Finds the first field record with the given tag number.
+#[inline]
+pub(crate) fn find_field(encoded_message: &[u8], tag_number: u64) -> Option<FieldRecord> {
+    find_field_checked(encoded_message, tag_number)
+        .ok()
+        .flatten()
+}
+
+/// Finds the first field record with the given tag number, failing on the first field that cannot
+/// be decoded.
+pub(crate) fn find_field_checked(
+    encoded_message: &[u8],
+    tag_number: u64,
+) -> Result<Option<FieldRecord>, WireError> {
+    let mut offset = 0;
+    while let Some(field) = read_field_checked(encoded_message, offset)? {
+        if field.field_number == tag_number {
+            return Ok(Some(field));
+        }
+        offset = field.end;
+    }
+    Ok(None)
+}
+
+/// Finds the first field with the given tag number and returns its value as a fixed-size array,
+/// if the field has the expected wire type.
+#[inline]
+pub(crate) fn find_fixed<const N: usize>(
+    encoded_message: &[u8],
+    tag_number: u64,
+    variant: Variant,
+) -> Option<[u8; N]> {
+    let field = find_field(encoded_message, tag_number)?;
+    if field.variant != variant {
+        return None;
+    }
+    encoded_message[field.value_start..field.end]
+        .try_into()
+        .ok()
+}
@@ -0,0 +1,376 @@
+#[cfg(feature = "alloc")]
+use crate::{decoders, StrictValidationError};
+use crate::{utils, FieldIterator, Variant, WireError};
+
+/// Configuration for [`validate_strict_with_config`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StrictValidationConfig {
+    /// Accept the deprecated group wire types (3 and 4), as long as every start-group tag is
+    /// closed by a matching end-group tag.
+    pub allow_groups: bool,
+}
+
+/// Strictly validates the structure of an encoded protobuf message.
+///
+/// Every field of the message is walked and the following is checked:
+/// - the tag decodes as a varint without overflowing,
+/// - the field number is in the valid range `1..=536870911`,
+/// - the wire type is one of varint (0), 64-bit (1), length-delimited (2) or 32-bit (5),
+/// - the value of every field, including the length prefix of length-delimited fields, fits inside
+///   the message.
+///
+/// Unlike the extraction functions, which silently skip or stop at bytes they don't understand, any
+/// undefined or deprecated wire type (3, 4, 6 and 7) is rejected. This is the mode to use for
+/// security-sensitive parsers and proto3-only systems that must not accept unknown wire types. Use
+/// [`validate_strict_with_config`] to accept groups.
+///
+/// Nested messages are not validated, since they cannot be told apart from `bytes` or `string`
+/// fields without a schema.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+///
+/// # Returns
+///
+/// `Ok(())` if the message is valid, otherwise the first [`StrictValidationError`] found.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{validate_strict, StrictValidationError};
+///
+/// assert_eq!(validate_strict(b"\x08\x01\x12\x02\x68\x69"), Ok(()));
+/// assert_eq!(
+///     validate_strict(b"\x08\x01\x0b"),
+///     Err(StrictValidationError::UnsupportedWireType { offset: 2, wire_type: 3 })
+/// );
+/// ```
+#[cfg(feature = "alloc")]
+pub fn validate_strict(encoded_message: &[u8]) -> Result<(), StrictValidationError> {
+    validate_strict_with_config(encoded_message, &StrictValidationConfig::default())
+}
+
+/// Strictly validates the structure of an encoded protobuf message with the given configuration.
+///
+/// See [`validate_strict`] for the checks that are performed.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{validate_strict_with_config, StrictValidationConfig};
+///
+/// // Field 1 is a group containing the varint field 2.
+/// let encoded_message = b"\x0b\x10\x01\x0c";
+/// let config = StrictValidationConfig { allow_groups: true };
+///
+/// assert!(validate_strict_with_config(encoded_message, &config).is_ok());
+/// ```
+#[cfg(feature = "alloc")]
+pub fn validate_strict_with_config(
+    encoded_message: &[u8],
+    config: &StrictValidationConfig,
+) -> Result<(), StrictValidationError> {
+    let mut open_groups = alloc::vec::Vec::new();
+    let mut offset = 0;
+
+    while offset < encoded_message.len() {
+        let field_offset = offset;
+        let (tag, new_offset) = decode_varint(encoded_message, offset)?;
+        offset = new_offset;
+
+        let field_number = decoders::field_number_from_tag(tag);
+        let wire_type = decoders::wire_type_from_tag(tag);
+
+        if field_number == 0 || field_number > utils::MAX_FIELD_NUMBER {
+            return Err(StrictValidationError::InvalidFieldNumber {
+                offset: field_offset,
+                field_number,
+            });
+        }
+
+        offset = match wire_type {
+            0 => decode_varint(encoded_message, offset)?.1,
+            1 => decoders::decode_double(encoded_message, offset).ok_or(
+                StrictValidationError::TruncatedMessage {
+                    offset: field_offset,
+                },
+            )?,
+            2 => {
+                let (length, value_offset) = decode_varint(encoded_message, offset)?;
+                usize::try_from(length)
+                    .ok()
+                    .and_then(|length| value_offset.checked_add(length))
+                    .filter(|end| *end <= encoded_message.len())
+                    .ok_or(StrictValidationError::TruncatedMessage {
+                        offset: field_offset,
+                    })?
+            }
+            5 => decoders::decode_float(encoded_message, offset).ok_or(
+                StrictValidationError::TruncatedMessage {
+                    offset: field_offset,
+                },
+            )?,
+            3 if config.allow_groups => {
+                open_groups.push(field_number);
+                offset
+            }
+            4 if config.allow_groups => {
+                if open_groups.pop() != Some(field_number) {
+                    return Err(StrictValidationError::UnmatchedEndGroup {
+                        offset: field_offset,
+                        field_number,
+                    });
+                }
+                offset
+            }
+            _ => {
+                return Err(StrictValidationError::UnsupportedWireType {
+                    offset: field_offset,
+                    wire_type,
+                })
+            }
+        };
+    }
+
+    match open_groups.pop() {
+        Some(field_number) => Err(StrictValidationError::UnterminatedGroup { field_number }),
+        None => Ok(()),
+    }
+}
+
+/// Checks that an encoded protobuf message is structurally valid.
+///
+/// Every field of the message is walked and the following is checked:
+/// - the tag decodes as a varint without overflowing,
+/// - the wire type is one of varint (0), 64-bit (1), length-delimited (2) or 32-bit (5),
+/// - the length prefix of every length-delimited field stays inside the message,
+/// - every fixed-width field has enough bytes left.
+///
+/// A message that passes is safe to use with all extraction functions: any `None` they return
+/// means the field is missing, not that the message is malformed. Use [`validate_strict`] to also
+/// check field numbers.
+///
+/// # Arguments
+///
+/// * `encoded_message` - A byte slice (`&[u8]`) containing the encoded protobuf message.
+///
+/// # Returns
+///
+/// `Ok(())` if the message is valid, otherwise the first [`WireError`] found.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{validate_message, WireError};
+///
+/// assert_eq!(validate_message(b"\x08\x01\x12\x02\x68\x69"), Ok(()));
+/// assert_eq!(
+///     validate_message(b"\x08\x01\x12\x05\x68\x69"),
+///     Err(WireError::InvalidLength { offset: 3 })
+/// );
+/// ```
+pub fn validate_message(encoded_message: &[u8]) -> Result<(), WireError> {
+    validate_message_recursive(encoded_message, 0)
+}
+
+/// Checks that an encoded protobuf message and the messages nested inside it are structurally
+/// valid.
+///
+/// The message is checked like in [`validate_message`]. In addition, the payload of every
+/// length-delimited field is validated as a nested message, up to `max_depth` levels deep. A
+/// `max_depth` of 0 does not look inside length-delimited fields at all.
+///
+/// Without a schema, nested messages cannot be told apart from `string`, `bytes` or packed
+/// repeated fields, so every length-delimited field is treated as a nested message. This is only
+/// useful for messages whose length-delimited fields, down to `max_depth`, are all messages.
+///
+/// Error offsets are relative to the start of `encoded_message`, also for errors found inside
+/// nested messages.
+///
+/// Nested messages are validated recursively, so `max_depth` is capped at 100 levels, the
+/// recursion limit of the reference implementation, to keep deeply nested input from overflowing
+/// the stack. Messages nested deeper than that are not looked inside.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{validate_message, validate_message_recursive, WireError};
+///
+/// // Field 1 is a nested message whose only field claims more bytes than it has.
+/// let encoded_message = b"\x0a\x03\x12\x05\x68";
+///
+/// assert_eq!(validate_message(encoded_message), Ok(()));
+/// assert_eq!(
+///     validate_message_recursive(encoded_message, 1),
+///     Err(WireError::InvalidLength { offset: 3 })
+/// );
+/// ```
+pub fn validate_message_recursive(encoded_message: &[u8], max_depth: u32) -> Result<(), WireError> {
+    validate_fields(encoded_message, 0, max_depth.min(utils::MAX_GROUP_DEPTH))
+}
+
+/// Guesses whether the payload of a length-delimited field is a nested message.
+///
+/// Without a schema, nested messages cannot be told apart from `string`, `bytes` or packed
+/// repeated fields for sure. A payload is taken for a message if it is not empty, decodes
+/// completely as a message, and is not printable text: valid UTF-8 without control characters
+/// other than whitespace. Tags of the fields 1 to 3 are control characters, so most messages are
+/// not printable text, while short strings often happen to decode as a message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::field_looks_like_message;
+///
+/// assert!(field_looks_like_message(b"\x08\x01\x12\x02\x68\x69"));
+/// // "hi" decodes as a message holding the varint field 13, but it is printable text.
+/// assert!(!field_looks_like_message(b"hi"));
+/// assert!(!field_looks_like_message(b"\x12\x05\x68"));
+/// ```
+pub fn field_looks_like_message(payload: &[u8]) -> bool {
+    let printable = match core::str::from_utf8(payload) {
+        Ok(text) => text.chars().all(|c| !c.is_control() || c.is_whitespace()),
+        Err(_) => false,
+    };
+    !payload.is_empty() && !printable && validate_message(payload).is_ok()
+}
+
+/// Estimates how deeply the messages nested inside an encoded message go.
+///
+/// Every length-delimited field is treated as a nested message, so `string`, `bytes` and packed
+/// fields count as a level of nesting too, and their payload is scanned for fields. A flat message
+/// has a depth of 0, and a message with a nested message has a depth of 1.
+///
+/// This is a cheap pre-flight check for untrusted input, e.g. to reject messages that would
+/// recurse too deeply before decoding them. It never fails: scanning a message or a payload stops
+/// at the first bytes that cannot be decoded. The message is walked without recursion, so deeply
+/// nested input cannot overflow the stack.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::estimate_nesting_depth;
+///
+/// assert_eq!(estimate_nesting_depth(b"\x08\x01"), 0);
+/// // Field 1 holds a message whose field 2 holds a message.
+/// assert_eq!(estimate_nesting_depth(b"\x0a\x04\x12\x02\x08\x01"), 2);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn estimate_nesting_depth(encoded_message: &[u8]) -> usize {
+    walk_nested_fields(encoded_message, usize::MAX).0
+}
+
+/// Counts the fields of an encoded message, without looking inside nested messages.
+///
+/// Every occurrence of a repeated field counts, and a packed field counts once. Counting stops at
+/// the first field that cannot be decoded.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::estimate_field_count;
+///
+/// assert_eq!(estimate_field_count(b"\x08\x01\x0a\x04\x12\x02\x08\x01\x08\x02"), 3);
+/// ```
+pub fn estimate_field_count(encoded_message: &[u8]) -> usize {
+    FieldIterator::new(encoded_message).count()
+}
+
+/// Counts the fields of an encoded message and of the messages nested inside it, up to
+/// `max_depth` levels deep.
+///
+/// Like in [`estimate_nesting_depth`], every length-delimited field is treated as a nested
+/// message, and counting stops at the first bytes of a message or payload that cannot be decoded.
+/// A `max_depth` of 0 counts like [`estimate_field_count`].
+///
+/// # Example
+///
+/// ```
+/// use rustwire::estimate_total_field_count_recursive;
+///
+/// let encoded_message = b"\x08\x01\x0a\x04\x12\x02\x08\x01";
+/// assert_eq!(estimate_total_field_count_recursive(encoded_message, 0), 2);
+/// assert_eq!(estimate_total_field_count_recursive(encoded_message, 1), 3);
+/// assert_eq!(estimate_total_field_count_recursive(encoded_message, 2), 4);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn estimate_total_field_count_recursive(encoded_message: &[u8], max_depth: u32) -> usize {
+    walk_nested_fields(encoded_message, max_depth as usize).1
+}
+
+/// Walks the fields of `encoded_message` and of its length-delimited fields, up to `max_depth`
+/// levels deep, returning the deepest level reached and the number of fields seen.
+///
+/// The end offsets of the enclosing fields are kept on a heap-allocated stack instead of
+/// recursing.
+#[cfg(feature = "alloc")]
+fn walk_nested_fields(encoded_message: &[u8], max_depth: usize) -> (usize, usize) {
+    let mut enclosing_ends = alloc::vec::Vec::new();
+    let (mut offset, mut end) = (0, encoded_message.len());
+    let (mut deepest, mut fields) = (0, 0);
+
+    loop {
+        if offset >= end {
+            match enclosing_ends.pop() {
+                Some(enclosing_end) => {
+                    offset = end;
+                    end = enclosing_end;
+                    continue;
+                }
+                None => return (deepest, fields),
+            }
+        }
+
+        match utils::read_field(&encoded_message[..end], offset) {
+            Some(field) => {
+                fields += 1;
+                if field.variant == Variant::LengthDelimited && enclosing_ends.len() < max_depth {
+                    enclosing_ends.push(end);
+                    deepest = deepest.max(enclosing_ends.len());
+                    offset = field.value_start;
+                    end = field.end;
+                } else {
+                    offset = field.end;
+                }
+            }
+            // Not a message, or a malformed one: skip the rest of it.
+            None => offset = end,
+        }
+    }
+}
+
+/// Validates the fields between `offset` and the end of `encoded_message`.
+///
+/// Nested messages are validated by passing a slice that ends where the enclosing field ends, so
+/// offsets stay relative to the start of the outermost message.
+fn validate_fields(
+    encoded_message: &[u8],
+    mut offset: usize,
+    max_depth: u32,
+) -> Result<(), WireError> {
+    while offset < encoded_message.len() {
+        let field = utils::read_record_checked(encoded_message, offset)?;
+        if max_depth > 0 && field.variant == Variant::LengthDelimited {
+            validate_fields(
+                &encoded_message[..field.end],
+                field.value_start,
+                max_depth - 1,
+            )?;
+        }
+        offset = field.end;
+    }
+    Ok(())
+}
+
+/// Decodes a varint, telling a truncated message apart from an overflowing varint.
+#[cfg(feature = "alloc")]
+fn decode_varint(
+    encoded_message: &[u8],
+    offset: usize,
+) -> Result<(u64, usize), StrictValidationError> {
+    decoders::decode_varint_checked(encoded_message, offset).map_err(|err| match err {
+        WireError::VarintOverflow { offset } => StrictValidationError::VarintOverflow { offset },
+        _ => StrictValidationError::TruncatedMessage { offset },
+    })
+}
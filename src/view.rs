@@ -0,0 +1,313 @@
+use crate::{decoders, extract_field_view, FieldIterator, Variant};
+#[cfg(feature = "alloc")]
+use crate::{extract_field_by_tag, extract_multiple_fields_by_tag};
+#[cfg(feature = "alloc")]
+use alloc::{sync::Arc, vec::Vec};
+use core::fmt;
+use core::ops::Index;
+
+/// A single field of an encoded protobuf message, with typed accessors for its value.
+///
+/// `FieldView` is returned by [`FieldIterator`](crate::FieldIterator) and
+/// [`extract_field_view`](crate::extract_field_view). The raw value has the same form as the value
+/// returned by [`extract_field_by_tag`](crate::extract_field_by_tag), and every typed accessor
+/// returns `None` if the wire type of the field does not match the requested type.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{extract_field_view, Variant};
+///
+/// let encoded_message = b"\x08\x96\x01\x12\x02\x68\x69";
+///
+/// let field = extract_field_view(encoded_message, 1).unwrap();
+/// assert_eq!(field.wire_type, Variant::Varint);
+/// assert_eq!(field.as_u64(), Some(150));
+/// assert_eq!(field.as_str(), None);
+///
+/// let field = extract_field_view(encoded_message, 2).unwrap();
+/// assert_eq!(field.as_str(), Some("hi"));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct FieldView<'a> {
+    /// The field number.
+    pub tag: u64,
+    /// The wire type of the field.
+    pub wire_type: Variant,
+    raw: &'a [u8],
+}
+
+impl<'a> FieldView<'a> {
+    /// Creates a view of a field from its tag number, wire type and raw value.
+    ///
+    /// `raw` must have the same form as the values returned by
+    /// [`extract_field_by_tag`](crate::extract_field_by_tag): the encoded varint, the payload
+    /// without its length prefix, or the 4 or 8 little-endian bytes. This is useful to
+    /// build new fields for [`map_fields`](crate::map_fields) or
+    /// [`collect_to_message`](crate::collect_to_message).
+    pub fn new(tag: u64, wire_type: Variant, raw: &'a [u8]) -> Self {
+        FieldView {
+            tag,
+            wire_type,
+            raw,
+        }
+    }
+
+    /// Decodes a varint field (`uint32`, `uint64`, `enum`) as a `u64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.wire_type {
+            Variant::Varint => decoders::decode_varint(self.raw, 0).map(|(value, _)| value),
+            _ => None,
+        }
+    }
+
+    /// Decodes a varint field (`int32`, `int64`) as an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_u64().map(|value| value as i64)
+    }
+
+    /// Decodes a zigzag-encoded varint field (`sint32`, `sint64`) as an `i64`.
+    pub fn as_sint64(&self) -> Option<i64> {
+        self.as_u64().map(decoders::decode_zigzag_i64)
+    }
+
+    /// Decodes a 32-bit field as a `float`.
+    pub fn as_f32(&self) -> Option<f32> {
+        match self.wire_type {
+            Variant::ThirtyTwoBit => self.raw.try_into().ok().map(f32::from_le_bytes),
+            _ => None,
+        }
+    }
+
+    /// Decodes a 64-bit field as a `double`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.wire_type {
+            Variant::SixtyFourBit => self.raw.try_into().ok().map(f64::from_le_bytes),
+            _ => None,
+        }
+    }
+
+    /// Decodes a varint field as a `bool`. Any non-zero value is `true`.
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_u64().map(|value| value != 0)
+    }
+
+    /// Returns the raw value of the field, whatever its wire type.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// Returns the value of a length-delimited field as a `string`, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self.wire_type {
+            Variant::LengthDelimited => core::str::from_utf8(self.raw).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Shows the raw value as a list of hex bytes, e.g.
+/// `FieldView { tag: 2, wire_type: LengthDelimited, raw: [0x68, 0x69] }`.
+impl fmt::Debug for FieldView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldView")
+            .field("tag", &self.tag)
+            .field("wire_type", &self.wire_type)
+            .field("raw", &HexBytes(self.raw))
+            .finish()
+    }
+}
+
+/// Shows the field number and the value in a short form, e.g. `#1: 150` or `#2: "hi"`.
+///
+/// Varint fields are shown as unsigned integers, and fixed-width fields as hex integers, since
+/// their type cannot be told from the wire type alone. Length-delimited fields are shown as a
+/// quoted string if they are valid UTF-8, and as hex bytes otherwise.
+impl fmt::Display for FieldView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}: ", self.tag)?;
+        match self.wire_type {
+            Variant::Varint => match self.as_u64() {
+                Some(value) => write!(f, "{}", value),
+                None => write!(f, "{:?}", HexBytes(self.raw)),
+            },
+            Variant::LengthDelimited => match self.as_str() {
+                Some(value) => write!(f, "{:?}", value),
+                None => write!(f, "{:?}", HexBytes(self.raw)),
+            },
+            Variant::SixtyFourBit => match self.raw.try_into() {
+                Ok(bytes) => write!(f, "{:#018x}", u64::from_le_bytes(bytes)),
+                Err(_) => write!(f, "{:?}", HexBytes(self.raw)),
+            },
+            Variant::ThirtyTwoBit => match self.raw.try_into() {
+                Ok(bytes) => write!(f, "{:#010x}", u32::from_le_bytes(bytes)),
+                Err(_) => write!(f, "{:?}", HexBytes(self.raw)),
+            },
+        }
+    }
+}
+
+/// Formats bytes as a list of hex bytes, e.g. `[0x68, 0x69]`.
+struct HexBytes<'a>(&'a [u8]);
+
+impl fmt::Debug for HexBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|byte| HexByte(*byte)))
+            .finish()
+    }
+}
+
+struct HexByte(u8);
+
+impl fmt::Debug for HexByte {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#04x}", self.0)
+    }
+}
+
+/// A zero-copy view of an encoded protobuf message.
+///
+/// `MessageView` wraps the encoded bytes and decodes fields only when they are asked for, so
+/// creating one costs nothing. It is `Copy`, and nested messages are views into the same buffer,
+/// which makes it cheap to pass around and to walk into nested messages.
+///
+/// Indexing a view by tag number returns the raw value of the field like
+/// [`extract_field_by_tag`](crate::extract_field_by_tag), and panics if the field is missing.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::MessageView;
+///
+/// // Field 5 is a nested message holding the string field 2.
+/// let encoded_message = b"\x08\x01\x2a\x04\x12\x02\x68\x69";
+/// let message = MessageView::new(encoded_message);
+///
+/// assert_eq!(message.field_count(), 2);
+/// assert_eq!(message[1], [0x01]);
+///
+/// let nested = message.nested_field(5).unwrap();
+/// assert_eq!(nested.field(2).and_then(|field| field.as_str()), Some("hi"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageView<'a>(&'a [u8]);
+
+impl<'a> MessageView<'a> {
+    /// Creates a view of `encoded_message`.
+    pub fn new(encoded_message: &'a [u8]) -> Self {
+        MessageView(encoded_message)
+    }
+
+    /// Returns the encoded message.
+    pub fn as_bytes(self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Returns the first field with the given tag number.
+    pub fn field(self, tag: u64) -> Option<FieldView<'a>> {
+        extract_field_view(self.0, tag)
+    }
+
+    /// Returns an iterator over every field of the message.
+    pub fn all_fields(self) -> FieldIterator<'a> {
+        FieldIterator::new(self.0)
+    }
+
+    /// Returns the number of fields in the message, counting every occurrence of repeated fields.
+    pub fn field_count(self) -> usize {
+        self.all_fields().count()
+    }
+
+    /// Returns `true` if the message contains a field with the given tag number.
+    pub fn has_field(self, tag: u64) -> bool {
+        self.field(tag).is_some()
+    }
+
+    /// Returns a view of the nested message stored in the first field with the given tag number,
+    /// or `None` if the field is missing or not length-delimited.
+    pub fn nested_field(self, tag: u64) -> Option<MessageView<'a>> {
+        match self.field(tag)? {
+            field if field.wire_type == Variant::LengthDelimited => {
+                Some(MessageView(field.as_bytes()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for MessageView<'a> {
+    fn from(encoded_message: &'a [u8]) -> Self {
+        MessageView::new(encoded_message)
+    }
+}
+
+impl Index<u64> for MessageView<'_> {
+    type Output = [u8];
+
+    /// Returns the raw value of the first field with the given tag number.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message has no field with the given tag number.
+    fn index(&self, tag: u64) -> &[u8] {
+        match self.field(tag) {
+            Some(field) => field.as_bytes(),
+            None => panic!("no field with tag number {}", tag),
+        }
+    }
+}
+
+/// An encoded protobuf message that can be shared between threads.
+///
+/// The message is held in an [`Arc`], so cloning a `SharedMessageView` or sending it to another
+/// thread does not copy the buffer. Since the buffer may be released once the last clone is
+/// dropped, the extraction methods return owned copies of the values. Use
+/// [`clone_arc`](SharedMessageView::clone_arc) to borrow values without copying them instead.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::SharedMessageView;
+///
+/// let message = SharedMessageView::new(b"\x08\x01\x12\x02\x68\x69".to_vec());
+///
+/// let shared = message.clone();
+/// let name = std::thread::spawn(move || shared.extract_field(2)).join().unwrap();
+/// assert_eq!(name, Some(b"hi".to_vec()));
+/// assert_eq!(message.extract_multiple_fields(&[1]), [(1, vec![0x01])]);
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedMessageView(Arc<Vec<u8>>);
+
+#[cfg(feature = "alloc")]
+impl SharedMessageView {
+    /// Creates a shared view that takes ownership of the encoded message.
+    pub fn new(data: Vec<u8>) -> Self {
+        SharedMessageView(Arc::new(data))
+    }
+
+    /// Returns a copy of the value of the first field with the tag number, like
+    /// [`extract_field_by_tag`].
+    pub fn extract_field(&self, tag: u64) -> Option<Vec<u8>> {
+        extract_field_by_tag(&self.0, tag).map(<[u8]>::to_vec)
+    }
+
+    /// Returns copies of the fields with the given tag numbers, like
+    /// [`extract_multiple_fields_by_tag`].
+    pub fn extract_multiple_fields(&self, tags: &[u64]) -> Vec<(u64, Vec<u8>)> {
+        extract_multiple_fields_by_tag(&self.0, tags)
+            .into_iter()
+            .map(|(tag, value)| (tag, value.to_vec()))
+            .collect()
+    }
+
+    /// Returns a new reference to the shared buffer.
+    ///
+    /// Values can be borrowed from the returned `Arc` without copying them, for as long as it is
+    /// kept alive.
+    pub fn clone_arc(&self) -> Arc<Vec<u8>> {
+        Arc::clone(&self.0)
+    }
+}
@@ -0,0 +1,248 @@
+#[cfg(feature = "alloc")]
+use crate::encoders;
+use crate::{utils, FieldView, Variant};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Encodes a `google.protobuf.Timestamp` message.
+///
+/// The message holds an `int64 seconds = 1` and an `int32 nanos = 2` field. Like any proto3
+/// message, fields holding zero are left out of the encoding.
+///
+/// # Arguments
+///
+/// * `seconds` - Seconds since the Unix epoch.
+/// * `nanos` - Non-negative fractions of a second at nanosecond resolution.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{decode_timestamp, encode_timestamp};
+///
+/// let encoded = encode_timestamp(1_700_000_000, 500);
+/// assert_eq!(decode_timestamp(&encoded), Some((1_700_000_000, 500)));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_timestamp(seconds: i64, nanos: i32) -> Vec<u8> {
+    encode_seconds_and_nanos(seconds, nanos)
+}
+
+/// Decodes a `google.protobuf.Timestamp` message into its `seconds` and `nanos`.
+///
+/// Missing fields are zero, and if a field appears more than once, the last occurrence wins.
+/// Unknown fields are ignored. The values are not range checked.
+///
+/// # Returns
+///
+/// `Some((seconds, nanos))`, or `None` if the message is malformed or a field has the wrong wire
+/// type.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_timestamp;
+///
+/// assert_eq!(decode_timestamp(b"\x08\x80\xe2\xcf\xaa\x06\x10\x05"), Some((1_700_000_000, 5)));
+/// assert_eq!(decode_timestamp(b""), Some((0, 0)));
+/// ```
+pub fn decode_timestamp(bytes: &[u8]) -> Option<(i64, i32)> {
+    decode_seconds_and_nanos(bytes)
+}
+
+/// Encodes a `google.protobuf.Timestamp` message as a length-delimited field with the given tag
+/// number, ready to be embedded in a larger message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_timestamp, encode_timestamp_field};
+///
+/// let field = encode_timestamp_field(3, 1_700_000_000, 0);
+/// assert_eq!(field[..2], [0x1a, 0x06]);
+/// assert_eq!(field[2..], encode_timestamp(1_700_000_000, 0));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_timestamp_field(tag: u64, seconds: i64, nanos: i32) -> Vec<u8> {
+    encoders::encode_field(
+        tag,
+        Variant::LengthDelimited,
+        &encode_timestamp(seconds, nanos),
+    )
+}
+
+/// Encodes a `google.protobuf.Duration` message.
+///
+/// # Arguments
+///
+/// * `seconds` - Signed seconds of the span of time.
+/// * `nanos` - Signed fractions of a second at nanosecond resolution, with the same sign as
+///   `seconds` if `seconds` is not zero.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{decode_duration, encode_duration};
+///
+/// let encoded = encode_duration(-1, -500_000_000);
+/// assert_eq!(decode_duration(&encoded), Some((-1, -500_000_000)));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_duration(seconds: i64, nanos: i32) -> Vec<u8> {
+    encode_seconds_and_nanos(seconds, nanos)
+}
+
+/// Decodes a `google.protobuf.Duration` message into its `seconds` and `nanos`.
+///
+/// This follows the same rules as [`decode_timestamp`].
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_duration;
+///
+/// assert_eq!(decode_duration(b"\x08\x3c"), Some((60, 0)));
+/// ```
+pub fn decode_duration(bytes: &[u8]) -> Option<(i64, i32)> {
+    decode_seconds_and_nanos(bytes)
+}
+
+/// Encodes a `google.protobuf.Duration` message as a length-delimited field with the given tag
+/// number, ready to be embedded in a larger message.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::encode_duration_field;
+///
+/// assert_eq!(encode_duration_field(1, 60, 0), [0x0a, 0x02, 0x08, 0x3c]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_duration_field(tag: u64, seconds: i64, nanos: i32) -> Vec<u8> {
+    encoders::encode_field(
+        tag,
+        Variant::LengthDelimited,
+        &encode_duration(seconds, nanos),
+    )
+}
+
+#[cfg(feature = "alloc")]
+fn encode_seconds_and_nanos(seconds: i64, nanos: i32) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    if seconds != 0 {
+        encoded.extend(encoders::encode_varint_field(1, seconds as u64));
+    }
+    if nanos != 0 {
+        // Negative `int32` values are sign-extended to 64 bits, like in the reference encoders.
+        encoded.extend(encoders::encode_varint_field(2, i64::from(nanos) as u64));
+    }
+    encoded
+}
+
+fn decode_seconds_and_nanos(bytes: &[u8]) -> Option<(i64, i32)> {
+    let (mut seconds, mut nanos) = (0, 0);
+    let mut offset = 0;
+    while let Some(field) = utils::read_field_checked(bytes, offset).ok()? {
+        let value = FieldView::new(
+            field.field_number,
+            field.variant,
+            &bytes[field.value_start..field.end],
+        );
+        match field.field_number {
+            1 => seconds = value.as_i64()?,
+            2 => nanos = value.as_i64()? as i32,
+            _ => {}
+        }
+        offset = field.end;
+    }
+    Some((seconds, nanos))
+}
+
+/// Encodes a `google.protobuf.Any` message wrapping an encoded message.
+///
+/// The message holds a `string type_url = 1` field, which identifies the type of the wrapped
+/// message, e.g. `type.googleapis.com/my.package.MyMessage`, and a `bytes value = 2` field with
+/// the wrapped message. Like any proto3 message, empty fields are left out of the encoding.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{decode_any, encode_any};
+///
+/// let encoded_any = encode_any("type.googleapis.com/Foo", b"\x08\x01");
+/// assert_eq!(decode_any(&encoded_any), Some(("type.googleapis.com/Foo", &b"\x08\x01"[..])));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_any(type_url: &str, encoded_message: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    if !type_url.is_empty() {
+        encoded.extend(encoders::encode_string_field(1, type_url));
+    }
+    if !encoded_message.is_empty() {
+        encoded.extend(encoders::encode_field(
+            2,
+            Variant::LengthDelimited,
+            encoded_message,
+        ));
+    }
+    encoded
+}
+
+/// Decodes a `google.protobuf.Any` message into its type URL and the wrapped encoded message.
+///
+/// Missing fields are empty, and if a field appears more than once, the last occurrence wins.
+/// Unknown fields are ignored.
+///
+/// # Returns
+///
+/// `Some((type_url, encoded_message))`, or `None` if the message is malformed, a field has the
+/// wrong wire type, or the type URL is not valid UTF-8.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::decode_any;
+///
+/// let encoded_any = b"\x0a\x03\x46\x6f\x6f\x12\x02\x08\x01";
+/// assert_eq!(decode_any(encoded_any), Some(("Foo", &b"\x08\x01"[..])));
+/// ```
+pub fn decode_any(encoded_any: &[u8]) -> Option<(&str, &[u8])> {
+    let (mut type_url, mut value) = ("", &[][..]);
+    let mut offset = 0;
+    while let Some(field) = utils::read_field_checked(encoded_any, offset).ok()? {
+        let view = FieldView::new(
+            field.field_number,
+            field.variant,
+            &encoded_any[field.value_start..field.end],
+        );
+        match field.field_number {
+            1 => type_url = view.as_str()?,
+            2 if field.variant == Variant::LengthDelimited => value = view.as_bytes(),
+            2 => return None,
+            _ => {}
+        }
+        offset = field.end;
+    }
+    Some((type_url, value))
+}
+
+/// Extracts the type URL of a `google.protobuf.Any` message, without looking at the rest of it.
+///
+/// Scanning stops at the first `type_url` field, which encoders write before the wrapped message,
+/// so this is cheap even for large messages. This makes it suitable for routing messages by type.
+///
+/// # Returns
+///
+/// The type URL, or `None` if it is missing, is not valid UTF-8, or the message is malformed
+/// before it.
+///
+/// # Example
+///
+/// ```
+/// use rustwire::{encode_any, extract_any_type_url};
+///
+/// let encoded_any = encode_any("type.googleapis.com/Foo", &[0x08; 4096]);
+/// assert_eq!(extract_any_type_url(&encoded_any), Some("type.googleapis.com/Foo"));
+/// ```
+pub fn extract_any_type_url(encoded_any: &[u8]) -> Option<&str> {
+    crate::extract_field_as_str(encoded_any, 1)
+}